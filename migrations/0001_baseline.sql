@@ -0,0 +1,48 @@
+-- Everything up to the introduction of schema_version. Written to be a no-op
+-- on databases created by earlier releases, which ran these statements on
+-- every start.
+CREATE TABLE IF NOT EXISTS notes (
+    id SERIAL PRIMARY KEY,
+    title TEXT UNIQUE NOT NULL,
+    content TEXT,
+    tags TEXT DEFAULT '{}',
+    archived BOOLEAN DEFAULT FALSE
+);
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ DEFAULT now();
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS encrypted BOOLEAN DEFAULT FALSE;
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS remind_at TIMESTAMPTZ;
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ;
+ALTER TABLE notes ALTER COLUMN created_at SET DEFAULT now();
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS supersedes INTEGER
+    REFERENCES notes(id) ON DELETE SET NULL;
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+CREATE TABLE IF NOT EXISTS pins (
+    note_id INTEGER REFERENCES notes(id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (note_id, tag)
+);
+CREATE TABLE IF NOT EXISTS smart_filters (
+    expression TEXT PRIMARY KEY
+);
+CREATE TABLE IF NOT EXISTS tag_meta (
+    name TEXT PRIMARY KEY,
+    color TEXT,
+    parent TEXT
+);
+CREATE TABLE IF NOT EXISTS note_revisions (
+    id SERIAL PRIMARY KEY,
+    note_id INTEGER NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+    content TEXT,
+    encrypted BOOLEAN NOT NULL DEFAULT FALSE,
+    saved_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS note_revisions_note_id_idx
+    ON note_revisions (note_id, saved_at);
+CREATE TABLE IF NOT EXISTS note_presence (
+    note_id INTEGER REFERENCES notes(id) ON DELETE CASCADE,
+    pid INTEGER NOT NULL,
+    client TEXT NOT NULL,
+    since TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (note_id, pid)
+);
@@ -0,0 +1,12 @@
+-- The baseline created notes.tags as TEXT, while every query reads and writes
+-- it as TEXT[]. Databases fixed by hand are left alone.
+DO $$
+BEGIN
+    IF (SELECT data_type FROM information_schema.columns
+        WHERE table_schema = current_schema() AND table_name = 'notes'
+          AND column_name = 'tags') <> 'ARRAY' THEN
+        ALTER TABLE notes ALTER COLUMN tags DROP DEFAULT,
+            ALTER COLUMN tags TYPE TEXT[] USING tags::TEXT[],
+            ALTER COLUMN tags SET DEFAULT '{}';
+    END IF;
+END $$;
@@ -0,0 +1,6 @@
+-- Notebooks group notes one level above tags; a note is in at most one.
+CREATE TABLE notebooks (
+    id SERIAL PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL
+);
+ALTER TABLE notes ADD COLUMN notebook_id INTEGER REFERENCES notebooks(id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- Words added and removed by the edit that replaced each revision; NULL when
+-- unknown (older revisions, encrypted notes, restores).
+ALTER TABLE note_revisions ADD COLUMN words_added INTEGER;
+ALTER TABLE note_revisions ADD COLUMN words_removed INTEGER;
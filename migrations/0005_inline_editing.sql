@@ -0,0 +1,9 @@
+-- Unsaved text of the inline editor, kept until it is saved or discarded so a
+-- crash doesn't lose it. Encrypted notes keep encrypted drafts.
+CREATE TABLE drafts (
+    note_id INTEGER PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    saved_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+-- Notes that open in the inline editor instead of the external one.
+ALTER TABLE notes ADD COLUMN edit_inline BOOLEAN NOT NULL DEFAULT FALSE;
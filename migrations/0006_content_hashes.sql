@@ -0,0 +1,7 @@
+-- SHA-256 of each note's content, written by pgnotes alongside the content so
+-- `pgnotes verify` can spot changes made behind its back.
+CREATE FUNCTION sha256_hex(text) RETURNS TEXT
+    AS $$ SELECT encode(sha256(convert_to($1, 'UTF8')), 'hex') $$
+    LANGUAGE SQL IMMUTABLE;
+ALTER TABLE notes ADD COLUMN content_hash TEXT;
+UPDATE notes SET content_hash = sha256_hex(content);
@@ -0,0 +1,2 @@
+-- Notes pinned to the top of every list, unlike `pins`, which pin within a tag.
+ALTER TABLE notes ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Notes listed in the Favorites view.
+ALTER TABLE notes ADD COLUMN favorite BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- What kind of note this is; picks its default tags, preview and editor file type.
+ALTER TABLE notes ADD COLUMN note_type TEXT NOT NULL DEFAULT 'note'
+    CHECK (note_type IN ('note', 'snippet', 'task-list', 'journal', 'template', 'script'));
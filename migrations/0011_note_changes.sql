@@ -0,0 +1,13 @@
+-- Announces edits to a note's title or content on `pgnotes_changes`, so other
+-- instances watching the note hear about it whichever client made the change.
+CREATE FUNCTION pgnotes_notify_change() RETURNS TRIGGER
+    AS $$ BEGIN
+        PERFORM pg_notify('pgnotes_changes', NEW.id::text);
+        RETURN NULL;
+    END $$
+    LANGUAGE plpgsql;
+CREATE TRIGGER notes_notify_change
+    AFTER UPDATE OF title, content ON notes
+    FOR EACH ROW
+    WHEN (OLD.title IS DISTINCT FROM NEW.title OR OLD.content IS DISTINCT FROM NEW.content)
+    EXECUTE FUNCTION pgnotes_notify_change();
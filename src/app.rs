@@ -1,19 +1,62 @@
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, Event, KeyEventKind, read},
+    event::{
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture, Event, EventStream, KeyEventKind,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
+use futures_util::StreamExt;
 use ratatui::{Terminal, backend::CrosstermBackend};
 use std::io::{self, stdout};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
 
+mod capture;
+pub mod cli;
+mod clip;
+mod clipboard;
 mod config;
+mod crypto;
+pub mod data_dir;
+mod date_filter;
 mod db;
+mod diff;
 mod editor;
 mod events;
+mod export;
+mod filter_expr;
+mod health;
+mod import;
+mod input;
+mod links;
+mod lint;
+mod log;
+mod lorem;
+mod mail;
+mod migrations;
+mod note_type;
+mod preview;
+mod query;
+mod search;
+mod search_history;
+mod sources;
 mod state;
+mod tag_editor;
+mod taxonomy;
+mod text_area;
+mod theme;
 mod ui;
 
-use self::{config::Config, db::Database, events::handle_key_event, state::AppState, ui::ui};
+use self::{
+    config::Config,
+    db::Database,
+    events::{handle_key_event, handle_paste},
+    state::AppState,
+    ui::ui,
+};
+
+const TICK_RATE: Duration = Duration::from_millis(250);
 
 pub struct App {
     terminal: Terminal<CrosstermBackend<std::io::Stdout>>,
@@ -22,24 +65,68 @@ pub struct App {
 }
 
 impl App {
-    pub fn new() -> io::Result<Self> {
+    /// `safe_mode` connects read-only without migrating and skips everything
+    /// at startup that writes or reaches beyond the database.
+    pub async fn new(safe_mode: bool) -> io::Result<Self> {
         // 1. Load Config
         let config = Config::new();
 
         // 2. Init Database (Wrapped)
-        let mut database = Database::new(&config.database_url)?;
+        let mut database = if safe_mode {
+            Database::connect_read_only(&config.database_url, &config.tls).await?
+        } else {
+            Database::new(&config.database_url, &config.tls).await?
+        };
 
         // 3. Init State (Pass DB info to state if needed, or just editor cmd)
-        let editor_cmd = config.get_editor_command();
-        let mut state = AppState::new(config.database_url.clone(), editor_cmd);
+        let mut state = AppState::new(&config);
+        state.safe_mode = safe_mode;
+
+        let warnings = health::startup_warnings(&config, &mut database).await;
+        for warning in &warnings {
+            log::warn(warning);
+            state.set_status(format!("Warning: {}", warning));
+        }
+
+        // Initial data fetch; the note list arrives in the background.
+        if safe_mode {
+            state.refresh_notes(&database);
+            state.set_status(
+                "Safe mode: read-only browsing, no migrations, sources or external commands."
+                    .to_string(),
+            );
+        } else {
+            state.reload_sources().await;
+            state.refresh_notes(&database);
+            state.load_presence(&mut database).await;
+            state.load_notebooks(&mut database).await;
 
-        // Initial data fetch
-        state.refresh_notes(&mut database)?;
+            // Inline edits that were never saved or discarded, e.g. after a crash.
+            if let Ok(titles) = database.draft_titles().await
+                && !titles.is_empty()
+            {
+                state.set_status(format!(
+                    "Unsaved draft of {}; open it with 'i' to restore it.",
+                    titles.join(", ")
+                ));
+            }
+        }
+
+        // The status line is easy to miss right after launch; show the history instead.
+        if !warnings.is_empty() {
+            state.open_status_history();
+        }
 
         // 4. Init Terminal
         enable_raw_mode()?;
         let mut stdout = stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        execute!(
+            stdout,
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableBracketedPaste,
+            EnableFocusChange
+        )?;
         let backend = CrosstermBackend::new(stdout);
         let terminal = Terminal::new(backend)?;
 
@@ -50,32 +137,89 @@ impl App {
         })
     }
 
-    pub fn run(&mut self) -> io::Result<()> {
+    pub async fn run(&mut self) -> io::Result<()> {
+        let mut events = EventStream::new();
+        // Wake up periodically so background work can report back.
+        let mut tick = tokio::time::interval(TICK_RATE);
+        tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         loop {
             self.terminal.draw(|f| ui(f, &mut self.state))?;
+            let search_due = self.state.search_due();
+            let preview_due = self.state.preview_due();
 
-            if let Event::Key(key) = read()?
-                && key.kind == KeyEventKind::Press
-            {
-                // Pass specific subsystems to event handler
-                let should_continue =
-                    handle_key_event(key, &mut self.state, &mut self.database, &mut self.terminal)?;
+            tokio::select! {
+                event = events.next() => {
+                    // Only time what this event does.
+                    self.database.take_slow_time();
+                    let statuses_before = self.state.statuses_set();
+                    if let Some(Ok(Event::FocusLost)) = event {
+                        self.state.autosave_draft(&mut self.database, true);
+                    } else if let Some(Event::Paste(text)) = event.as_ref().and_then(|e| e.as_ref().ok()) {
+                        self.state.last_activity = Instant::now();
+                        handle_paste(text, &mut self.state, &mut self.database).await;
+                    } else if let Some(Event::Key(key)) = event.transpose()?
+                        && key.kind == KeyEventKind::Press
+                    {
+                        self.state.last_activity = Instant::now();
+                        // Pass specific subsystems to event handler
+                        let should_continue = handle_key_event(
+                            key,
+                            &mut self.state,
+                            &mut self.database,
+                            &mut self.terminal,
+                        )
+                        .await?;
 
-                if !should_continue {
-                    break;
+                        if !should_continue {
+                            break;
+                        }
+                    }
+                    self.state.report_slow_calls(&mut self.database, statuses_before);
+                }
+                fetched = self.state.fetched() => {
+                    self.state.apply_fetched(&mut self.database, fetched);
                 }
+                _ = wait_until(search_due) => {}
+                _ = wait_until(preview_due) => {}
+                _ = tick.tick() => {}
             }
+
+            self.state.settle_preview(false);
+            self.state.poll_search(&self.database);
+            self.state.load_content(&self.database);
+            self.state.poll_connection_check(&mut self.database);
+            self.state.poll_reconnect(&mut self.database);
+            self.state.poll_conflict_queue();
+            self.state.sync_db_health(&self.database);
+            self.state.poll_notifications(&mut self.database);
+            self.state.autosave_draft(&mut self.database, false);
+            self.state.check_idle_lock();
+            self.state.check_due_reminders();
         }
 
+        // Let writes still running land before the process goes.
+        self.state.finish_calls(&mut self.database).await;
+
         // Cleanup on exit
         disable_raw_mode()?;
         execute!(
             self.terminal.backend_mut(),
             LeaveAlternateScreen,
-            DisableMouseCapture
+            DisableMouseCapture,
+            DisableBracketedPaste,
+            DisableFocusChange
         )?;
         self.terminal.show_cursor()?;
 
         Ok(())
     }
 }
+
+/// Sleeps until `at`; forever without one.
+async fn wait_until(at: Option<Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at.into()).await,
+        None => std::future::pending().await,
+    }
+}
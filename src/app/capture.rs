@@ -0,0 +1,494 @@
+//! Capturing notes from outside pgnotes into the #inbox. A `CaptureSource` only
+//! says what arrived; `ingest` names, tags and deduplicates it the same way for
+//! every source, so a new integration is a `poll` and an entry in `SOURCES`.
+
+use crate::app::clip;
+use crate::app::clipboard;
+use crate::app::config::TitleRules;
+use crate::app::db::{self, Database, ImportReport};
+use crate::app::import::{ImportedNote, TitleCollision};
+use crate::app::mail;
+use chrono::Local;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Tag of every captured note, until it is sorted into place.
+pub const INBOX_TAG: &str = "inbox";
+/// Feeds are bigger than pages' heads, but still shouldn't be downloaded without end.
+const MAX_FEED_BYTES: u64 = 4 * 1024 * 1024;
+/// Where `webhook` listens unless told otherwise; only this machine can post.
+const WEBHOOK_ADDRESS: &str = "127.0.0.1:8787";
+const MAX_WEBHOOK_BYTES: usize = 1024 * 1024;
+/// How long a webhook client may take to send its request.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// A webhook not bound to loopback only takes requests whose `SECRET_HEADER`
+/// holds this variable's value.
+const WEBHOOK_SECRET_VAR: &str = "PGNOTES_WEBHOOK_SECRET";
+const SECRET_HEADER: &str = "x-secret";
+/// `imap` reads the password from here rather than the command line, where
+/// other users could see it.
+const IMAP_PASSWORD_VAR: &str = "PGNOTES_IMAP_PASSWORD";
+
+/// One thing that arrived from a source.
+#[derive(Debug, Clone)]
+pub struct Captured {
+    /// `None` leaves it to `ingest`: "Capture" and the time.
+    pub title: Option<String>,
+    pub content: String,
+}
+
+pub trait CaptureSource {
+    /// What arrived since the last call; may block, e.g. on the network.
+    fn poll(&mut self) -> io::Result<Vec<Captured>>;
+
+    /// Called once what the last `poll` returned is stored, for sources that
+    /// should only let go of items then.
+    fn captured(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Builds a source from the `capture` argument, e.g. a feed URL.
+type OpenSource = fn(Option<&str>) -> Result<Box<dyn CaptureSource>, String>;
+
+/// A capture source by the name `capture --from` takes.
+pub struct SourceKind {
+    pub name: &'static str,
+    pub about: &'static str,
+    /// Whether `--watch` makes sense, i.e. `poll` can find more later.
+    pub watchable: bool,
+    pub open: OpenSource,
+}
+
+pub const SOURCES: &[SourceKind] = &[
+    SourceKind {
+        name: "stdin",
+        about: "standard input, as one note",
+        watchable: false,
+        open: |_| Ok(Box::new(Stdin { read: false })),
+    },
+    SourceKind {
+        name: "clipboard",
+        about: "the clipboard's text; with --watch, each new copy",
+        watchable: true,
+        open: |_| Ok(Box::new(Clipboard { last: None })),
+    },
+    SourceKind {
+        name: "rss",
+        about: "items of the RSS or Atom feed at URL",
+        watchable: true,
+        open: |url| match url {
+            Some(url) => Ok(Box::new(Feed {
+                url: url.to_string(),
+            })),
+            None => Err("rss needs the feed's URL.".to_string()),
+        },
+    },
+    SourceKind {
+        name: "imap",
+        about: "unread mail at imaps://USER@HOST[/MAILBOX], password in $PGNOTES_IMAP_PASSWORD",
+        watchable: true,
+        open: |url| {
+            let account = mail::Account::parse(url.ok_or("imap needs the mailbox's URL.")?)?;
+            let password = std::env::var(IMAP_PASSWORD_VAR)
+                .map_err(|_| format!("imap reads the password from ${}.", IMAP_PASSWORD_VAR))?;
+            Ok(Box::new(Imap {
+                account,
+                password,
+                pending: Vec::new(),
+            }))
+        },
+    },
+    SourceKind {
+        name: "webhook",
+        about: "bodies POSTed to ADDRESS (127.0.0.1:8787), titled by X-Title; \
+                beyond loopback X-Secret must match $PGNOTES_WEBHOOK_SECRET",
+        watchable: true,
+        open: |address| {
+            let address = address.unwrap_or(WEBHOOK_ADDRESS);
+            let listener = TcpListener::bind(address)
+                .map_err(|e| format!("Can't listen on {}: {}", address, e))?;
+            let secret = std::env::var(WEBHOOK_SECRET_VAR)
+                .ok()
+                .filter(|s| !s.is_empty());
+            let loopback = listener.local_addr().is_ok_and(|a| a.ip().is_loopback());
+            if secret.is_none() && !loopback {
+                return Err(format!(
+                    "Set ${} to listen on {}, which other machines can reach.",
+                    WEBHOOK_SECRET_VAR, address
+                ));
+            }
+            Ok(Box::new(Webhook { listener, secret }))
+        },
+    },
+];
+
+pub fn find(name: &str) -> Result<&'static SourceKind, String> {
+    SOURCES.iter().find(|s| s.name == name).ok_or_else(|| {
+        let known: Vec<String> = SOURCES
+            .iter()
+            .map(|s| format!("  {:<10} {}", s.name, s.about))
+            .collect();
+        format!(
+            "No capture source '{}'. There are:\n{}",
+            name,
+            known.join("\n")
+        )
+    })
+}
+
+/// Creates a note for each of `items`, tagged #inbox and `tags`. Titles are made
+/// valid and unique; items whose content a note already has are left out.
+pub async fn ingest(
+    db: &mut Database,
+    rules: &TitleRules,
+    items: Vec<Captured>,
+    tags: &[String],
+) -> Result<ImportReport, db::Error> {
+    let mut all_tags = vec![INBOX_TAG.to_string()];
+    for tag in tags {
+        if !all_tags.contains(tag) {
+            all_tags.push(tag.clone());
+        }
+    }
+    let notes: Vec<ImportedNote> = items
+        .into_iter()
+        .map(|item| {
+            let title = item
+                .title
+                .map(|t| rules.sanitize(&t))
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| format!("Capture {}", Local::now().format("%Y-%m-%d %H:%M:%S")));
+            ImportedNote {
+                title,
+                content: item.content,
+                tags: all_tags.clone(),
+                created_at: None,
+                updated_at: None,
+                archived: false,
+                encrypted: false,
+                checksum_ok: None,
+                path: PathBuf::new(),
+            }
+        })
+        .collect();
+    db.import_notes(&notes, TitleCollision::Rename, true).await
+}
+
+struct Stdin {
+    read: bool,
+}
+
+impl CaptureSource for Stdin {
+    fn poll(&mut self) -> io::Result<Vec<Captured>> {
+        if std::mem::replace(&mut self.read, true) {
+            return Ok(Vec::new());
+        }
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        Ok(vec![Captured {
+            title: None,
+            content,
+        }])
+    }
+}
+
+struct Clipboard {
+    last: Option<String>,
+}
+
+impl CaptureSource for Clipboard {
+    fn poll(&mut self) -> io::Result<Vec<Captured>> {
+        let text = clipboard::paste()?;
+        if text.trim().is_empty() || self.last.as_ref() == Some(&text) {
+            return Ok(Vec::new());
+        }
+        self.last = Some(text.clone());
+        // A copied link becomes a link, as when it is pasted into the TUI.
+        let content = match clip::as_url(&text) {
+            Some(url) => format!("<{}>\n", url),
+            None => text,
+        };
+        Ok(vec![Captured {
+            title: None,
+            content,
+        }])
+    }
+}
+
+struct Feed {
+    url: String,
+}
+
+impl CaptureSource for Feed {
+    /// Every item in the feed; the ones captured before are duplicates to `ingest`.
+    fn poll(&mut self) -> io::Result<Vec<Captured>> {
+        let xml = clip::get(&self.url, MAX_FEED_BYTES)?;
+        Ok(feed_items(&xml))
+    }
+}
+
+/// The `<item>`s of an RSS feed or the `<entry>`s of an Atom one, as their link
+/// followed by their description.
+fn feed_items(xml: &str) -> Vec<Captured> {
+    let mut items = Vec::new();
+    for tag in ["item", "entry"] {
+        let mut rest = xml;
+        while let Some((block, after)) = element(rest, tag) {
+            let link = element(block, "link")
+                .map(|(text, _)| text_of(text))
+                .filter(|link| !link.is_empty())
+                .or_else(|| link_href(block))
+                .unwrap_or_default();
+            let body = ["description", "content:encoded", "summary", "content"]
+                .into_iter()
+                .find_map(|name| element(block, name))
+                .map(|(text, _)| text_of(text))
+                .unwrap_or_default();
+            let content = [link, body]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            items.push(Captured {
+                title: element(block, "title").map(|(text, _)| text_of(text)),
+                content: format!("{}\n", content),
+            });
+            rest = after;
+        }
+    }
+    items
+}
+
+/// The inside of the first `<name ...>...</name>` in `xml`, and what follows it.
+fn element<'a>(xml: &'a str, name: &str) -> Option<(&'a str, &'a str)> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut from = 0;
+    loop {
+        let start = from + xml[from..].find(&open)?;
+        let after_name = start + open.len();
+        from = after_name;
+        // `<link` must not match `<linkage>`.
+        if !xml[after_name..].starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            continue;
+        }
+        let inner = after_name + xml[after_name..].find('>')? + 1;
+        if xml[..inner].ends_with("/>") {
+            continue;
+        }
+        let end = inner + xml[inner..].find(&close)?;
+        return Some((&xml[inner..end], &xml[end + close.len()..]));
+    }
+}
+
+/// Atom's `<link href="..."/>`.
+fn link_href(block: &str) -> Option<String> {
+    let start = block.find("<link ")?;
+    let tag = &block[start..start + block[start..].find('>')?];
+    let href = tag.find("href=\"")? + "href=\"".len();
+    let len = tag[href..].find('"')?;
+    Some(clip::decode_entities(&tag[href..href + len]))
+}
+
+/// Element text without CDATA wrapping or entities.
+fn text_of(text: &str) -> String {
+    let text = text.trim();
+    match text
+        .strip_prefix("<![CDATA[")
+        .and_then(|t| t.strip_suffix("]]>"))
+    {
+        Some(raw) => raw.trim().to_string(),
+        None => clip::decode_entities(text),
+    }
+}
+
+struct Imap {
+    account: mail::Account,
+    password: String,
+    /// Messages the last `poll` returned, marked read once they are stored.
+    pending: Vec<u64>,
+}
+
+impl CaptureSource for Imap {
+    fn poll(&mut self) -> io::Result<Vec<Captured>> {
+        let messages = self.account.fetch_unseen(&self.password)?;
+        self.pending = messages.iter().map(|m| m.uid).collect();
+        Ok(messages
+            .into_iter()
+            .map(|message| Captured {
+                title: message.subject,
+                content: message.text,
+            })
+            .collect())
+    }
+
+    /// If marking fails, the next `poll` fetches the messages again and
+    /// `ingest` skips them as duplicates.
+    fn captured(&mut self) -> io::Result<()> {
+        let uids = std::mem::take(&mut self.pending);
+        self.account.mark_seen(&self.password, &uids)
+    }
+}
+
+struct Webhook {
+    listener: TcpListener,
+    secret: Option<String>,
+}
+
+impl CaptureSource for Webhook {
+    /// Waits for the next request, then takes the ones queued behind it.
+    fn poll(&mut self) -> io::Result<Vec<Captured>> {
+        self.listener.set_nonblocking(false)?;
+        let (stream, _) = self.listener.accept()?;
+        let secret = self.secret.as_deref();
+        let mut items: Vec<Captured> = receive(stream, secret).into_iter().collect();
+        self.listener.set_nonblocking(true)?;
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => items.extend(receive(stream, secret)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(items),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Reads one POST and answers it. A bad request is answered and dropped
+/// rather than stopping the watch.
+fn receive(stream: TcpStream, secret: Option<&str>) -> Option<Captured> {
+    let mut reply = stream.try_clone().ok()?;
+    let read = stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(Some(WEBHOOK_TIMEOUT)))
+        .map_err(|_| BAD_REQUEST)
+        .and_then(|_| read_post(BufReader::new(stream), secret));
+    let (status, item) = match read {
+        Ok(item) => ("202 Accepted", Some(item)),
+        Err(status) => (status, None),
+    };
+    let _ = write!(
+        reply,
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+    item
+}
+
+const BAD_REQUEST: &str = "400 Bad Request";
+
+/// The item a POST request carries, or the status to refuse it with.
+fn read_post(mut reader: impl BufRead, secret: Option<&str>) -> Result<Captured, &'static str> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|_| BAD_REQUEST)?;
+    if !line.starts_with("POST ") {
+        return Err("405 Method Not Allowed");
+    }
+    let mut length = None;
+    let mut title = None;
+    let mut given_secret = None;
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|_| BAD_REQUEST)?;
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value.parse::<usize>().ok();
+        } else if name.eq_ignore_ascii_case("x-title") {
+            title = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case(SECRET_HEADER) {
+            given_secret = Some(value.to_string());
+        }
+    }
+    if let Some(secret) = secret
+        && !given_secret.is_some_and(|given| same_secret(&given, secret))
+    {
+        return Err("401 Unauthorized");
+    }
+    let length = length.ok_or("411 Length Required")?;
+    if length > MAX_WEBHOOK_BYTES {
+        return Err("413 Content Too Large");
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|_| BAD_REQUEST)?;
+    Ok(Captured {
+        title,
+        content: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+/// Compares in time that doesn't depend on where the two differ.
+fn same_secret(given: &str, secret: &str) -> bool {
+    given.len() == secret.len()
+        && given
+            .bytes()
+            .zip(secret.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(request: &str, secret: Option<&str>) -> Result<Captured, &'static str> {
+        read_post(request.as_bytes(), secret)
+    }
+
+    #[test]
+    fn read_post_takes_the_body_and_title() {
+        let item = post(
+            "POST / HTTP/1.1\r\nHost: x\r\nX-Title: Build log\r\ncontent-length: 5\r\n\r\nhello",
+            None,
+        )
+        .unwrap();
+        assert_eq!(item.title.as_deref(), Some("Build log"));
+        assert_eq!(item.content, "hello");
+    }
+
+    #[test]
+    fn read_post_refuses_bad_requests() {
+        assert_eq!(
+            post("GET / HTTP/1.1\r\n\r\n", None).unwrap_err(),
+            "405 Method Not Allowed"
+        );
+        assert_eq!(
+            post("POST / HTTP/1.1\r\n\r\nbody", None).unwrap_err(),
+            "411 Length Required"
+        );
+        let too_big = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_WEBHOOK_BYTES + 1
+        );
+        assert_eq!(post(&too_big, None).unwrap_err(), "413 Content Too Large");
+        assert_eq!(
+            post("POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort", None).unwrap_err(),
+            BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn read_post_checks_the_secret() {
+        let request =
+            |header: &str| format!("POST / HTTP/1.1\r\n{}Content-Length: 2\r\n\r\nhi", header);
+        assert_eq!(
+            post(&request(""), Some("s3cret")).unwrap_err(),
+            "401 Unauthorized"
+        );
+        assert_eq!(
+            post(&request("X-Secret: s3cres\r\n"), Some("s3cret")).unwrap_err(),
+            "401 Unauthorized"
+        );
+        assert_eq!(
+            post(&request("X-Secret: s3cret\r\n"), Some("s3cret"))
+                .unwrap()
+                .content,
+            "hi"
+        );
+        assert!(post(&request("X-Secret: anything\r\n"), None).is_ok());
+    }
+}
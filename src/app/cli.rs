@@ -0,0 +1,540 @@
+//! Headless subcommands: each talks to the database directly and exits, without
+//! starting the TUI, so pgnotes can be used from scripts and pipes.
+
+use chrono::{DateTime, Utc};
+use clap::Subcommand;
+use serde::Serialize;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::capture;
+use super::config::Config;
+use super::crypto;
+use super::data_dir;
+use super::date_filter::DateFilter;
+use super::db::{self, Database};
+use super::export;
+use super::import::{self, ImportedNote, TitleCollision};
+use super::state::Note;
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List notes as tab-separated id, title and tags
+    List {
+        /// Only notes with this tag
+        #[arg(long, short)]
+        tag: Option<String>,
+        /// Archived notes instead of active ones
+        #[arg(long)]
+        archived: bool,
+        /// Notes in the trash instead of active ones
+        #[arg(long, conflicts_with = "archived")]
+        trash: bool,
+        /// Date range as in the TUI, e.g. "updated 2026-10-01.." or "created ..2026-09-30"
+        #[arg(long)]
+        date: Option<String>,
+        /// Print a JSON array of notes, content included
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print a note's content
+    Show {
+        title: String,
+        /// Print the note as a JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Create a note and print its id
+    Add {
+        title: String,
+        /// The note's content; "-" reads it from stdin
+        content: Option<String>,
+        /// Tag the new note; repeat for several tags
+        #[arg(long, short)]
+        tag: Vec<String>,
+    },
+    /// Create #inbox notes from a capture source and print their ids, e.g.
+    /// `make 2>&1 | pgnotes capture` or `pgnotes capture --from rss URL --watch 600`
+    Capture {
+        /// stdin, clipboard, rss, imap or webhook; content a note already has is skipped
+        #[arg(long, default_value = "stdin")]
+        from: String,
+        /// What the source reads, e.g. the feed URL for rss or the address webhook listens on
+        target: Option<String>,
+        /// Title of the new notes; defaults to the item's, else "Capture" and the current time
+        #[arg(long)]
+        title: Option<String>,
+        /// Tag the new notes besides #inbox; repeat for several tags
+        #[arg(long, short)]
+        tag: Vec<String>,
+        /// Keep polling the source every SECONDS, until interrupted
+        #[arg(long, value_name = "SECONDS")]
+        watch: Option<u64>,
+    },
+    /// Move a note to the trash
+    Rm {
+        title: String,
+        /// Delete it for good instead
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Add tags to a note, or print its tags when none are given
+    Tag {
+        title: String,
+        tags: Vec<String>,
+        /// Remove the given tags instead
+        #[arg(long, short)]
+        remove: bool,
+    },
+    /// Create notes from the .md files under a directory; front matter sets title and tags
+    Import {
+        /// Directory to read, subdirectories included
+        #[arg(long)]
+        dir: PathBuf,
+        /// What to do when a title is already taken
+        #[arg(long, value_enum, default_value_t)]
+        on_conflict: TitleCollision,
+    },
+    /// Restore an `export --dir` backup: create missing notes and update ones unchanged
+    /// since, leaving notes edited after the backup alone
+    Restore {
+        /// Directory the backup was exported to
+        #[arg(long)]
+        dir: PathBuf,
+        /// Only report what would happen and check the files' checksums
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the profile's data directory (logs, search history) and its files
+    Data {
+        /// Delete the directory and everything in it
+        #[arg(long)]
+        clean: bool,
+    },
+    /// Re-hash every note's content and report notes changed outside pgnotes
+    Verify,
+    /// Write every note, archived ones included, to <slug>.md files with YAML front matter
+    Export {
+        /// Directory to write into; created if missing
+        #[arg(long)]
+        dir: PathBuf,
+        /// Only notes with this tag
+        #[arg(long, short)]
+        tag: Option<String>,
+    },
+}
+
+pub async fn run(command: Command) -> io::Result<()> {
+    // Needs no database.
+    if let Command::Data { clean } = command {
+        return show_data_dir(clean);
+    }
+
+    let config = Config::new();
+    let mut db = Database::new(&config.database_url, &config.tls).await?;
+
+    match command {
+        Command::List {
+            tag,
+            archived,
+            trash,
+            date,
+            json,
+        } => {
+            let date = date
+                .map(|range| DateFilter::parse(&range))
+                .transpose()
+                .map_err(io::Error::other)?;
+            let mut notes = load_notes(&mut db).await?;
+            notes.sort_by(|a, b| a.title.cmp(&b.title));
+            let listed = notes.iter().filter(|n| {
+                let in_view = if trash {
+                    n.deleted_at.is_some()
+                } else {
+                    n.deleted_at.is_none() && n.archived == archived
+                };
+                in_view
+                    && tag.as_ref().is_none_or(|t| n.tags.contains(t))
+                    && date.as_ref().is_none_or(|d| d.matches(n))
+            });
+            if json {
+                let listed: Vec<JsonNote> = listed.map(JsonNote::from).collect();
+                print_json(&listed)?;
+            } else {
+                for note in listed {
+                    println!("{}\t{}\t{}", note.id, note.title, note.tags.join(","));
+                }
+            }
+        }
+        Command::Show { title, json } => {
+            let notes = load_notes(&mut db).await?;
+            let note = find(&notes, &title)?;
+            if json {
+                print_json(&JsonNote::from(note))?;
+            } else if note.encrypted {
+                return Err(io::Error::other(format!(
+                    "'{}' is encrypted; unlock it in the TUI.",
+                    note.title
+                )));
+            } else {
+                print!("{}", note.content);
+                if !note.content.ends_with('\n') && !note.content.is_empty() {
+                    println!();
+                }
+            }
+        }
+        Command::Add {
+            title,
+            content,
+            tag,
+        } => {
+            let content = match content.as_deref() {
+                Some("-") => read_stdin()?,
+                Some(content) => content.to_string(),
+                None => String::new(),
+            };
+            add(&mut db, &config, &title, &content, &tag).await?;
+        }
+        Command::Capture {
+            from,
+            target,
+            title,
+            tag,
+            watch,
+        } => {
+            let kind = capture::find(&from).map_err(io::Error::other)?;
+            if watch.is_some() && !kind.watchable {
+                return Err(io::Error::other(format!("{} can't be watched.", kind.name)));
+            }
+            let mut source = (kind.open)(target.as_deref()).map_err(io::Error::other)?;
+            loop {
+                let mut items = tokio::task::block_in_place(|| source.poll())?;
+                if let Some(title) = &title {
+                    for item in &mut items {
+                        item.title = Some(title.clone());
+                    }
+                }
+                let report = capture::ingest(&mut db, &config.titles, items, &tag)
+                    .await
+                    .map_err(db_error)?;
+                tokio::task::block_in_place(|| source.captured())?;
+                for id in &report.ids {
+                    println!("{}", id);
+                }
+                if !report.duplicates.is_empty() {
+                    eprintln!(
+                        "Skipped {} item(s) already captured.",
+                        report.duplicates.len()
+                    );
+                }
+                let Some(seconds) = watch else {
+                    break;
+                };
+                tokio::time::sleep(Duration::from_secs(seconds)).await;
+            }
+        }
+        Command::Rm { title, purge } => {
+            let notes = load_notes(&mut db).await?;
+            let note = find(&notes, &title)?;
+            if purge {
+                db.purge_note(note.id).await.map_err(db_error)?;
+                eprintln!("Deleted '{}'.", note.title);
+            } else {
+                db.delete_note(note.id).await.map_err(db_error)?;
+                eprintln!("Moved '{}' to the trash.", note.title);
+            }
+        }
+        Command::Tag {
+            title,
+            tags,
+            remove,
+        } => {
+            let notes = load_notes(&mut db).await?;
+            let note = find(&notes, &title)?;
+            if tags.is_empty() {
+                for tag in &note.tags {
+                    println!("{}", tag);
+                }
+                return Ok(());
+            }
+            let mut new_tags = note.tags.clone();
+            for tag in tags {
+                if remove {
+                    new_tags.retain(|t| *t != tag);
+                } else if !new_tags.contains(&tag) {
+                    new_tags.push(tag);
+                }
+            }
+            db.update_note_tags(note.id, &new_tags)
+                .await
+                .map_err(db_error)?;
+        }
+        Command::Import { dir, on_conflict } => {
+            let (mut notes, problems) = import::read_dir(&dir)?;
+            for problem in problems {
+                eprintln!("Skipped {}", problem);
+            }
+            notes.retain_mut(|note| {
+                note.title = config.titles.normalize(&note.title);
+                let valid = match config.titles.validate(&note.title) {
+                    _ if note.title.is_empty() => Err("Title is empty.".to_string()),
+                    result => result,
+                };
+                if let Err(e) = &valid {
+                    eprintln!("Skipped '{}': {}", note.title, e);
+                }
+                valid.is_ok()
+            });
+            let report = db
+                .import_notes(&notes, on_conflict, config.import.skip_duplicates)
+                .await
+                .map_err(db_error)?;
+            for title in &report.duplicates {
+                eprintln!("Duplicate of an existing note: '{}'", title);
+            }
+            eprintln!(
+                "Imported {} note(s): {} new, {} renamed, {} overwritten, {} skipped, {} duplicate(s).",
+                notes.len(),
+                report.created,
+                report.renamed,
+                report.overwritten,
+                report.skipped,
+                report.duplicates.len()
+            );
+        }
+        Command::Restore { dir, dry_run } => {
+            let (backup, problems) = import::read_dir(&dir)?;
+            for problem in &problems {
+                eprintln!("Unreadable: {}", problem);
+            }
+            let corrupt: Vec<&ImportedNote> = backup
+                .iter()
+                .filter(|n| n.checksum_ok == Some(false))
+                .collect();
+            for note in &corrupt {
+                eprintln!("Checksum mismatch: {}", note.path.display());
+            }
+            let unchecked = backup.iter().filter(|n| n.checksum_ok.is_none()).count();
+
+            let plan = import::plan_restore(&backup, &load_notes(&mut db).await?);
+            for &i in &plan.conflicts {
+                eprintln!(
+                    "Conflict: '{}' was changed after the backup was taken",
+                    backup[i].title
+                );
+            }
+            let verb = if dry_run { "would be " } else { "" };
+            eprintln!(
+                "{} note(s) in the backup: {} {}created, {} updated, {} unchanged, {} conflicted.",
+                backup.len(),
+                plan.create.len(),
+                verb,
+                plan.update.len(),
+                plan.unchanged,
+                plan.conflicts.len()
+            );
+            if unchecked > 0 {
+                eprintln!("{} file(s) carry no checksum.", unchecked);
+            }
+            if !corrupt.is_empty() || !problems.is_empty() {
+                return Err(io::Error::other(format!(
+                    "{} file(s) failed to verify; nothing restored.",
+                    corrupt.len() + problems.len()
+                )));
+            }
+            if dry_run {
+                return Ok(());
+            }
+
+            let pick = |indices: &[usize]| -> Vec<ImportedNote> {
+                indices.iter().map(|&i| backup[i].clone()).collect()
+            };
+            // A backup restores what it holds, duplicates included, all or nothing.
+            // A title to create can only be a trashed note's, which comes back.
+            let notes = pick(&[plan.create, plan.update].concat());
+            db.import_notes(&notes, TitleCollision::Overwrite, false)
+                .await
+                .map_err(db_error)?;
+        }
+        Command::Data { .. } => unreachable!("handled before connecting"),
+        Command::Verify => {
+            let notes = db.content_hashes().await.map_err(db_error)?;
+            let mut mismatched = 0;
+            let mut unhashed = 0;
+            for (id, title, content, hash) in &notes {
+                match hash {
+                    Some(hash) if *hash == crypto::content_hash(content) => {}
+                    Some(_) => {
+                        mismatched += 1;
+                        println!("{}\t{}", id, title);
+                    }
+                    None => unhashed += 1,
+                }
+            }
+            eprintln!(
+                "Checked {} note(s): {} changed outside pgnotes, {} without a hash.",
+                notes.len(),
+                mismatched,
+                unhashed
+            );
+            if mismatched > 0 {
+                return Err(io::Error::other(format!(
+                    "{} note(s) do not match their hash.",
+                    mismatched
+                )));
+            }
+        }
+        Command::Export { dir, tag } => {
+            let notes: Vec<Note> = load_notes(&mut db)
+                .await?
+                .into_iter()
+                .filter(|n| n.deleted_at.is_none())
+                .filter(|n| tag.as_ref().is_none_or(|t| n.tags.contains(t)))
+                .collect();
+            export::export_files(&notes, &dir)?;
+            eprintln!("Exported {} note(s) to {}.", notes.len(), dir.display());
+        }
+    }
+    Ok(())
+}
+
+fn show_data_dir(clean: bool) -> io::Result<()> {
+    let root = data_dir::root();
+    if clean {
+        data_dir::clean()?;
+        eprintln!(
+            "Deleted {} (profile '{}').",
+            root.display(),
+            data_dir::profile()
+        );
+        return Ok(());
+    }
+    println!("{}", root.display());
+    let files = data_dir::files()?;
+    for (path, bytes) in &files {
+        let path = path.strip_prefix(&root).unwrap_or(path);
+        println!("  {}\t{} bytes", path.display(), bytes);
+    }
+    eprintln!(
+        "Profile '{}': {} file(s), {} bytes.",
+        data_dir::profile(),
+        files.len(),
+        files.iter().map(|(_, bytes)| bytes).sum::<u64>()
+    );
+    Ok(())
+}
+
+/// Creates the note and prints its id.
+async fn add(
+    db: &mut Database,
+    config: &Config,
+    title: &str,
+    content: &str,
+    tags: &[String],
+) -> io::Result<()> {
+    let title = config.titles.normalize(title);
+    config.titles.validate(&title).map_err(io::Error::other)?;
+    if title.is_empty() {
+        return Err(io::Error::other("Title is empty."));
+    }
+    let id = db
+        .create_note_with_content(&title, content, tags)
+        .await
+        .map_err(db_error)?;
+    println!("{}", id);
+    Ok(())
+}
+
+fn read_stdin() -> io::Result<String> {
+    let mut content = String::new();
+    io::stdin().read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// A note as `list --json` and `show --json` print it.
+#[derive(Serialize)]
+struct JsonNote<'a> {
+    id: i32,
+    title: &'a str,
+    tags: &'a [String],
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    archived_at: Option<DateTime<Utc>>,
+    deleted_at: Option<DateTime<Utc>>,
+    archived: bool,
+    encrypted: bool,
+    /// `None` for encrypted notes; their ciphertext is of no use outside the TUI.
+    content: Option<&'a str>,
+}
+
+impl<'a> From<&'a Note> for JsonNote<'a> {
+    fn from(note: &'a Note) -> Self {
+        Self {
+            id: note.id,
+            title: &note.title,
+            tags: &note.tags,
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+            archived_at: note.archived_at,
+            deleted_at: note.deleted_at,
+            archived: note.archived,
+            encrypted: note.encrypted,
+            content: (!note.encrypted).then_some(note.content.as_str()),
+        }
+    }
+}
+
+fn print_json(value: &impl Serialize) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, value)?;
+    writeln!(out)
+}
+
+fn db_error(e: db::Error) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+/// Every note, content included.
+async fn load_notes(db: &mut Database) -> io::Result<Vec<Note>> {
+    let mut notes = db
+        .load_notes(None)
+        .map_err(db_error)?
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    db.load_contents(&mut notes).await.map_err(db_error)?;
+    Ok(notes)
+}
+
+/// The note titled `title`: an exact match, or else the only one matching
+/// without regard to case. Notes in the trash are left out unless nothing else matches.
+fn find<'a>(notes: &'a [Note], title: &str) -> io::Result<&'a Note> {
+    let candidates = |matches: &dyn Fn(&Note) -> bool| {
+        let live: Vec<&Note> = notes
+            .iter()
+            .filter(|n| n.deleted_at.is_none() && matches(n))
+            .collect();
+        if live.is_empty() {
+            notes.iter().filter(|n| matches(n)).collect()
+        } else {
+            live
+        }
+    };
+    let exact = candidates(&|n| n.title == title);
+    let found = if exact.is_empty() {
+        candidates(&|n| n.title.to_lowercase() == title.to_lowercase())
+    } else {
+        exact
+    };
+    match found[..] {
+        [note] => Ok(note),
+        [] => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No note titled '{}'.", title),
+        )),
+        _ => Err(io::Error::other(format!(
+            "Several notes are titled '{}'; rename one in the TUI.",
+            title
+        ))),
+    }
+}
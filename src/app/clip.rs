@@ -0,0 +1,82 @@
+//! Turning a pasted URL into a Markdown link titled after the page.
+
+use std::io::{self, Read};
+use std::time::Duration;
+
+/// Tag of notes created from a pasted URL.
+pub const CLIPPED_TAG: &str = "clipped";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+/// The title is in the head; don't download whole pages for it.
+const MAX_BYTES: u64 = 256 * 1024;
+
+/// The pasted text as a URL, if that is all it is.
+pub fn as_url(text: &str) -> Option<&str> {
+    let text = text.trim();
+    let is_url = (text.starts_with("http://") || text.starts_with("https://"))
+        && !text.contains(char::is_whitespace);
+    is_url.then_some(text)
+}
+
+/// The page's `<title>`, or `None` when it can't be fetched or has none.
+pub async fn fetch_title(url: &str) -> Option<String> {
+    let url = url.to_string();
+    tokio::task::spawn_blocking(move || extract_title(&get(&url, MAX_BYTES).ok()?))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// The first `max_bytes` of the body at `url`. Blocks; see `FETCH_TIMEOUT`.
+pub fn get(url: &str, max_bytes: u64) -> io::Result<String> {
+    let config = ureq::Agent::config_builder()
+        .timeout_global(Some(FETCH_TIMEOUT))
+        .tls_config(
+            ureq::tls::TlsConfig::builder()
+                .provider(ureq::tls::TlsProvider::NativeTls)
+                .build(),
+        )
+        .build();
+    let agent = ureq::Agent::new_with_config(config);
+    let mut body = Vec::new();
+    agent
+        .get(url)
+        .call()
+        .map_err(io::Error::other)?
+        .into_body()
+        .into_reader()
+        .take(max_bytes)
+        .read_to_end(&mut body)?;
+    // The cut at `max_bytes` can split a character, and not every page is UTF-8.
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+fn extract_title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = decode_entities(&html[start..end])
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+pub fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// `[title](url)`, with brackets in the title escaped.
+pub fn markdown_link(title: &str, url: &str) -> String {
+    format!(
+        "[{}]({})",
+        title.replace('[', "\\[").replace(']', "\\]"),
+        url
+    )
+}
@@ -0,0 +1,162 @@
+//! Copying a note to the system clipboard as Markdown, HTML or plain text.
+
+use crate::app::export::escape_html;
+use base64::Engine;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd, html};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CopyFormat {
+    Markdown,
+    /// Markdown rendered to an HTML fragment; other filetypes as a `<pre>` block.
+    Html,
+    /// Markdown syntax stripped, keeping list markers and line structure.
+    PlainText,
+}
+
+impl CopyFormat {
+    pub const ALL: [CopyFormat; 3] = [
+        CopyFormat::Markdown,
+        CopyFormat::Html,
+        CopyFormat::PlainText,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CopyFormat::Markdown => "Raw Markdown",
+            CopyFormat::Html => "Rendered HTML",
+            CopyFormat::PlainText => "Plain text",
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            CopyFormat::Html => "text/html",
+            CopyFormat::Markdown | CopyFormat::PlainText => "text/plain",
+        }
+    }
+}
+
+/// `content` in `format`. Only Markdown notes (`markdown` is true) are rendered
+/// or stripped; other filetypes are copied as they are, or wrapped in `<pre>`.
+pub fn render(content: &str, markdown: bool, format: CopyFormat) -> String {
+    match format {
+        CopyFormat::Markdown => content.to_string(),
+        CopyFormat::Html if markdown => {
+            let mut out = String::new();
+            html::push_html(&mut out, Parser::new_ext(content, options()));
+            out
+        }
+        CopyFormat::Html => format!("<pre>{}</pre>\n", escape_html(content)),
+        CopyFormat::PlainText if markdown => strip_markdown(content),
+        CopyFormat::PlainText => content.to_string(),
+    }
+}
+
+fn options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH
+}
+
+fn strip_markdown(content: &str) -> String {
+    let mut out = String::new();
+    // Next number of each open list; `None` for bullet lists.
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    for event in Parser::new_ext(content, options()) {
+        match event {
+            Event::Text(text) | Event::Code(text) => out.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => out.push('\n'),
+            Event::TaskListMarker(done) => out.push_str(if done { "[x] " } else { "[ ] " }),
+            Event::Start(Tag::List(first)) => lists.push(first),
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    out.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        out.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => out.push_str("- "),
+                }
+            }
+            Event::End(TagEnd::Item) if !out.ends_with('\n') => out.push('\n'),
+            Event::End(TagEnd::TableRow | TagEnd::TableHead) => out.push('\n'),
+            Event::End(TagEnd::TableCell) => out.push('\t'),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_)) if lists.is_empty() => {
+                out.push_str("\n\n")
+            }
+            Event::End(TagEnd::CodeBlock | TagEnd::BlockQuote(_) | TagEnd::Table) => out.push('\n'),
+            _ => {}
+        }
+    }
+    let mut out = out.replace("\t\n", "\n").trim_end().to_string();
+    out.push('\n');
+    out
+}
+
+/// Puts `text` on the clipboard through the first of wl-copy, xclip and pbcopy
+/// that runs, unless `run_tools` is off, else through the terminal (OSC 52, which
+/// also works over SSH but only carries plain text). Returns what was used.
+pub fn copy(text: &str, format: CopyFormat, run_tools: bool) -> io::Result<&'static str> {
+    let mime = format.mime_type();
+    let tools: [(&str, Vec<&str>); 3] = [
+        ("wl-copy", vec!["--type", mime]),
+        ("xclip", vec!["-selection", "clipboard", "-t", mime]),
+        ("pbcopy", vec![]),
+    ];
+    for (tool, args) in tools.into_iter().filter(|_| run_tools) {
+        if pipe_to(tool, &args, text).is_ok() {
+            return Ok(tool);
+        }
+    }
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()?;
+    Ok("the terminal")
+}
+
+/// Text on the clipboard, read through the first of wl-paste, xclip and pbpaste that runs.
+pub fn paste() -> io::Result<String> {
+    let tools: [(&str, &[&str]); 3] = [
+        ("wl-paste", &["--no-newline"]),
+        ("xclip", &["-o", "-selection", "clipboard"]),
+        ("pbpaste", &[]),
+    ];
+    let mut last_error = io::Error::other("no clipboard tool found");
+    for (tool, args) in tools {
+        match Command::new(tool).args(args).stderr(Stdio::null()).output() {
+            Ok(output) if output.status.success() => {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+            Ok(_) => last_error = io::Error::other(format!("{} failed", tool)),
+            Err(_) => {}
+        }
+    }
+    Err(last_error)
+}
+
+fn pipe_to(tool: &str, args: &[&str], text: &str) -> io::Result<()> {
+    let mut child = Command::new(tool)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{} failed", tool)))
+    }
+}
@@ -0,0 +1,86 @@
+//! Per-note encryption: ChaCha20-Poly1305 with a key derived from a passphrase by Argon2,
+//! and content checksums.
+
+use argon2::Argon2;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::{
+    AeadCore, ChaCha20Poly1305, Key, KeyInit, Nonce,
+    aead::{Aead, OsRng, rand_core::RngCore},
+};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// Prefix marking stored content as ciphertext, followed by base64(salt | nonce | ciphertext).
+const PAYLOAD_PREFIX: &str = "pgnotes-enc:v1:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug)]
+pub enum CryptoError {
+    /// Wrong passphrase or tampered ciphertext; the AEAD tag did not verify.
+    Decrypt,
+    Malformed,
+    KeyDerivation,
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptoError::Decrypt => write!(f, "wrong passphrase or corrupted note"),
+            CryptoError::Malformed => write!(f, "note is not in the encrypted format"),
+            CryptoError::KeyDerivation => write!(f, "could not derive key from passphrase"),
+        }
+    }
+}
+
+/// Hex SHA-256 of `content`, as in exported files and the `content_hash` column.
+pub fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn encrypt(plaintext: &str, passphrase: &str) -> Result<String, CryptoError> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = cipher_for(passphrase, &salt)?;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| CryptoError::Decrypt)?;
+
+    let mut payload = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", PAYLOAD_PREFIX, STANDARD.encode(payload)))
+}
+
+pub fn decrypt(stored: &str, passphrase: &str) -> Result<String, CryptoError> {
+    let encoded = stored
+        .strip_prefix(PAYLOAD_PREFIX)
+        .ok_or(CryptoError::Malformed)?;
+    let payload = STANDARD
+        .decode(encoded.trim())
+        .map_err(|_| CryptoError::Malformed)?;
+    if payload.len() < SALT_LEN + NONCE_LEN {
+        return Err(CryptoError::Malformed);
+    }
+    let (salt, rest) = payload.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let cipher = cipher_for(passphrase, salt)?;
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::Decrypt)?;
+    String::from_utf8(plaintext).map_err(|_| CryptoError::Malformed)
+}
+
+fn cipher_for(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, CryptoError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|_| CryptoError::KeyDerivation)?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
@@ -0,0 +1,104 @@
+//! Per-profile directory for what pgnotes keeps besides its config:
+//! `<data dir>/pgnotes/<profile>/` with `logs/` and `state/` (e.g. search history).
+//! Profiles keep several databases' histories apart; the config stays shared.
+
+use crate::app::config::CONFIG_DIR_NAME;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::{fs, io};
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Chooses the profile for the rest of the run: `name`, else `$PGNOTES_PROFILE`,
+/// else the default one. Must be called before anything is read or written.
+pub fn set_profile(name: Option<String>) -> io::Result<()> {
+    let name = name
+        .or_else(|| std::env::var("PGNOTES_PROFILE").ok())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+    if name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(io::Error::other(format!(
+            "'{}' can't be a profile name; use letters, digits, '-' or '_'.",
+            name
+        )));
+    }
+    if name == DEFAULT_PROFILE {
+        adopt_legacy_files();
+    }
+    let _ = PROFILE.set(name);
+    Ok(())
+}
+
+pub fn profile() -> &'static str {
+    PROFILE.get().map_or(DEFAULT_PROFILE, String::as_str)
+}
+
+/// The current profile's directory.
+pub fn root() -> PathBuf {
+    base().join(profile())
+}
+
+/// A file under `state/`, kept between runs.
+pub fn state_file(name: &str) -> PathBuf {
+    root().join("state").join(name)
+}
+
+pub fn log_file(name: &str) -> PathBuf {
+    root().join("logs").join(name)
+}
+
+/// Every file under the profile's directory with its size in bytes, by path.
+pub fn files() -> io::Result<Vec<(PathBuf, u64)>> {
+    let mut files = Vec::new();
+    let root = root();
+    if root.exists() {
+        collect(&root, &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Deletes the profile's directory and everything in it.
+pub fn clean() -> io::Result<()> {
+    match fs::remove_dir_all(root()) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn base() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(CONFIG_DIR_NAME)
+}
+
+fn collect(dir: &Path, files: &mut Vec<(PathBuf, u64)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect(&entry.path(), files)?;
+        } else {
+            files.push((entry.path(), metadata.len()));
+        }
+    }
+    Ok(())
+}
+
+/// Moves files from before profiles existed, which sat directly in the base
+/// directory, into the default profile.
+fn adopt_legacy_files() {
+    let base = base();
+    let default = base.join(DEFAULT_PROFILE);
+    for (name, to) in [
+        ("pgnotes.log", default.join("logs")),
+        ("search_history", default.join("state")),
+    ] {
+        let from = base.join(name);
+        if from.is_file() && !to.join(name).exists() && fs::create_dir_all(&to).is_ok() {
+            let _ = fs::rename(&from, to.join(name));
+        }
+    }
+}
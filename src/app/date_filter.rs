@@ -0,0 +1,186 @@
+//! Filters on note timestamps: presets such as "updated in the last 24h" and
+//! custom ranges typed as `[created|updated] FROM..TO` with `YYYY-MM-DD` dates.
+
+use crate::app::state::Note;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeDelta, Utc};
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DateField {
+    Created,
+    Updated,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct DateFilter {
+    pub field: DateField,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    label: String,
+}
+
+impl fmt::Display for DateFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+impl DateFilter {
+    pub fn matches(&self, note: &Note) -> bool {
+        let value = match self.field {
+            DateField::Created => note.created_at,
+            DateField::Updated => note.updated_at,
+        };
+        // Notes from before the column existed have no timestamp and never match.
+        value.is_some_and(|t| {
+            self.from.is_none_or(|from| t >= from) && self.to.is_none_or(|to| t < to)
+        })
+    }
+
+    /// Presets offered in the filter selector.
+    pub fn presets() -> Vec<DateFilter> {
+        let now = Utc::now();
+        let today = Local::now().date_naive();
+        let week_start = today - TimeDelta::days(today.weekday().num_days_from_monday() as i64);
+        vec![
+            DateFilter {
+                field: DateField::Created,
+                from: Some(local_midnight(week_start)),
+                to: None,
+                label: "Created this week".to_string(),
+            },
+            DateFilter {
+                field: DateField::Updated,
+                from: Some(now - TimeDelta::hours(24)),
+                to: None,
+                label: "Updated in the last 24h".to_string(),
+            },
+            DateFilter {
+                field: DateField::Updated,
+                from: Some(now - TimeDelta::days(7)),
+                to: None,
+                label: "Updated in the last 7 days".to_string(),
+            },
+        ]
+    }
+
+    /// Parses `[created|updated] FROM..TO`; either end may be left out and `TO` is inclusive.
+    pub fn parse(input: &str) -> Result<DateFilter, String> {
+        let input = input.trim();
+        let (field, range) = match input.split_once(char::is_whitespace) {
+            Some((word, rest)) if word.eq_ignore_ascii_case("created") => {
+                (DateField::Created, rest)
+            }
+            Some((word, rest)) if word.eq_ignore_ascii_case("updated") => {
+                (DateField::Updated, rest)
+            }
+            _ => (DateField::Created, input),
+        };
+        let (from, to) = range
+            .trim()
+            .split_once("..")
+            .ok_or_else(|| "Expected a range like 2024-01-01..2024-01-31.".to_string())?;
+        let parse_date = |s: &str| -> Result<Option<NaiveDate>, String> {
+            let s = s.trim();
+            if s.is_empty() {
+                return Ok(None);
+            }
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(Some)
+                .map_err(|_| format!("'{}' is not a YYYY-MM-DD date.", s))
+        };
+        let (from, to) = (parse_date(from)?, parse_date(to)?);
+        if from.is_none() && to.is_none() {
+            return Err("Give at least one end of the range.".to_string());
+        }
+        if let (Some(from), Some(to)) = (from, to)
+            && from > to
+        {
+            return Err("Range starts after it ends.".to_string());
+        }
+
+        let field_label = match field {
+            DateField::Created => "Created",
+            DateField::Updated => "Updated",
+        };
+        let label = format!(
+            "{} {}..{}",
+            field_label,
+            from.map(|d| d.to_string()).unwrap_or_default(),
+            to.map(|d| d.to_string()).unwrap_or_default()
+        );
+        Ok(DateFilter {
+            field,
+            from: from.map(local_midnight),
+            to: to.map(|d| local_midnight(d + TimeDelta::days(1))),
+            label,
+        })
+    }
+}
+
+/// Start of `date` in the local timezone.
+pub fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| date.and_time(NaiveTime::MIN).and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn range_end_is_inclusive() {
+        let filter = DateFilter::parse("updated 2024-01-01..2024-01-31").unwrap();
+        assert_eq!(filter.field, DateField::Updated);
+        assert_eq!(filter.from, Some(local_midnight(day(2024, 1, 1))));
+        assert_eq!(filter.to, Some(local_midnight(day(2024, 2, 1))));
+        assert_eq!(filter.to_string(), "Updated 2024-01-01..2024-01-31");
+    }
+
+    #[test]
+    fn field_defaults_to_created_and_ends_are_optional() {
+        let filter = DateFilter::parse(" 2024-03-01.. ").unwrap();
+        assert_eq!(filter.field, DateField::Created);
+        assert_eq!(filter.to, None);
+        assert_eq!(filter.to_string(), "Created 2024-03-01..");
+
+        let filter = DateFilter::parse("CREATED ..2024-03-01").unwrap();
+        assert_eq!(filter.field, DateField::Created);
+        assert_eq!(filter.from, None);
+    }
+
+    #[test]
+    fn malformed_ranges_are_rejected() {
+        let error = |input| DateFilter::parse(input).unwrap_err();
+        assert_eq!(
+            error("2024-01-01"),
+            "Expected a range like 2024-01-01..2024-01-31."
+        );
+        assert_eq!(error("created .."), "Give at least one end of the range.");
+        assert_eq!(
+            error("2024-02-30..2024-03-01"),
+            "'2024-02-30' is not a YYYY-MM-DD date."
+        );
+        assert_eq!(
+            error("modified 2024-01-01.."),
+            "'modified 2024-01-01' is not a YYYY-MM-DD date."
+        );
+        assert_eq!(
+            error("2024-02-01..2024-01-01"),
+            "Range starts after it ends."
+        );
+    }
+
+    #[test]
+    fn a_single_day_range_is_allowed() {
+        let filter = DateFilter::parse("2024-01-01..2024-01-01").unwrap();
+        assert!(filter.from < filter.to);
+    }
+}
@@ -1,90 +1,2125 @@
-use crate::app::state::Note;
-use postgres::{Client, Error, NoTls};
+use crate::app::config::TlsConfig;
+use crate::app::crypto;
+use crate::app::diff;
+use crate::app::import::{ImportedNote, TitleCollision};
+use crate::app::log;
+use crate::app::migrations;
+use crate::app::note_type::NoteType;
+use crate::app::state::{ACTIVITY_WEEKS, EditActivity, Note};
+use crate::app::taxonomy::TagMeta;
+use chrono::{DateTime, Utc};
+use futures_util::{FutureExt, StreamExt, TryStreamExt, stream};
+use native_tls::{Certificate, Identity, TlsConnector};
+use postgres_native_tls::MakeTlsConnector;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{AsyncMessage, Client, Notification, Row};
+
+const BREAKER_BASE_COOLDOWN: Duration = Duration::from_secs(1);
+const BREAKER_MAX_COOLDOWN: Duration = Duration::from_secs(60);
+/// Used unless `database_url` sets `connect_timeout`, so a reconnect attempt on a
+/// dead network fails and backs off instead of hanging.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// TCP keepalives, unless `database_url` sets its own.
+const KEEPALIVE_IDLE: Duration = Duration::from_secs(30);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+/// Used unless `database_url` sets `statement_timeout` through `options`.
+/// Migrations may take longer.
+const STATEMENT_TIMEOUT: Duration = Duration::from_secs(30);
+/// NOTIFY channel raised whenever someone starts or stops editing a note.
+const PRESENCE_CHANNEL: &str = "pgnotes_presence";
+/// NOTIFY channel a trigger raises with the id of a note whose title or content changed.
+const CHANGES_CHANNEL: &str = "pgnotes_changes";
+/// First key of the advisory locks held on notes during an edit ("pgnt"), so
+/// they can't collide with locks other applications take on the same database.
+const EDIT_LOCK_SPACE: i32 = 0x7067_6e74;
+/// Notes read per round trip by `load_notes`.
+const NOTE_PAGE_SIZE: i64 = 500;
+/// Calls taking longer are logged and their time shown in the status line.
+const SLOW_CALL: Duration = Duration::from_millis(250);
 
 pub struct Database {
-    client: Client,
+    /// Shared with background loads. Writes are single statements, so no
+    /// transaction state ever lives on the client.
+    client: Arc<Client>,
+    /// NOTIFY messages forwarded by the task driving the connection.
+    notifications: UnboundedReceiver<Notification>,
+    /// Backend process of the connection, to tell our own notifications apart.
+    pid: i32,
+    breaker: CircuitBreaker,
+    /// What `poll_reconnect` needs to open the connection again.
+    db_url: String,
+    tls: TlsConfig,
+    read_only: bool,
+    reconnect: Option<JoinHandle<std::io::Result<Connection>>>,
+    /// Time spent in slow calls since `take_slow_time` was last called.
+    slow_time: Duration,
+}
+
+type Connection = (Client, UnboundedReceiver<Notification>, i32);
+
+#[derive(Debug)]
+pub enum Error {
+    Postgres(tokio_postgres::Error),
+    /// The circuit breaker is open; the call was skipped without touching the network.
+    Offline {
+        retry_in: Duration,
+    },
+    /// The connection was lost and a new one is being opened; see `poll_reconnect`.
+    Reconnecting,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Postgres(e) => write!(f, "{}", e),
+            Error::Offline { retry_in } => {
+                write!(f, "database offline, retrying in {}s", retry_in.as_secs())
+            }
+            Error::Reconnecting => write!(f, "connection lost, reconnecting"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<tokio_postgres::Error> for Error {
+    fn from(e: tokio_postgres::Error) -> Self {
+        Error::Postgres(e)
+    }
+}
+
+/// Stops hammering an unreachable server: after a connection-level failure,
+/// calls fail fast for a cooldown that doubles with each consecutive failure.
+#[derive(Default)]
+struct CircuitBreaker {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    fn record_success(&mut self) {
+        self.failures = 0;
+        self.open_until = None;
+    }
+
+    fn record_failure(&mut self) {
+        let cooldown = BREAKER_BASE_COOLDOWN
+            .saturating_mul(1 << self.failures.min(6))
+            .min(BREAKER_MAX_COOLDOWN);
+        self.failures += 1;
+        self.open_until = Some(Instant::now() + cooldown);
+    }
+
+    /// Remaining cooldown, or `Some(ZERO)` when the next call is the retry.
+    fn retry_in(&self) -> Option<Duration> {
+        self.open_until
+            .map(|until| until.saturating_duration_since(Instant::now()))
+    }
+}
+
+/// Storage used by notes, largest first, and by the tables behind them.
+#[derive(Debug, Clone, Default)]
+pub struct SizeReport {
+    /// (id, title, content bytes)
+    pub notes: Vec<(i32, String, i64)>,
+    pub notes_table_bytes: i64,
+    /// `None` when there is no attachments table.
+    pub attachments_bytes: Option<i64>,
+    pub database_bytes: i64,
+}
+
+/// Content a note had before one of its edits.
+#[derive(Debug, Clone)]
+pub struct Revision {
+    pub id: i32,
+    pub content: String,
+    /// The content is ciphertext, as the note was encrypted at the time.
+    pub encrypted: bool,
+    pub saved_at: DateTime<Utc>,
+}
+
+/// A file attached to a note, without its data.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub id: i32,
+    pub name: String,
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What `search_notes` found: the matching ids, and content it loaded by id.
+pub type SearchMatches = (HashSet<i32>, HashMap<i32, String>);
+
+/// What `import_notes` did with each note it was given.
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    pub created: usize,
+    /// Created under a new title because theirs was taken.
+    pub renamed: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+    /// Titles of the notes left out because their content is already there.
+    pub duplicates: Vec<String>,
+    /// Ids of the notes created, renamed ones included, in order.
+    pub ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notebook {
+    pub id: i32,
+    pub name: String,
+}
+
+/// Another pgnotes instance with a note open in its editor.
+#[derive(Debug, Clone)]
+pub struct Presence {
+    pub note_id: i32,
+    pub client: String,
+    pub since: DateTime<Utc>,
+}
+
+/// NOTIFY messages from other instances, collected by `take_notifications`.
+#[derive(Debug, Default)]
+pub struct Notified {
+    /// Someone started or stopped editing a note.
+    pub presence: bool,
+    /// Notes whose title or content someone else changed.
+    pub changed_notes: HashSet<i32>,
+}
+
+/// Result of a connection test: round-trip time and who answered.
+#[derive(Debug)]
+pub struct ConnectionReport {
+    pub connect_time: Duration,
+    pub latency: Duration,
+    pub server_version: String,
+    pub database: String,
+    pub user: String,
+    pub address: Option<String>,
 }
 
 impl Database {
-    pub fn new(db_url: &str) -> std::io::Result<Self> {
-        let mut client = Client::connect(db_url, NoTls)
+    /// Connects without touching the schema and makes every transaction read-only.
+    pub async fn connect_read_only(db_url: &str, tls: &TlsConfig) -> std::io::Result<Self> {
+        let connection = connect(db_url, tls, true).await?;
+        Ok(Self::from_connection(connection, db_url, tls, true))
+    }
+
+    pub async fn new(db_url: &str, tls: &TlsConfig) -> std::io::Result<Self> {
+        let (mut client, notifications, pid) = connect(db_url, tls, false)
+            .await
             .map_err(|e| std::io::Error::other(format!("DB connect error: {:#?}", e)))?;
 
-        client
-            .batch_execute(
-                "CREATE TABLE IF NOT EXISTS notes (
-                id SERIAL PRIMARY KEY,
-                title TEXT UNIQUE NOT NULL,
-                content TEXT,
-                tags TEXT DEFAULT '{}',
-                archived BOOLEAN DEFAULT FALSE
-            );",
-            )
-            .map_err(std::io::Error::other)?;
+        migrations::migrate(&mut client).await?;
 
-        Ok(Self { client })
+        Ok(Self::from_connection(
+            (client, notifications, pid),
+            db_url,
+            tls,
+            false,
+        ))
     }
 
-    pub fn get_all_notes(&mut self) -> Result<Vec<Note>, Error> {
-        let mut notes = Vec::new();
+    fn from_connection(
+        (client, notifications, pid): Connection,
+        db_url: &str,
+        tls: &TlsConfig,
+        read_only: bool,
+    ) -> Self {
+        Self {
+            client: Arc::new(client),
+            notifications,
+            pid,
+            breaker: CircuitBreaker::default(),
+            db_url: db_url.to_string(),
+            tls: tls.clone(),
+            read_only,
+            reconnect: None,
+            slow_time: Duration::ZERO,
+        }
+    }
 
-        for row in self
-            .client
-            .query("SELECT id, title, content, tags, archived FROM notes", &[])?
+    /// Replaces a lost connection: once the breaker allows another attempt, a new
+    /// connection is opened in the background and swapped in when it is ready;
+    /// failed attempts back off like any other connection failure. Call on every
+    /// tick. Returns `true` right after a new connection was swapped in.
+    pub fn poll_reconnect(&mut self) -> bool {
+        match self
+            .reconnect
+            .take_if(|task| task.is_finished())
+            .and_then(FutureExt::now_or_never)
+        {
+            Some(Ok(Ok((client, notifications, pid)))) => {
+                self.client = Arc::new(client);
+                self.notifications = notifications;
+                self.pid = pid;
+                self.breaker.record_success();
+                return true;
+            }
+            Some(_) => self.breaker.record_failure(),
+            None => {}
+        }
+
+        if self.client.is_closed()
+            && self.reconnect.is_none()
+            && self.breaker.retry_in().is_none_or(|d| d.is_zero())
         {
+            let (db_url, tls, read_only) = (self.db_url.clone(), self.tls.clone(), self.read_only);
+            self.reconnect = Some(tokio::spawn(async move {
+                connect(&db_url, &tls, read_only).await
+            }));
+        }
+        false
+    }
+    /// Runs a query through the circuit breaker. Only connection-level errors
+    /// count as failures; SQL errors like constraint violations pass through.
+    async fn run<T>(
+        &mut self,
+        op: impl AsyncFnOnce(&Client) -> Result<T, tokio_postgres::Error>,
+    ) -> Result<T, Error> {
+        self.check_breaker()?;
+        if self.client.is_closed() {
+            return Err(Error::Reconnecting);
+        }
+        let started = Instant::now();
+        let result = op(&self.client).await;
+        let elapsed = started.elapsed();
+        if elapsed >= SLOW_CALL {
+            log::warn(&format!(
+                "Slow database call: took {}",
+                format_duration(elapsed)
+            ));
+            self.slow_time += elapsed;
+        }
+        self.settle(result)
+    }
+
+    /// Time spent in calls slower than `SLOW_CALL` since the last time this was
+    /// asked, if there were any.
+    pub fn take_slow_time(&mut self) -> Option<Duration> {
+        Some(std::mem::take(&mut self.slow_time)).filter(|t| !t.is_zero())
+    }
+
+    fn check_breaker(&self) -> Result<(), Error> {
+        match self.breaker.retry_in() {
+            Some(retry_in) if !retry_in.is_zero() => Err(Error::Offline { retry_in }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Feeds the result of a query that ran outside `run` (see `load_notes`)
+    /// into the circuit breaker.
+    pub fn settle<T>(&mut self, result: Result<T, tokio_postgres::Error>) -> Result<T, Error> {
+        match result {
+            Ok(value) => {
+                self.breaker.record_success();
+                Ok(value)
+            }
+            Err(e) => {
+                if e.as_db_error().is_none() {
+                    self.breaker.record_failure();
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Feeds the result of a call made on a `detached` copy into the circuit
+    /// breaker, as `settle` does for queries.
+    pub fn settle_detached<T>(&mut self, result: &Result<T, Error>) {
+        match result {
+            Ok(_) => self.breaker.record_success(),
+            Err(Error::Postgres(e)) if e.as_db_error().is_none() => self.breaker.record_failure(),
+            Err(_) => {}
+        }
+    }
+
+    /// How long until the breaker lets calls through again, if it is open.
+    pub fn offline_retry_in(&self) -> Option<Duration> {
+        self.breaker.retry_in()
+    }
+
+    pub fn record_connection_success(&mut self) {
+        self.breaker.record_success();
+    }
+
+    pub fn record_connection_failure(&mut self) {
+        self.breaker.record_failure();
+    }
+
+    pub async fn size_report(&mut self) -> Result<SizeReport, Error> {
+        self.run(async |client| {
+            let notes = client
+                .query(
+                    "SELECT id, title, COALESCE(octet_length(content), 0)::bigint AS bytes
+                     FROM notes ORDER BY bytes DESC, title",
+                    &[],
+                )
+                .await?
+                .iter()
+                .map(|row| (row.get(0), row.get(1), row.get(2)))
+                .collect();
+            let totals = client
+                .query_one(
+                    "SELECT pg_total_relation_size('notes'),
+                        pg_total_relation_size(to_regclass('attachments')),
+                        pg_database_size(current_database())",
+                    &[],
+                )
+                .await?;
+            Ok(SizeReport {
+                notes,
+                notes_table_bytes: totals.get(0),
+                attachments_bytes: totals.get(1),
+                database_bytes: totals.get(2),
+            })
+        })
+        .await
+    }
+
+    /// Whether this session's connection to the server uses TLS.
+    pub async fn connection_encrypted(&mut self) -> Result<bool, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_one(
+                        "SELECT COALESCE(
+                             (SELECT ssl FROM pg_stat_ssl WHERE pid = pg_backend_pid()), FALSE)",
+                        &[],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(row.get(0))
+    }
+
+    /// Column names and `information_schema` data types of the notes table.
+    pub async fn notes_columns(&mut self) -> Result<Vec<(String, String)>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "SELECT column_name::text, data_type::text FROM information_schema.columns
+                         WHERE table_name = 'notes' AND table_schema = current_schema()",
+                        &[],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// Fetches every note without holding on to `self`, so the caller can spawn
+    /// the query and keep drawing. Hand the result to `settle` when it arrives.
+    /// Content is left out; fetch it with `get_note_content` or `load_contents`.
+    ///
+    /// Notes are read `NOTE_PAGE_SIZE` at a time, in id order, so only a page of
+    /// rows is held at once; the first page goes to `first_page` as soon as it is in.
+    /// Read-only connections (safe mode) read only the columns every schema has,
+    /// so a database whose migrations failed can still be browsed.
+    pub fn load_notes(
+        &self,
+        first_page: Option<oneshot::Sender<Vec<Note>>>,
+    ) -> Result<
+        impl Future<Output = Result<Vec<Note>, tokio_postgres::Error>> + Send + 'static,
+        Error,
+    > {
+        let client = self.background_client()?;
+        let basic = self.read_only;
+        Ok(async move {
+            let started = Instant::now();
+            let mut first_page = first_page;
+            let mut notes = Vec::new();
+            loop {
+                let after = notes.last().map_or(i32::MIN, |n: &Note| n.id);
+                let params: [&(dyn ToSql + Sync); 2] = [&after, &NOTE_PAGE_SIZE];
+                let rows = client
+                    .query_raw(
+                        if basic {
+                            // Columns later schemas added are read through
+                            // jsonb, which yields NULL where they don't exist.
+                            "SELECT id, title, tags, octet_length(COALESCE(content, '')),
+                                    COALESCE((extra ->> 'archived')::BOOLEAN, FALSE),
+                                    COALESCE((extra ->> 'encrypted')::BOOLEAN, FALSE),
+                                    (extra ->> 'created_at')::TIMESTAMPTZ,
+                                    (extra ->> 'updated_at')::TIMESTAMPTZ,
+                                    (extra ->> 'deleted_at')::TIMESTAMPTZ
+                             FROM (SELECT *, to_jsonb(notes) - 'content' AS extra FROM notes
+                                   WHERE id > $1 ORDER BY id LIMIT $2) AS notes
+                             ORDER BY id"
+                        } else {
+                            "SELECT id, title, tags, archived, archived_at, updated_at, encrypted,
+                                    ARRAY(SELECT tag FROM pins WHERE pins.note_id = notes.id),
+                                    remind_at, created_at, supersedes, deleted_at, notebook_id,
+                                    edit_inline, pinned, favorite, note_type,
+                                    octet_length(COALESCE(content, ''))
+                             FROM notes WHERE id > $1 ORDER BY id LIMIT $2"
+                        },
+                        params,
+                    )
+                    .await?;
+                let mut rows = std::pin::pin!(rows);
+                let page_start = notes.len();
+                while let Some(row) = rows.try_next().await? {
+                    notes.push(if basic {
+                        basic_note_from_row(&row)?
+                    } else {
+                        note_from_row(&row)?
+                    });
+                }
+                if let Some(sender) = first_page.take() {
+                    let _ = sender.send(notes.clone());
+                }
+                if ((notes.len() - page_start) as i64) < NOTE_PAGE_SIZE {
+                    break;
+                }
+            }
+
+            if basic {
+                return Ok(notes);
+            }
+            let rows = client
+                .query(
+                    "SELECT note_id,
+                            floor(extract(epoch FROM now() - saved_at) / 604800)::INTEGER,
+                            SUM(words_added), SUM(words_removed)
+                     FROM note_revisions
+                     WHERE saved_at > now() - make_interval(weeks => $1) AND words_added IS NOT NULL
+                     GROUP BY 1, 2",
+                    &[&(ACTIVITY_WEEKS as i32)],
+                )
+                .await?;
+            let mut activity: HashMap<i32, EditActivity> = HashMap::new();
+            for row in rows {
+                let weeks_ago: i32 = row.get(1);
+                let (added, removed): (i64, i64) = (row.get(2), row.get(3));
+                let entry = activity.entry(row.get(0)).or_default();
+                entry.words_added += added;
+                entry.words_removed += removed;
+                if let Some(week) = (ACTIVITY_WEEKS - 1).checked_sub(weeks_ago as usize) {
+                    entry.weekly[week] += added + removed;
+                }
+            }
+            for note in &mut notes {
+                if let Some(activity) = activity.remove(&note.id) {
+                    note.activity = activity;
+                }
+            }
+            let elapsed = started.elapsed();
+            if elapsed >= SLOW_CALL {
+                log::warn(&format!(
+                    "Slow note list load: took {}",
+                    format_duration(elapsed)
+                ));
+            }
+            Ok(notes)
+        })
+    }
+
+    /// The client for work spawned off the event loop, e.g. `load_notes`. Its
+    /// result goes through `settle` once it is back.
+    fn background_client(&self) -> Result<Arc<Client>, Error> {
+        self.check_breaker()?;
+        if self.client.is_closed() {
+            return Err(Error::Reconnecting);
+        }
+        Ok(Arc::clone(&self.client))
+    }
+
+    /// A copy of this handle for one call spawned off the event loop, e.g. by
+    /// `AppState::spawn_call`: same connection, but no notifications, no
+    /// reconnecting and a breaker of its own. Its result goes through
+    /// `settle_detached` once it is back.
+    pub fn detached(&self) -> Result<Database, Error> {
+        Ok(Self {
+            client: self.background_client()?,
+            notifications: mpsc::unbounded_channel().1,
+            pid: self.pid,
+            breaker: CircuitBreaker::default(),
+            db_url: self.db_url.clone(),
+            tls: self.tls.clone(),
+            read_only: self.read_only,
+            reconnect: None,
+            slow_time: Duration::ZERO,
+        })
+    }
+
+    /// The note's content; `None` if there is no such note.
+    pub async fn get_note_content(&mut self, id: i32) -> Result<Option<String>, Error> {
+        self.run(async |client| note_content(client, id).await)
+            .await
+    }
+
+    /// `get_note_content` to run in the background, like `load_notes`.
+    pub fn fetch_note_content(
+        &self,
+        id: i32,
+    ) -> Result<
+        impl Future<Output = Result<Option<String>, tokio_postgres::Error>> + Send + 'static,
+        Error,
+    > {
+        let client = self.background_client()?;
+        Ok(async move { note_content(&client, id).await })
+    }
+
+    /// Content of the notes `ids`, by id.
+    pub async fn get_note_contents(&mut self, ids: &[i32]) -> Result<HashMap<i32, String>, Error> {
+        self.run(async |client| note_contents(client, ids).await)
+            .await
+    }
+
+    /// Fills in the content of every note in `notes` that has none yet, in one query.
+    pub async fn load_contents(&mut self, notes: &mut [Note]) -> Result<(), Error> {
+        let ids: Vec<i32> = notes
+            .iter()
+            .filter(|n| !n.content_loaded)
+            .map(|n| n.id)
+            .collect();
+        let mut contents = self.get_note_contents(&ids).await?;
+        for note in notes.iter_mut().filter(|n| !n.content_loaded) {
+            if let Some(content) = contents.remove(&note.id) {
+                note.content = content;
+                note.content_loaded = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Searches in the background, like `load_notes`: the ids of the notes whose
+    /// title, a tag, or content unless encrypted, contains `query` in any case
+    /// (none for an empty one), and the content of those among `unloaded`. With
+    /// `all_content`, that of all of `unloaded`, for matching phrases locally.
+    pub fn search_notes(
+        &self,
+        query: &str,
+        unloaded: Vec<i32>,
+        all_content: bool,
+    ) -> Result<
+        impl Future<Output = Result<SearchMatches, tokio_postgres::Error>> + Send + 'static,
+        Error,
+    > {
+        let client = self.background_client()?;
+        let pattern = (!query.is_empty()).then(|| {
+            format!(
+                "%{}%",
+                query
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_")
+            )
+        });
+        Ok(async move {
+            let ids: HashSet<i32> = match pattern {
+                Some(pattern) => client
+                    .query(
+                        "SELECT id FROM notes
+                         WHERE title ILIKE $1
+                            OR EXISTS (SELECT 1 FROM unnest(tags) AS tag WHERE tag ILIKE $1)
+                            OR (NOT COALESCE(encrypted, FALSE) AND content ILIKE $1)",
+                        &[&pattern],
+                    )
+                    .await?
+                    .iter()
+                    .map(|row| row.get(0))
+                    .collect(),
+                None => HashSet::new(),
+            };
+            // Matches show a snippet, which needs their content.
+            let wanted: Vec<i32> = unloaded
+                .into_iter()
+                .filter(|id| all_content || ids.contains(id))
+                .collect();
+            let contents = note_contents(&client, &wanted).await?;
+            Ok((ids, contents))
+        })
+    }
+
+    /// Notes of a read-only source. Only the columns every pgnotes schema has are read,
+    /// so sources running older versions still work.
+    pub async fn get_source_notes(&mut self, source: &str) -> Result<Vec<Note>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "SELECT id, title, COALESCE(content, ''), tags, COALESCE(archived, FALSE)
+                         FROM notes ORDER BY id",
+                        &[],
+                    )
+                    .await
+            })
+            .await?;
+        let mut notes = Vec::with_capacity(rows.len());
+        for row in rows {
             notes.push(Note {
                 id: row.get(0),
                 title: row.get(1),
+                size: row.get::<_, String>(2).len(),
                 content: row.get(2),
-                tags: row.get(3),
+                content_loaded: true,
+                tags: row.try_get(3)?,
                 archived: row.get(4),
+                archived_at: None,
+                updated_at: None,
+                encrypted: false,
+                pinned_in: Vec::new(),
+                remind_at: None,
+                created_at: None,
+                supersedes: None,
+                deleted_at: None,
+                notebook_id: None,
+                edit_inline: false,
+                pinned: false,
+                favorite: false,
+                note_type: NoteType::Note,
+                source: Some(source.to_string()),
+                activity: EditActivity::default(),
             });
         }
         Ok(notes)
     }
 
-    pub fn create_note(&mut self, title: &str) -> Result<(), Error> {
-        self.client.execute(
-            "INSERT INTO notes (title, content, tags) VALUES ($1, '', '{}')",
-            &[&title],
-        )?;
+    /// Returns the new note's id.
+    pub async fn create_note(&mut self, title: &str) -> Result<i32, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_one(
+                        "INSERT INTO notes (title, content, tags, content_hash)
+                         VALUES ($1, '', '{}', sha256_hex('')) RETURNING id",
+                        &[&title],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(row.get(0))
+    }
+
+    /// Inserts a note that starts out with `content` and `tags`, as captured from a pipe.
+    pub async fn create_note_with_content(
+        &mut self,
+        title: &str,
+        content: &str,
+        tags: &[String],
+    ) -> Result<i32, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_one(
+                        "INSERT INTO notes (title, content, tags, content_hash)
+                         VALUES ($1, $2, $3, sha256_hex($2)) RETURNING id",
+                        &[&title, &content, &tags],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(row.get(0))
+    }
+
+    /// Inserts `notes`, resolving titles that are already taken (or repeat within
+    /// `notes`) as `collision` says. A title is taken by trashed notes too; one
+    /// overwritten comes out of the trash. With `skip_duplicates`, notes whose
+    /// content a note already has (or an earlier one in `notes`) are left out;
+    /// blank ones never are. The writes are one statement, so a failure imports
+    /// nothing.
+    pub async fn import_notes(
+        &mut self,
+        notes: &[ImportedNote],
+        collision: TitleCollision,
+        skip_duplicates: bool,
+    ) -> Result<ImportReport, Error> {
+        /// Where a title went: to a note already there, or to the `writes` entry
+        /// that creates it.
+        enum Taken {
+            Existing(i32),
+            Planned(usize),
+        }
+        /// A note to write: over `target`, else as a new note titled `title`.
+        struct Write<'a> {
+            target: Option<i32>,
+            title: String,
+            note: &'a ImportedNote,
+        }
+
+        self.run(async |client| {
+            let mut titles: HashMap<String, Taken> = client
+                .query("SELECT title, id FROM notes", &[])
+                .await?
+                .iter()
+                .map(|row| (row.get(0), Taken::Existing(row.get(1))))
+                .collect();
+            let mut hashes: HashSet<String> = client
+                .query(
+                    "SELECT content_hash FROM notes
+                     WHERE deleted_at IS NULL AND content_hash IS NOT NULL",
+                    &[],
+                )
+                .await?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            let mut report = ImportReport::default();
+            let mut writes: Vec<Write> = Vec::new();
+            for note in notes {
+                if skip_duplicates && !note.content.trim().is_empty() {
+                    let hash = crypto::content_hash(&note.content);
+                    if hashes.contains(&hash) {
+                        report.duplicates.push(note.title.clone());
+                        continue;
+                    }
+                    hashes.insert(hash);
+                }
+                let mut title = note.title.clone();
+                if let Some(taken) = titles.get(&title) {
+                    match collision {
+                        TitleCollision::Skip => {
+                            report.skipped += 1;
+                            continue;
+                        }
+                        TitleCollision::Overwrite => {
+                            // Overwriting the same note twice keeps the later one.
+                            match *taken {
+                                Taken::Planned(i) => writes[i].note = note,
+                                Taken::Existing(id) => {
+                                    match writes.iter_mut().find(|w| w.target == Some(id)) {
+                                        Some(earlier) => earlier.note = note,
+                                        None => writes.push(Write {
+                                            target: Some(id),
+                                            title,
+                                            note,
+                                        }),
+                                    }
+                                }
+                            }
+                            report.overwritten += 1;
+                            continue;
+                        }
+                        TitleCollision::Rename => {
+                            title = (2..)
+                                .map(|n| format!("{} ({})", note.title, n))
+                                .find(|t| !titles.contains_key(t))
+                                .unwrap_or_default();
+                            report.renamed += 1;
+                        }
+                    }
+                } else {
+                    report.created += 1;
+                }
+                titles.insert(title.clone(), Taken::Planned(writes.len()));
+                writes.push(Write {
+                    target: None,
+                    title,
+                    note,
+                });
+            }
+            if writes.is_empty() {
+                return Ok(report);
+            }
+
+            // Ragged TEXT[][] can't be passed, so each note's tags travel as JSON.
+            let tags: Vec<String> = writes
+                .iter()
+                .map(|w| serde_json::Value::from(w.note.tags.clone()).to_string())
+                .collect();
+            let rows = client
+                .query(
+                    "WITH input AS (
+                         SELECT target, title, content, encrypted, archived,
+                                created_at, updated_at,
+                                ARRAY(SELECT jsonb_array_elements_text(tags::JSONB)) AS tags
+                         FROM unnest($1::INT[], $2::TEXT[], $3::TEXT[], $4::TEXT[],
+                                     $5::BOOL[], $6::BOOL[], $7::TIMESTAMPTZ[], $8::TIMESTAMPTZ[])
+                              WITH ORDINALITY AS t(target, title, content, tags, encrypted,
+                                                   archived, created_at, updated_at, n)
+                         ORDER BY n
+                     ),
+                     snapshot AS (
+                         INSERT INTO note_revisions (note_id, content, encrypted)
+                         SELECT n.id, n.content, COALESCE(n.encrypted, FALSE)
+                         FROM notes n JOIN input i ON n.id = i.target
+                     ),
+                     overwritten AS (
+                         UPDATE notes n SET content = i.content, tags = i.tags,
+                             encrypted = i.encrypted, content_hash = sha256_hex(i.content),
+                             updated_at = COALESCE(i.updated_at, now()), deleted_at = NULL
+                         FROM input i WHERE n.id = i.target
+                     )
+                     INSERT INTO notes
+                         (title, content, tags, encrypted, archived, archived_at,
+                          created_at, updated_at, content_hash)
+                     SELECT title, content, tags, encrypted, archived,
+                            CASE WHEN archived THEN now() END,
+                            COALESCE(created_at, now()), COALESCE(updated_at, now()),
+                            sha256_hex(content)
+                     FROM input WHERE target IS NULL
+                     RETURNING title, id",
+                    &[
+                        &writes.iter().map(|w| w.target).collect::<Vec<_>>(),
+                        &writes.iter().map(|w| w.title.as_str()).collect::<Vec<_>>(),
+                        &writes
+                            .iter()
+                            .map(|w| w.note.content.as_str())
+                            .collect::<Vec<_>>(),
+                        &tags,
+                        &writes.iter().map(|w| w.note.encrypted).collect::<Vec<_>>(),
+                        &writes.iter().map(|w| w.note.archived).collect::<Vec<_>>(),
+                        &writes.iter().map(|w| w.note.created_at).collect::<Vec<_>>(),
+                        &writes.iter().map(|w| w.note.updated_at).collect::<Vec<_>>(),
+                    ],
+                )
+                .await?;
+            let created: HashMap<String, i32> =
+                rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+            report.ids = writes
+                .iter()
+                .filter(|w| w.target.is_none())
+                .filter_map(|w| created.get(&w.title).copied())
+                .collect();
+            Ok(report)
+        })
+        .await
+    }
+
+    /// Notes whose title contains `title` or is contained in it, ignoring case,
+    /// spacing and punctuation; closest in length first.
+    pub async fn similar_titles(&mut self, title: &str) -> Result<Vec<(i32, String)>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "WITH input AS (SELECT regexp_replace(lower($1), '[^[:alnum:]]', '', 'g') AS key),
+                              candidates AS (
+                                  SELECT id, title,
+                                         regexp_replace(lower(title), '[^[:alnum:]]', '', 'g') AS key
+                                  FROM notes WHERE deleted_at IS NULL
+                              )
+                         SELECT c.id, c.title FROM candidates c, input i
+                         WHERE c.key <> '' AND (strpos(c.key, i.key) > 0 OR strpos(i.key, c.key) > 0)
+                         ORDER BY abs(length(c.key) - length(i.key)), c.title
+                         LIMIT 3",
+                        &[&title],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// Saves the current content as a revision, with the words this edit adds and
+    /// removes, then replaces it. With `loaded_at`, the note must still have that
+    /// `updated_at`; returns `false` without writing when it changed in the meantime.
+    pub async fn update_note_content(
+        &mut self,
+        id: i32,
+        content: &str,
+        loaded_at: Option<DateTime<Utc>>,
+    ) -> Result<bool, Error> {
+        let updated = self.run(async |client| {
+            // Ciphertext has no words to count.
+            let (added, removed) = match client
+                .query_opt(
+                    "SELECT content, COALESCE(encrypted, FALSE) FROM notes WHERE id = $1",
+                    &[&id],
+                )
+                .await?
+            {
+                Some(row) if !row.get::<_, bool>(1) => {
+                    let old: Option<String> = row.get(0);
+                    let (added, removed) = diff::word_changes(old.as_deref().unwrap_or(""), content);
+                    (Some(added), Some(removed))
+                }
+                _ => (None, None),
+            };
+            client
+                .execute(
+                    "WITH current AS (
+                         SELECT * FROM notes
+                         WHERE id = $2 AND ($5::timestamptz IS NULL OR updated_at = $5)
+                     ),
+                     snapshot AS (
+                         INSERT INTO note_revisions (note_id, content, encrypted, words_added, words_removed)
+                         SELECT id, content, COALESCE(encrypted, FALSE), $3, $4 FROM current
+                         WHERE content IS DISTINCT FROM $1
+                     )
+                     UPDATE notes SET content = $1, content_hash = sha256_hex($1), updated_at = now()
+                     WHERE id IN (SELECT id FROM current)",
+                    &[&content, &id, &added, &removed, &loaded_at],
+                )
+                .await
+        })
+        .await?;
+        Ok(updated > 0)
+    }
+
+    /// A note's stored content, encryption flag and `updated_at`, to compare with
+    /// an edit that could not be saved.
+    pub async fn get_note_version(
+        &mut self,
+        id: i32,
+    ) -> Result<Option<(String, bool, Option<DateTime<Utc>>)>, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_opt(
+                        "SELECT content, COALESCE(encrypted, FALSE), updated_at
+                         FROM notes WHERE id = $1",
+                        &[&id],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(row.map(|row| (row.get(0), row.get(1), row.get(2))))
+    }
+
+    /// Earlier versions of a note, newest first.
+    pub async fn get_revisions(&mut self, note_id: i32) -> Result<Vec<Revision>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "SELECT id, content, encrypted, saved_at FROM note_revisions
+                         WHERE note_id = $1 ORDER BY saved_at DESC, id DESC",
+                        &[&note_id],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| Revision {
+                id: row.get(0),
+                content: row.get::<_, Option<String>>(1).unwrap_or_default(),
+                encrypted: row.get(2),
+                saved_at: row.get(3),
+            })
+            .collect())
+    }
+
+    pub async fn get_attachments(&mut self, note_id: i32) -> Result<Vec<Attachment>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "SELECT id, name, octet_length(data), created_at FROM attachments
+                         WHERE note_id = $1 ORDER BY name, id",
+                        &[&note_id],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| Attachment {
+                id: row.get(0),
+                name: row.get(1),
+                size: row.get::<_, i32>(2).into(),
+                created_at: row.get(3),
+            })
+            .collect())
+    }
+
+    pub async fn get_attachment_data(&mut self, id: i32) -> Result<Vec<u8>, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_one("SELECT data FROM attachments WHERE id = $1", &[&id])
+                    .await
+            })
+            .await?;
+        Ok(row.get(0))
+    }
+
+    pub async fn rename_attachment(&mut self, id: i32, name: &str) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE attachments SET name = $1 WHERE id = $2",
+                    &[&name, &id],
+                )
+                .await
+        })
+        .await?;
         Ok(())
     }
 
-    pub fn update_note_content(&mut self, id: i32, content: &str) -> Result<(), Error> {
-        self.client.execute(
-            "UPDATE notes SET content = $1 WHERE id = $2",
-            &[&content, &id],
-        )?;
+    pub async fn delete_attachment(&mut self, id: i32) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute("DELETE FROM attachments WHERE id = $1", &[&id])
+                .await
+        })
+        .await?;
         Ok(())
     }
 
-    pub fn update_note_tags(&mut self, id: i32, tags: &[String]) -> Result<(), Error> {
-        self.client
-            .execute("UPDATE notes SET tags = $1 WHERE id = $2", &[&tags, &id])?;
+    /// Puts a revision's content (and encryption state) back on its note. The content
+    /// being replaced is kept as a revision too, so a restore can itself be undone.
+    pub async fn restore_revision(&mut self, note_id: i32, revision_id: i32) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "WITH snapshot AS (
+                         INSERT INTO note_revisions (note_id, content, encrypted)
+                         SELECT id, content, COALESCE(encrypted, FALSE) FROM notes WHERE id = $1
+                     )
+                     UPDATE notes SET content = r.content, encrypted = r.encrypted,
+                         content_hash = sha256_hex(r.content), updated_at = now()
+                     FROM note_revisions r
+                     WHERE notes.id = $1 AND r.id = $2 AND r.note_id = $1",
+                    &[&note_id, &revision_id],
+                )
+                .await
+        })
+        .await?;
         Ok(())
     }
 
-    pub fn rename_note(&mut self, id: i32, new_title: &str) -> Result<(), Error> {
-        self.client.execute(
-            "UPDATE notes SET title = $1 WHERE id = $2",
-            &[&new_title, &id],
-        )?;
+    pub async fn update_note_tags(&mut self, id: i32, tags: &[String]) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET tags = $1, updated_at = now() WHERE id = $2",
+                    &[&tags, &id],
+                )
+                .await
+        })
+        .await?;
         Ok(())
     }
 
-    pub fn delete_note(&mut self, id: i32) -> Result<(), Error> {
-        self.client
-            .execute("DELETE FROM notes WHERE id = $1", &[&id])?;
+    /// Adds `add` to and removes `remove` from the tags of every note in `ids`, in one
+    /// statement, keeping each note's tag order. Returns how many notes changed.
+    pub async fn retag_notes(
+        &mut self,
+        ids: &[i32],
+        add: &[String],
+        remove: &[String],
+    ) -> Result<u64, Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET updated_at = now(), tags = ARRAY(
+                         SELECT tag FROM unnest(tags || $2::TEXT[]) WITH ORDINALITY AS t(tag, i)
+                         WHERE tag <> ALL($3::TEXT[])
+                         GROUP BY tag ORDER BY min(i))
+                     WHERE id = ANY($1)",
+                    &[&ids, &add, &remove],
+                )
+                .await
+        })
+        .await
+    }
+
+    /// Renames the tag `old` to `new` on every note, trashed ones included, along
+    /// with its pins and `tag_meta` entry; notes that already have `new` keep it
+    /// once, so renaming to an existing tag merges the two. One statement, so it
+    /// applies to all rows or none. Returns how many notes changed.
+    pub async fn rename_tag(&mut self, old: &str, new: &str) -> Result<u64, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_one(
+                        "WITH renamed AS (
+                             UPDATE notes SET updated_at = now(), tags = ARRAY(
+                                 SELECT tag FROM unnest(array_replace(tags, $1, $2))
+                                     WITH ORDINALITY AS t(tag, i)
+                                 GROUP BY tag ORDER BY min(i))
+                             WHERE $1 = ANY(tags)
+                             RETURNING id
+                         ), new_pins AS (
+                             INSERT INTO pins (note_id, tag)
+                             SELECT note_id, $2 FROM pins WHERE tag = $1
+                             ON CONFLICT DO NOTHING
+                         ), old_pins AS (
+                             DELETE FROM pins WHERE tag = $1
+                         ), new_meta AS (
+                             INSERT INTO tag_meta (name, color, parent)
+                             SELECT $2, color, parent FROM tag_meta WHERE name = $1
+                             ON CONFLICT (name) DO NOTHING
+                         ), old_meta AS (
+                             DELETE FROM tag_meta WHERE name = $1
+                         ), children AS (
+                             UPDATE tag_meta SET parent = $2 WHERE parent = $1 AND name <> $1
+                         )
+                         SELECT count(*) FROM renamed",
+                        &[&old, &new],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(row.get::<_, i64>(0) as u64)
+    }
+
+    /// Every tag of a note outside the trash, with how many such notes have it.
+    pub async fn tag_counts(&mut self) -> Result<Vec<(String, i64)>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "SELECT tag, count(*) FROM notes, unnest(tags) AS tag
+                         WHERE deleted_at IS NULL AND tag <> ''
+                         GROUP BY tag ORDER BY tag",
+                        &[],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// Removes `tag` from every note, trashed ones included, along with its pins
+    /// and `tag_meta` entry; tags under it lose their parent. One statement, like
+    /// `rename_tag`. Returns how many notes changed.
+    pub async fn delete_tag(&mut self, tag: &str) -> Result<u64, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_one(
+                        "WITH stripped AS (
+                             UPDATE notes SET updated_at = now(), tags = array_remove(tags, $1)
+                             WHERE $1 = ANY(tags)
+                             RETURNING id
+                         ), old_pins AS (
+                             DELETE FROM pins WHERE tag = $1
+                         ), old_meta AS (
+                             DELETE FROM tag_meta WHERE name = $1
+                         ), children AS (
+                             UPDATE tag_meta SET parent = NULL WHERE parent = $1 AND name <> $1
+                         )
+                         SELECT count(*) FROM stripped",
+                        &[&tag],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(row.get::<_, i64>(0) as u64)
+    }
+
+    pub async fn rename_note(&mut self, id: i32, new_title: &str) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET title = $1, updated_at = now() WHERE id = $2",
+                    &[&new_title, &id],
+                )
+                .await
+        })
+        .await?;
         Ok(())
     }
 
-    pub fn update_archive_status(&mut self, id: i32, archived: bool) -> Result<(), Error> {
-        self.client.execute(
-            "UPDATE notes SET archived = $1 WHERE id = $2",
-            &[&archived, &id],
-        )?;
+    /// Renames a note and rewrites `[[old_title]]` links in `linking_ids` in one statement.
+    /// Links are matched as `links::extract_links` reads them, so `[[ Old |text]]`
+    /// becomes `[[New|text]]` too. Returns how many linking notes changed.
+    pub async fn rename_note_with_links(
+        &mut self,
+        id: i32,
+        old_title: &str,
+        new_title: &str,
+        linking_ids: &[i32],
+    ) -> Result<u64, Error> {
+        // A backslash before any punctuation makes it literal in a Postgres regex.
+        let mut escaped = String::new();
+        for c in old_title.chars() {
+            if c.is_ascii_punctuation() {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        let pattern = format!(r"\[\[\s*{}\s*(\||\]\])", escaped);
+        let replacement = format!(r"[[{}\1", new_title.replace('\\', r"\\"));
+
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "WITH changed AS (
+                             SELECT id FROM notes WHERE id = ANY($3) AND content ~ $1
+                         )
+                         UPDATE notes
+                         SET title = CASE WHEN id = $4 THEN $5 ELSE title END,
+                             content = CASE WHEN id IN (SELECT id FROM changed)
+                                 THEN regexp_replace(content, $1, $2, 'g')
+                                 ELSE content END,
+                             content_hash = CASE WHEN id IN (SELECT id FROM changed)
+                                 THEN sha256_hex(regexp_replace(content, $1, $2, 'g'))
+                                 ELSE content_hash END,
+                             updated_at = now()
+                         WHERE id = $4 OR id IN (SELECT id FROM changed)
+                         RETURNING id IN (SELECT id FROM changed)",
+                        &[&pattern, &replacement, &linking_ids, &id, &new_title],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows.iter().filter(|row| row.get::<_, bool>(0)).count() as u64)
+    }
+
+    /// Creates `new_title` as the next version of note `id` (same tags, `content`,
+    /// `supersedes` pointing back) and archives the original, in one statement.
+    /// Returns the new note's id.
+    pub async fn supersede_note(
+        &mut self,
+        id: i32,
+        new_title: &str,
+        content: &str,
+    ) -> Result<i32, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_one(
+                        "WITH original AS (
+                             UPDATE notes
+                             SET archived = TRUE, archived_at = now(), updated_at = now()
+                             WHERE id = $3
+                         )
+                         INSERT INTO notes (title, content, tags, encrypted, supersedes, content_hash)
+                         SELECT $1, $2, tags, encrypted, id, sha256_hex($2) FROM notes WHERE id = $3
+                         RETURNING id",
+                        &[&new_title, &content, &id],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(row.get(0))
+    }
+
+    /// Moves a note to the trash; it can be restored until it is purged.
+    pub async fn delete_note(&mut self, id: i32) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute("UPDATE notes SET deleted_at = now() WHERE id = $1", &[&id])
+                .await
+        })
+        .await?;
         Ok(())
     }
+
+    pub async fn restore_note(&mut self, id: i32) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET deleted_at = NULL, updated_at = now() WHERE id = $1",
+                    &[&id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Moves every note in `ids` to the trash in one statement. Returns how many moved.
+    pub async fn delete_notes(&mut self, ids: &[i32]) -> Result<u64, Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET deleted_at = now() WHERE id = ANY($1)",
+                    &[&ids],
+                )
+                .await
+        })
+        .await
+    }
+
+    pub async fn restore_notes(&mut self, ids: &[i32]) -> Result<u64, Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET deleted_at = NULL, updated_at = now() WHERE id = ANY($1)",
+                    &[&ids],
+                )
+                .await
+        })
+        .await
+    }
+
+    /// Removes the trashed notes among `ids` for good, like `purge_note`.
+    pub async fn purge_notes(&mut self, ids: &[i32]) -> Result<u64, Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "DELETE FROM notes WHERE id = ANY($1) AND deleted_at IS NOT NULL",
+                    &[&ids],
+                )
+                .await
+        })
+        .await
+    }
+
+    /// Removes a trashed note for good, with its revisions, pins and presence.
+    pub async fn purge_note(&mut self, id: i32) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "DELETE FROM notes WHERE id = $1 AND deleted_at IS NOT NULL",
+                    &[&id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Marks a note as reviewed without changing its content.
+    pub async fn touch_note(&mut self, id: i32) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute("UPDATE notes SET updated_at = now() WHERE id = $1", &[&id])
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Replaces the content with its ciphertext (or plaintext) and flips the flag together.
+    pub async fn set_note_encrypted(
+        &mut self,
+        id: i32,
+        encrypted: bool,
+        content: &str,
+    ) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET content = $1, encrypted = $2, content_hash = sha256_hex($1),
+                         updated_at = now()
+                     WHERE id = $3",
+                    &[&content, &encrypted, &id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Sets, moves or (with `None`) clears the reminder on a note.
+    pub async fn set_reminder(
+        &mut self,
+        id: i32,
+        remind_at: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET remind_at = $1 WHERE id = $2",
+                    &[&remind_at, &id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Pins or unpins a note within `tag`. Returns `true` if the note is now pinned.
+    pub async fn toggle_pin(&mut self, id: i32, tag: &str) -> Result<bool, Error> {
+        self.run(async |client| {
+            let removed = client
+                .execute(
+                    "DELETE FROM pins WHERE note_id = $1 AND tag = $2",
+                    &[&id, &tag],
+                )
+                .await?;
+            if removed == 0 {
+                client
+                    .execute(
+                        "INSERT INTO pins (note_id, tag) VALUES ($1, $2)",
+                        &[&id, &tag],
+                    )
+                    .await?;
+            }
+            Ok(removed == 0)
+        })
+        .await
+    }
+
+    pub async fn get_smart_filters(&mut self) -> Result<Vec<String>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "SELECT expression FROM smart_filters ORDER BY expression",
+                        &[],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    pub async fn save_smart_filter(&mut self, expression: &str) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "INSERT INTO smart_filters (expression) VALUES ($1) ON CONFLICT DO NOTHING",
+                    &[&expression],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_smart_filter(&mut self, expression: &str) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "DELETE FROM smart_filters WHERE expression = $1",
+                    &[&expression],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_notebooks(&mut self) -> Result<Vec<Notebook>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query("SELECT id, name FROM notebooks ORDER BY name", &[])
+                    .await
+            })
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| Notebook {
+                id: row.get(0),
+                name: row.get(1),
+            })
+            .collect())
+    }
+
+    /// Returns the new notebook's id.
+    pub async fn create_notebook(&mut self, name: &str) -> Result<i32, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_one(
+                        "INSERT INTO notebooks (name) VALUES ($1) RETURNING id",
+                        &[&name],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(row.get(0))
+    }
+
+    /// Deletes the notebook; its notes become unfiled.
+    pub async fn delete_notebook(&mut self, id: i32) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute("DELETE FROM notebooks WHERE id = $1", &[&id])
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Moves the note into `notebook_id`, or out of any notebook with `None`.
+    pub async fn move_to_notebook(
+        &mut self,
+        note_id: i32,
+        notebook_id: Option<i32>,
+    ) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET notebook_id = $2 WHERE id = $1",
+                    &[&note_id, &notebook_id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_tag_meta(&mut self) -> Result<Vec<TagMeta>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "SELECT name, color, parent FROM tag_meta ORDER BY name",
+                        &[],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| TagMeta {
+                name: row.get(0),
+                color: row.get(1),
+                parent: row.get(2),
+            })
+            .collect())
+    }
+
+    /// Inserts or overwrites metadata for each tag in one statement. When a tag
+    /// appears more than once, the last entry wins.
+    pub async fn upsert_tag_meta(&mut self, tags: &[TagMeta]) -> Result<u64, Error> {
+        let by_name: BTreeMap<&str, &TagMeta> =
+            tags.iter().map(|tag| (tag.name.as_str(), tag)).collect();
+        let names: Vec<&str> = by_name.keys().copied().collect();
+        let colors: Vec<Option<&str>> = by_name.values().map(|t| t.color.as_deref()).collect();
+        let parents: Vec<Option<&str>> = by_name.values().map(|t| t.parent.as_deref()).collect();
+        self.run(async |client| {
+            client
+                .execute(
+                    "INSERT INTO tag_meta (name, color, parent)
+                     SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[])
+                     ON CONFLICT (name) DO UPDATE SET color = EXCLUDED.color, parent = EXCLUDED.parent",
+                    &[&names, &colors, &parents],
+                )
+                .await
+        })
+        .await
+    }
+
+    pub async fn update_archive_status(&mut self, id: i32, archived: bool) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET archived = $1,
+                        archived_at = CASE WHEN $1 THEN now() ELSE NULL END, updated_at = now()
+                     WHERE id = $2",
+                    &[&archived, &id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn update_archive_statuses(
+        &mut self,
+        ids: &[i32],
+        archived: bool,
+    ) -> Result<u64, Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET archived = $1,
+                        archived_at = CASE WHEN $1 THEN now() ELSE NULL END, updated_at = now()
+                     WHERE id = ANY($2) AND archived IS DISTINCT FROM $1",
+                    &[&archived, &ids],
+                )
+                .await
+        })
+        .await
+    }
+
+    /// Whether note `id` opens in the inline editor instead of the external one.
+    pub async fn set_edit_inline(&mut self, id: i32, inline: bool) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET edit_inline = $1 WHERE id = $2",
+                    &[&inline, &id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_pinned(&mut self, id: i32, pinned: bool) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET pinned = $1 WHERE id = $2",
+                    &[&pinned, &id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_favorite(&mut self, id: i32, favorite: bool) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET favorite = $1 WHERE id = $2",
+                    &[&favorite, &id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Makes note `id` a `note_type` note and adds the type's default tags.
+    pub async fn set_note_type(&mut self, id: i32, note_type: NoteType) -> Result<(), Error> {
+        let tags: Vec<&str> = note_type.default_tags().to_vec();
+        self.run(async |client| {
+            client
+                .execute(
+                    "UPDATE notes SET note_type = $1, updated_at = now(), tags = ARRAY(
+                         SELECT tag FROM unnest(tags || $2::TEXT[]) WITH ORDINALITY AS t(tag, i)
+                         GROUP BY tag ORDER BY min(i))
+                     WHERE id = $3",
+                    &[&note_type.name(), &tags, &id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Every note's id, title, content and the hash stored when pgnotes last wrote
+    /// the content; `None` for notes written before hashes were kept.
+    pub async fn content_hashes(
+        &mut self,
+    ) -> Result<Vec<(i32, String, String, Option<String>)>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "SELECT id, title, content, content_hash FROM notes ORDER BY id",
+                        &[],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+            .collect())
+    }
+
+    /// Replaces the inline editor's draft of note `id`.
+    pub async fn save_draft(&mut self, id: i32, content: &str) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "INSERT INTO drafts (note_id, content) VALUES ($1, $2)
+                     ON CONFLICT (note_id) DO UPDATE SET content = $2, saved_at = now()",
+                    &[&id, &content],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// The draft left of note `id` and when it was written.
+    pub async fn get_draft(&mut self, id: i32) -> Result<Option<(String, DateTime<Utc>)>, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_opt(
+                        "SELECT content, saved_at FROM drafts WHERE note_id = $1",
+                        &[&id],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(row.map(|row| (row.get(0), row.get(1))))
+    }
+
+    pub async fn delete_draft(&mut self, id: i32) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute("DELETE FROM drafts WHERE note_id = $1", &[&id])
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Titles of the notes with a draft left over, e.g. from a crash.
+    pub async fn draft_titles(&mut self) -> Result<Vec<String>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "SELECT notes.title FROM drafts JOIN notes ON notes.id = drafts.note_id
+                         ORDER BY drafts.saved_at",
+                        &[],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    /// Records that this connection has note `id` open in the editor and tells
+    /// other instances.
+    pub async fn announce_editing(&mut self, id: i32) -> Result<(), Error> {
+        let client_label = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "someone".to_string());
+        self.run(async |client| {
+            client
+                .execute(
+                    "INSERT INTO note_presence (note_id, pid, client) VALUES ($1, pg_backend_pid(), $2)
+                     ON CONFLICT (note_id, pid) DO UPDATE SET since = now()",
+                    &[&id, &client_label],
+                )
+                .await?;
+            client
+                .execute(
+                    "SELECT pg_notify($1, $2)",
+                    &[&PRESENCE_CHANNEL, &id.to_string()],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn clear_editing(&mut self, id: i32) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "DELETE FROM note_presence WHERE note_id = $1 AND pid = pg_backend_pid()",
+                    &[&id],
+                )
+                .await?;
+            client
+                .execute(
+                    "SELECT pg_notify($1, $2)",
+                    &[&PRESENCE_CHANNEL, &id.to_string()],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Takes the session advisory lock on note `id`. Returns `false` without
+    /// waiting when another session already holds it.
+    pub async fn try_lock_note(&mut self, id: i32) -> Result<bool, Error> {
+        let row = self
+            .run(async |client| {
+                client
+                    .query_one(
+                        "SELECT pg_try_advisory_lock($1, $2)",
+                        &[&EDIT_LOCK_SPACE, &id],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(row.get(0))
+    }
+
+    pub async fn unlock_note(&mut self, id: i32) -> Result<(), Error> {
+        self.run(async |client| {
+            client
+                .execute(
+                    "SELECT pg_advisory_unlock($1, $2)",
+                    &[&EDIT_LOCK_SPACE, &id],
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Notes open in other instances' editors. Rows left behind by sessions that
+    /// have since disconnected are ignored.
+    pub async fn get_presence(&mut self) -> Result<Vec<Presence>, Error> {
+        let rows = self
+            .run(async |client| {
+                client
+                    .query(
+                        "SELECT note_id, client, since FROM note_presence
+                         WHERE pid <> pg_backend_pid()
+                           AND pid IN (SELECT pid FROM pg_stat_activity)
+                         ORDER BY since",
+                        &[],
+                    )
+                    .await
+            })
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| Presence {
+                note_id: row.get(0),
+                client: row.get(1),
+                since: row.get(2),
+            })
+            .collect())
+    }
+
+    /// What other instances announced since the last call. Never waits.
+    pub fn take_notifications(&mut self) -> Notified {
+        let mut notified = Notified::default();
+        while let Ok(notification) = self.notifications.try_recv() {
+            match notification.channel() {
+                PRESENCE_CHANNEL => notified.presence = true,
+                CHANGES_CHANNEL if notification.process_id() != self.pid => {
+                    if let Ok(id) = notification.payload().parse() {
+                        notified.changed_notes.insert(id);
+                    }
+                }
+                _ => {}
+            }
+        }
+        notified
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.client.is_closed()
+    }
+
+    /// Opens a fresh connection and measures it. Meant to run in a background task;
+    /// the new `Database` can replace a connection that has gone away.
+    pub async fn test_connection(
+        db_url: &str,
+        tls: &TlsConfig,
+    ) -> std::io::Result<(Self, ConnectionReport)> {
+        let started = Instant::now();
+        let database = Self::new(db_url, tls).await?;
+        let connect_time = started.elapsed();
+
+        let started = Instant::now();
+        database
+            .client
+            .simple_query("SELECT 1")
+            .await
+            .map_err(std::io::Error::other)?;
+        let latency = started.elapsed();
+
+        let row = database
+            .client
+            .query_one(
+                "SELECT current_setting('server_version'), current_database()::text,
+                    current_user::text,
+                    host(inet_server_addr()) || ':' || inet_server_port()::text",
+                &[],
+            )
+            .await
+            .map_err(std::io::Error::other)?;
+
+        let report = ConnectionReport {
+            connect_time,
+            latency,
+            server_version: row.get(0),
+            database: row.get(1),
+            user: row.get(2),
+            address: row.get(3),
+        };
+        Ok((database, report))
+    }
+}
+
+/// Opens a connection, spawns the task that drives it and prepares the session:
+/// read-only, or listening for presence and note changes. The task forwards NOTIFY
+/// messages and ends once the client is dropped or the server goes away.
+/// Whether TLS is used follows the `sslmode` of `db_url`.
+async fn connect(db_url: &str, tls: &TlsConfig, read_only: bool) -> std::io::Result<Connection> {
+    let (db_url, verify) = split_verify_mode(db_url);
+    let mut config: tokio_postgres::Config = db_url.parse().map_err(std::io::Error::other)?;
+    if config.get_connect_timeout().is_none() {
+        config.connect_timeout(DEFAULT_CONNECT_TIMEOUT);
+    }
+    // A server that went away unannounced is noticed in about a minute, not
+    // after the system's two hours of TCP silence.
+    if !db_url.contains("keepalives_idle") {
+        config.keepalives_idle(KEEPALIVE_IDLE);
+    }
+    if !db_url.contains("keepalives_interval") {
+        config.keepalives_interval(KEEPALIVE_INTERVAL);
+    }
+    // And a query stuck behind a lock is cancelled by the server.
+    let options = config.get_options().unwrap_or_default();
+    if !options.contains("statement_timeout") {
+        let options = format!(
+            "{} -c statement_timeout={}",
+            options,
+            STATEMENT_TIMEOUT.as_millis()
+        );
+        config.options(options.trim_start());
+    }
+    let (client, mut connection) = config
+        .connect(tls_connector(tls, verify)?)
+        .await
+        .map_err(std::io::Error::other)?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(Ok(message)) = messages.next().await {
+            if let AsyncMessage::Notification(notification) = message {
+                let _ = tx.send(notification);
+            }
+        }
+    });
+
+    client
+        .batch_execute(if read_only {
+            "SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY"
+        } else {
+            "LISTEN pgnotes_presence; LISTEN pgnotes_changes"
+        })
+        .await
+        .map_err(std::io::Error::other)?;
+    let pid = client
+        .query_one("SELECT pg_backend_pid()", &[])
+        .await
+        .map_err(std::io::Error::other)?
+        .get(0);
+    Ok((client, rx, pid))
+}
+
+async fn note_content(client: &Client, id: i32) -> Result<Option<String>, tokio_postgres::Error> {
+    let row = client
+        .query_opt("SELECT content FROM notes WHERE id = $1", &[&id])
+        .await?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+async fn note_contents(
+    client: &Client,
+    ids: &[i32],
+) -> Result<HashMap<i32, String>, tokio_postgres::Error> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let rows = client
+        .query("SELECT id, content FROM notes WHERE id = ANY($1)", &[&ids])
+        .await?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// A row of `load_notes`, without content.
+fn note_from_row(row: &Row) -> Result<Note, tokio_postgres::Error> {
+    Ok(Note {
+        id: row.get(0),
+        title: row.get(1),
+        content: String::new(),
+        content_loaded: false,
+        // Older schemas created `tags` as TEXT; report that instead of panicking.
+        tags: row.try_get(2)?,
+        archived: row.get(3),
+        archived_at: row.get(4),
+        updated_at: row.get(5),
+        encrypted: row.get(6),
+        pinned_in: row.get(7),
+        remind_at: row.get(8),
+        created_at: row.get(9),
+        supersedes: row.get(10),
+        deleted_at: row.get(11),
+        notebook_id: row.get(12),
+        edit_inline: row.get(13),
+        pinned: row.get(14),
+        favorite: row.get(15),
+        note_type: NoteType::from_name(row.get(16)).unwrap_or_default(),
+        size: row.get::<_, i32>(17) as usize,
+        source: None,
+        activity: EditActivity::default(),
+    })
+}
+
+/// A row of `load_notes` in safe mode. What the schema may not have is left at
+/// its default.
+fn basic_note_from_row(row: &Row) -> Result<Note, tokio_postgres::Error> {
+    Ok(Note {
+        id: row.get(0),
+        title: row.get(1),
+        content: String::new(),
+        content_loaded: false,
+        tags: row.try_get(2)?,
+        size: row.get::<_, i32>(3) as usize,
+        archived: row.get(4),
+        encrypted: row.get(5),
+        created_at: row.get(6),
+        updated_at: row.get(7),
+        deleted_at: row.get(8),
+        archived_at: None,
+        pinned_in: Vec::new(),
+        remind_at: None,
+        supersedes: None,
+        notebook_id: None,
+        edit_inline: false,
+        pinned: false,
+        favorite: false,
+        note_type: NoteType::Note,
+        source: None,
+        activity: EditActivity::default(),
+    })
+}
+
+/// "1.4s", as slow calls are reported.
+pub fn format_duration(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
 }
 
+/// libpq's `sslmode`s that check the server certificate. tokio-postgres doesn't
+/// know them, so the connector does the checking.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Verify {
+    /// The certificate is signed by `ca_cert`.
+    Ca,
+    /// That, and it is for the host connected to.
+    Full,
+}
+
+/// `db_url` with `sslmode=verify-ca` or `verify-full` turned into `require`,
+/// which tokio-postgres can parse, and the verification they asked for.
+fn split_verify_mode(db_url: &str) -> (String, Option<Verify>) {
+    for (mode, verify) in [("verify-ca", Verify::Ca), ("verify-full", Verify::Full)] {
+        let option = format!("sslmode={}", mode);
+        if db_url.contains(&option) {
+            return (db_url.replace(&option, "sslmode=require"), Some(verify));
+        }
+    }
+    (db_url.to_string(), None)
+}
+
+/// Parses `db_url` as `connect` does, `verify-ca` and `verify-full` included.
+pub fn parse_url(db_url: &str) -> Result<tokio_postgres::Config, tokio_postgres::Error> {
+    split_verify_mode(db_url).0.parse()
+}
+
+/// Builds the TLS connector from the configured PEM files. Like libpq's `prefer`
+/// and `require`, the server certificate is only verified once `ca_cert` is set;
+/// until then TLS protects against eavesdropping but not impersonation.
+/// `verify-ca` and `verify-full` insist on `ca_cert` instead.
+fn tls_connector(tls: &TlsConfig, verify: Option<Verify>) -> std::io::Result<MakeTlsConnector> {
+    let read = |path: &str| {
+        let path = shellexpand::tilde(path).into_owned();
+        std::fs::read(&path)
+            .map_err(|e| std::io::Error::other(format!("Cannot read TLS file {}: {}", path, e)))
+    };
+
+    let mut builder = TlsConnector::builder();
+    match (&tls.ca_cert, verify) {
+        (Some(path), _) => {
+            let ca = Certificate::from_pem(&read(path)?).map_err(std::io::Error::other)?;
+            builder.add_root_certificate(ca);
+            // verify-ca trusts any host name the CA signed for.
+            builder.danger_accept_invalid_hostnames(verify == Some(Verify::Ca));
+        }
+        (None, Some(_)) => {
+            return Err(std::io::Error::other(
+                "sslmode=verify-ca and verify-full need tls.ca_cert to verify the server against",
+            ));
+        }
+        (None, None) => {
+            builder.danger_accept_invalid_certs(true);
+        }
+    }
+    match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => {
+            let identity =
+                Identity::from_pkcs8(&read(cert)?, &read(key)?).map_err(std::io::Error::other)?;
+            builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(std::io::Error::other(
+                "tls.client_cert and tls.client_key must be set together",
+            ));
+        }
+    }
+    let connector = builder.build().map_err(std::io::Error::other)?;
+    Ok(MakeTlsConnector::new(connector))
+}
@@ -0,0 +1,101 @@
+//! Line diff of an external edit, shown before it is saved, word counts of
+//! what an edit changed, and conflict markers for merging two edits.
+
+use similar::{ChangeTag, TextDiff};
+
+/// Unchanged lines kept around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Context(String),
+    Added(String),
+    Removed(String),
+    /// Unchanged lines left out between two changes.
+    Gap,
+}
+
+/// The changes from `old` to `new` with a few lines of context each.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let diff = TextDiff::from_lines(old, new);
+    let mut lines = Vec::new();
+    for (i, group) in diff.grouped_ops(CONTEXT).iter().enumerate() {
+        if i > 0 {
+            lines.push(DiffLine::Gap);
+        }
+        for op in group {
+            for change in diff.iter_changes(op) {
+                let text = change.value().trim_end_matches(['\n', '\r']).to_string();
+                lines.push(match change.tag() {
+                    ChangeTag::Equal => DiffLine::Context(text),
+                    ChangeTag::Insert => DiffLine::Added(text),
+                    ChangeTag::Delete => DiffLine::Removed(text),
+                });
+            }
+        }
+    }
+    lines
+}
+
+/// Added and removed line counts.
+pub fn stats(lines: &[DiffLine]) -> (usize, usize) {
+    lines
+        .iter()
+        .fold((0, 0), |(added, removed), line| match line {
+            DiffLine::Added(_) => (added + 1, removed),
+            DiffLine::Removed(_) => (added, removed + 1),
+            _ => (added, removed),
+        })
+}
+
+/// Words added and removed going from `old` to `new`.
+pub fn word_changes(old: &str, new: &str) -> (i32, i32) {
+    let diff = TextDiff::from_words(old, new);
+    let (mut added, mut removed) = (0, 0);
+    for change in diff.iter_all_changes() {
+        if change.value().trim().is_empty() {
+            continue;
+        }
+        match change.tag() {
+            ChangeTag::Insert => added += 1,
+            ChangeTag::Delete => removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    (added, removed)
+}
+
+/// `theirs` with each region `mine` changes wrapped in git-style conflict markers,
+/// for merging two edits of a note by hand.
+pub fn conflict_markers(theirs: &str, mine: &str) -> String {
+    let diff = TextDiff::from_lines(theirs, mine);
+    let mut out = String::new();
+    let mut ours = String::new();
+    let mut other = String::new();
+    let flush = |out: &mut String, ours: &mut String, other: &mut String| {
+        if ours.is_empty() && other.is_empty() {
+            return;
+        }
+        for (marker, side) in [("<<<<<<< mine\n", ours), ("=======\n", other)] {
+            out.push_str(marker);
+            out.push_str(side);
+            if !side.is_empty() && !side.ends_with('\n') {
+                out.push('\n');
+            }
+            side.clear();
+        }
+        out.push_str(">>>>>>> theirs\n");
+    };
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Equal => {
+                flush(&mut out, &mut ours, &mut other);
+                out.push_str(change.value());
+            }
+            ChangeTag::Delete => other.push_str(change.value()),
+            ChangeTag::Insert => ours.push_str(change.value()),
+        }
+    }
+    flush(&mut out, &mut ours, &mut other);
+    out
+}
@@ -1,10 +1,30 @@
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    event::{DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
     execute,
     terminal::{EnterAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use ratatui::{Terminal, backend::Backend};
-use std::{io, path::Path, process::Command};
+use std::{
+    io,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Opens `file_path` in the desktop's default application for it, without waiting.
+pub fn open_with_system(file_path: &Path) -> io::Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    Command::new(opener)
+        .arg(file_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
 
 /// Opens an external editor (vim, nano, etc.) for the given file path.
 /// Handles the terminal state transitions required to exit and re-enter the TUI.
@@ -27,7 +47,9 @@ pub fn open_editor<B: Backend + io::Write>(
     execute!(
         terminal.backend_mut(),
         EnterAlternateScreen,
-        EnableMouseCapture
+        EnableMouseCapture,
+        // Editors commonly switch bracketed paste off when they exit.
+        EnableBracketedPaste
     )?;
     terminal.clear()?; // Force a full redraw to clear artifacts
 
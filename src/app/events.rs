@@ -1,55 +1,800 @@
 use crate::app::{
+    clip,
+    clipboard::CopyFormat,
+    crypto,
     db::Database,
+    diff,
     editor::open_editor,
-    state::{AppState, InputMode},
+    export::ExportFormat,
+    filter_expr,
+    links::ReportEntry,
+    note_type::NoteType,
+    preview,
+    state::{
+        AppState, AttachmentAction, COMMANDS, Column, EditSession, InlineEdit, InputMode,
+        PassphrasePurpose, PendingRename, SortMode, TagFilter, ViewMode,
+    },
+    tag_editor::TagEditorAction,
+    text_area::TextArea,
+    ui::ui,
 };
+use chrono::{Local, TimeDelta, Utc};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{Terminal, backend::Backend};
-use std::{fs, io};
+use std::fs;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-fn edit_note_in_external_editor<B: Backend + io::Write>(
+async fn edit_note_in_external_editor<B: Backend + io::Write>(
     app: &mut AppState,
     db: &mut Database,
     terminal: &mut Terminal<B>,
 ) -> io::Result<()> {
+    if !app.ensure_selected_content(db).await {
+        return Ok(());
+    }
     // The ID is correct, so database operations will target the correct note.
-    let selection = app.get_selected_note().map(|n| (n.id, n.content.clone()));
+    let selection = app
+        .get_selected_note()
+        .map(|n| (n.id, n.title.clone(), n.updated_at, app.readable_content(n)));
 
-    if let Some((id, content)) = selection {
-        let temp_dir = std::env::temp_dir();
+    if let Some((id, title, loaded_at, content)) = selection {
+        let Some(content) = content else {
+            app.open_passphrase_prompt(PassphrasePurpose::Unlock(id));
+            return Ok(());
+        };
+        let session = EditSession {
+            id,
+            title,
+            passphrase: app.unlocked_notes.get(&id).map(|(_, p)| p.clone()),
+            original: content,
+            loaded_at,
+        };
+        let draft = session.original.clone();
+        edit_locked(app, db, terminal, session, &draft).await?;
+    }
+    Ok(())
+}
 
-        let temp_file_path = temp_dir.join(format!("pgnote_{}.txt", id));
-        fs::write(&temp_file_path, &content)?;
+/// Opens `draft` in the editor while holding the note's edit lock.
+async fn edit_locked<B: Backend + io::Write>(
+    app: &mut AppState,
+    db: &mut Database,
+    terminal: &mut Terminal<B>,
+    session: EditSession,
+    draft: &str,
+) -> io::Result<()> {
+    // The lock keeps two instances from editing the same note at once. When the
+    // database can't be asked, editing goes ahead and the save is queued as usual.
+    let id = session.id;
+    let locked = match db.try_lock_note(id).await {
+        Ok(false) => {
+            app.set_status(format!(
+                "'{}' is being edited in another instance; try again once it is saved.",
+                session.title
+            ));
+            return Ok(());
+        }
+        Ok(true) => true,
+        Err(_) => false,
+    };
+
+    let result = edit_and_save(app, db, terminal, session, draft).await;
+    if locked {
+        app.spawn_quiet(db, move |mut db| async move { db.unlock_note(id).await });
+    }
+    result
+}
+
+/// Round-trips `draft` through the editor via a temp file, then saves the result
+/// or, with `confirm.edit`, shows its diff against the original first.
+async fn edit_and_save<B: Backend + io::Write>(
+    app: &mut AppState,
+    db: &mut Database,
+    terminal: &mut Terminal<B>,
+    session: EditSession,
+    draft: &str,
+) -> io::Result<()> {
+    let id = session.id;
 
-        let success = open_editor(terminal, &temp_file_path, &app.editor_cmd)?;
+    // The extension lets the editor pick its syntax mode (.org, .sh, .csv, ...).
+    let note_type = app
+        .all_notes
+        .iter()
+        .find(|n| n.id == id)
+        .map_or_else(NoteType::default, |n| n.note_type);
+    let extension = preview::filetype(&session.title, &session.original, note_type);
+    // Decrypted notes pass through here too: the file is private to the user,
+    // has a name nobody can plant a symlink at beforehand, and is removed when
+    // `temp_file` is dropped, however this returns.
+    let mut temp_file = tempfile::Builder::new()
+        .prefix(&format!("pgnote_{}_", id))
+        .suffix(&format!(".{}", extension.as_deref().unwrap_or("txt")))
+        .tempfile()?;
+    temp_file.write_all(draft.as_bytes())?;
+    temp_file.flush()?;
+    let temp_file_path = temp_file.path();
 
-        if success {
-            let new_content = fs::read_to_string(&temp_file_path)?;
+    // Presence is advisory; failing to announce it must not block editing.
+    app.spawn_quiet(
+        db,
+        move |mut db| async move { db.announce_editing(id).await },
+    );
+    let success = open_editor(terminal, temp_file_path, &app.editor_cmd);
+    app.spawn_quiet(db, move |mut db| async move { db.clear_editing(id).await });
+    let success = success?;
 
-            if let Err(e) = db.update_note_content(id, &new_content) {
-                app.set_status(format!("Error saving note: {}", e));
-            } else {
-                app.set_status("Note saved.".to_string());
+    let result = if success {
+        match fs::read_to_string(temp_file_path) {
+            Ok(new_content) if new_content == session.original => {
+                app.set_status("No changes.".to_string());
+                Ok(())
+            }
+            Ok(new_content) if app.confirm.edit => {
+                app.open_edit_review(session, new_content);
+                Ok(())
             }
-        } else {
-            app.set_status("Editor exited with error.".to_string());
+            Ok(new_content) => save_edit(app, db, session, new_content),
+            Err(e) => Err(e),
         }
+    } else {
+        app.set_status("Editor exited with error.".to_string());
+        Ok(())
+    };
+
+    app.refresh_notes(db);
+    result
+}
+
+/// Opens the selected note in the inline editor, with the draft left by a crash
+/// in place of its content if there is one.
+async fn open_inline_editor(app: &mut AppState, db: &mut Database) {
+    // The editor can only show the real text, which screencasts must not.
+    if app.screencast {
+        app.set_status("Leave screencast mode with 'Shift+s' to edit inline.".to_string());
+        return;
+    }
+    if !app.ensure_selected_content(db).await {
+        return;
+    }
+    let selection = app
+        .get_selected_note()
+        .map(|n| (n.id, n.title.clone(), n.updated_at, app.readable_content(n)));
+    let Some((id, title, loaded_at, content)) = selection else {
+        return;
+    };
+    let Some(content) = content else {
+        app.open_passphrase_prompt(PassphrasePurpose::Unlock(id));
+        return;
+    };
+    let locked = match db.try_lock_note(id).await {
+        Ok(false) => {
+            app.set_status(format!(
+                "'{}' is being edited in another instance; try again once it is saved.",
+                title
+            ));
+            return;
+        }
+        Ok(true) => true,
+        Err(_) => false,
+    };
+    let session = EditSession {
+        id,
+        title,
+        passphrase: app.unlocked_notes.get(&id).map(|(_, p)| p.clone()),
+        original: content,
+        loaded_at,
+    };
+
+    let mut restored = None;
+    let mut text = session.original.clone();
+    if let Ok(Some((draft, saved_at))) = db.get_draft(id).await {
+        let draft = match &session.passphrase {
+            Some(passphrase) => crypto::decrypt(&draft, passphrase).ok(),
+            None => Some(draft),
+        };
+        if let Some(draft) = draft.filter(|d| *d != text) {
+            text = draft;
+            restored = Some(saved_at);
+        }
+    }
+
+    app.spawn_quiet(
+        db,
+        move |mut db| async move { db.announce_editing(id).await },
+    );
+    app.inline_edit = Some(InlineEdit {
+        session,
+        area: TextArea::new(&text),
+        locked,
+        draft_dirty: false,
+        draft_saved_at: None,
+        discard_armed: false,
+    });
+    app.push_mode(InputMode::InlineEdit);
+    app.set_status(match restored {
+        Some(saved_at) => format!(
+            "Restored the unsaved draft from {}. [Ctrl+s] save, [Esc] close",
+            saved_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ),
+        None => "[Ctrl+s] save, [Esc] close".to_string(),
+    });
+}
 
-        let _ = fs::remove_file(temp_file_path);
-        app.refresh_notes(db)?;
+/// Closes the inline editor, saving its text first with `save`. Either way the
+/// draft is done with; a save that fails is kept as a pending save instead.
+fn close_inline_editor(app: &mut AppState, db: &mut Database, save: bool) -> io::Result<()> {
+    let Some(edit) = app.inline_edit.take() else {
+        return Ok(());
+    };
+    app.pop_mode();
+    let id = edit.session.id;
+    let text = edit.area.text();
+    let result = if !save {
+        Ok(())
+    } else if text == edit.session.original {
+        app.set_status("No changes.".to_string());
+        Ok(())
+    } else {
+        save_edit(app, db, edit.session, text)
+    };
+
+    // Queued after the save, so the lock is held until it is in.
+    app.spawn_quiet(db, move |mut db| async move { db.delete_draft(id).await });
+    app.spawn_quiet(db, move |mut db| async move { db.clear_editing(id).await });
+    if edit.locked {
+        app.spawn_quiet(db, move |mut db| async move { db.unlock_note(id).await });
+    }
+    result
+}
+
+/// Opens the selected note in the editor it is set to use.
+async fn edit_selected<B: Backend + io::Write>(
+    app: &mut AppState,
+    db: &mut Database,
+    terminal: &mut Terminal<B>,
+) -> io::Result<()> {
+    if app.get_selected_note().is_some_and(|n| n.edit_inline) {
+        open_inline_editor(app, db).await;
+        Ok(())
+    } else {
+        edit_note_in_external_editor(app, db, terminal).await
     }
+}
+
+/// Writes an edit to the database off the event loop, re-encrypting it if the
+/// note is encrypted; a failed write is kept as a pending save, and an edit of a
+/// note that changed meanwhile goes to `ResolvingConflict`.
+fn save_edit(
+    app: &mut AppState,
+    db: &mut Database,
+    session: EditSession,
+    new_content: String,
+) -> io::Result<()> {
+    let id = session.id;
+    let stored = match &session.passphrase {
+        Some(passphrase) => crypto::encrypt(&new_content, passphrase)
+            .map_err(|e| io::Error::other(e.to_string()))?,
+        None => new_content.clone(),
+    };
+
+    let (written, loaded_at) = (stored.clone(), session.loaded_at);
+    app.spawn_call(
+        db,
+        move |mut db| async move { db.update_note_content(id, &written, loaded_at).await },
+        move |app, db, result| match result {
+            Ok(true) => {
+                if let Some(passphrase) = session.passphrase {
+                    app.unlocked_notes
+                        .insert(id, (new_content.clone(), passphrase));
+                }
+                app.pending_saves.retain(|p| p.session.id != id);
+                app.set_status("Note saved.".to_string());
+                app.refresh_notes(db);
+                app.lint_saved_content(&new_content);
+            }
+            Ok(false) => {
+                app.open_edit_conflict(db, session, new_content);
+                app.refresh_notes(db);
+            }
+            Err(e) => {
+                // Keep the edit around so it isn't lost with the temp file.
+                app.queue_pending_save(session, new_content, stored);
+                app.set_status(format!("Error saving note (kept as pending): {}", e));
+            }
+        },
+    );
     Ok(())
 }
 
-pub fn handle_key_event<B: Backend + io::Write>(
+/// Moves the selected note to the trash, or deletes it for good when it is already there.
+fn delete_selected(app: &mut AppState, db: &mut Database) {
+    let purge = app.view_mode == ViewMode::Trash;
+    let done = if purge {
+        "permanently deleted"
+    } else {
+        "moved to the trash ('v' to Trash, 'x' restores)"
+    };
+    let marked: Vec<i32> = app.marked_notes().iter().map(|n| n.id).collect();
+    if !marked.is_empty() {
+        app.spawn_call(
+            db,
+            move |mut db| async move {
+                if purge {
+                    db.purge_notes(&marked).await
+                } else {
+                    db.delete_notes(&marked).await
+                }
+            },
+            move |app, db, result| match result {
+                Ok(n) => {
+                    app.marked.clear();
+                    app.set_status(format!("{} note(s) {}.", n, done));
+                    app.refresh_notes(db);
+                }
+                Err(e) => app.set_status(format!("Error deleting notes: {}", e)),
+            },
+        );
+        return;
+    }
+    let selection = app.get_selected_note().map(|n| (n.id, n.title.clone()));
+    if let Some((id, title)) = selection {
+        app.spawn_call(
+            db,
+            move |mut db| async move {
+                if purge {
+                    db.purge_note(id).await
+                } else {
+                    db.delete_note(id).await
+                }
+            },
+            move |app, db, result| match result {
+                Ok(_) => {
+                    app.set_status(format!("Note '{}' {}.", title, done));
+                    app.refresh_notes(db);
+                }
+                Err(e) => app.set_status(format!("Error deleting note: {}", e)),
+            },
+        );
+    }
+}
+
+fn toggle_archive_selected(app: &mut AppState, db: &mut Database) {
+    let marked = app.marked_notes();
+    if !marked.is_empty() {
+        // Archive them all unless they all are already.
+        let archive = marked.iter().any(|n| !n.archived);
+        let ids: Vec<i32> = marked.iter().map(|n| n.id).collect();
+        app.spawn_call(
+            db,
+            move |mut db| async move { db.update_archive_statuses(&ids, archive).await },
+            move |app, db, result| match result {
+                Ok(n) => {
+                    app.marked.clear();
+                    let action = if archive { "archived" } else { "unarchived" };
+                    app.set_status(format!("{} note(s) {}.", n, action));
+                    app.refresh_notes(db);
+                }
+                Err(e) => app.set_status(format!("Error updating archive status: {}", e)),
+            },
+        );
+        return;
+    }
+    let selection = app
+        .get_selected_note()
+        .map(|n| (n.id, n.title.clone(), !n.archived));
+    if let Some((id, title, new_status)) = selection {
+        app.spawn_call(
+            db,
+            move |mut db| async move { db.update_archive_status(id, new_status).await },
+            move |app, db, result| match result {
+                Ok(_) => {
+                    let action = if new_status { "Archived" } else { "Unarchived" };
+                    app.set_status(format!("Note '{}' {}.", title, action));
+                    app.refresh_notes(db);
+                }
+                Err(e) => app.set_status(format!("Error updating archive status: {}", e)),
+            },
+        );
+    }
+}
+
+/// Applies a rename, optionally rewriting wikilinks in the notes listed in `rewrite`,
+/// and keeps the renamed note selected.
+fn finish_rename(
+    app: &mut AppState,
+    db: &mut Database,
+    id: i32,
+    new_title: &str,
+    rewrite: Option<&PendingRename>,
+) -> io::Result<()> {
+    let new_title = new_title.to_string();
+    let rewrite = rewrite.map(|rename| {
+        let ids: Vec<i32> = rename.linking_notes.iter().map(|(id, _)| *id).collect();
+        (rename.old_title.clone(), ids)
+    });
+    app.spawn_call(
+        db,
+        move |mut db| async move {
+            match rewrite {
+                Some((old_title, ids)) => db
+                    .rename_note_with_links(id, &old_title, &new_title, &ids)
+                    .await
+                    .map(|n| format!("Note renamed; links updated in {} note(s).", n)),
+                None => db
+                    .rename_note(id, &new_title)
+                    .await
+                    .map(|_| "Note renamed.".to_string()),
+            }
+        },
+        move |app, db, result| match result {
+            Ok(message) => {
+                app.set_status(message);
+                app.refresh_notes_and_select(db, id);
+            }
+            Err(e) => app.set_status(format!("Error renaming note: {}", e)),
+        },
+    );
+    Ok(())
+}
+
+/// Column navigation for the miller-columns layout. Returns `true` when the key
+/// was consumed; everything else falls through to the normal bindings.
+fn handle_columns_key(key: KeyEvent, app: &mut AppState) -> bool {
+    match key.code {
+        KeyCode::Char('h') | KeyCode::Left => app.focus_column_left(),
+        KeyCode::Char('l') | KeyCode::Right => app.focus_column_right(),
+        KeyCode::Char('j') | KeyCode::Char('k')
+            if key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            return false;
+        }
+        KeyCode::Char('j') | KeyCode::Down => match app.focused_column {
+            Column::Tags => app.move_column_filter(true),
+            Column::Notes => app.next(),
+            Column::Preview => app.scroll_preview_down(),
+        },
+        KeyCode::Char('k') | KeyCode::Up => match app.focused_column {
+            Column::Tags => app.move_column_filter(false),
+            Column::Notes => app.previous(),
+            Column::Preview => app.scroll_preview_up(),
+        },
+        KeyCode::Enter if app.focused_column == Column::Tags => {
+            app.focused_column = Column::Notes;
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Normal-mode keys that change the selected note.
+fn is_write_key(key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Enter => true,
+        KeyCode::Char(c) => "edrxutXAViIpf".contains(c),
+        _ => false,
+    }
+}
+
+/// Normal-mode keys refused in safe mode: writes, and keys that run other
+/// programs, open another connection or need features the schema may lack.
+fn is_unsafe_key(key: KeyEvent) -> bool {
+    is_write_key(key) || matches!(key.code, KeyCode::Char(c) if "anmwFP".contains(c))
+}
+
+/// Tells the user that safe mode is read-only, if it is on.
+fn refuse_in_safe_mode(app: &mut AppState) -> bool {
+    if app.safe_mode {
+        app.set_status(
+            "Safe mode is read-only; restart without --safe-mode to change notes.".to_string(),
+        );
+    }
+    app.safe_mode
+}
+
+/// Runs a `:` command line. Commands that have a key binding replay that key,
+/// so they ask, check and report exactly as the key does.
+async fn run_command<B: Backend + io::Write>(
+    line: &str,
+    app: &mut AppState,
+    db: &mut Database,
+    terminal: &mut Terminal<B>,
+) -> io::Result<bool> {
+    let (name, arg) = line.split_once(' ').unwrap_or((line, ""));
+    let arg = arg.trim();
+    let Some((name, _)) = COMMANDS
+        .iter()
+        .find(|(n, _)| n.starts_with(name) && !name.is_empty())
+    else {
+        if !name.is_empty() {
+            app.set_status(format!("Unknown command ':{}'.", name));
+        }
+        return Ok(true);
+    };
+
+    match (*name, arg) {
+        ("new", "") => press(KeyCode::Char('a'), app, db, terminal).await,
+        ("new", title) => {
+            press(KeyCode::Char('a'), app, db, terminal).await?;
+            if app.input_mode != InputMode::EditingFilename {
+                return Ok(true);
+            }
+            app.filename_input.set(title);
+            press(KeyCode::Enter, app, db, terminal).await
+        }
+        ("edit", _) => press(KeyCode::Char('e'), app, db, terminal).await,
+        ("rename", "") => press(KeyCode::Char('r'), app, db, terminal).await,
+        ("rename", title) => {
+            press(KeyCode::Char('r'), app, db, terminal).await?;
+            if app.input_mode != InputMode::RenamingScript {
+                return Ok(true);
+            }
+            app.filename_input.set(title);
+            press(KeyCode::Enter, app, db, terminal).await
+        }
+        ("delete", _) => press(KeyCode::Char('d'), app, db, terminal).await,
+        ("archive", _) => press(KeyCode::Char('x'), app, db, terminal).await,
+        ("view", _) => press(KeyCode::Char('v'), app, db, terminal).await,
+        ("reconnect", _) => press(KeyCode::Char('P'), app, db, terminal).await,
+        ("tags", _) => press(KeyCode::Char('G'), app, db, terminal).await,
+        ("help", _) => press(KeyCode::Char('?'), app, db, terminal).await,
+        ("quit", _) => press(KeyCode::Char('q'), app, db, terminal).await,
+        ("search", "") => press(KeyCode::Char('/'), app, db, terminal).await,
+        ("search", query) => {
+            app.search_query = query.to_string();
+            app.search_history.record(query);
+            app.run_search(db).await;
+            if !app.notes.is_empty() {
+                app.list_state.select(Some(0));
+            }
+            app.update_preview();
+            app.set_status(format!("Search applied: '{}'", query));
+            Ok(true)
+        }
+        ("filter", expr) => {
+            let expr = expr.replace('#', "");
+            let filter = match expr.trim() {
+                "" | "all" => TagFilter::All,
+                "untagged" => TagFilter::Untagged,
+                tag if !tag.contains(char::is_whitespace) => TagFilter::Specific(tag.to_string()),
+                source => match filter_expr::parse(source) {
+                    Ok(expr) => TagFilter::Expression {
+                        source: source.to_string(),
+                        expr,
+                    },
+                    Err(e) => {
+                        app.set_status(format!("Invalid filter: {}", e));
+                        return Ok(true);
+                    }
+                },
+            };
+            app.apply_filter(filter);
+            app.set_status(format!("Filter applied: {}", app.active_filter));
+            Ok(true)
+        }
+        ("type", "") => {
+            let names: Vec<&str> = NoteType::ALL.iter().map(|t| t.name()).collect();
+            match app.get_selected_note() {
+                Some(note) => app.set_status(format!(
+                    "'{}' is a {} note. Types: {}.",
+                    note.title,
+                    note.note_type.name(),
+                    names.join(", ")
+                )),
+                None => app.set_status(format!("Types: {}.", names.join(", "))),
+            }
+            Ok(true)
+        }
+        ("type", _) | ("tag" | "untag", _) if refuse_in_safe_mode(app) => Ok(true),
+        ("type", name) => {
+            if let Some(source) = app.selected_source() {
+                app.set_status(format!(
+                    "Notes from '{}' are read-only; changes only go to your own database.",
+                    source
+                ));
+                return Ok(true);
+            }
+            let Some(id) = app.get_selected_note().map(|n| n.id) else {
+                app.set_status("No note selected.".to_string());
+                return Ok(true);
+            };
+            let Some(note_type) = NoteType::from_name(name) else {
+                app.set_status(format!("Unknown note type '{}'.", name));
+                return Ok(true);
+            };
+            app.spawn_call(
+                db,
+                move |mut db| async move { db.set_note_type(id, note_type).await },
+                move |app, db, result| match result {
+                    Ok(()) => {
+                        app.set_status(format!("Note is now a {} note.", note_type.name()));
+                        app.refresh_notes(db);
+                    }
+                    Err(e) => app.set_status(format!("Error setting note type: {}", e)),
+                },
+            );
+            Ok(true)
+        }
+        ("tag" | "untag", "") => {
+            app.set_status(format!("Usage: :{} TAG...", name));
+            Ok(true)
+        }
+        ("tag" | "untag", tags) => {
+            if let Some(source) = app.selected_source() {
+                app.set_status(format!(
+                    "Notes from '{}' are read-only; changes only go to your own database.",
+                    source
+                ));
+                return Ok(true);
+            }
+            let Some((id, mut new_tags)) = app.get_selected_note().map(|n| (n.id, n.tags.clone()))
+            else {
+                app.set_status("No note selected.".to_string());
+                return Ok(true);
+            };
+            for tag in tags.split_whitespace().map(|t| t.trim_start_matches('#')) {
+                if *name == "tag" && !new_tags.iter().any(|t| t == tag) {
+                    new_tags.push(tag.to_string());
+                } else if *name == "untag" {
+                    new_tags.retain(|t| t != tag);
+                }
+            }
+            app.spawn_call(
+                db,
+                move |mut db| async move { db.update_note_tags(id, &new_tags).await },
+                move |app, db, result| match result {
+                    Ok(_) => {
+                        app.set_status("Tags updated.".to_string());
+                        app.refresh_notes(db);
+                    }
+                    Err(e) => app.set_status(format!("Error updating tags: {}", e)),
+                },
+            );
+            Ok(true)
+        }
+        _ => Ok(true),
+    }
+}
+
+/// Handles `code` as if it were pressed in the current mode.
+async fn press<B: Backend + io::Write>(
+    code: KeyCode,
+    app: &mut AppState,
+    db: &mut Database,
+    terminal: &mut Terminal<B>,
+) -> io::Result<bool> {
+    Box::pin(handle_key_event(KeyEvent::from(code), app, db, terminal)).await
+}
+
+/// Bracketed paste: goes into the focused text field, and a URL pasted onto
+/// the note list offers to become a link.
+pub async fn handle_paste(text: &str, app: &mut AppState, db: &mut Database) {
+    match app.input_mode {
+        InputMode::EditingFilename => {
+            app.filename_input.insert(text);
+            app.validate_title_input();
+            app.update_title_suggestions(db).await;
+        }
+        InputMode::RenamingScript => {
+            app.filename_input.insert(text);
+            app.validate_title_input();
+        }
+        InputMode::EditingTags => app.tag_editor.input.insert(text),
+        InputMode::EditingFilterExpression => app.filter_expr_input.insert(text),
+        InputMode::Command => app.command_input.insert(text),
+        InputMode::InlineEdit => {
+            if let Some(edit) = app.inline_edit.as_mut() {
+                edit.area.insert(text);
+                edit.draft_dirty = true;
+                edit.discard_armed = false;
+            }
+        }
+        InputMode::EditingDateRange => app.date_range_input.insert(text),
+        InputMode::RenamingTag => app.tag_rename_input.insert(text),
+        InputMode::NamingNotebook => app.notebook_input.insert(text),
+        InputMode::NamingAttachment => app.attachment_input.insert(text),
+        InputMode::Searching => {
+            app.search_query
+                .push_str(&text.lines().collect::<Vec<_>>().join(" "));
+            app.apply_current_filter();
+        }
+        InputMode::Normal if app.safe_mode => {
+            refuse_in_safe_mode(app);
+        }
+        InputMode::Normal => match clip::as_url(text) {
+            Some(url) => app.open_url_paste(url),
+            None => app
+                .set_status("Only URLs can be pasted here; paste text in the editor.".to_string()),
+        },
+        _ => {}
+    }
+}
+
+pub async fn handle_key_event<B: Backend + io::Write>(
     key: KeyEvent,
     app: &mut AppState,
     db: &mut Database,
     terminal: &mut Terminal<B>,
 ) -> io::Result<bool> {
+    if app.input_mode == InputMode::Locked {
+        match key.code {
+            KeyCode::Enter => app.try_unlock_screen(),
+            KeyCode::Esc => app.lock_input.clear(),
+            KeyCode::Backspace => {
+                app.lock_input.pop();
+            }
+            KeyCode::Char(c) => app.lock_input.push(c),
+            _ => {}
+        }
+        return Ok(true);
+    }
+    // Every key but moving on with j/k acts on the note the preview should show.
+    if !matches!(key.code, KeyCode::Char('j') | KeyCode::Char('k'))
+        || key.modifiers.contains(KeyModifiers::CONTROL)
+    {
+        app.settle_preview(true);
+    }
+    if key.code == KeyCode::Char('l') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        app.lock_screen();
+        return Ok(true);
+    }
+
+    // Layer-independent keys: help can open over any popup, and Ctrl+c
+    // unwinds the whole modal stack at once.
+    if key.code == KeyCode::F(1) && app.input_mode != InputMode::ShowHelp {
+        app.push_mode(InputMode::ShowHelp);
+        return Ok(true);
+    }
+    if key.code == KeyCode::Char('c')
+        && key.modifiers.contains(KeyModifiers::CONTROL)
+        && !matches!(app.input_mode, InputMode::Normal | InputMode::InlineEdit)
+    {
+        app.close_all_modes();
+        app.set_status("Closed all popups.".to_string());
+        return Ok(true);
+    }
+
+    if app.input_mode == InputMode::Normal && app.columns_view && handle_columns_key(key, app) {
+        return Ok(true);
+    }
+
+    if app.input_mode == InputMode::Normal && is_unsafe_key(key) && refuse_in_safe_mode(app) {
+        return Ok(true);
+    }
+    if app.input_mode == InputMode::Normal
+        && app.view_mode == ViewMode::Trash
+        && is_write_key(key)
+        && !matches!(key.code, KeyCode::Char('d') | KeyCode::Char('x'))
+    {
+        app.set_status("Restore the note with 'x' before changing it.".to_string());
+        return Ok(true);
+    }
+    if app.input_mode == InputMode::Normal
+        && is_write_key(key)
+        && let Some(source) = app.selected_source()
+    {
+        app.set_status(format!(
+            "Notes from '{}' are read-only; changes only go to your own database.",
+            source
+        ));
+        return Ok(true);
+    }
+
     match app.input_mode {
         InputMode::Normal => match key.code {
-            KeyCode::Char('q') => return Ok(false),
+            KeyCode::Char('q') => {
+                // A save still running may yet fail and become a pending one.
+                app.finish_calls(db).await;
+                if app.pending_saves.is_empty() {
+                    return Ok(false);
+                }
+                app.push_mode(InputMode::ConfirmingQuit);
+                app.set_status(format!(
+                    "{} unsaved change(s). [f] flush & quit, [y] quit anyway, [n] cancel",
+                    app.pending_saves.len()
+                ));
+            }
             KeyCode::Char('j') => {
                 if key.modifiers.contains(KeyModifiers::CONTROL) {
                     app.scroll_preview_down();
@@ -67,22 +812,107 @@ pub fn handle_key_event<B: Backend + io::Write>(
 
             KeyCode::Down => app.scroll_preview_down(),
             KeyCode::Up => app.scroll_preview_up(),
+            KeyCode::Left => app.scroll_preview_sideways(false),
+            KeyCode::Right => app.scroll_preview_sideways(true),
 
             KeyCode::Enter | KeyCode::Char('e') => {
-                edit_note_in_external_editor(app, db, terminal)?;
+                app.load_presence(db).await;
+                if let Some(warning) = app
+                    .concurrent_edit_warning()
+                    .filter(|_| app.confirm.conflict)
+                {
+                    app.push_mode(InputMode::ConfirmingConcurrentEdit);
+                    app.set_status(warning);
+                } else {
+                    edit_selected(app, db, terminal).await?;
+                }
             }
+            KeyCode::Char('i') => open_inline_editor(app, db).await,
+            KeyCode::Char('I') => {
+                let selection = app
+                    .get_selected_note()
+                    .map(|n| (n.id, n.title.clone(), !n.edit_inline));
+                if let Some((id, title, inline)) = selection {
+                    app.spawn_call(
+                        db,
+                        move |mut db| async move { db.set_edit_inline(id, inline).await },
+                        move |app, db, result| match result {
+                            Ok(()) => {
+                                app.set_status(if inline {
+                                    format!("'{}' now opens in the inline editor.", title)
+                                } else {
+                                    format!("'{}' now opens in the external editor.", title)
+                                });
+                                app.refresh_notes(db);
+                            }
+                            Err(e) => app.set_status(format!("Error: {}", e)),
+                        },
+                    );
+                }
+            }
+            KeyCode::Char('n') => app.open_new_from_template(db).await,
             KeyCode::Char('a') => {
-                app.input_mode = InputMode::EditingFilename;
+                app.new_from_template = None;
+                app.push_mode(InputMode::EditingFilename);
                 app.filename_input.clear();
+                app.input_error = None;
+                app.title_suggestions.clear();
                 app.set_status(
                     "Enter new note title. Press [Enter] to confirm, [Esc] to cancel.".to_string(),
                 );
             }
+            KeyCode::Char(' ') => app.toggle_mark(),
+            KeyCode::Char('w') => app.toggle_watch(),
+            KeyCode::Char('y') => app.open_copy_selector(),
+            KeyCode::Char('m') if !app.columns_view => {
+                app.set_status(
+                    "Open the tag sidebar with 'c' to move notes between tags.".to_string(),
+                );
+            }
+            KeyCode::Char('m')
+                if app.moving_note.is_some() && app.focused_column == Column::Tags =>
+            {
+                app.drop_note_on_tag(db);
+            }
+            KeyCode::Char('m') => app.pick_up_note(),
+            KeyCode::Esc if app.moving_note.is_some() => {
+                app.moving_note = None;
+                app.set_status("Move cancelled.".to_string());
+            }
+            KeyCode::Esc if !app.marked.is_empty() => {
+                app.marked.clear();
+                app.set_status("All notes unmarked.".to_string());
+            }
+            KeyCode::Char('d') if !app.marked.is_empty() => {
+                let count = app.marked.len();
+                if !app.confirm.delete && !app.confirm.bulk {
+                    delete_selected(app, db);
+                } else {
+                    app.push_mode(InputMode::ConfirmingDelete);
+                    app.set_status(if app.view_mode == ViewMode::Trash {
+                        format!(
+                            "Permanently delete {} marked notes? This cannot be undone. (y/n)",
+                            count
+                        )
+                    } else {
+                        format!("Move {} marked notes to the trash? (y/n)", count)
+                    });
+                }
+            }
             KeyCode::Char('d') => {
                 let selection = app.get_selected_note().map(|n| n.title.clone());
-                if let Some(title) = selection {
-                    app.input_mode = InputMode::ConfirmingDelete;
-                    app.set_status(format!("Delete '{}'? (y/n)", title));
+                if selection.is_some() && !app.confirm.delete {
+                    delete_selected(app, db);
+                } else if let Some(title) = selection {
+                    app.push_mode(InputMode::ConfirmingDelete);
+                    app.set_status(if app.view_mode == ViewMode::Trash {
+                        format!(
+                            "Permanently delete '{}'? This cannot be undone. (y/n)",
+                            title
+                        )
+                    } else {
+                        format!("Move '{}' to the trash? (y/n)", title)
+                    });
                 } else {
                     app.set_status("No note selected to delete.".to_string());
                 }
@@ -90,8 +920,9 @@ pub fn handle_key_event<B: Backend + io::Write>(
             KeyCode::Char('r') => {
                 let selection = app.get_selected_note().map(|n| n.title.clone());
                 if let Some(title) = selection {
-                    app.input_mode = InputMode::RenamingScript;
-                    app.filename_input = title;
+                    app.push_mode(InputMode::RenamingScript);
+                    app.filename_input.set_selected(title);
+                    app.input_error = None;
                     app.set_status(
                         "Enter new title. Press [Enter] to confirm, [Esc] to cancel.".to_string(),
                     );
@@ -100,17 +931,67 @@ pub fn handle_key_event<B: Backend + io::Write>(
                 }
             }
 
-            KeyCode::Char('x') => {
-                if let Some(note) = app.get_selected_note() {
-                    let new_status = !note.archived;
-                    match db.update_archive_status(note.id, new_status) {
-                        Ok(_) => {
-                            let action = if new_status { "Archived" } else { "Unarchived" };
-                            app.set_status(format!("Note '{}' {}.", note.title, action));
-                            app.refresh_notes(db)?;
+            KeyCode::Char('x') if app.view_mode == ViewMode::Trash && !app.marked.is_empty() => {
+                let ids: Vec<i32> = app.marked_notes().iter().map(|n| n.id).collect();
+                app.spawn_call(
+                    db,
+                    move |mut db| async move { db.restore_notes(&ids).await },
+                    move |app, db, result| match result {
+                        Ok(n) => {
+                            app.marked.clear();
+                            app.set_status(format!("{} note(s) restored from the trash.", n));
+                            app.refresh_notes(db);
                         }
-                        Err(e) => app.set_status(format!("Error updating archive status: {}", e)),
+                        Err(e) => app.set_status(format!("Error restoring notes: {}", e)),
+                    },
+                );
+            }
+            KeyCode::Char('x') if app.view_mode == ViewMode::Trash => {
+                let selection = app.get_selected_note().map(|n| (n.id, n.title.clone()));
+                if let Some((id, title)) = selection {
+                    app.spawn_call(
+                        db,
+                        move |mut db| async move { db.restore_note(id).await },
+                        move |app, db, result| match result {
+                            Ok(_) => {
+                                app.set_status(format!(
+                                    "Note '{}' restored from the trash.",
+                                    title
+                                ));
+                                app.refresh_notes(db);
+                            }
+                            Err(e) => app.set_status(format!("Error restoring note: {}", e)),
+                        },
+                    );
+                }
+            }
+            KeyCode::Char('x') if !app.marked.is_empty() => {
+                let marked = app.marked_notes();
+                let archive = marked.iter().any(|n| !n.archived);
+                let count = marked.len();
+                if (archive && app.confirm.archive) || app.confirm.bulk {
+                    app.push_mode(InputMode::ConfirmingArchive);
+                    app.set_status(if archive {
+                        format!("Archive {} marked notes? (y/n)", count)
+                    } else {
+                        format!("Unarchive {} marked notes? (y/n)", count)
+                    });
+                } else {
+                    toggle_archive_selected(app, db);
+                }
+            }
+            KeyCode::Char('x') => {
+                // Unarchiving loses nothing, so only archiving asks.
+                let selection = app
+                    .get_selected_note()
+                    .filter(|n| !n.archived)
+                    .map(|n| n.title.clone());
+                match selection {
+                    Some(title) if app.confirm.archive => {
+                        app.push_mode(InputMode::ConfirmingArchive);
+                        app.set_status(format!("Archive '{}'? (y/n)", title));
                     }
+                    _ => toggle_archive_selected(app, db),
                 }
             }
 
@@ -122,41 +1003,187 @@ pub fn handle_key_event<B: Backend + io::Write>(
                     app.list_state.select(Some(0));
                 }
                 app.update_preview();
-                let view_name = match app.view_mode {
-                    crate::app::state::ViewMode::Active => "Active Notes",
-                    crate::app::state::ViewMode::Archived => "Archived Notes",
-                };
-                app.set_status(format!("Switched to {}", view_name));
+                app.set_status(format!("Switched to {}", app.view_mode.label()));
             }
 
-            KeyCode::Char('t') => {
-                let current_tags = app.get_selected_note().map(|n| n.tags.join(", "));
+            KeyCode::Char('V') => app.supersede_selected(db).await,
 
-                if let Some(tags) = current_tags {
-                    app.input_mode = InputMode::EditingTags;
-                    app.filename_input = tags; // Pre-fill with current tags
-                    app.set_status(
-                        "Edit tags (comma separated). [Enter] save, [Esc] cancel.".to_string(),
+            KeyCode::Char('u') => {
+                let selection = app.get_selected_note().map(|n| (n.id, n.title.clone()));
+                if let Some((id, title)) = selection {
+                    app.spawn_call(
+                        db,
+                        move |mut db| async move { db.touch_note(id).await },
+                        move |app, db, result| match result {
+                            Ok(_) => {
+                                app.set_status(format!("Note '{}' marked as reviewed.", title));
+                                app.refresh_notes(db);
+                            }
+                            Err(e) => app.set_status(format!("Error touching note: {}", e)),
+                        },
                     );
-                } else {
-                    app.set_status("No note selected.".to_string());
                 }
             }
 
+            KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.open_quick_tags();
+            }
+            KeyCode::Char('t') => app.open_tag_editor(),
+
+            KeyCode::Char('s') => app.toggle_sort_mode(),
+            KeyCode::Char('o') => app.open_sort_selector(),
+            KeyCode::Char('#') => app.filter_by_selected_tag(),
+            KeyCode::Char('b') => app.open_notebook_selector(db).await,
+            KeyCode::Char('G') => app.open_tag_manager(db).await,
             KeyCode::Char('T') => {
+                app.load_tag_meta(db).await;
+                app.load_smart_filters(db).await;
                 app.open_tag_selector();
             }
 
+            KeyCode::Char(':') => app.open_command_line(),
             KeyCode::Char('/') => {
-                app.input_mode = InputMode::Searching;
+                app.push_mode(InputMode::Searching);
                 app.set_status(
-                    "Search mode: Type to filter, [Enter] to keep filter, [Esc] to clear."
+                    "Search mode: Type to filter, [Tab] titles only/everything, [Enter] to keep filter, [Esc] to clear."
                         .to_string(),
                 );
             }
 
+            KeyCode::Char('c') if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.toggle_columns_view();
+                app.set_status(if app.columns_view {
+                    "Columns view: h/l to move between tags, notes and preview.".to_string()
+                } else {
+                    "Columns view off.".to_string()
+                });
+            }
+            KeyCode::Char('U') => {
+                if !app.ensure_selected_content(db).await {
+                    return Ok(true);
+                }
+                let selection = app.get_selected_note().map(|n| (n.id, n.encrypted));
+                match selection {
+                    Some((id, true)) if app.unlocked_notes.remove(&id).is_some() => {
+                        app.update_preview();
+                        app.set_status("Note locked.".to_string());
+                    }
+                    Some((id, true)) => {
+                        if !app.try_known_passphrases(id) {
+                            app.open_passphrase_prompt(PassphrasePurpose::Unlock(id));
+                        }
+                    }
+                    Some((_, false)) => app.set_status("Note is not encrypted.".to_string()),
+                    None => app.set_status("No note selected.".to_string()),
+                }
+            }
+            KeyCode::Char('X') => {
+                if !app.ensure_selected_content(db).await {
+                    return Ok(true);
+                }
+                let selection = app
+                    .get_selected_note()
+                    .map(|n| (n.id, n.encrypted, app.readable_content(n)));
+                match selection {
+                    Some((id, false, _)) => {
+                        app.open_passphrase_prompt(PassphrasePurpose::Encrypt(id));
+                    }
+                    Some((id, true, Some(plaintext))) => {
+                        app.spawn_call(
+                            db,
+                            move |mut db| async move {
+                                db.set_note_encrypted(id, false, &plaintext).await
+                            },
+                            move |app, db, result| match result {
+                                Ok(_) => {
+                                    app.unlocked_notes.remove(&id);
+                                    app.set_status(
+                                        "Note decrypted and stored as plain text.".to_string(),
+                                    );
+                                    app.refresh_notes(db);
+                                }
+                                Err(e) => app.set_status(format!("Error decrypting note: {}", e)),
+                            },
+                        );
+                    }
+                    Some((_, true, None)) => {
+                        app.set_status("Unlock the note with 'U' before decrypting it.".to_string())
+                    }
+                    None => app.set_status("No note selected.".to_string()),
+                }
+            }
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.toggle_pin(db);
+            }
+            KeyCode::Char('p') => {
+                let selection = app
+                    .get_selected_note()
+                    .map(|n| (n.id, n.title.clone(), !n.pinned));
+                if let Some((id, title, pinned)) = selection {
+                    app.spawn_call(
+                        db,
+                        move |mut db| async move { db.set_pinned(id, pinned).await },
+                        move |app, db, result| match result {
+                            Ok(()) => {
+                                app.set_status(if pinned {
+                                    format!("'{}' pinned to the top.", title)
+                                } else {
+                                    format!("'{}' unpinned.", title)
+                                });
+                                app.refresh_notes_and_select(db, id);
+                            }
+                            Err(e) => app.set_status(format!("Error updating pin: {}", e)),
+                        },
+                    );
+                }
+            }
+            KeyCode::Char('f') => {
+                let selection = app
+                    .get_selected_note()
+                    .map(|n| (n.id, n.title.clone(), !n.favorite));
+                if let Some((id, title, favorite)) = selection {
+                    app.spawn_call(
+                        db,
+                        move |mut db| async move { db.set_favorite(id, favorite).await },
+                        move |app, db, result| match result {
+                            Ok(()) => {
+                                app.set_status(if favorite {
+                                    format!("'{}' added to Favorites.", title)
+                                } else {
+                                    format!("'{}' removed from Favorites.", title)
+                                });
+                                app.refresh_notes(db);
+                            }
+                            Err(e) => app.set_status(format!("Error updating favorite: {}", e)),
+                        },
+                    );
+                }
+            }
+            KeyCode::Char('A') => app.open_reminder_popup(),
+            KeyCode::Char('R') => app.toggle_reveal_secret(),
+            KeyCode::Char('S') => app.toggle_screencast(),
+            KeyCode::Char('M') => app.open_status_history(),
+            KeyCode::Char('E') => app.open_export_selector(db).await,
+            KeyCode::Char('g') => app.open_link_graph(db).await,
+            KeyCode::Char('O') => app.open_orphan_report(db).await,
+            KeyCode::Char('Z') => app.open_size_report(db).await,
+            KeyCode::Char('H') => app.open_history(db).await,
+            KeyCode::Char('F') => app.open_attachments(db).await,
+            KeyCode::Char('P') => {
+                app.start_connection_check();
+                if !app.sources.is_empty() {
+                    app.reload_sources().await;
+                    app.refresh_notes(db);
+                }
+            }
+
+            KeyCode::Char('D') => {
+                app.toggle_list_density();
+                app.set_status(format!("List density: {:?}", app.list_density));
+            }
+
             KeyCode::Char('?') => {
-                app.input_mode = InputMode::ShowHelp;
+                app.push_mode(InputMode::ShowHelp);
             }
             _ => {}
         },
@@ -164,189 +1191,967 @@ pub fn handle_key_event<B: Backend + io::Write>(
         InputMode::Searching => match key.code {
             KeyCode::Enter => {
                 // Keep the filter applied, return to normal navigation
-                app.input_mode = InputMode::Normal;
+                app.search_history.record(&app.search_query);
+                app.pop_mode();
                 app.set_status(format!("Search applied: '{}'", app.search_query));
             }
             KeyCode::Esc => {
                 // Clear search and return to normal
+                app.search_history.reset_cursor();
                 app.search_query.clear();
                 app.apply_current_filter();
-                app.input_mode = InputMode::Normal;
+                app.pop_mode();
                 app.set_status("Search cleared.".to_string());
                 // Reset list to top
                 if !app.notes.is_empty() {
                     app.list_state.select(Some(0));
                 }
             }
+            KeyCode::Up => app.recall_search(true),
+            KeyCode::Down => app.recall_search(false),
+            KeyCode::Tab => app.toggle_search_scope(),
             KeyCode::Backspace => {
                 app.search_query.pop();
-                app.apply_current_filter();
+                app.search_changed();
             }
             KeyCode::Char(c) => {
                 app.search_query.push(c);
-                app.apply_current_filter();
+                app.search_changed();
             }
             _ => {}
         },
 
         InputMode::EditingFilename => match key.code {
             KeyCode::Enter => {
-                let title = app.filename_input.trim().to_string();
+                let Some(title) = app.validate_title_input() else {
+                    return Ok(true);
+                };
                 if title.is_empty() {
-                    app.input_mode = InputMode::Normal;
+                    app.pop_mode();
                     app.set_status("New note cancelled.".to_string());
                 } else {
-                    match db.create_note(&title) {
-                        Ok(_) => {
+                    let created = match app.new_from_template.take() {
+                        Some((content, tags)) => {
+                            db.create_note_with_content(&title, &content, &tags).await
+                        }
+                        None => db.create_note(&title).await,
+                    };
+                    match created {
+                        Ok(id) => {
                             app.set_status(format!("Note '{}' created.", title));
-                            app.refresh_notes(db)?;
-
-                            if let Some(idx) = app.notes.iter().position(|n| n.title == title) {
-                                app.list_state.select(Some(idx));
-                                app.update_preview();
-                                edit_note_in_external_editor(app, db, terminal)?;
+                            // The editor opens the selected note, so wait for the new list.
+                            app.refresh_notes_now(db).await;
+                            app.select_note_by_id(id);
+                            if app.get_selected_note().is_some_and(|n| n.id == id) {
+                                edit_note_in_external_editor(app, db, terminal).await?;
                             }
                         }
                         Err(e) => app.set_status(format!("Error creating note: {}", e)),
                     }
-                    app.input_mode = InputMode::Normal;
+                    app.pop_mode();
                 }
             }
             KeyCode::Esc => {
-                app.input_mode = InputMode::Normal;
+                app.pop_mode();
                 app.set_status("New note cancelled.".to_string());
             }
-            KeyCode::Backspace => {
-                app.filename_input.pop();
+            KeyCode::Down if !app.title_suggestions.is_empty() => {
+                app.selected_suggestion =
+                    (app.selected_suggestion + 1) % app.title_suggestions.len();
             }
-            KeyCode::Char(c) => {
-                app.filename_input.push(c);
+            KeyCode::Up if !app.title_suggestions.is_empty() => {
+                let len = app.title_suggestions.len();
+                app.selected_suggestion = (app.selected_suggestion + len - 1) % len;
+            }
+            KeyCode::Tab => {
+                let Some((id, title)) = app.title_suggestions.get(app.selected_suggestion).cloned()
+                else {
+                    return Ok(true);
+                };
+                app.pop_mode();
+                app.select_note_by_id(id);
+                if app.get_selected_note().is_some_and(|n| n.id == id) {
+                    app.set_status(format!("Opened existing note '{}'.", title));
+                    edit_note_in_external_editor(app, db, terminal).await?;
+                }
+            }
+            _ => {
+                if app.filename_input.handle_key(key) {
+                    app.validate_title_input();
+                    app.update_title_suggestions(db).await;
+                }
             }
-            _ => {}
         },
 
-        InputMode::EditingTags => match key.code {
-            KeyCode::Enter => {
-                let tags: Vec<String> = app
-                    .filename_input
-                    .split(',')
-                    .map(|s| s.trim().to_string())
-                    .filter(|s| !s.is_empty())
+        InputMode::EditingTags => match app.tag_editor.handle_key(key) {
+            TagEditorAction::Save if app.bulk_tags.is_some() => {
+                let shared = app.bulk_tags.take().unwrap_or_default();
+                let tags = &app.tag_editor.tags;
+                let add: Vec<String> = tags
+                    .iter()
+                    .filter(|t| !shared.contains(t))
+                    .cloned()
                     .collect();
-
-                if let Some(note) = app.get_selected_note() {
-                    match db.update_note_tags(note.id, &tags) {
-                        Ok(_) => {
-                            app.set_status("Tags updated.".to_string());
-                            app.refresh_notes(db)?;
+                let remove: Vec<String> =
+                    shared.into_iter().filter(|t| !tags.contains(t)).collect();
+                let ids: Vec<i32> = app.marked_notes().iter().map(|n| n.id).collect();
+                app.spawn_call(
+                    db,
+                    move |mut db| async move { db.retag_notes(&ids, &add, &remove).await },
+                    move |app, db, result| match result {
+                        Ok(n) => {
+                            app.marked.clear();
+                            app.set_status(format!("Tags updated on {} note(s).", n));
+                            app.refresh_notes(db);
                         }
                         Err(e) => app.set_status(format!("Error updating tags: {}", e)),
-                    }
+                    },
+                );
+                app.pop_mode();
+            }
+            TagEditorAction::Save => {
+                if let Some(id) = app.get_selected_note().map(|n| n.id) {
+                    let tags = app.tag_editor.tags.clone();
+                    app.spawn_call(
+                        db,
+                        move |mut db| async move { db.update_note_tags(id, &tags).await },
+                        move |app, db, result| match result {
+                            Ok(_) => {
+                                app.set_status("Tags updated.".to_string());
+                                app.refresh_notes(db);
+                            }
+                            Err(e) => app.set_status(format!("Error updating tags: {}", e)),
+                        },
+                    );
                 }
-                app.input_mode = InputMode::Normal;
+                app.pop_mode();
             }
-            KeyCode::Esc => {
-                app.input_mode = InputMode::Normal;
+            TagEditorAction::Cancel => {
+                app.bulk_tags = None;
+                app.pop_mode();
                 app.set_status("Tag editing cancelled.".to_string());
             }
-            KeyCode::Backspace => {
-                app.filename_input.pop();
+            TagEditorAction::None => {}
+        },
+        InputMode::ConfirmingConcurrentEdit => match key.code {
+            KeyCode::Char('y') => {
+                app.pop_mode();
+                edit_selected(app, db, terminal).await?;
             }
-            KeyCode::Char(c) => {
-                app.filename_input.push(c);
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.pop_mode();
+                app.set_status("Edit cancelled.".to_string());
             }
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
             _ => {}
         },
         InputMode::ConfirmingDelete => match key.code {
             KeyCode::Char('y') => {
-                let selection = app.get_selected_note().map(|n| (n.id, n.title.clone()));
-                if let Some((id, title)) = selection {
-                    match db.delete_note(id) {
-                        Ok(_) => {
-                            app.set_status(format!("Note '{}' deleted.", title));
-                            app.refresh_notes(db)?;
-                        }
-                        Err(e) => app.set_status(format!("Error deleting note: {}", e)),
-                    }
-                }
-                app.input_mode = InputMode::Normal;
+                delete_selected(app, db);
+                app.pop_mode();
             }
             KeyCode::Char('n') | KeyCode::Esc => {
-                app.input_mode = InputMode::Normal;
+                app.pop_mode();
                 app.set_status("Deletion cancelled.".to_string());
             }
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+        InputMode::ConfirmingArchive => match key.code {
+            KeyCode::Char('y') => {
+                toggle_archive_selected(app, db);
+                app.pop_mode();
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.pop_mode();
+                app.set_status("Archive cancelled.".to_string());
+            }
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+        InputMode::ConfirmingTaxonomyImport => match key.code {
+            KeyCode::Char('y') => {
+                app.pop_mode();
+                app.import_taxonomy(db);
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.pop_mode();
+                app.set_status("Taxonomy import cancelled.".to_string());
+            }
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
             _ => {}
         },
         InputMode::RenamingScript => match key.code {
             KeyCode::Enter => {
-                let new_title = app.filename_input.trim().to_string();
+                let Some(new_title) = app.validate_title_input() else {
+                    return Ok(true);
+                };
                 if new_title.is_empty() {
-                    app.input_mode = InputMode::Normal;
+                    app.pop_mode();
                     app.set_status("Rename cancelled.".to_string());
                 } else {
-                    let selection = app.get_selected_note().map(|n| n.id);
-                    if let Some(id) = selection {
-                        match db.rename_note(id, &new_title) {
+                    let selection = app.get_selected_note().map(|n| (n.id, n.title.clone()));
+                    if let Some((id, old_title)) = selection {
+                        if !app.load_all_content(db).await {
+                            return Ok(true);
+                        }
+                        let linking_notes = app.notes_linking_to(&old_title, id);
+                        if !linking_notes.is_empty() && old_title != new_title {
+                            app.pending_rename = Some(PendingRename {
+                                id,
+                                old_title,
+                                new_title,
+                                linking_notes,
+                            });
+                            app.push_mode(InputMode::ConfirmingLinkRewrite);
+                            app.set_status(
+                                "[y] rename and rewrite links, [n] rename only, [Esc] back"
+                                    .to_string(),
+                            );
+                            return Ok(true);
+                        }
+                        finish_rename(app, db, id, &new_title, None)?;
+                    }
+                    app.pop_mode();
+                }
+            }
+            KeyCode::Esc => {
+                app.pop_mode();
+                app.set_status("Rename cancelled.".to_string());
+            }
+            _ => {
+                if app.filename_input.handle_key(key) {
+                    app.validate_title_input();
+                }
+            }
+        },
+
+        InputMode::ConfirmingLinkRewrite => match key.code {
+            KeyCode::Char('y') | KeyCode::Char('n') => {
+                if let Some(rename) = app.pending_rename.take() {
+                    let rewrite = (key.code == KeyCode::Char('y')).then_some(&rename);
+                    finish_rename(app, db, rename.id, &rename.new_title, rewrite)?;
+                }
+                // Close both the confirmation and the rename popup beneath it.
+                app.pop_mode();
+                app.pop_mode();
+            }
+            KeyCode::Esc => {
+                app.pending_rename = None;
+                app.pop_mode();
+                app.set_status("Back to renaming.".to_string());
+            }
+            _ => {}
+        },
+
+        InputMode::LinkGraph => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                let last = app.graph_entries.len().saturating_sub(1);
+                let i = app
+                    .graph_list_state
+                    .selected()
+                    .map_or(0, |i| (i + 1).min(last));
+                app.graph_list_state.select(Some(i));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                let i = app
+                    .graph_list_state
+                    .selected()
+                    .map_or(0, |i| i.saturating_sub(1));
+                app.graph_list_state.select(Some(i));
+            }
+            KeyCode::Enter => {
+                let entry = app
+                    .graph_list_state
+                    .selected()
+                    .and_then(|i| app.graph_entries.get(i))
+                    .map(|e| (e.note_id, e.title.clone()));
+                match entry {
+                    Some((Some(id), _)) => app.center_link_graph(id),
+                    Some((None, title)) => {
+                        app.set_status(format!("'{}' does not exist yet.", title))
+                    }
+                    None => {}
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('g') => app.pop_mode(),
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+
+        InputMode::OrphanReport => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                let last = app.orphan_report.len().saturating_sub(1);
+                let i = app
+                    .report_list_state
+                    .selected()
+                    .map_or(0, |i| (i + 1).min(last));
+                app.report_list_state.select(Some(i));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                let i = app
+                    .report_list_state
+                    .selected()
+                    .map_or(0, |i| i.saturating_sub(1));
+                app.report_list_state.select(Some(i));
+            }
+            KeyCode::Enter => {
+                let entry = app
+                    .report_list_state
+                    .selected()
+                    .and_then(|i| app.orphan_report.get(i))
+                    .cloned();
+                match entry {
+                    Some(ReportEntry::Orphan { id, .. }) => {
+                        app.pop_mode();
+                        app.select_note_by_id(id);
+                    }
+                    Some(ReportEntry::BrokenLink { target, .. }) => app.spawn_call(
+                        db,
+                        {
+                            let target = target.clone();
+                            move |mut db| async move { db.create_note(&target).await }
+                        },
+                        move |app, db, result| match result {
                             Ok(_) => {
-                                app.set_status("Note renamed.".to_string());
-                                app.refresh_notes(db)?;
-                                if let Some(idx) =
-                                    app.notes.iter().position(|n| n.title == new_title)
-                                {
-                                    app.list_state.select(Some(idx));
+                                app.set_status(format!("Note '{}' created.", target));
+                                app.refresh_notes(db);
+                                app.drop_broken_links_to(&target);
+                                if app.orphan_report.is_empty() {
+                                    app.pop_mode_from(InputMode::OrphanReport);
                                 }
                             }
-                            Err(e) => app.set_status(format!("Error renaming note: {}", e)),
-                        }
+                            Err(e) => app.set_status(format!("Error creating note: {}", e)),
+                        },
+                    ),
+                    None => {}
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => app.pop_mode(),
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+
+        InputMode::InlineEdit => match key.code {
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                close_inline_editor(app, db, true)?;
+            }
+            KeyCode::Esc => {
+                let unsaved = app
+                    .inline_edit
+                    .as_ref()
+                    .is_some_and(|e| !e.discard_armed && e.area.text() != e.session.original);
+                if unsaved {
+                    if let Some(edit) = app.inline_edit.as_mut() {
+                        edit.discard_armed = true;
                     }
-                    app.input_mode = InputMode::Normal;
+                    app.set_status(
+                        "Unsaved changes. [Ctrl+s] save, [Esc] again discards them".to_string(),
+                    );
+                } else {
+                    close_inline_editor(app, db, false)?;
+                    app.set_status("Edit closed.".to_string());
+                }
+            }
+            _ => {
+                if let Some(edit) = app.inline_edit.as_mut()
+                    && edit.area.handle_key(key)
+                {
+                    edit.draft_dirty = true;
+                    edit.discard_armed = false;
+                }
+            }
+        },
+        InputMode::ResolvingConflict => match key.code {
+            KeyCode::Char('m') => {
+                app.pop_mode();
+                if let Some(conflict) = app.edit_conflict.take() {
+                    let session = EditSession {
+                        loaded_at: conflict.theirs_at,
+                        ..conflict.session
+                    };
+                    save_edit(app, db, session, conflict.mine)?;
+                }
+            }
+            KeyCode::Char('e') => {
+                app.pop_mode();
+                if let Some(conflict) = app.edit_conflict.take() {
+                    let draft = diff::conflict_markers(&conflict.theirs, &conflict.mine);
+                    let session = EditSession {
+                        original: conflict.theirs,
+                        loaded_at: conflict.theirs_at,
+                        ..conflict.session
+                    };
+                    edit_locked(app, db, terminal, session, &draft).await?;
+                }
+            }
+            KeyCode::Char('t') | KeyCode::Esc => {
+                app.pop_mode();
+                app.edit_conflict = None;
+                app.set_status("Kept the other version; your edit was discarded.".to_string());
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                if let Some(conflict) = &mut app.edit_conflict {
+                    conflict.scroll = conflict.scroll.saturating_add(1);
+                }
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                if let Some(conflict) = &mut app.edit_conflict {
+                    conflict.scroll = conflict.scroll.saturating_sub(1);
+                }
+            }
+            _ => {}
+        },
+        InputMode::ReviewingEdit => match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                app.pop_mode();
+                if let Some(review) = app.edit_review.take() {
+                    save_edit(app, db, review.session, review.edited)?;
                 }
             }
+            KeyCode::Char('e') => {
+                app.pop_mode();
+                if let Some(review) = app.edit_review.take() {
+                    edit_locked(app, db, terminal, review.session, &review.edited).await?;
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.pop_mode();
+                app.edit_review = None;
+                app.set_status("Edit discarded.".to_string());
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                if let Some(review) = &mut app.edit_review {
+                    review.scroll = review.scroll.saturating_add(1);
+                }
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                if let Some(review) = &mut app.edit_review {
+                    review.scroll = review.scroll.saturating_sub(1);
+                }
+            }
+            _ => {}
+        },
+
+        InputMode::ConfirmingUrlPaste => match key.code {
+            KeyCode::Char(c @ ('l' | 'c')) => {
+                let Some(url) = app.pasted_url.take() else {
+                    return Ok(true);
+                };
+                app.pop_mode();
+                // Fetching can take a few seconds; say so before the UI stalls.
+                app.set_status(format!("Fetching the title of {}...", url));
+                terminal.draw(|f| ui(f, app))?;
+                let title = clip::fetch_title(&url).await;
+                let link = clip::markdown_link(title.as_deref().unwrap_or(&url), &url);
+                if c == 'l' {
+                    app.append_link_to_selected(db, link).await;
+                } else {
+                    app.create_clipped_note(db, title.as_deref().unwrap_or(&url), link);
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('n') => {
+                app.pasted_url = None;
+                app.pop_mode();
+                app.set_status("Paste cancelled.".to_string());
+            }
+            _ => {}
+        },
+
+        InputMode::TogglingTags => match key.code {
+            KeyCode::Char(c @ '1'..='9') => {
+                app.toggle_quick_tag(db, c as usize - '0' as usize);
+            }
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => app.pop_mode(),
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+
+        InputMode::SelectingNotebook => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => app.move_notebook_selection(true),
+            KeyCode::Char('k') | KeyCode::Up => app.move_notebook_selection(false),
+            KeyCode::Enter => app.switch_to_selected_notebook(),
+            KeyCode::Char('m') => app.move_selected_to_notebook(db),
+            KeyCode::Char('n') => {
+                app.notebook_input.clear();
+                app.input_error = None;
+                app.push_mode(InputMode::NamingNotebook);
+            }
+            KeyCode::Char('d') => app.delete_selected_notebook(db),
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('b') => app.pop_mode(),
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+
+        InputMode::ManagingTags => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => app.move_tag_selection(true),
+            KeyCode::Char('k') | KeyCode::Up => app.move_tag_selection(false),
+            KeyCode::Enter => app.filter_by_managed_tag(),
+            KeyCode::Char('r' | 'm' | 'd') if refuse_in_safe_mode(app) => {}
+            KeyCode::Char('r') => app.rename_managed_tag(),
+            KeyCode::Char('m') => app.merge_managed_tag(db),
+            KeyCode::Char('d') => app.delete_managed_tag(db, false),
+            KeyCode::Esc if app.merging_tag.is_some() => {
+                app.merging_tag = None;
+                app.set_status("Merge cancelled.".to_string());
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('G') => app.pop_mode(),
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+        InputMode::ConfirmingTagDelete => match key.code {
+            KeyCode::Char('y') => {
+                app.pop_mode();
+                app.delete_managed_tag(db, true);
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.pop_mode();
+                app.set_status("Tag kept.".to_string());
+            }
+            _ => {}
+        },
+
+        InputMode::NamingNotebook => match key.code {
+            KeyCode::Enter => app.create_notebook(db),
+            KeyCode::Esc => {
+                app.input_error = None;
+                app.pop_mode();
+            }
+            _ => {
+                app.notebook_input.handle_key(key);
+            }
+        },
+
+        InputMode::Command => match key.code {
+            KeyCode::Enter => {
+                let line = app.command_input.value().trim().to_string();
+                app.pop_mode();
+                return run_command(&line, app, db, terminal).await;
+            }
             KeyCode::Esc => {
-                app.input_mode = InputMode::Normal;
+                app.pop_mode();
+                app.set_status("Command cancelled.".to_string());
+            }
+            KeyCode::Tab => app.complete_command(),
+            _ => {
+                app.command_input.handle_key(key);
+            }
+        },
+        InputMode::EditingFilterExpression => match key.code {
+            KeyCode::Enter => app.submit_filter_expression(),
+            KeyCode::Esc => {
+                app.input_error = None;
+                app.pop_mode();
+                app.set_status("Expression cancelled.".to_string());
+            }
+            _ => {
+                app.filter_expr_input.handle_key(key);
+            }
+        },
+
+        InputMode::EditingDateRange => match key.code {
+            KeyCode::Enter => app.submit_date_range(),
+            KeyCode::Esc => {
+                app.input_error = None;
+                app.pop_mode();
+                app.set_status("Date range cancelled.".to_string());
+            }
+            _ => {
+                app.date_range_input.handle_key(key);
+            }
+        },
+
+        InputMode::RenamingTag => match key.code {
+            KeyCode::Enter => app.submit_tag_rename(db),
+            KeyCode::Esc => {
+                app.input_error = None;
+                app.renaming_tag = None;
+                app.pop_mode();
                 app.set_status("Rename cancelled.".to_string());
             }
+            _ => {
+                app.tag_rename_input.handle_key(key);
+            }
+        },
+
+        InputMode::EnteringPassphrase => match key.code {
+            KeyCode::Enter => {
+                let passphrase = std::mem::take(&mut app.passphrase_input);
+                let purpose = app.passphrase_purpose.take();
+                // A mistyped new passphrase would lock the note or the screen for good.
+                let is_new = matches!(
+                    purpose,
+                    Some(PassphrasePurpose::Encrypt(_) | PassphrasePurpose::SetLock)
+                );
+                if is_new && !passphrase.is_empty() {
+                    match app.passphrase_first.take() {
+                        None => {
+                            app.passphrase_first = Some(passphrase);
+                            app.passphrase_purpose = purpose;
+                            app.set_status("Enter the passphrase again to confirm.".to_string());
+                            return Ok(true);
+                        }
+                        Some(first) if first != passphrase => {
+                            app.pop_mode();
+                            app.set_status(
+                                "The passphrases differ; nothing was changed.".to_string(),
+                            );
+                            return Ok(true);
+                        }
+                        Some(_) => {}
+                    }
+                }
+                match purpose {
+                    _ if passphrase.is_empty() => {
+                        app.set_status("Passphrase cannot be empty.".to_string());
+                    }
+                    Some(PassphrasePurpose::Unlock(id)) => {
+                        app.unlock_note(id, &passphrase);
+                    }
+                    Some(PassphrasePurpose::SetLock) => {
+                        app.lock_passphrase = Some(passphrase);
+                        app.pop_mode();
+                        app.lock_screen();
+                        return Ok(true);
+                    }
+                    Some(PassphrasePurpose::Encrypt(id)) => {
+                        let plaintext = app
+                            .all_notes
+                            .iter()
+                            .find(|n| n.id == id && n.content_loaded)
+                            .map(|n| n.content.clone());
+                        let Some(plaintext) = plaintext else {
+                            app.pop_mode();
+                            app.set_status("The note's content is still loading.".to_string());
+                            return Ok(true);
+                        };
+                        match crypto::encrypt(&plaintext, &passphrase) {
+                            Ok(cipher) => app.spawn_call(
+                                db,
+                                move |mut db| async move {
+                                    db.set_note_encrypted(id, true, &cipher).await
+                                },
+                                move |app, db, result| match result {
+                                    Ok(_) => {
+                                        app.unlocked_notes.insert(id, (plaintext, passphrase));
+                                        app.set_status("Note encrypted.".to_string());
+                                        app.refresh_notes(db);
+                                    }
+                                    Err(e) => {
+                                        app.set_status(format!("Error encrypting note: {}", e))
+                                    }
+                                },
+                            ),
+                            Err(e) => app.set_status(format!("Error encrypting note: {}", e)),
+                        }
+                    }
+                    None => {}
+                }
+                app.pop_mode();
+            }
+            KeyCode::Esc => {
+                app.passphrase_input.clear();
+                app.passphrase_first = None;
+                app.passphrase_purpose = None;
+                app.pop_mode();
+                app.set_status("Passphrase entry cancelled.".to_string());
+            }
             KeyCode::Backspace => {
-                app.filename_input.pop();
+                app.passphrase_input.pop();
             }
             KeyCode::Char(c) => {
-                app.filename_input.push(c);
+                app.passphrase_input.push(c);
             }
             _ => {}
         },
 
+        InputMode::Reminder => match key.code {
+            KeyCode::Char('1') => app.apply_reminder(db, Some(Utc::now() + TimeDelta::minutes(10))),
+            KeyCode::Char('2') => app.apply_reminder(db, Some(Utc::now() + TimeDelta::hours(1))),
+            KeyCode::Char('3') => app.apply_reminder(db, Some(Utc::now() + TimeDelta::days(1))),
+            KeyCode::Char('c') => app.apply_reminder(db, None),
+            KeyCode::Enter if app.reminder_due => {
+                let id = app.reminder_note;
+                app.apply_reminder(db, None);
+                if let Some(id) = id {
+                    app.select_note_by_id(id);
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.reminder_note = None;
+                app.pop_mode();
+            }
+            _ => {}
+        },
+
+        InputMode::SizeReport => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                let last = app.size_report.notes.len().saturating_sub(1);
+                let i = app
+                    .size_list_state
+                    .selected()
+                    .map_or(0, |i| (i + 1).min(last));
+                app.size_list_state.select(Some(i));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                let i = app
+                    .size_list_state
+                    .selected()
+                    .map_or(0, |i| i.saturating_sub(1));
+                app.size_list_state.select(Some(i));
+            }
+            KeyCode::Enter => {
+                let id = app
+                    .size_list_state
+                    .selected()
+                    .and_then(|i| app.size_report.notes.get(i))
+                    .map(|(id, _, _)| *id);
+                if let Some(id) = id {
+                    app.pop_mode();
+                    app.select_note_by_id(id);
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => app.pop_mode(),
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+
+        InputMode::History => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                let last = app.revisions.len().saturating_sub(1);
+                let i = app
+                    .revision_list_state
+                    .selected()
+                    .map_or(0, |i| (i + 1).min(last));
+                app.revision_list_state.select(Some(i));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                let i = app
+                    .revision_list_state
+                    .selected()
+                    .map_or(0, |i| i.saturating_sub(1));
+                app.revision_list_state.select(Some(i));
+            }
+            KeyCode::Enter => app.restore_selected_revision(db),
+            KeyCode::Esc | KeyCode::Char('q') => app.pop_mode(),
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+
+        InputMode::Attachments => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => app.move_attachment_selection(true),
+            KeyCode::Char('k') | KeyCode::Up => app.move_attachment_selection(false),
+            KeyCode::Enter | KeyCode::Char('o') => app.open_selected_attachment(db).await,
+            KeyCode::Char('s') => app.start_attachment_input(AttachmentAction::SaveAs),
+            KeyCode::Char('r') => app.start_attachment_input(AttachmentAction::Rename),
+            KeyCode::Char('d') => app.delete_selected_attachment(db),
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('F') => app.pop_mode(),
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+        InputMode::NamingAttachment => match key.code {
+            KeyCode::Enter => app.finish_attachment_input(db).await,
+            KeyCode::Esc => {
+                app.input_error = None;
+                app.pop_mode();
+            }
+            _ => {
+                app.attachment_input.handle_key(key);
+            }
+        },
+
+        InputMode::LintReport => match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter => app.pop_mode(),
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+
         InputMode::SelectingTagFilter => match key.code {
             KeyCode::Char('j') => app.next_filter(),
             KeyCode::Char('k') => app.previous_filter(),
             KeyCode::Enter => {
-                if let Some(idx) = app.filter_list_state.selected() {
-                    if let Some(filter) = app.available_filters.get(idx).cloned() {
-                        app.active_filter = filter.clone();
-                        app.apply_current_filter();
-                        // Reset list selection
-                        if !app.notes.is_empty() {
-                            app.list_state.select(Some(0));
-                        } else {
-                            app.list_state.select(None);
-                        }
-                        app.update_preview();
-                        app.set_status(format!("Filter applied: {}", filter));
-                    }
+                if let Some(idx) = app.filter_list_state.selected()
+                    && let Some(filter) = app.available_filters.get(idx).cloned()
+                {
+                    app.apply_filter(filter.clone());
+                    app.set_status(format!("Filter applied: {}", filter));
                 }
-                app.input_mode = InputMode::Normal;
+                app.pop_mode();
+            }
+            KeyCode::Char('n') => app.open_filter_expression_input(),
+            KeyCode::Char('r') => app.open_date_range_input(),
+            KeyCode::Char('R' | 's' | 'x' | 'i') if refuse_in_safe_mode(app) => {}
+            KeyCode::Char('R') => app.open_tag_rename(),
+            KeyCode::Char('s') => app.save_smart_filter(db),
+            KeyCode::Char('x') => app.delete_selected_smart_filter(db),
+            KeyCode::Char('e') => app.export_taxonomy(db).await,
+            KeyCode::Char('i') if app.confirm.bulk => {
+                app.push_mode(InputMode::ConfirmingTaxonomyImport);
+                app.set_status(
+                    "Overwrite tag colors and parents from the taxonomy file? (y/n)".to_string(),
+                );
+            }
+            KeyCode::Char('i') => {
+                app.import_taxonomy(db);
             }
             KeyCode::Esc | KeyCode::Char('q') => {
-                app.input_mode = InputMode::Normal;
+                app.pop_mode();
                 app.set_status("Filter cancelled.".to_string());
             }
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
             _ => {}
         },
+        InputMode::ConfirmingQuit => match key.code {
+            KeyCode::Char('f') => {
+                let flushed = Arc::new(AtomicUsize::new(0));
+                let count = Arc::clone(&flushed);
+                app.flush_pending_saves(db, move |_, n| count.store(n, Ordering::Relaxed));
+                // Quitting waits for the outcome anyway.
+                app.finish_calls(db).await;
+                let flushed = flushed.load(Ordering::Relaxed);
+                if !app.conflict_queue.is_empty() {
+                    app.pop_mode();
+                    app.set_status(format!(
+                        "Flushed {}; {} conflict(s) to review before quitting.",
+                        flushed,
+                        app.conflict_queue.len()
+                    ));
+                    return Ok(true);
+                }
+                if app.pending_saves.is_empty() {
+                    return Ok(false);
+                }
+                app.set_status(format!(
+                    "Flushed {}, {} still failing. [f] retry, [y] quit anyway, [n] cancel",
+                    flushed,
+                    app.pending_saves.len()
+                ));
+            }
+            KeyCode::Char('y') => return Ok(false),
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.pop_mode();
+                app.set_status("Quit cancelled.".to_string());
+            }
+            _ => {}
+        },
+        InputMode::StatusHistory => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                let last = app.status_history.len().saturating_sub(1);
+                let i = app
+                    .history_list_state
+                    .selected()
+                    .map_or(0, |i| (i + 1).min(last));
+                app.history_list_state.select(Some(i));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                let i = app
+                    .history_list_state
+                    .selected()
+                    .map_or(0, |i| i.saturating_sub(1));
+                app.history_list_state.select(Some(i));
+            }
+            KeyCode::Char('q') | KeyCode::Esc | KeyCode::Char('M') => app.pop_mode(),
+            KeyCode::Char('?') => app.push_mode(InputMode::ShowHelp),
+            _ => {}
+        },
+        InputMode::SelectingSortMode => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                let last = SortMode::ALL.len() - 1;
+                let i = app
+                    .sort_mode_state
+                    .selected()
+                    .map_or(0, |i| (i + 1).min(last));
+                app.sort_mode_state.select(Some(i));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                let i = app
+                    .sort_mode_state
+                    .selected()
+                    .map_or(0, |i| i.saturating_sub(1));
+                app.sort_mode_state.select(Some(i));
+            }
+            KeyCode::Enter => {
+                let sort_mode = app
+                    .sort_mode_state
+                    .selected()
+                    .and_then(|i| SortMode::ALL.get(i).copied());
+                app.pop_mode();
+                if let Some(sort_mode) = sort_mode {
+                    app.set_sort_mode(sort_mode);
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('o') => app.pop_mode(),
+            _ => {}
+        },
+        InputMode::SelectingCopyFormat => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                let last = CopyFormat::ALL.len() - 1;
+                let i = app
+                    .copy_format_state
+                    .selected()
+                    .map_or(0, |i| (i + 1).min(last));
+                app.copy_format_state.select(Some(i));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                let i = app
+                    .copy_format_state
+                    .selected()
+                    .map_or(0, |i| i.saturating_sub(1));
+                app.copy_format_state.select(Some(i));
+            }
+            KeyCode::Enter => {
+                let format = app
+                    .copy_format_state
+                    .selected()
+                    .and_then(|i| CopyFormat::ALL.get(i).copied());
+                app.pop_mode();
+                if let Some(format) = format {
+                    app.copy_selected_note(db, format).await;
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('y') => app.pop_mode(),
+            _ => {}
+        },
+        InputMode::SelectingExportFormat => match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                let last = ExportFormat::ALL.len() - 1;
+                let i = app
+                    .export_format_state
+                    .selected()
+                    .map_or(0, |i| (i + 1).min(last));
+                app.export_format_state.select(Some(i));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                let i = app
+                    .export_format_state
+                    .selected()
+                    .map_or(0, |i| i.saturating_sub(1));
+                app.export_format_state.select(Some(i));
+            }
+            KeyCode::Enter => {
+                let format = app
+                    .export_format_state
+                    .selected()
+                    .and_then(|i| ExportFormat::ALL.get(i).copied());
+                app.pop_mode();
+                if let Some(format) = format {
+                    app.export_current_list(format);
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.pop_mode();
+                app.set_status("Export cancelled.".to_string());
+            }
+            _ => {}
+        },
+        // Handled before the global keys above.
+        InputMode::Locked => {}
         InputMode::ShowHelp => match key.code {
             KeyCode::Char('q') | KeyCode::Esc | KeyCode::Char('?') => {
-                app.input_mode = InputMode::Normal;
+                app.pop_mode();
             }
             _ => {}
         },
     }
     Ok(true)
-}
\ No newline at end of file
+}
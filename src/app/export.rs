@@ -0,0 +1,213 @@
+use crate::app::crypto;
+use crate::app::state::Note;
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use std::collections::HashSet;
+use std::{fs, io, path::Path, path::PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    /// One `<slug>.md` file per note in a new directory.
+    MarkdownFiles,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [
+        ExportFormat::Markdown,
+        ExportFormat::Html,
+        ExportFormat::MarkdownFiles,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "Markdown (.md)",
+            ExportFormat::Html => "HTML (.html)",
+            ExportFormat::MarkdownFiles => "Markdown files, one per note",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown | ExportFormat::MarkdownFiles => "md",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+/// Writes `notes` into a single printable document with a table of contents,
+/// or a directory of files for `MarkdownFiles`, and returns the created path.
+pub fn export_document(
+    notes: &[Note],
+    heading: &str,
+    format: ExportFormat,
+    dir: &Path,
+) -> io::Result<PathBuf> {
+    let name = format!(
+        "pgnotes-{}-{}",
+        slugify(heading),
+        Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let document = match format {
+        ExportFormat::Markdown => render_markdown(notes, heading),
+        ExportFormat::Html => render_html(notes, heading),
+        ExportFormat::MarkdownFiles => {
+            let path = dir.join(name);
+            export_files(notes, &path)?;
+            return Ok(path);
+        }
+    };
+
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.{}", name, format.extension()));
+    fs::write(&path, document)?;
+    Ok(path)
+}
+
+/// Writes each note to `<slug>.md` in `dir`, with its title, tags, timestamps
+/// and a checksum of its content in YAML front matter. Encrypted notes are
+/// written as the ciphertext they are stored as.
+pub fn export_files(notes: &[Note], dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut used = HashSet::new();
+    for note in notes {
+        // Titles differing only in case or punctuation share a slug; the id tells them apart.
+        let mut slug = slugify(&note.title);
+        if slug.is_empty() || !used.insert(slug.clone()) {
+            slug = format!("{}-{}", slug, note.id)
+                .trim_start_matches('-')
+                .to_string();
+            used.insert(slug.clone());
+        }
+        let mut file = front_matter(note);
+        file.push_str(&note.content);
+        if !note.content.ends_with('\n') {
+            file.push('\n');
+        }
+        fs::write(dir.join(format!("{}.md", slug)), file)?;
+    }
+    Ok(())
+}
+
+fn front_matter(note: &Note) -> String {
+    let mut out = format!("---\ntitle: {}\n", yaml_string(&note.title));
+    let tags: Vec<String> = note.tags.iter().map(|t| yaml_string(t)).collect();
+    out.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+    let timestamp = |t: DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::Secs, true);
+    if let Some(created) = note.created_at {
+        out.push_str(&format!("created: {}\n", timestamp(created)));
+    }
+    if let Some(updated) = note.updated_at {
+        out.push_str(&format!("updated: {}\n", timestamp(updated)));
+    }
+    if note.archived {
+        out.push_str("archived: true\n");
+    }
+    if note.encrypted {
+        out.push_str("encrypted: true\n");
+    }
+    out.push_str(&format!(
+        "sha256: {}\n",
+        crypto::content_hash(&note.content)
+    ));
+    out.push_str("---\n");
+    out
+}
+
+/// A double-quoted YAML scalar.
+fn yaml_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn render_markdown(notes: &[Note], heading: &str) -> String {
+    let mut out = format!(
+        "# {}\n\n_Exported {} · {} note(s)_\n\n## Contents\n\n",
+        heading,
+        Local::now().format("%Y-%m-%d %H:%M"),
+        notes.len()
+    );
+    for (i, note) in notes.iter().enumerate() {
+        out.push_str(&format!(
+            "{}. [{}](#{})\n",
+            i + 1,
+            note.title,
+            slugify(&note.title)
+        ));
+    }
+
+    for note in notes {
+        out.push_str(&format!("\n---\n\n## {}\n\n", note.title));
+        if !note.tags.is_empty() {
+            let tags: Vec<String> = note.tags.iter().map(|t| format!("#{}", t)).collect();
+            out.push_str(&format!("_Tags: {}_\n\n", tags.join(" ")));
+        }
+        out.push_str(note.content.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn render_html(notes: &[Note], heading: &str) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\n\
+         body {{ font-family: sans-serif; max-width: 50em; margin: auto; }}\n\
+         pre {{ white-space: pre-wrap; font-family: inherit; }}\n\
+         .tags {{ color: #666; font-style: italic; }}\n\
+         @media print {{ section {{ page-break-before: always; }} }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+         <p><em>Exported {date} · {count} note(s)</em></p>\n<nav>\n<h2>Contents</h2>\n<ol>\n",
+        title = escape_html(heading),
+        date = Local::now().format("%Y-%m-%d %H:%M"),
+        count = notes.len()
+    );
+    for note in notes {
+        out.push_str(&format!(
+            "<li><a href=\"#note-{}\">{}</a></li>\n",
+            note.id,
+            escape_html(&note.title)
+        ));
+    }
+    out.push_str("</ol>\n</nav>\n");
+
+    for note in notes {
+        out.push_str(&format!(
+            "<section id=\"note-{}\">\n<h2>{}</h2>\n",
+            note.id,
+            escape_html(&note.title)
+        ));
+        if !note.tags.is_empty() {
+            let tags: Vec<String> = note.tags.iter().map(|t| format!("#{}", t)).collect();
+            out.push_str(&format!(
+                "<p class=\"tags\">{}</p>\n",
+                escape_html(&tags.join(" "))
+            ));
+        }
+        out.push_str(&format!(
+            "<pre>{}</pre>\n</section>\n",
+            escape_html(note.content.trim_end())
+        ));
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Lowercase, dash-separated form used for file names and Markdown anchors.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.ends_with('-') && !slug.is_empty() {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
@@ -0,0 +1,227 @@
+//! Boolean tag filter expressions such as `work AND (urgent OR today) AND NOT done`.
+//!
+//! Grammar, loosest binding first:
+//!   or   := and ("OR" | "||" | "|") and ...
+//!   and  := not ("AND" | "&&" | "&") not ...
+//!   not  := ("NOT" | "!") not | atom
+//!   atom := "(" or ")" | tag
+//! Keywords are case-insensitive; a leading '#' on tags is ignored.
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expr {
+    Tag(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn matches(&self, tags: &[String]) -> bool {
+        match self {
+            Expr::Tag(tag) => tags.iter().any(|t| t == tag),
+            Expr::Not(inner) => !inner.matches(tags),
+            Expr::And(a, b) => a.matches(tags) && b.matches(tags),
+            Expr::Or(a, b) => a.matches(tags) || b.matches(tags),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Tag(String),
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '!' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Not,
+                });
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.peek() == Some(&c) {
+                    chars.next();
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()!&|".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.to_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Tag(word.trim_start_matches('#').to_string()),
+                });
+            }
+        }
+    }
+    tokens
+}
+
+pub fn parse(input: &str) -> Result<Expr, String> {
+    let tokens = tokenize(input);
+    if tokens.is_empty() {
+        return Err("Expression is empty.".to_string());
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.or()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(expr),
+        Some(Token::Close) => Err("Unmatched ')'.".to_string()),
+        Some(_) => Err("Expected AND or OR between terms.".to_string()),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Open) => {
+                self.pos += 1;
+                let expr = self.or()?;
+                if !self.eat(&Token::Close) {
+                    return Err("Missing ')'.".to_string());
+                }
+                Ok(expr)
+            }
+            Some(Token::Tag(tag)) if !tag.is_empty() => {
+                let expr = Expr::Tag(tag.clone());
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(_) => Err("Expected a tag or '('.".to_string()),
+            None => Err("Expression ends too early.".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str) -> Box<Expr> {
+        Box::new(Expr::Tag(name.to_string()))
+    }
+
+    fn tags(names: &[&str]) -> Vec<String> {
+        names.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse("a OR b AND c").unwrap(),
+            Expr::Or(tag("a"), Box::new(Expr::And(tag("b"), tag("c"))))
+        );
+        assert_eq!(
+            parse("a && b || c").unwrap(),
+            Expr::Or(Box::new(Expr::And(tag("a"), tag("b"))), tag("c"))
+        );
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        assert_eq!(
+            parse("work and (urgent | today)").unwrap(),
+            Expr::And(tag("work"), Box::new(Expr::Or(tag("urgent"), tag("today"))))
+        );
+    }
+
+    #[test]
+    fn not_binds_tightest_and_nests() {
+        assert_eq!(
+            parse("NOT a AND !!b").unwrap(),
+            Expr::And(
+                Box::new(Expr::Not(tag("a"))),
+                Box::new(Expr::Not(Box::new(Expr::Not(tag("b")))))
+            )
+        );
+    }
+
+    #[test]
+    fn hash_prefix_is_ignored() {
+        assert_eq!(
+            parse("#work&#ops").unwrap(),
+            Expr::And(tag("work"), tag("ops"))
+        );
+    }
+
+    #[test]
+    fn matches_evaluates_the_tree() {
+        let expr = parse("work AND (urgent OR today) AND NOT done").unwrap();
+        assert!(expr.matches(&tags(&["work", "today"])));
+        assert!(!expr.matches(&tags(&["work", "today", "done"])));
+        assert!(!expr.matches(&tags(&["work"])));
+        assert!(!expr.matches(&tags(&["urgent"])));
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        let error = |input| parse(input).unwrap_err();
+        assert_eq!(error("  "), "Expression is empty.");
+        assert_eq!(error("(a OR b"), "Missing ')'.");
+        assert_eq!(error("a)"), "Unmatched ')'.");
+        assert_eq!(error("a b"), "Expected AND or OR between terms.");
+        assert_eq!(error("a AND"), "Expression ends too early.");
+        assert_eq!(error("OR a"), "Expected a tag or '('.");
+        assert_eq!(error("#"), "Expected a tag or '('.");
+    }
+}
@@ -0,0 +1,83 @@
+//! Checks run once at startup for setups that work at first but fail later in
+//! confusing ways.
+
+use crate::app::config::{self, Config};
+use crate::app::db::{self, Database};
+use tokio_postgres::config::Host;
+
+pub async fn startup_warnings(config: &Config, db: &mut Database) -> Vec<String> {
+    let rules = &config.startup_warnings;
+    let mut warnings = Vec::new();
+
+    if rules.default_database_url
+        && (config.database_url == config::default_database_url()
+            || config.database_url == config::EXAMPLE_DATABASE_URL)
+    {
+        warnings.push(
+            "database_url is still the default/example value; set it in config.toml.".to_string(),
+        );
+    }
+
+    let bad_colors = config.theme.invalid();
+    if !bad_colors.is_empty() {
+        warnings.push(format!(
+            "[theme] has values that are not colors: {}.",
+            bad_colors.join(", ")
+        ));
+    }
+
+    // The server decides under sslmode=prefer, so ask it rather than the URL.
+    if rules.insecure_connection
+        && let Some(host) = remote_host(&config.database_url)
+        && !db.connection_encrypted().await.unwrap_or(true)
+    {
+        warnings.push(format!(
+            "Connection to '{}' is not encrypted; credentials and notes travel in plain text. \
+             Add sslmode=require to database_url.",
+            host
+        ));
+    }
+
+    if rules.schema {
+        match db.notes_columns().await {
+            Ok(columns) => {
+                let type_of = |name: &str| {
+                    columns
+                        .iter()
+                        .find(|(column, _)| column == name)
+                        .map(|(_, data_type)| data_type.as_str())
+                };
+                for column in ["archived", "tags"] {
+                    if type_of(column).is_none() {
+                        warnings.push(format!("notes.{} column is missing.", column));
+                    }
+                }
+                if let Some(data_type) = type_of("tags")
+                    && data_type != "ARRAY"
+                {
+                    warnings.push(format!(
+                        "notes.tags is {} instead of TEXT[]; notes and tags will fail to load. \
+                         Fix with: ALTER TABLE notes ALTER COLUMN tags DROP DEFAULT, \
+                         ALTER COLUMN tags TYPE TEXT[] USING tags::TEXT[], \
+                         ALTER COLUMN tags SET DEFAULT '{{}}'",
+                        data_type
+                    ));
+                }
+            }
+            Err(e) => warnings.push(format!("Could not inspect the notes table: {}", e)),
+        }
+    }
+
+    warnings
+}
+
+/// The TCP host of `db_url` unless it is this machine (or a Unix socket).
+fn remote_host(db_url: &str) -> Option<String> {
+    let parsed = db::parse_url(db_url).ok()?;
+    parsed.get_hosts().iter().find_map(|host| match host {
+        Host::Tcp(name) if !matches!(name.as_str(), "localhost" | "127.0.0.1" | "::1") => {
+            Some(name.clone())
+        }
+        _ => None,
+    })
+}
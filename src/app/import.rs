@@ -0,0 +1,346 @@
+//! Reading a directory of Markdown files back in, the inverse of
+//! `export::export_files`. Front matter is optional; without it the file name
+//! is the title.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::{fs, io, path::Path, path::PathBuf};
+
+use super::crypto;
+use super::state::Note;
+
+#[derive(Debug, Clone)]
+pub struct ImportedNote {
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub archived: bool,
+    /// Exported ciphertext; stays encrypted with the passphrase it had.
+    pub encrypted: bool,
+    /// Whether the content matches the file's `sha256`; `None` when it has none.
+    pub checksum_ok: Option<bool>,
+    /// The file the note was read from.
+    pub path: PathBuf,
+}
+
+/// What to do with a file whose title is already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum TitleCollision {
+    /// Leave the existing note alone.
+    #[default]
+    Skip,
+    /// Replace the existing note's content and tags, keeping a revision.
+    Overwrite,
+    /// Import under a free title such as "Title (2)".
+    Rename,
+}
+
+/// Every `.md` file under `dir`, in path order, and a message for each file
+/// that could not be read.
+pub fn read_dir(dir: &Path) -> io::Result<(Vec<ImportedNote>, Vec<String>)> {
+    let mut paths = Vec::new();
+    collect_markdown_files(dir, &mut paths)?;
+    paths.sort();
+
+    let mut notes = Vec::new();
+    let mut problems = Vec::new();
+    for path in paths {
+        match fs::read_to_string(&path) {
+            Ok(text) => notes.push(parse_file(&path, &text)),
+            Err(e) => problems.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    Ok((notes, problems))
+}
+
+fn collect_markdown_files(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_markdown_files(&path, paths)?;
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == "md" || ext == "markdown")
+        {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn parse_file(path: &Path, text: &str) -> ImportedNote {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut note = ImportedNote {
+        title: stem,
+        content: text.to_string(),
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        archived: false,
+        encrypted: false,
+        checksum_ok: None,
+        path: path.to_path_buf(),
+    };
+    let Some((front_matter, content)) = split_front_matter(text) else {
+        return note;
+    };
+    note.content = content.to_string();
+
+    let mut lines = front_matter.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "title" => note.title = unquote(value),
+            "tags" if value.is_empty() => {
+                // Block list: one "- tag" per following line.
+                while let Some(item) = lines.next_if(|l| l.trim_start().starts_with('-')) {
+                    note.tags.push(unquote(item.trim_start()[1..].trim()));
+                }
+            }
+            "tags" => {
+                let list = value.trim_start_matches('[').trim_end_matches(']');
+                note.tags = list
+                    .split(',')
+                    .map(|t| unquote(t.trim()))
+                    .filter(|t| !t.is_empty())
+                    .collect();
+            }
+            "created" => note.created_at = parse_timestamp(&unquote(value)),
+            "updated" => note.updated_at = parse_timestamp(&unquote(value)),
+            "archived" => note.archived = value == "true",
+            "encrypted" => note.encrypted = value == "true",
+            "sha256" => {
+                // Export ends every file with a newline the note may not have had.
+                let hash = unquote(value);
+                let trimmed = note.content.strip_suffix('\n').unwrap_or(&note.content);
+                note.checksum_ok = Some(if crypto::content_hash(&note.content) == hash {
+                    true
+                } else if crypto::content_hash(trimmed) == hash {
+                    note.content = trimmed.to_string();
+                    true
+                } else {
+                    false
+                });
+            }
+            _ => {}
+        }
+    }
+    note
+}
+
+/// The text between a leading `---` line and the next one, and what follows.
+fn split_front_matter(text: &str) -> Option<(&str, &str)> {
+    let rest = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))?;
+    let end = if rest.starts_with("---") {
+        0
+    } else {
+        rest.find("\n---")? + 1
+    };
+    let front_matter = &rest[..end];
+    let after = &rest[end + 3..];
+    let content = after
+        .strip_prefix("\r\n")
+        .or_else(|| after.strip_prefix('\n'))
+        .unwrap_or(after);
+    Some((front_matter, content))
+}
+
+/// A YAML scalar without its quotes; double-quoted ones may escape `"` and `\`.
+fn unquote(value: &str) -> String {
+    if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        let mut out = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => out.extend(chars.next()),
+                c => out.push(c),
+            }
+        }
+        out
+    } else if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        inner.replace("''", "'")
+    } else {
+        value.to_string()
+    }
+}
+
+/// RFC 3339, or a bare `YYYY-MM-DD` taken as midnight UTC.
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        })
+}
+
+/// What restoring a backup would do with each of its notes, by index.
+#[derive(Debug, Default)]
+pub struct RestorePlan {
+    /// No live note has the title.
+    pub create: Vec<usize>,
+    /// The live note differs and has not changed since the backup was taken.
+    pub update: Vec<usize>,
+    /// The live note matches the backup.
+    pub unchanged: usize,
+    /// The live note differs and was changed after the backup was taken.
+    pub conflicts: Vec<usize>,
+}
+
+/// Compares `backup` against the `existing` notes by title.
+pub fn plan_restore(backup: &[ImportedNote], existing: &[Note]) -> RestorePlan {
+    let live: HashMap<&str, &Note> = existing
+        .iter()
+        .filter(|n| n.deleted_at.is_none() && n.source.is_none())
+        .map(|n| (n.title.as_str(), n))
+        .collect();
+    let mut plan = RestorePlan::default();
+    for (i, note) in backup.iter().enumerate() {
+        match live.get(note.title.as_str()) {
+            None => plan.create.push(i),
+            Some(current) if current.content == note.content && current.tags == note.tags => {
+                plan.unchanged += 1
+            }
+            // Exported timestamps are whole seconds.
+            Some(current) => match (current.updated_at, note.updated_at) {
+                (Some(current), Some(backed_up)) if current.timestamp() > backed_up.timestamp() => {
+                    plan.conflicts.push(i)
+                }
+                (Some(_), None) => plan.conflicts.push(i),
+                _ => plan.update.push(i),
+            },
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> Option<DateTime<Utc>> {
+        Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).single()
+    }
+
+    fn backup(title: &str, content: &str, updated_at: Option<DateTime<Utc>>) -> ImportedNote {
+        ImportedNote {
+            title: title.to_string(),
+            content: content.to_string(),
+            tags: vec!["work".to_string()],
+            created_at: at(0),
+            updated_at,
+            archived: false,
+            encrypted: false,
+            checksum_ok: None,
+            path: PathBuf::from(format!("{}.md", title)),
+        }
+    }
+
+    fn live(title: &str, content: &str, updated_at: Option<DateTime<Utc>>) -> Note {
+        Note {
+            id: 1,
+            title: title.to_string(),
+            content: content.to_string(),
+            content_loaded: true,
+            tags: vec!["work".to_string()],
+            updated_at,
+            created_at: at(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn missing_titles_are_created() {
+        let plan = plan_restore(
+            &[backup("New", "text", at(1))],
+            &[live("Other", "x", at(1))],
+        );
+        assert_eq!(plan.create, vec![0]);
+        assert!(plan.update.is_empty() && plan.conflicts.is_empty());
+    }
+
+    #[test]
+    fn trashed_and_merged_notes_do_not_count_as_live() {
+        let mut trashed = live("Gone", "old", at(1));
+        trashed.deleted_at = at(2);
+        let mut merged = live("Shared", "old", at(1));
+        merged.source = Some("team".to_string());
+        let plan = plan_restore(
+            &[
+                backup("Gone", "text", at(1)),
+                backup("Shared", "text", at(1)),
+            ],
+            &[trashed, merged],
+        );
+        assert_eq!(plan.create, vec![0, 1]);
+    }
+
+    #[test]
+    fn identical_notes_are_unchanged() {
+        let plan = plan_restore(
+            &[backup("Same", "text", at(1))],
+            &[live("Same", "text", at(5))],
+        );
+        assert_eq!(plan.unchanged, 1);
+        assert!(plan.create.is_empty() && plan.update.is_empty() && plan.conflicts.is_empty());
+    }
+
+    #[test]
+    fn differing_tags_count_as_a_difference() {
+        let mut current = live("Tagged", "text", at(1));
+        current.tags.push("ops".to_string());
+        let plan = plan_restore(&[backup("Tagged", "text", at(1))], &[current]);
+        assert_eq!(plan.update, vec![0]);
+    }
+
+    #[test]
+    fn notes_untouched_since_the_backup_are_updated() {
+        let mut same_second = live("Older", "edited", at(1));
+        same_second.updated_at = same_second
+            .updated_at
+            .map(|t| t + chrono::TimeDelta::milliseconds(400));
+        let plan = plan_restore(
+            &[
+                backup("Older", "text", at(1)),
+                backup("Earlier", "text", at(3)),
+                backup("Undated", "text", None),
+            ],
+            &[
+                same_second,
+                live("Earlier", "edited", at(2)),
+                live("Undated", "edited", None),
+            ],
+        );
+        assert_eq!(plan.update, vec![0, 1, 2]);
+        assert!(plan.conflicts.is_empty());
+    }
+
+    #[test]
+    fn notes_changed_after_the_backup_conflict() {
+        let plan = plan_restore(
+            &[
+                backup("Newer", "text", at(1)),
+                backup("Unknown", "text", None),
+            ],
+            &[
+                live("Newer", "edited", at(2)),
+                live("Unknown", "edited", at(2)),
+            ],
+        );
+        assert_eq!(plan.conflicts, vec![0, 1]);
+        assert!(plan.update.is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+//! Single-line text input with a cursor, used by the title, tag and filter popups.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Debug, Default, Clone)]
+pub struct TextInput {
+    value: String,
+    /// Cursor position in chars, `0..=len`.
+    cursor: usize,
+    /// The whole value is selected; typing replaces it.
+    selected: bool,
+}
+
+impl TextInput {
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.selected
+    }
+
+    /// Replaces the value and puts the cursor at the end.
+    pub fn set(&mut self, value: impl Into<String>) {
+        self.value = value.into();
+        self.cursor = self.value.chars().count();
+        self.selected = false;
+    }
+
+    /// Replaces the value with all of it selected, so typing starts over.
+    pub fn set_selected(&mut self, value: impl Into<String>) {
+        self.set(value);
+        self.selected = !self.value.is_empty();
+    }
+
+    pub fn clear(&mut self) {
+        self.set(String::new());
+    }
+
+    /// Inserts pasted text at the cursor, joining lines with spaces.
+    pub fn insert(&mut self, text: &str) {
+        self.take_selection();
+        let text = text.lines().collect::<Vec<_>>().join(" ");
+        let at = self.byte_index(self.cursor);
+        self.value.insert_str(at, &text);
+        self.cursor += text.chars().count();
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.value
+            .char_indices()
+            .nth(char_index)
+            .map_or(self.value.len(), |(i, _)| i)
+    }
+
+    fn take_selection(&mut self) -> bool {
+        if self.selected {
+            self.clear();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Applies an editing or cursor key. Returns `true` if the value changed.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('a') if ctrl => self.move_to(0),
+            KeyCode::Char('e') if ctrl => self.move_to(usize::MAX),
+            KeyCode::Char('u') if ctrl => {
+                if self.take_selection() {
+                    return true;
+                }
+                let at = self.byte_index(self.cursor);
+                self.value.replace_range(..at, "");
+                self.cursor = 0;
+                return at > 0;
+            }
+            KeyCode::Char('w') if ctrl => {
+                if self.take_selection() {
+                    return true;
+                }
+                let end = self.byte_index(self.cursor);
+                let trimmed = self.value[..end].trim_end();
+                let start = trimmed.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+                let removed = self.value[start..end].chars().count();
+                self.value.replace_range(start..end, "");
+                self.cursor -= removed;
+                return removed > 0;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.take_selection();
+                let at = self.byte_index(self.cursor);
+                self.value.insert(at, c);
+                self.cursor += 1;
+                return true;
+            }
+            KeyCode::Backspace => {
+                if self.take_selection() {
+                    return true;
+                }
+                if self.cursor == 0 {
+                    return false;
+                }
+                self.cursor -= 1;
+                let at = self.byte_index(self.cursor);
+                self.value.remove(at);
+                return true;
+            }
+            KeyCode::Delete => {
+                if self.take_selection() {
+                    return true;
+                }
+                if self.cursor >= self.value.chars().count() {
+                    return false;
+                }
+                let at = self.byte_index(self.cursor);
+                self.value.remove(at);
+                return true;
+            }
+            KeyCode::Left if self.selected => self.move_to(0),
+            KeyCode::Right if self.selected => self.move_to(usize::MAX),
+            KeyCode::Left => self.move_to(self.cursor.saturating_sub(1)),
+            KeyCode::Right => self.move_to(self.cursor + 1),
+            KeyCode::Home => self.move_to(0),
+            KeyCode::End => self.move_to(usize::MAX),
+            _ => {}
+        }
+        false
+    }
+
+    fn move_to(&mut self, cursor: usize) {
+        self.selected = false;
+        self.cursor = cursor.min(self.value.chars().count());
+    }
+}
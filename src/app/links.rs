@@ -0,0 +1,232 @@
+//! Wikilinks: `[[Note Title]]` or `[[Note Title|shown text]]` inside note content.
+
+use crate::app::state::Note;
+use std::collections::{HashMap, HashSet};
+
+/// Returns the target titles of every wikilink in `content`, in order of appearance.
+pub fn extract_links(content: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            break;
+        };
+        let inner = &after[..end];
+        let target = inner.split('|').next().unwrap_or("").trim();
+        if !target.is_empty() && !target.contains('\n') {
+            links.push(target.to_string());
+        }
+        rest = &after[end + 2..];
+    }
+    links
+}
+
+pub fn links_to(content: &str, title: &str) -> bool {
+    extract_links(content).iter().any(|t| t == title)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LinkDirection {
+    Root,
+    Outgoing,
+    Backlink,
+}
+
+/// One row of the link neighborhood tree shown in the graph popup.
+#[derive(Debug, Clone)]
+pub struct GraphEntry {
+    pub depth: usize,
+    pub direction: LinkDirection,
+    pub title: String,
+    /// `None` when the link points at a title that does not exist.
+    pub note_id: Option<i32>,
+}
+
+/// Builds the link neighborhood of `root` (outgoing links and backlinks) as a tree
+/// up to `max_depth` hops. Each note is expanded at most once to keep cycles finite.
+/// Notes in the Trash are left out, so links to them show as missing.
+pub fn neighborhood(notes: &[Note], root: &Note, max_depth: usize) -> Vec<GraphEntry> {
+    let notes = live(notes);
+    let mut walker = GraphWalker {
+        by_title: notes.iter().map(|n| (n.title.as_str(), *n)).collect(),
+        notes,
+        max_depth,
+        expanded: HashSet::from([root.id]),
+        entries: vec![GraphEntry {
+            depth: 0,
+            direction: LinkDirection::Root,
+            title: root.title.clone(),
+            note_id: Some(root.id),
+        }],
+    };
+    walker.visit(root, None, 1);
+    walker.entries
+}
+
+struct GraphWalker<'a> {
+    notes: Vec<&'a Note>,
+    by_title: HashMap<&'a str, &'a Note>,
+    max_depth: usize,
+    expanded: HashSet<i32>,
+    entries: Vec<GraphEntry>,
+}
+
+impl<'a> GraphWalker<'a> {
+    fn visit(&mut self, note: &'a Note, parent: Option<i32>, depth: usize) {
+        let mut outgoing = extract_links(&note.content);
+        outgoing.dedup();
+        let neighbors = outgoing
+            .into_iter()
+            .map(|title| {
+                let target = self.by_title.get(title.as_str()).copied();
+                (LinkDirection::Outgoing, title, target)
+            })
+            .chain(
+                self.notes
+                    .iter()
+                    .filter(|n| n.id != note.id && links_to(&n.content, &note.title))
+                    .map(|&n| (LinkDirection::Backlink, n.title.clone(), Some(n))),
+            )
+            .collect::<Vec<_>>();
+
+        for (direction, title, target) in neighbors {
+            // The edge back to where we came from is already on screen.
+            if parent.is_some() && target.map(|n| n.id) == parent {
+                continue;
+            }
+            self.entries.push(GraphEntry {
+                depth,
+                direction,
+                title,
+                note_id: target.map(|n| n.id),
+            });
+            if let Some(target) = target
+                && depth < self.max_depth
+                && self.expanded.insert(target.id)
+            {
+                self.visit(target, Some(note.id), depth + 1);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ReportEntry {
+    /// Active note with no tags, no outgoing links and no backlinks.
+    Orphan { id: i32, title: String },
+    /// `source` links to `target`, which no note outside the Trash is titled.
+    BrokenLink { source: String, target: String },
+}
+
+/// Orphans and broken links among the notes not in the Trash.
+pub fn orphan_report(notes: &[Note]) -> Vec<ReportEntry> {
+    let notes = live(notes);
+    let titles: HashSet<&str> = notes.iter().map(|n| n.title.as_str()).collect();
+    let outgoing: HashMap<i32, Vec<String>> = notes
+        .iter()
+        .map(|n| (n.id, extract_links(&n.content)))
+        .collect();
+    let linked: HashSet<&str> = outgoing.values().flatten().map(|t| t.as_str()).collect();
+
+    let mut report: Vec<ReportEntry> = notes
+        .iter()
+        .filter(|n| {
+            !n.archived
+                && n.tags.is_empty()
+                && outgoing[&n.id].is_empty()
+                && !linked.contains(n.title.as_str())
+        })
+        .map(|n| ReportEntry::Orphan {
+            id: n.id,
+            title: n.title.clone(),
+        })
+        .collect();
+
+    for note in &notes {
+        let mut seen = HashSet::new();
+        for target in &outgoing[&note.id] {
+            if !titles.contains(target.as_str()) && seen.insert(target) {
+                report.push(ReportEntry::BrokenLink {
+                    source: note.title.clone(),
+                    target: target.clone(),
+                });
+            }
+        }
+    }
+    report
+}
+
+fn live(notes: &[Note]) -> Vec<&Note> {
+    notes.iter().filter(|n| n.deleted_at.is_none()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn note(id: i32, title: &str, content: &str) -> Note {
+        Note {
+            id,
+            title: title.to_string(),
+            content: content.to_string(),
+            content_loaded: true,
+            ..Default::default()
+        }
+    }
+
+    fn trashed(id: i32, title: &str, content: &str) -> Note {
+        Note {
+            deleted_at: Some(Utc::now()),
+            ..note(id, title, content)
+        }
+    }
+
+    #[test]
+    fn extract_links_reads_targets_and_skips_aliases() {
+        assert_eq!(
+            extract_links("see [[Deploy|the deploy]] and [[ Ops ]], not [[]] or [[a\nb]]"),
+            vec!["Deploy".to_string(), "Ops".to_string()]
+        );
+    }
+
+    #[test]
+    fn trashed_notes_are_left_out_of_the_neighborhood() {
+        let notes = [
+            note(1, "Root", "[[Kept]] [[Binned]]"),
+            note(2, "Kept", ""),
+            trashed(3, "Binned", ""),
+            trashed(4, "Old", "[[Root]]"),
+        ];
+        let entries = neighborhood(&notes, &notes[0], 2);
+        let rows: Vec<(&str, Option<i32>)> = entries
+            .iter()
+            .map(|e| (e.title.as_str(), e.note_id))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("Root", Some(1)), ("Kept", Some(2)), ("Binned", None)]
+        );
+    }
+
+    #[test]
+    fn orphan_report_ignores_the_trash() {
+        let notes = [
+            note(1, "Lonely", ""),
+            note(2, "Linker", "[[Binned]]"),
+            trashed(3, "Binned", ""),
+            trashed(4, "Old", "[[Lonely]] [[Nowhere]]"),
+        ];
+        let report: Vec<String> = orphan_report(&notes)
+            .iter()
+            .map(|entry| match entry {
+                ReportEntry::Orphan { title, .. } => format!("orphan {}", title),
+                ReportEntry::BrokenLink { source, target } => {
+                    format!("broken {} -> {}", source, target)
+                }
+            })
+            .collect();
+        assert_eq!(report, vec!["orphan Lonely", "broken Linker -> Binned"]);
+    }
+}
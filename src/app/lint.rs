@@ -0,0 +1,79 @@
+use crate::app::config::{LintRules, Severity};
+use crate::app::links;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    /// 1-based line number, when the problem is tied to one line.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// Runs the enabled structural checks over `content`. `titles` is the set of
+/// existing note titles, used to spot broken wikilinks.
+pub fn lint(content: &str, titles: &HashSet<&str>, rules: &LintRules) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    if rules.unclosed_code_fence != Severity::Off {
+        let mut open_fence = None;
+        for (i, line) in content.lines().enumerate() {
+            if line.trim_start().starts_with("```") {
+                open_fence = match open_fence {
+                    Some(_) => None,
+                    None => Some(i + 1),
+                };
+            }
+        }
+        if let Some(line) = open_fence {
+            findings.push(Finding {
+                severity: rules.unclosed_code_fence,
+                line: Some(line),
+                message: "Code fence is never closed.".to_string(),
+            });
+        }
+    }
+
+    if rules.broken_link != Severity::Off {
+        for (i, line) in content.lines().enumerate() {
+            for target in links::extract_links(line) {
+                if !titles.contains(target.as_str()) {
+                    findings.push(Finding {
+                        severity: rules.broken_link,
+                        line: Some(i + 1),
+                        message: format!("[[{}]] does not match any note.", target),
+                    });
+                }
+            }
+        }
+    }
+
+    if rules.duplicate_heading != Severity::Off {
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut in_fence = false;
+        for (i, line) in content.lines().enumerate() {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            }
+            if in_fence || !line.starts_with('#') {
+                continue;
+            }
+            let heading = line.trim_start_matches('#').trim().to_lowercase();
+            if heading.is_empty() {
+                continue;
+            }
+            if let Some(first) = seen.get(&heading) {
+                findings.push(Finding {
+                    severity: rules.duplicate_heading,
+                    line: Some(i + 1),
+                    message: format!("Heading repeats the one on line {}.", first),
+                });
+            } else {
+                seen.insert(heading, i + 1);
+            }
+        }
+    }
+
+    findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    findings
+}
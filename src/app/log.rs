@@ -0,0 +1,25 @@
+//! Append-only log file in the profile's data directory, for problems worth keeping after the
+//! status line has moved on.
+
+use crate::app::data_dir;
+use chrono::Local;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+pub const LOG_FILE_NAME: &str = "pgnotes.log";
+
+/// Best effort: a log that can't be written must never take the app down.
+pub fn warn(message: &str) {
+    let path = data_dir::log_file(LOG_FILE_NAME);
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(
+            file,
+            "{} WARN {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            message
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Placeholder text for screencast mode. Output is derived from the real text so the
+//! same note always gets the same placeholder and keeps roughly its shape.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+const WORDS: &[&str] = &[
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+    "nostrud",
+    "exercitation",
+    "ullamco",
+    "laboris",
+    "nisi",
+    "aliquip",
+    "ex",
+    "ea",
+    "commodo",
+    "consequat",
+];
+
+/// A short placeholder title, two to four words.
+pub fn title(real: &str) -> String {
+    let words = Words::new(real);
+    let count = 2 + (words.seed % 3) as usize;
+    let mut title = words.take(count).collect::<Vec<_>>().join(" ");
+    if let Some(first) = title.get(..1) {
+        title.replace_range(..1, &first.to_uppercase());
+    }
+    title
+}
+
+/// Replaces every line with placeholder words of about the same length.
+pub fn text(real: &str) -> String {
+    let mut words = Words::new(real);
+    real.lines()
+        .map(|line| {
+            let target = line.chars().count();
+            let mut out = String::new();
+            while out.len() < target {
+                if !out.is_empty() {
+                    out.push(' ');
+                }
+                out.push_str(words.next().unwrap_or("lorem"));
+            }
+            out
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One placeholder word, e.g. for a tag.
+pub fn word(real: &str) -> &'static str {
+    Words::new(real).next().unwrap_or("lorem")
+}
+
+/// A status message with what it quotes ('Title', 'query') and its #tags
+/// replaced, so messages about notes give nothing away either.
+pub fn status(real: &str) -> String {
+    let chars: Vec<char> = real.chars().collect();
+    let is_word = |i: usize| chars.get(i).is_some_and(|c| c.is_alphanumeric());
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let opens = i == 0 || !is_word(i - 1);
+        if chars[i] == '\'' && opens {
+            // The closing quote is the first one not followed by a letter, so
+            // "'Bob's notes'" is quoted as a whole.
+            let close = (i + 1..chars.len()).find(|&j| chars[j] == '\'' && !is_word(j + 1));
+            if let Some(close) = close {
+                let quoted: String = chars[i + 1..close].iter().collect();
+                out.push_str(&format!("'{}'", title(&quoted)));
+                i = close + 1;
+                continue;
+            }
+        }
+        if chars[i] == '#' && opens && is_word(i + 1) {
+            let end = (i + 1..chars.len())
+                .find(|&j| !(is_word(j) || matches!(chars[j], '-' | '_' | '/')))
+                .unwrap_or(chars.len());
+            let tag: String = chars[i + 1..end].iter().collect();
+            out.push('#');
+            out.push_str(word(&tag));
+            i = end;
+            continue;
+        }
+        out.push(chars[i]);
+        i += 1;
+    }
+    out
+}
+
+struct Words {
+    seed: u64,
+}
+
+impl Words {
+    fn new(real: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        real.hash(&mut hasher);
+        Self {
+            seed: hasher.finish(),
+        }
+    }
+}
+
+impl Iterator for Words {
+    type Item = &'static str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // xorshift64; only needs to look random, not be random.
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        Some(WORDS[(self.seed % WORDS.len() as u64) as usize])
+    }
+}
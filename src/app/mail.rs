@@ -0,0 +1,554 @@
+//! Just enough IMAP to fetch a mailbox's unread messages, and just enough MIME
+//! to turn each into a subject and its plain text.
+
+use base64::Engine;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::time::Duration;
+
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A mailbox given as `imaps://USER@HOST[:PORT][/MAILBOX]`, or `imap://` for
+/// a bridge on this machine that doesn't speak TLS.
+#[derive(Debug, PartialEq)]
+pub struct Account {
+    tls: bool,
+    host: String,
+    port: u16,
+    user: String,
+    mailbox: String,
+}
+
+pub struct Message {
+    pub uid: u64,
+    pub subject: Option<String>,
+    pub text: String,
+}
+
+impl Account {
+    pub fn parse(url: &str) -> Result<Account, String> {
+        let usage = || format!("'{}' is not imaps://USER@HOST[:PORT][/MAILBOX].", url);
+        let (tls, rest) = match url.split_once("://") {
+            Some(("imaps", rest)) => (true, rest),
+            Some(("imap", rest)) => (false, rest),
+            _ => return Err(usage()),
+        };
+        let (authority, mailbox) = rest.split_once('/').unwrap_or((rest, "INBOX"));
+        // The user is often an address, so the host follows the last '@'.
+        let (user, address) = authority.rsplit_once('@').ok_or_else(usage)?;
+        let default_port = if tls { 993 } else { 143 };
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| usage())?),
+            None => (address, default_port),
+        };
+        if user.is_empty() || host.is_empty() {
+            return Err(usage());
+        }
+        // LOGIN sends the password as it is; only TLS keeps it off the wire.
+        if !tls && !is_loopback(host) {
+            return Err(format!(
+                "imap:// would send the password unencrypted to {}; use imaps://.",
+                host
+            ));
+        }
+        Ok(Account {
+            tls,
+            host: host.to_string(),
+            port,
+            user: user.to_string(),
+            mailbox: if mailbox.is_empty() { "INBOX" } else { mailbox }.to_string(),
+        })
+    }
+
+    /// The mailbox's unread messages. They stay unread until `mark_seen`, so a
+    /// message that fails to be stored is fetched again. Blocks; see `IO_TIMEOUT`.
+    pub fn fetch_unseen(&self, password: &str) -> io::Result<Vec<Message>> {
+        let mut session = self.log_in(password)?;
+        let uids: Vec<u64> = session
+            .command("UID SEARCH UNSEEN")?
+            .iter()
+            .filter_map(|r| r.line.strip_prefix("* SEARCH"))
+            .flat_map(|uids| uids.split_whitespace().filter_map(|uid| uid.parse().ok()))
+            .collect();
+        let mut messages = Vec::new();
+        for uid in uids {
+            let responses = session.command(&format!("UID FETCH {} BODY.PEEK[]", uid))?;
+            if let Some(raw) = responses.iter().find_map(|r| r.literals.first()) {
+                messages.push(parse_message(uid, raw));
+            }
+        }
+        // The messages are in hand; a failed goodbye doesn't matter.
+        let _ = session.command("LOGOUT");
+        Ok(messages)
+    }
+
+    /// Marks the messages with `uids` read, so `fetch_unseen` skips them.
+    pub fn mark_seen(&self, password: &str, uids: &[u64]) -> io::Result<()> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+        let mut session = self.log_in(password)?;
+        let set: Vec<String> = uids.iter().map(u64::to_string).collect();
+        session.command(&format!(
+            "UID STORE {} +FLAGS.SILENT (\\Seen)",
+            set.join(",")
+        ))?;
+        let _ = session.command("LOGOUT");
+        Ok(())
+    }
+
+    /// A session with the mailbox selected.
+    fn log_in(&self, password: &str) -> io::Result<Session> {
+        let mut session = Session::open(self)?;
+        session.command(&format!(
+            "LOGIN {} {}",
+            quote(&self.user)?,
+            quote(password)?
+        ))?;
+        session.command(&format!("SELECT {}", quote(&self.mailbox)?))?;
+        Ok(session)
+    }
+}
+
+fn is_loopback(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+/// One untagged response: its text, with the `{n}` literals it carried taken out.
+struct Response {
+    line: String,
+    literals: Vec<Vec<u8>>,
+}
+
+struct Session {
+    conn: BufReader<Box<dyn Stream>>,
+    tag: u32,
+}
+
+impl Session {
+    fn open(account: &Account) -> io::Result<Session> {
+        let tcp = TcpStream::connect((account.host.as_str(), account.port))?;
+        tcp.set_read_timeout(Some(IO_TIMEOUT))?;
+        tcp.set_write_timeout(Some(IO_TIMEOUT))?;
+        let stream: Box<dyn Stream> = if account.tls {
+            let connector = native_tls::TlsConnector::new().map_err(io::Error::other)?;
+            Box::new(
+                connector
+                    .connect(&account.host, tcp)
+                    .map_err(io::Error::other)?,
+            )
+        } else {
+            Box::new(tcp)
+        };
+        let mut session = Session {
+            conn: BufReader::new(stream),
+            tag: 0,
+        };
+        let greeting = session.read_line()?;
+        if !greeting.starts_with("* OK") {
+            return Err(io::Error::other(format!(
+                "Unexpected IMAP greeting: {}",
+                greeting
+            )));
+        }
+        Ok(session)
+    }
+
+    /// Sends `command` and collects the untagged responses up to its own,
+    /// which must be OK.
+    fn command(&mut self, command: &str) -> io::Result<Vec<Response>> {
+        self.tag += 1;
+        let tag = format!("a{}", self.tag);
+        let stream = self.conn.get_mut();
+        stream.write_all(format!("{} {}\r\n", tag, command).as_bytes())?;
+        stream.flush()?;
+        let mut responses = Vec::new();
+        loop {
+            let mut response = Response {
+                line: self.read_line()?,
+                literals: Vec::new(),
+            };
+            if let Some(status) = response.line.strip_prefix(&format!("{} ", tag)) {
+                if status.starts_with("OK") {
+                    return Ok(responses);
+                }
+                // Don't echo the password back in the error.
+                let verb = command.split(' ').next().unwrap_or(command);
+                return Err(io::Error::other(format!(
+                    "IMAP {} failed: {}",
+                    verb, status
+                )));
+            }
+            while let Some(len) = literal_len(&response.line) {
+                let mut literal = vec![0; len];
+                self.conn.read_exact(&mut literal)?;
+                response.literals.push(literal);
+                let rest = self.read_line()?;
+                response.line.push_str(&rest);
+            }
+            responses.push(response);
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = Vec::new();
+        if self.conn.read_until(b'\n', &mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The IMAP server closed the connection.",
+            ));
+        }
+        let line = String::from_utf8_lossy(&line);
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+/// The length of the `{n}` literal that follows `line`, if one does.
+fn literal_len(line: &str) -> Option<usize> {
+    line.strip_suffix('}')?.rsplit_once('{')?.1.parse().ok()
+}
+
+fn quote(text: &str) -> io::Result<String> {
+    if text.contains(['\r', '\n']) {
+        return Err(io::Error::other("IMAP strings can't contain line breaks."));
+    }
+    Ok(format!(
+        "\"{}\"",
+        text.replace('\\', "\\\\").replace('"', "\\\"")
+    ))
+}
+
+/// The subject and plain text of an RFC 5322 message.
+fn parse_message(uid: u64, raw: &[u8]) -> Message {
+    let (headers, body) = split_headers(raw);
+    Message {
+        uid,
+        subject: header(&headers, "subject").map(|s| decode_words(&s)),
+        text: body_text(&headers, body).unwrap_or_default(),
+    }
+}
+
+/// Unfolded `(lowercase name, value)` pairs.
+type Headers = Vec<(String, String)>;
+
+/// The headers, and the body after them.
+fn split_headers(raw: &[u8]) -> (Headers, &[u8]) {
+    // A MIME part may have no headers at all.
+    if let Some(body) = raw
+        .strip_prefix(b"\r\n")
+        .or_else(|| raw.strip_prefix(b"\n"))
+    {
+        return (Vec::new(), body);
+    }
+    let end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|at| (at, at + 4))
+        .or_else(|| {
+            raw.windows(2)
+                .position(|w| w == b"\n\n")
+                .map(|at| (at, at + 2))
+        })
+        .unwrap_or((raw.len(), raw.len()));
+    let mut headers: Headers = Vec::new();
+    for line in String::from_utf8_lossy(&raw[..end.0]).lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    (headers, &raw[end.1..])
+}
+
+fn header(headers: &[(String, String)], name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.clone())
+}
+
+/// A `name=value` parameter of a header such as Content-Type.
+fn param(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|p| {
+        let (n, v) = p.split_once('=')?;
+        n.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+/// The first text/plain part, else the first text part of any kind.
+fn body_text(headers: &[(String, String)], body: &[u8]) -> Option<String> {
+    let content_type = header(headers, "content-type").unwrap_or_else(|| "text/plain".into());
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if mime.starts_with("multipart/") {
+        let boundary = format!("--{}", param(&content_type, "boundary")?);
+        let parts: Vec<(Headers, &[u8])> = split_parts(body, boundary.as_bytes())
+            .into_iter()
+            .map(split_headers)
+            .collect();
+        let is_plain = |headers: &[(String, String)]| {
+            header(headers, "content-type")
+                .is_none_or(|t| t.to_ascii_lowercase().starts_with("text/plain"))
+        };
+        return parts
+            .iter()
+            .filter(|(headers, _)| is_plain(headers))
+            .chain(parts.iter())
+            .find_map(|(headers, body)| body_text(headers, body));
+    }
+    if !mime.starts_with("text/") {
+        return None;
+    }
+    let encoding = header(headers, "content-transfer-encoding").unwrap_or_default();
+    let bytes = match encoding.to_ascii_lowercase().as_str() {
+        "base64" => {
+            let compact: Vec<u8> = body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(compact)
+                .ok()?
+        }
+        "quoted-printable" => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    };
+    let charset = param(&content_type, "charset").unwrap_or_default();
+    Some(decode_charset(&bytes, &charset).replace("\r\n", "\n"))
+}
+
+/// The parts between `boundary` lines, up to the closing one.
+fn split_parts<'a>(body: &'a [u8], boundary: &[u8]) -> Vec<&'a [u8]> {
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut at = 0;
+    while at < body.len() {
+        let end = body[at..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(body.len(), |i| at + i + 1);
+        let line = &body[at..end];
+        if line.starts_with(boundary) {
+            if let Some(start) = start {
+                // The line break before the boundary belongs to it.
+                let part: &[u8] = &body[start..at];
+                let part = part.strip_suffix(b"\n").unwrap_or(part);
+                parts.push(part.strip_suffix(b"\r").unwrap_or(part));
+            }
+            if line[boundary.len()..].starts_with(b"--") {
+                break;
+            }
+            start = Some(end);
+        }
+        at = end;
+    }
+    parts
+}
+
+/// `=XX` escapes; soft line breaks are dropped. In headers `_` is a space.
+fn decode_quoted_printable(text: &[u8], header: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        match text[i] {
+            b'=' if text[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if text[i + 1..].starts_with(b"\n") => i += 2,
+            b'=' if i + 2 < text.len() => {
+                match std::str::from_utf8(&text[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(b'=');
+                        i += 1;
+                    }
+                }
+            }
+            b'_' if header => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset.to_ascii_lowercase().as_str() {
+        "iso-8859-1" | "latin1" | "windows-1252" => bytes.iter().map(|&b| b as char).collect(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// A header value with its `=?charset?B|Q?text?=` encoded words decoded.
+fn decode_words(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let decoded = rest[start + 2..].splitn(4, '?').collect::<Vec<_>>();
+        let word = match decoded.as_slice() {
+            [charset, encoding, text, tail] if tail.starts_with('=') => {
+                let bytes = match encoding.to_ascii_uppercase().as_str() {
+                    "B" => base64::engine::general_purpose::STANDARD.decode(text).ok(),
+                    "Q" => Some(decode_quoted_printable(text.as_bytes(), true)),
+                    _ => None,
+                };
+                // "=?" charset "?" encoding "?" text "?="
+                let len = charset.len() + encoding.len() + text.len() + 6;
+                bytes.map(|b| (decode_charset(&b, charset), len))
+            }
+            _ => None,
+        };
+        let Some((word, len)) = word else {
+            out.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            after_word = false;
+            continue;
+        };
+        let between = &rest[..start];
+        // Space between two encoded words is not part of the text.
+        if !(after_word && between.trim().is_empty()) {
+            out.push_str(between);
+        }
+        out.push_str(&word);
+        rest = &rest[start + len..];
+        after_word = true;
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_fills_in_defaults() {
+        assert_eq!(
+            Account::parse("imaps://me@example.com@mail.example.com").unwrap(),
+            Account {
+                tls: true,
+                host: "mail.example.com".to_string(),
+                port: 993,
+                user: "me@example.com".to_string(),
+                mailbox: "INBOX".to_string(),
+            }
+        );
+        let account = Account::parse("imaps://me@mail.example.com:1993/Notes/Inbox").unwrap();
+        assert_eq!(
+            (account.port, account.mailbox.as_str()),
+            (1993, "Notes/Inbox")
+        );
+        assert_eq!(Account::parse("imaps://me@host/").unwrap().mailbox, "INBOX");
+    }
+
+    #[test]
+    fn parse_allows_plain_imap_only_on_loopback() {
+        for url in [
+            "imap://me@localhost",
+            "imap://me@127.0.0.1:1143",
+            "imap://me@[::1]:143",
+        ] {
+            let account = Account::parse(url).unwrap();
+            assert!(!account.tls, "{}", url);
+        }
+        let error = Account::parse("imap://me@mail.example.com").unwrap_err();
+        assert!(error.contains("unencrypted"), "{}", error);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_urls() {
+        for url in [
+            "pop3://me@host",
+            "mail.example.com",
+            "imaps://mail.example.com",
+            "imaps://@host",
+            "imaps://me@",
+            "imaps://me@host:port",
+        ] {
+            assert!(Account::parse(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn split_parts_stops_at_the_closing_boundary() {
+        let body = b"preamble\r\n--b\r\nfirst\r\n--b\r\nsecond\nline\n--b--\r\nepilogue\r\n--b\r\n";
+        assert_eq!(
+            split_parts(body, b"--b"),
+            vec![&b"first"[..], &b"second\nline"[..]]
+        );
+        assert!(split_parts(b"no boundaries here", b"--b").is_empty());
+    }
+
+    #[test]
+    fn quoted_printable_decodes_escapes_and_soft_breaks() {
+        assert_eq!(
+            decode_quoted_printable(b"Caf=C3=A9 =3D ok=\r\ncontinued=\nhere", false),
+            "Café = okcontinuedhere".as_bytes()
+        );
+        assert_eq!(decode_quoted_printable(b"a_b", false), b"a_b");
+        assert_eq!(decode_quoted_printable(b"a_b", true), b"a b");
+        // Broken escapes are kept as they are.
+        assert_eq!(decode_quoted_printable(b"=ZZ x=4", false), b"=ZZ x=4");
+    }
+
+    #[test]
+    fn decode_words_joins_adjacent_words() {
+        assert_eq!(decode_words("plain subject"), "plain subject");
+        assert_eq!(
+            decode_words("Re: =?UTF-8?B?Q2Fmw6k=?= =?utf-8?q?_menu_=E2=9C=93?= today"),
+            "Re: Café menu ✓ today"
+        );
+        assert_eq!(decode_words("=?ISO-8859-1?Q?na=EFve?="), "naïve");
+    }
+
+    #[test]
+    fn decode_words_keeps_malformed_words() {
+        assert_eq!(
+            decode_words("=?utf-8?X?abc?= and =? alone"),
+            "=?utf-8?X?abc?= and =? alone"
+        );
+        assert_eq!(decode_words("ends =?utf-8?B?"), "ends =?utf-8?B?");
+    }
+
+    #[test]
+    fn messages_prefer_the_plain_text_part() {
+        let raw = b"Subject: =?utf-8?Q?Hi?=\r\n\
+            Content-Type: multipart/alternative;\r\n boundary=\"x\"\r\n\r\n\
+            --x\r\nContent-Type: text/html\r\n\r\n<p>html</p>\r\n\
+            --x\r\nContent-Type: text/plain; charset=utf-8\r\n\
+            Content-Transfer-Encoding: base64\r\n\r\nUGxhaW4K\r\n--x--\r\n";
+        let message = parse_message(7, raw);
+        assert_eq!(message.uid, 7);
+        assert_eq!(message.subject.as_deref(), Some("Hi"));
+        assert_eq!(message.text, "Plain\n");
+    }
+}
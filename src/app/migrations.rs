@@ -0,0 +1,129 @@
+//! Versioned schema changes. Each migration runs once, in order, in its own
+//! transaction, and is recorded in `schema_version`. Add new ones at the end
+//! of `MIGRATIONS`; never edit one that has shipped.
+
+use tokio_postgres::Client;
+
+/// Serializes migrations when several instances start at once.
+const MIGRATION_LOCK: i64 = 0x7067_6e6d;
+
+struct Migration {
+    version: i32,
+    name: &'static str,
+    sql: &'static str,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        sql: include_str!("../../migrations/0001_baseline.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "tags_array",
+        sql: include_str!("../../migrations/0002_tags_array.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "notebooks",
+        sql: include_str!("../../migrations/0003_notebooks.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "revision_word_stats",
+        sql: include_str!("../../migrations/0004_revision_word_stats.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "inline_editing",
+        sql: include_str!("../../migrations/0005_inline_editing.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "content_hashes",
+        sql: include_str!("../../migrations/0006_content_hashes.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "pinned",
+        sql: include_str!("../../migrations/0007_pinned.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "favorites",
+        sql: include_str!("../../migrations/0008_favorites.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "note_type",
+        sql: include_str!("../../migrations/0009_note_type.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "attachments",
+        sql: include_str!("../../migrations/0010_attachments.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "note_changes",
+        sql: include_str!("../../migrations/0011_note_changes.sql"),
+    },
+];
+
+/// Brings the schema up to date. Refuses to run against a schema newer than
+/// this build knows, rather than guessing what changed.
+pub async fn migrate(client: &mut Client) -> std::io::Result<()> {
+    client
+        .batch_execute(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )",
+        )
+        .await
+        .map_err(std::io::Error::other)?;
+
+    let latest = MIGRATIONS.last().map_or(0, |m| m.version);
+    for migration in MIGRATIONS {
+        let tx = client.transaction().await.map_err(std::io::Error::other)?;
+        tx.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK])
+            .await
+            .map_err(std::io::Error::other)?;
+        let current: i32 = tx
+            .query_one("SELECT COALESCE(max(version), 0) FROM schema_version", &[])
+            .await
+            .map_err(std::io::Error::other)?
+            .get(0);
+        if current > latest {
+            return Err(std::io::Error::other(format!(
+                "database schema is at version {} but this pgnotes only knows up to {}; \
+                 upgrade pgnotes",
+                current, latest
+            )));
+        }
+        if current >= migration.version {
+            continue;
+        }
+
+        // The connection's statement_timeout is meant for the UI's queries.
+        tx.batch_execute("SET LOCAL statement_timeout = 0")
+            .await
+            .map_err(std::io::Error::other)?;
+        tx.batch_execute(migration.sql).await.map_err(|e| {
+            std::io::Error::other(format!(
+                "migration {} ({}) failed: {}",
+                migration.version, migration.name, e
+            ))
+        })?;
+        tx.execute(
+            "INSERT INTO schema_version (version, name) VALUES ($1, $2)",
+            &[&migration.version, &migration.name],
+        )
+        .await
+        .map_err(std::io::Error::other)?;
+        tx.commit().await.map_err(std::io::Error::other)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Kinds of notes. A note's type gives it default tags, a preview renderer and
+//! editor file type when its title has no extension, and type-specific actions
+//! (new notes are made from templates with 'n').
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NoteType {
+    #[default]
+    Note,
+    /// A piece of text or code kept for reuse.
+    Snippet,
+    /// Markdown checkboxes.
+    TaskList,
+    /// Dated entries; never listed as stale.
+    Journal,
+    /// Copied into new notes with 'n'; never listed as stale.
+    Template,
+    /// A shell script.
+    Script,
+}
+
+impl NoteType {
+    pub const ALL: [NoteType; 6] = [
+        NoteType::Note,
+        NoteType::Snippet,
+        NoteType::TaskList,
+        NoteType::Journal,
+        NoteType::Template,
+        NoteType::Script,
+    ];
+
+    /// As stored in `notes.note_type` and typed after `:type`.
+    pub fn name(self) -> &'static str {
+        match self {
+            NoteType::Note => "note",
+            NoteType::Snippet => "snippet",
+            NoteType::TaskList => "task-list",
+            NoteType::Journal => "journal",
+            NoteType::Template => "template",
+            NoteType::Script => "script",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    /// Tags a note is given when it becomes this type.
+    pub fn default_tags(self) -> &'static [&'static str] {
+        match self {
+            NoteType::Note => &[],
+            NoteType::Snippet => &["snippet"],
+            NoteType::TaskList => &["todo"],
+            NoteType::Journal => &["journal"],
+            NoteType::Template => &["template"],
+            NoteType::Script => &["script"],
+        }
+    }
+
+    /// The filetype of notes whose title has no extension of its own.
+    pub fn filetype(self) -> Option<&'static str> {
+        match self {
+            NoteType::Script => Some("sh"),
+            _ => None,
+        }
+    }
+
+    /// Whether the Stale view lists it once it goes unchanged for a while.
+    pub fn goes_stale(self) -> bool {
+        !matches!(self, NoteType::Journal | NoteType::Template)
+    }
+}
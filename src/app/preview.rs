@@ -0,0 +1,545 @@
+//! Turning note content into styled preview text. Each content type has a
+//! `PreviewRenderer`; `renderer_for` picks one from the note's filetype, the
+//! extension of its title ("deploy.sh", "prices.csv") or else its `NoteType`'s.
+//! Other notes are Markdown, unless the content itself is plainly Org, CSV or TSV.
+
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use unicode_width::UnicodeWidthStr;
+
+use super::note_type::NoteType;
+use super::theme::Theme;
+
+pub trait PreviewRenderer {
+    /// Styled lines of `content`, one per source line.
+    fn render(&self, content: &str, theme: &Theme) -> Text<'static>;
+
+    /// Whether long lines wrap; tables scroll sideways instead so columns stay aligned.
+    fn wraps(&self) -> bool {
+        true
+    }
+
+    /// Leading lines kept in place while the rest scrolls, e.g. a table header.
+    fn sticky_lines(&self) -> usize {
+        0
+    }
+}
+
+/// The note's filetype: the extension of its title when it is one a renderer
+/// knows, else its type's, else one recognised from the content ("org", "tsv",
+/// "csv"). `None` for ordinary Markdown notes.
+pub fn filetype(title: &str, content: &str, note_type: NoteType) -> Option<String> {
+    title
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .filter(|ext| {
+            matches!(
+                ext.as_str(),
+                "txt" | "log" | "md" | "markdown" | "csv" | "tsv" | "org"
+            ) || Language::from_extension(ext).is_some()
+        })
+        .or_else(|| note_type.filetype().map(str::to_string))
+        .or_else(|| {
+            let sniffed = if is_org(content) {
+                "org"
+            } else {
+                match sniff_delimiter(content)? {
+                    '\t' => "tsv",
+                    _ => "csv",
+                }
+            };
+            Some(sniffed.to_string())
+        })
+}
+
+/// The renderer for a `note_type` note titled `title` holding `content`.
+pub fn renderer_for(title: &str, content: &str, note_type: NoteType) -> Box<dyn PreviewRenderer> {
+    match filetype(title, content, note_type).as_deref() {
+        Some("txt" | "log") => Box::new(Plain),
+        Some("csv") => Box::new(Table { delimiter: ',' }),
+        Some("tsv") => Box::new(Table { delimiter: '\t' }),
+        Some("org") => Box::new(Org),
+        Some(ext) => match Language::from_extension(ext) {
+            Some(language) => Box::new(Code { language }),
+            None => Box::new(Markdown),
+        },
+        None => Box::new(Markdown),
+    }
+}
+
+/// The content as it is.
+pub struct Plain;
+
+impl PreviewRenderer for Plain {
+    fn render(&self, content: &str, _theme: &Theme) -> Text<'static> {
+        Text::from(content.to_string())
+    }
+}
+
+/// Headings, lists, quotes and code, styled in place; the markup stays visible.
+pub struct Markdown;
+
+impl PreviewRenderer for Markdown {
+    fn render(&self, content: &str, theme: &Theme) -> Text<'static> {
+        let mut fence: Option<Option<Language>> = None;
+        let lines = content.lines().map(|line| {
+            let trimmed = line.trim_start();
+            if let Some(info) = trimmed.strip_prefix("```") {
+                fence = match fence {
+                    Some(_) => None,
+                    None => Some(Language::from_extension(info.trim())),
+                };
+                return Line::styled(line.to_string(), theme.code);
+            }
+            match fence {
+                Some(Some(language)) => highlight_line(line, language, theme),
+                Some(None) => Line::styled(line.to_string(), theme.code),
+                None if is_heading(trimmed) => Line::styled(line.to_string(), theme.heading),
+                None if trimmed.starts_with('>') => Line::styled(line.to_string(), theme.dim),
+                None => markdown_line(line, theme),
+            }
+        });
+        Text::from(lines.collect::<Vec<_>>())
+    }
+}
+
+/// "# Title" but not "#tag".
+fn is_heading(line: &str) -> bool {
+    let text = line.trim_start_matches('#');
+    text.len() < line.len() && (text.is_empty() || text.starts_with(' '))
+}
+
+/// A list marker, then inline `code` and **bold** spans.
+fn markdown_line(line: &str, theme: &Theme) -> Line<'static> {
+    let indent = line.len() - line.trim_start().len();
+    let marker_len = list_marker_len(&line[indent..]);
+    let mut spans = vec![Span::raw(line[..indent].to_string())];
+    if marker_len > 0 {
+        spans.push(Span::styled(
+            line[indent..indent + marker_len].to_string(),
+            theme.keyword,
+        ));
+    }
+
+    let rest = &line[indent + marker_len..];
+    let mut plain = String::new();
+    let mut chars = rest.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let (delimiter, style) = match c {
+            '`' => ("`", theme.code),
+            '*' if rest[i..].starts_with("**") => {
+                ("**", Style::default().add_modifier(Modifier::BOLD))
+            }
+            _ => {
+                plain.push(c);
+                continue;
+            }
+        };
+        let body_start = i + delimiter.len();
+        let Some(len) = rest[body_start..].find(delimiter) else {
+            plain.push(c);
+            continue;
+        };
+        spans.push(Span::raw(std::mem::take(&mut plain)));
+        let end = body_start + len + delimiter.len();
+        spans.push(Span::styled(rest[i..end].to_string(), style));
+        while chars.next_if(|&(j, _)| j < end).is_some() {}
+    }
+    spans.push(Span::raw(plain));
+    Line::from(spans)
+}
+
+/// Length of a leading "- ", "* ", "+ " or "12. " list marker, or 0.
+fn list_marker_len(text: &str) -> usize {
+    if ["- ", "* ", "+ "].iter().any(|m| text.starts_with(m)) {
+        return 2;
+    }
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && text[digits..].starts_with(". ") {
+        digits + 2
+    } else {
+        0
+    }
+}
+
+/// Org-mode: headlines with their TODO keyword, lists, `#+` keywords and source blocks.
+pub struct Org;
+
+impl PreviewRenderer for Org {
+    fn render(&self, content: &str, theme: &Theme) -> Text<'static> {
+        let mut block: Option<Option<Language>> = None;
+        let lines = content.lines().map(|line| {
+            let trimmed = line.trim_start();
+            let lower = trimmed.to_ascii_lowercase();
+            if let Some(info) = lower.strip_prefix("#+begin_") {
+                let language = info
+                    .strip_prefix("src")
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .and_then(Language::from_extension);
+                block = Some(language);
+                return Line::styled(line.to_string(), theme.dim);
+            }
+            if lower.starts_with("#+end_") {
+                block = None;
+                return Line::styled(line.to_string(), theme.dim);
+            }
+            match block {
+                Some(Some(language)) => highlight_line(line, language, theme),
+                Some(None) => Line::styled(line.to_string(), theme.code),
+                None if lower.starts_with("#+") => Line::styled(line.to_string(), theme.dim),
+                None if trimmed == "#" || trimmed.starts_with("# ") => {
+                    Line::styled(line.to_string(), theme.comment)
+                }
+                None if org_headline_level(line) > 0 => org_headline(line, theme),
+                None => org_line(line, theme),
+            }
+        });
+        Text::from(lines.collect::<Vec<_>>())
+    }
+}
+
+/// Content that starts with an Org `#+KEYWORD:` line.
+fn is_org(content: &str) -> bool {
+    content
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .is_some_and(|l| l.starts_with("#+") && l.contains(':'))
+}
+
+/// Number of stars of a "** Headline", or 0.
+fn org_headline_level(line: &str) -> usize {
+    let stars = line.chars().take_while(|&c| c == '*').count();
+    if line[stars..].starts_with(' ') {
+        stars
+    } else {
+        0
+    }
+}
+
+/// The headline styled as a heading, its TODO/DONE keyword set apart.
+fn org_headline(line: &str, theme: &Theme) -> Line<'static> {
+    let stars = org_headline_level(line);
+    let text = &line[stars + 1..];
+    let (keyword, rest) = text.split_once(' ').unwrap_or((text, ""));
+    let keyword_style = match keyword {
+        "TODO" | "NEXT" | "WAITING" => theme.keyword,
+        "DONE" | "CANCELED" | "CANCELLED" => theme.dim,
+        _ => return Line::styled(line.to_string(), theme.heading),
+    };
+    Line::from(vec![
+        Span::styled(line[..=stars].to_string(), theme.heading),
+        Span::styled(keyword.to_string(), keyword_style),
+        Span::styled(format!(" {}", rest), theme.heading),
+    ])
+}
+
+/// A list marker ("- ", "+ ", "1. ", "1) "), then ~code~ and =verbatim= spans.
+fn org_line(line: &str, theme: &Theme) -> Line<'static> {
+    let indent = line.len() - line.trim_start().len();
+    let item = &line[indent..];
+    let digits = item.chars().take_while(char::is_ascii_digit).count();
+    let marker_len = if item.starts_with("- ") || item.starts_with("+ ") {
+        2
+    } else if digits > 0 && (item[digits..].starts_with(". ") || item[digits..].starts_with(") ")) {
+        digits + 2
+    } else {
+        0
+    };
+    let mut spans = vec![
+        Span::raw(line[..indent].to_string()),
+        Span::styled(item[..marker_len].to_string(), theme.keyword),
+    ];
+
+    let mut rest = &item[marker_len..];
+    while let Some(start) = rest.find(['~', '=']) {
+        let delimiter = &rest[start..start + 1];
+        let Some(len) = rest[start + 1..].find(delimiter).filter(|&len| len > 0) else {
+            break;
+        };
+        let end = start + 1 + len + 1;
+        spans.push(Span::raw(rest[..start].to_string()));
+        spans.push(Span::styled(rest[start..end].to_string(), theme.code));
+        rest = &rest[end..];
+    }
+    spans.push(Span::raw(rest.to_string()));
+    Line::from(spans)
+}
+
+/// Source code with keywords, strings, numbers and comments highlighted.
+pub struct Code {
+    language: Language,
+}
+
+impl PreviewRenderer for Code {
+    fn render(&self, content: &str, theme: &Theme) -> Text<'static> {
+        Text::from(
+            content
+                .lines()
+                .map(|line| highlight_line(line, self.language, theme))
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Language {
+    comment: &'static str,
+    quotes: &'static str,
+    keywords: &'static [&'static str],
+    /// Keywords match in any case, as SQL is written both ways.
+    ignore_case: bool,
+}
+
+impl Language {
+    /// The language of a file extension or code fence tag.
+    fn from_extension(extension: &str) -> Option<Self> {
+        let (comment, quotes, keywords): (_, _, &[&str]) = match extension {
+            "rs" | "rust" => (
+                "//",
+                "\"",
+                &[
+                    "as", "async", "await", "break", "const", "continue", "else", "enum", "false",
+                    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+                    "pub", "ref", "return", "self", "Self", "static", "struct", "trait", "true",
+                    "type", "use", "where", "while",
+                ],
+            ),
+            "py" | "python" => (
+                "#",
+                "\"'",
+                &[
+                    "and", "as", "async", "await", "break", "class", "continue", "def", "elif",
+                    "else", "except", "False", "finally", "for", "from", "if", "import", "in",
+                    "is", "lambda", "None", "not", "or", "pass", "raise", "return", "True", "try",
+                    "while", "with", "yield",
+                ],
+            ),
+            "sh" | "bash" | "zsh" | "shell" => (
+                "#",
+                "\"'",
+                &[
+                    "case", "do", "done", "elif", "else", "esac", "exit", "export", "fi", "for",
+                    "function", "if", "in", "local", "return", "then", "until", "while",
+                ],
+            ),
+            "sql" => (
+                "--",
+                "'",
+                &[
+                    "and", "as", "by", "create", "delete", "from", "group", "insert", "into",
+                    "join", "left", "limit", "not", "null", "on", "or", "order", "select", "set",
+                    "table", "update", "values", "where", "with",
+                ],
+            ),
+            "js" | "ts" | "javascript" | "typescript" => (
+                "//",
+                "\"'`",
+                &[
+                    "async", "await", "break", "class", "const", "continue", "else", "export",
+                    "false", "for", "function", "if", "import", "let", "new", "null", "return",
+                    "this", "true", "typeof", "var", "while",
+                ],
+            ),
+            "go" => (
+                "//",
+                "\"`",
+                &[
+                    "break", "chan", "const", "continue", "defer", "else", "false", "for", "func",
+                    "go", "if", "import", "map", "nil", "package", "range", "return", "select",
+                    "struct", "switch", "true", "type", "var",
+                ],
+            ),
+            "c" | "h" | "cpp" | "hpp" => (
+                "//",
+                "\"'",
+                &[
+                    "break", "case", "char", "const", "continue", "double", "else", "enum",
+                    "float", "for", "if", "int", "long", "return", "sizeof", "static", "struct",
+                    "switch", "typedef", "unsigned", "void", "while",
+                ],
+            ),
+            "toml" | "yaml" | "yml" | "ini" | "conf" => ("#", "\"'", &["false", "true"]),
+            "json" => ("//", "\"", &["false", "null", "true"]),
+            _ => return None,
+        };
+        Some(Self {
+            comment,
+            quotes,
+            keywords,
+            ignore_case: extension == "sql",
+        })
+    }
+
+    fn is_keyword(&self, word: &str) -> bool {
+        if self.ignore_case {
+            self.keywords.iter().any(|k| k.eq_ignore_ascii_case(word))
+        } else {
+            self.keywords.contains(&word)
+        }
+    }
+}
+
+fn highlight_line(line: &str, language: Language, theme: &Theme) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let token_len = if rest.starts_with(language.comment) {
+            Some((rest.len(), theme.comment))
+        } else if language.quotes.contains(c) {
+            Some((string_len(rest, c), theme.string))
+        } else if c.is_ascii_digit() && !ends_in_word(&plain) {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '_')
+                .unwrap_or(rest.len());
+            Some((len, theme.string))
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            if language.is_keyword(word) && !ends_in_word(&plain) {
+                Some((len, theme.keyword))
+            } else {
+                plain.push_str(word);
+                rest = &rest[len..];
+                continue;
+            }
+        } else {
+            None
+        };
+        match token_len {
+            Some((len, style)) => {
+                if !plain.is_empty() {
+                    spans.push(Span::raw(std::mem::take(&mut plain)));
+                }
+                spans.push(Span::styled(rest[..len].to_string(), style));
+                rest = &rest[len..];
+            }
+            None => {
+                plain.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    spans.push(Span::raw(plain));
+    Line::from(spans)
+}
+
+/// Byte length of the string literal at the start of `text`, quotes included;
+/// an unterminated string runs to the end of the line.
+fn string_len(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == quote => return i + c.len_utf8(),
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+fn ends_in_word(text: &str) -> bool {
+    text.chars()
+        .next_back()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Delimited rows laid out in aligned columns under a header row.
+pub struct Table {
+    delimiter: char,
+}
+
+impl PreviewRenderer for Table {
+    fn render(&self, content: &str, theme: &Theme) -> Text<'static> {
+        let rows: Vec<Vec<String>> = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| split_row(line, self.delimiter))
+            .collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|c| {
+                rows.iter()
+                    .filter_map(|row| row.get(c))
+                    .map(|cell| cell.width())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let format_row = |row: &[String]| {
+            widths
+                .iter()
+                .enumerate()
+                .map(|(c, &width)| {
+                    let cell = row.get(c).map(String::as_str).unwrap_or("");
+                    format!("{}{}", cell, " ".repeat(width - cell.width()))
+                })
+                .collect::<Vec<_>>()
+                .join(" │ ")
+        };
+
+        let mut lines = Vec::new();
+        let mut rows = rows.iter();
+        if let Some(header) = rows.next() {
+            lines.push(Line::styled(format_row(header), theme.heading));
+            let rule = widths
+                .iter()
+                .map(|&w| "─".repeat(w))
+                .collect::<Vec<_>>()
+                .join("─┼─");
+            lines.push(Line::styled(rule, theme.dim));
+        }
+        lines.extend(rows.map(|row| Line::from(format_row(row))));
+        Text::from(lines)
+    }
+
+    fn wraps(&self) -> bool {
+        false
+    }
+
+    fn sticky_lines(&self) -> usize {
+        2
+    }
+}
+
+/// Tab or comma when every line splits into the same number of cells, at least two.
+/// Commas need three rows: two lines of prose can share a comma count by chance.
+fn sniff_delimiter(content: &str) -> Option<char> {
+    let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+    [('\t', 2), (',', 3)]
+        .into_iter()
+        .find(|&(delimiter, min_rows)| {
+            let cells = lines.first().map(|l| split_row(l, delimiter).len());
+            lines.len() >= min_rows
+                && cells.is_some_and(|n| n >= 2)
+                && lines
+                    .iter()
+                    .all(|l| Some(split_row(l, delimiter).len()) == cells)
+        })
+        .map(|(delimiter, _)| delimiter)
+}
+
+/// Splits one line into cells; double quotes group a cell and `""` is a quote.
+fn split_row(line: &str, delimiter: char) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => cells.push(std::mem::take(&mut cell)),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
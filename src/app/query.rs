@@ -0,0 +1,263 @@
+//! The search popup's query language, e.g. `tag:work -tag:archive "deploy" after:2024-01-01`.
+//!
+//! Terms, all of which must match:
+//!   tag:NAME             the note has the tag (a leading '#' is ignored)
+//!   after:DATE           created on or after DATE (YYYY-MM-DD)
+//!   before:DATE          created before DATE
+//!   "some phrase"        title or content contains the phrase, in any case
+//!   word                 free text, searched the way plain queries are
+//! A leading '-' negates any term; a negated word is treated as a phrase.
+
+use crate::app::date_filter::local_midnight;
+use crate::app::state::Note;
+use chrono::{DateTime, NaiveDate, Utc};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Term {
+    Tag(String),
+    After(DateTime<Utc>),
+    Before(DateTime<Utc>),
+    /// Lowercase text the title or content must contain.
+    Phrase(String),
+    Not(Box<Term>),
+}
+
+impl Term {
+    /// Whether `note` matches; phrases look at the title only unless `content` is set.
+    fn matches(&self, note: &Note, content: bool) -> bool {
+        match self {
+            Term::Tag(tag) => note.tags.iter().any(|t| t == tag),
+            Term::After(at) => note.created_at.is_some_and(|t| t >= *at),
+            Term::Before(at) => note.created_at.is_some_and(|t| t < *at),
+            Term::Phrase(phrase) => {
+                note.title.to_lowercase().contains(phrase)
+                    || (content && !note.encrypted && note.content.to_lowercase().contains(phrase))
+            }
+            Term::Not(inner) => !inner.matches(note, content),
+        }
+    }
+
+    fn is_phrase(&self) -> bool {
+        match self {
+            Term::Phrase(_) => true,
+            Term::Not(inner) => inner.is_phrase(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Query {
+    pub terms: Vec<Term>,
+    /// The plain words, joined by single spaces.
+    pub text: String,
+    /// Why a term was left out, e.g. a date still being typed.
+    pub problem: Option<String>,
+}
+
+impl Query {
+    pub fn matches(&self, note: &Note, content: bool) -> bool {
+        self.terms.iter().all(|term| term.matches(note, content))
+    }
+
+    /// Whether a term looks inside note content, which then has to be loaded.
+    pub fn needs_content(&self) -> bool {
+        self.terms.iter().any(Term::is_phrase)
+    }
+
+    /// What search snippets show: the free text, else the first phrase looked for.
+    pub fn snippet_text(&self) -> Option<&str> {
+        if !self.text.is_empty() {
+            return Some(&self.text);
+        }
+        self.terms.iter().find_map(|term| match term {
+            Term::Phrase(phrase) => Some(phrase.as_str()),
+            _ => None,
+        })
+    }
+}
+
+pub fn parse(input: &str) -> Query {
+    let mut query = Query::default();
+    let mut words = Vec::new();
+    for (negated, quoted, token) in tokenize(input) {
+        let term = match token.split_once(':') {
+            _ if quoted => Term::Phrase(token.to_lowercase()),
+            Some((key, value)) if KEYWORDS.contains(&key.to_ascii_lowercase().as_str()) => {
+                match keyword_term(&key.to_ascii_lowercase(), value) {
+                    Ok(term) => term,
+                    Err(problem) => {
+                        query.problem = Some(problem);
+                        continue;
+                    }
+                }
+            }
+            _ if negated => Term::Phrase(token.to_lowercase()),
+            _ => {
+                words.push(token);
+                continue;
+            }
+        };
+        query.terms.push(if negated {
+            Term::Not(Box::new(term))
+        } else {
+            term
+        });
+    }
+    query.text = words.join(" ");
+    query
+}
+
+const KEYWORDS: [&str; 3] = ["tag", "after", "before"];
+
+/// The term for `key:value`, `key` being one of `KEYWORDS` in lowercase.
+fn keyword_term(key: &str, value: &str) -> Result<Term, String> {
+    if value.is_empty() {
+        return Err(format!("'{}:' needs a value.", key));
+    }
+    if key == "tag" {
+        return Ok(Term::Tag(value.trim_start_matches('#').to_string()));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("'{}' is not a YYYY-MM-DD date.", value))?;
+    let at = local_midnight(date);
+    Ok(if key == "after" {
+        Term::After(at)
+    } else {
+        Term::Before(at)
+    })
+}
+
+/// Splits at whitespace outside double quotes into (negated, quoted, text).
+/// An unterminated quote runs to the end, so phrases match while being typed.
+fn tokenize(input: &str) -> Vec<(bool, bool, String)> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let negated = c == '-';
+        if negated {
+            chars.next();
+        }
+        let mut text = String::new();
+        let quoted = chars.peek() == Some(&'"');
+        if quoted {
+            chars.next();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                text.push(c);
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                text.push(c);
+                chars.next();
+            }
+        }
+        if !text.is_empty() {
+            tokens.push((negated, quoted, text));
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phrase(text: &str) -> Term {
+        Term::Phrase(text.to_string())
+    }
+
+    #[test]
+    fn plain_words_become_text() {
+        let query = parse("  deploy   notes ");
+        assert!(query.terms.is_empty());
+        assert_eq!(query.text, "deploy notes");
+        assert_eq!(query.problem, None);
+    }
+
+    #[test]
+    fn quoted_phrases_keep_spaces_and_colons() {
+        let query = parse(r#""Health Checks" "tag:work""#);
+        assert_eq!(
+            query.terms,
+            vec![phrase("health checks"), phrase("tag:work")]
+        );
+        assert_eq!(query.text, "");
+    }
+
+    #[test]
+    fn unterminated_quote_runs_to_the_end() {
+        assert_eq!(
+            parse(r#"tag:ops "half typed"#).terms,
+            vec![Term::Tag("ops".to_string()), phrase("half typed"),]
+        );
+    }
+
+    #[test]
+    fn negation_applies_to_any_term() {
+        let query = parse(r#"-tag:#archive -"old draft" -Word"#);
+        assert_eq!(
+            query.terms,
+            vec![
+                Term::Not(Box::new(Term::Tag("archive".to_string()))),
+                Term::Not(Box::new(phrase("old draft"))),
+                Term::Not(Box::new(phrase("word"))),
+            ]
+        );
+        assert_eq!(query.text, "");
+        assert!(query.needs_content());
+    }
+
+    #[test]
+    fn keywords_are_case_insensitive_and_dates_parse() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+        assert_eq!(
+            parse("AFTER:2024-01-31 Before:2024-01-31").terms,
+            vec![
+                Term::After(local_midnight(date)),
+                Term::Before(local_midnight(date)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_free_text() {
+        let query = parse("http://example.com");
+        assert!(query.terms.is_empty());
+        assert_eq!(query.text, "http://example.com");
+    }
+
+    #[test]
+    fn malformed_terms_are_left_out_with_a_problem() {
+        let query = parse("tag: after:2024-13-01 work");
+        assert!(query.terms.is_empty());
+        assert_eq!(query.text, "work");
+        assert_eq!(
+            query.problem.as_deref(),
+            Some("'2024-13-01' is not a YYYY-MM-DD date.")
+        );
+        assert_eq!(
+            parse("tag:").problem.as_deref(),
+            Some("'tag:' needs a value.")
+        );
+    }
+
+    #[test]
+    fn snippet_text_prefers_free_text() {
+        assert_eq!(parse(r#""a phrase" words"#).snippet_text(), Some("words"));
+        assert_eq!(
+            parse(r#"tag:x "a phrase""#).snippet_text(),
+            Some("a phrase")
+        );
+        assert_eq!(parse("tag:x").snippet_text(), None);
+    }
+}
@@ -0,0 +1,100 @@
+//! Helpers for the search popup: fuzzy title matching, case-insensitive
+//! matching and grep-like snippets.
+
+use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Matcher, Utf32Str};
+use std::ops::Range;
+
+/// Chars of context kept before a match in a snippet.
+const SNIPPET_LEAD: usize = 20;
+
+/// Byte range of the first case-insensitive occurrence of `needle` in `haystack`.
+/// `needle` must already be lowercase.
+pub fn find_ignore_case(haystack: &str, needle: &str) -> Option<Range<usize>> {
+    if needle.is_empty() {
+        return None;
+    }
+    haystack.char_indices().find_map(|(start, _)| {
+        let mut rest = needle.chars();
+        let mut end = start;
+        for c in haystack[start..].chars() {
+            for lower in c.to_lowercase() {
+                if rest.next() != Some(lower) {
+                    return None;
+                }
+            }
+            end += c.len_utf8();
+            if rest.as_str().is_empty() {
+                return Some(start..end);
+            }
+        }
+        None
+    })
+}
+
+/// A search query matched against titles the way fzf does, so "dbbkp" finds
+/// "database backup checklist". Words of the query may match in any order.
+pub struct Fuzzy {
+    pattern: Pattern,
+    matcher: Matcher,
+    buf: Vec<char>,
+}
+
+impl Fuzzy {
+    pub fn new(query: &str) -> Self {
+        Self {
+            pattern: Pattern::parse(query, CaseMatching::Ignore, Normalization::Smart),
+            matcher: Matcher::new(Config::DEFAULT),
+            buf: Vec::new(),
+        }
+    }
+
+    /// How well `text` matches, higher is better; `None` if it does not.
+    pub fn score(&mut self, text: &str) -> Option<u32> {
+        self.pattern
+            .score(Utf32Str::new(text, &mut self.buf), &mut self.matcher)
+    }
+
+    /// Positions of the chars of `text` the query matched, in order; empty if it does not match.
+    pub fn positions(&mut self, text: &str) -> Vec<usize> {
+        let mut indices = Vec::new();
+        self.pattern.indices(
+            Utf32Str::new(text, &mut self.buf),
+            &mut self.matcher,
+            &mut indices,
+        );
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(|i| i as usize).collect()
+    }
+}
+
+/// One line of content around a match, split so the match can be highlighted.
+pub struct Snippet {
+    pub before: String,
+    pub matched: String,
+    pub after: String,
+}
+
+/// The first content line containing `query` (lowercase), trimmed to a little
+/// lead-in before the match.
+pub fn snippet(content: &str, query: &str) -> Option<Snippet> {
+    content.lines().find_map(|line| {
+        let range = find_ignore_case(line, query)?;
+        let before = line[..range.start].trim_start();
+        let lead: Vec<char> = before.chars().collect();
+        let before = if lead.len() > SNIPPET_LEAD {
+            format!(
+                "…{}",
+                lead[lead.len() - SNIPPET_LEAD..].iter().collect::<String>()
+            )
+        } else {
+            before.to_string()
+        };
+        Some(Snippet {
+            before,
+            matched: line[range.clone()].to_string(),
+            after: line[range.end..].trim_end().to_string(),
+        })
+    })
+}
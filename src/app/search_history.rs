@@ -0,0 +1,76 @@
+//! Recent search queries, recalled with Up/Down in the search popup and kept
+//! in the data directory between sessions.
+
+use std::fs;
+use std::path::PathBuf;
+
+const SEARCH_HISTORY_LIMIT: usize = 50;
+pub const SEARCH_HISTORY_FILE_NAME: &str = "search_history";
+
+pub struct SearchHistory {
+    /// Oldest first.
+    entries: Vec<String>,
+    /// Index into `entries` while browsing; `None` when editing a fresh query.
+    cursor: Option<usize>,
+    path: PathBuf,
+}
+
+impl SearchHistory {
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|s| {
+                s.lines()
+                    .filter(|l| !l.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            entries,
+            cursor: None,
+            path,
+        }
+    }
+
+    /// Moves `query` to the newest position and writes the history out.
+    pub fn record(&mut self, query: &str) {
+        self.cursor = None;
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+        self.entries.retain(|e| e != query);
+        self.entries.push(query.to_string());
+        if self.entries.len() > SEARCH_HISTORY_LIMIT {
+            self.entries.remove(0);
+        }
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(&self.path, self.entries.join("\n"));
+    }
+
+    pub fn older(&mut self) -> Option<&str> {
+        let i = match self.cursor {
+            Some(i) => i.checked_sub(1)?,
+            None => self.entries.len().checked_sub(1)?,
+        };
+        self.cursor = Some(i);
+        self.entries.get(i).map(String::as_str)
+    }
+
+    /// Steps towards newer entries; past the newest returns an empty query.
+    pub fn newer(&mut self) -> Option<&str> {
+        let i = self.cursor? + 1;
+        if i >= self.entries.len() {
+            self.cursor = None;
+            return Some("");
+        }
+        self.cursor = Some(i);
+        self.entries.get(i).map(String::as_str)
+    }
+
+    pub fn reset_cursor(&mut self) {
+        self.cursor = None;
+    }
+}
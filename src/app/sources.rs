@@ -0,0 +1,53 @@
+use crate::app::config::{SourceConfig, TlsConfig};
+use crate::app::db::Database;
+use crate::app::state::Note;
+
+/// A read-only database whose notes are merged into the list.
+pub struct Source {
+    pub name: String,
+    database_url: String,
+    tls: TlsConfig,
+    db: Option<Database>,
+    /// Last successful fetch; kept when the source becomes unreachable.
+    pub notes: Vec<Note>,
+}
+
+impl Source {
+    pub fn new(config: &SourceConfig, tls: &TlsConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            database_url: config.database_url.clone(),
+            tls: tls.clone(),
+            db: None,
+            notes: Vec::new(),
+        }
+    }
+
+    /// Connects if needed and fetches the notes again.
+    pub async fn reload(&mut self) -> Result<(), String> {
+        if self.db.as_ref().is_none_or(|db| db.is_closed()) {
+            self.db = Some(
+                Database::connect_read_only(&self.database_url, &self.tls)
+                    .await
+                    .map_err(|e| e.to_string())?,
+            );
+        }
+        let db = self.db.as_mut().expect("connected above");
+        self.notes = db
+            .get_source_notes(&self.name)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// Gives source notes negative ids, unique across all sources, so they never
+/// collide with ids from the primary database. Source notes are never written
+/// back, so their original ids are not needed.
+pub fn assign_ids(sources: &mut [Source]) {
+    let mut next = -1;
+    for note in sources.iter_mut().flat_map(|s| s.notes.iter_mut()) {
+        note.id = next;
+        next -= 1;
+    }
+}
@@ -1,15 +1,209 @@
-use crate::app::db::Database;
+use crate::app::clip;
+use crate::app::clipboard::{self, CopyFormat};
+use crate::app::config::{
+    Config, Confirmations, LintRules, ListDensity, ListPosition, PreviewRefresh, TitleRules,
+    TlsConfig,
+};
+use crate::app::crypto;
+use crate::app::data_dir;
+use crate::app::date_filter::DateFilter;
+use crate::app::db::{
+    self, Attachment, ConnectionReport, Database, Notebook, Presence, Revision, SizeReport,
+};
+use crate::app::diff::{self, DiffLine};
+use crate::app::editor;
+use crate::app::export::{self, ExportFormat};
+use crate::app::filter_expr::{self, Expr};
+use crate::app::input::TextInput;
+use crate::app::links::{self, GraphEntry, ReportEntry};
+use crate::app::lint::{self, Finding};
+use crate::app::lorem;
+use crate::app::note_type::NoteType;
+use crate::app::preview;
+use crate::app::query;
+use crate::app::search;
+use crate::app::search_history::{self, SearchHistory};
+use crate::app::sources::{self, Source};
+use crate::app::tag_editor::TagEditor;
+use crate::app::taxonomy::{self, TagMeta};
+use crate::app::text_area::TextArea;
+use crate::app::theme::Theme;
+use chrono::{DateTime, Local, TimeDelta, Utc};
 use ratatui::widgets::ListState;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::future::Future;
 use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tokio::task::{JoinError, JoinHandle};
+use tokio_postgres::config::Host;
 
-#[derive(Debug, Clone)]
+pub const STATUS_HISTORY_LIMIT: usize = 100;
+/// Notes with this tag are masked in the preview until revealed.
+pub const SECRET_TAG: &str = "secret";
+
+#[derive(Debug, Clone, Default)]
 pub struct Note {
     pub id: i32,
     pub title: String,
+    /// Empty until `content_loaded`: the note list is fetched without content,
+    /// which is loaded when a note is shown or a feature needs it.
     pub content: String,
+    pub content_loaded: bool,
     pub tags: Vec<String>,
     pub archived: bool,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    /// `content` holds ciphertext from `crypto::encrypt`.
+    pub encrypted: bool,
+    /// Tags this note is pinned to the top of.
+    pub pinned_in: Vec<String>,
+    /// Pinned to the top of every list.
+    pub pinned: bool,
+    /// Listed in the Favorites view.
+    pub favorite: bool,
+    pub note_type: NoteType,
+    /// Bytes of content, known before the content itself is loaded.
+    pub size: usize,
+    pub remind_at: Option<DateTime<Utc>>,
+    /// Unknown for notes created before the column was added.
+    pub created_at: Option<DateTime<Utc>>,
+    /// The earlier version this note replaced.
+    pub supersedes: Option<i32>,
+    /// Set while the note is in the trash.
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub notebook_id: Option<i32>,
+    /// Opens in the inline editor instead of the external one.
+    pub edit_inline: bool,
+    /// Name of the read-only source this note was merged in from; `None` for the primary database.
+    pub source: Option<String>,
+    pub activity: EditActivity,
+}
+
+/// Weeks of edit history summed up in `EditActivity`.
+pub const ACTIVITY_WEEKS: usize = 12;
+
+/// Words changed by the note's edits over the last `ACTIVITY_WEEKS` weeks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditActivity {
+    /// Words added plus removed per week, oldest first.
+    pub weekly: [i64; ACTIVITY_WEEKS],
+    pub words_added: i64,
+    pub words_removed: i64,
+}
+
+impl Default for EditActivity {
+    fn default() -> Self {
+        Self {
+            weekly: [0; ACTIVITY_WEEKS],
+            words_added: 0,
+            words_removed: 0,
+        }
+    }
+}
+
+/// A rename waiting for confirmation because other notes link to the old title.
+#[derive(Debug, Clone)]
+pub struct PendingRename {
+    pub id: i32,
+    pub old_title: String,
+    pub new_title: String,
+    pub linking_notes: Vec<(i32, String)>,
+}
+
+/// An edit that could not be written to the database yet.
+#[derive(Debug, Clone)]
+pub struct PendingSave {
+    /// The edit it came from; its `loaded_at` tells a later replay whether the
+    /// note changed meanwhile.
+    pub session: EditSession,
+    /// The edit as plaintext.
+    pub mine: String,
+    /// The edit as it is written, encrypted for encrypted notes.
+    pub content: String,
+}
+
+/// A note open in the external or the inline editor.
+#[derive(Debug, Clone)]
+pub struct EditSession {
+    pub id: i32,
+    pub title: String,
+    /// Content in the database when editing started, as plaintext.
+    pub original: String,
+    /// Set for encrypted notes, which are encrypted again with it on save.
+    pub passphrase: Option<String>,
+    /// The note's `updated_at` when `original` was read; the save is refused if it moved on.
+    pub loaded_at: Option<DateTime<Utc>>,
+}
+
+/// An external edit held back until its diff is accepted.
+#[derive(Debug, Clone)]
+pub struct EditReview {
+    pub session: EditSession,
+    pub edited: String,
+    pub lines: Vec<DiffLine>,
+    pub scroll: u16,
+}
+
+/// An edit refused because the note changed while it was being made.
+#[derive(Debug, Clone)]
+pub struct EditConflict {
+    pub session: EditSession,
+    pub mine: String,
+    /// The note as it is now, as plaintext.
+    pub theirs: String,
+    pub theirs_at: Option<DateTime<Utc>>,
+    pub scroll: u16,
+}
+
+/// Pause in typing after which the search query runs.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// How long the selection must stay put before a `Settled` preview follows it.
+const PREVIEW_SETTLE: Duration = Duration::from_millis(100);
+
+/// Time between draft writes while the inline editor has unsaved changes.
+const DRAFT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a toast stays up.
+const TOAST_TIME: Duration = Duration::from_secs(5);
+
+/// Where the ids of watched notes are kept between runs, one per line.
+const WATCHED_FILE_NAME: &str = "watched";
+
+/// A note open in the inline editor, which replaces the preview pane.
+#[derive(Debug, Clone)]
+pub struct InlineEdit {
+    pub session: EditSession,
+    pub area: TextArea,
+    /// Whether `Database::try_lock_note` succeeded, so closing must unlock.
+    pub locked: bool,
+    /// Changed since the draft was last written.
+    pub draft_dirty: bool,
+    pub draft_saved_at: Option<Instant>,
+    /// Esc was pressed once with unsaved changes; pressing it again discards them.
+    pub discard_armed: bool,
+}
+
+/// What the passphrase prompt will be used for once submitted.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PassphrasePurpose {
+    Unlock(i32),
+    Encrypt(i32),
+    /// Chooses the lock screen passphrase for this session, then locks.
+    SetLock,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NotebookFilter {
+    All,
+    /// Notes in no notebook.
+    Unfiled,
+    Notebook(i32),
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -17,6 +211,13 @@ pub enum TagFilter {
     All,
     Untagged,
     Specific(String),
+    /// Boolean tag expression; `source` is what the user typed.
+    Expression {
+        source: String,
+        expr: Expr,
+    },
+    /// Created/updated timestamps within a range.
+    Date(DateFilter),
 }
 
 impl std::fmt::Display for TagFilter {
@@ -25,15 +226,109 @@ impl std::fmt::Display for TagFilter {
             TagFilter::All => write!(f, "All Notes"),
             TagFilter::Untagged => write!(f, "Untagged"),
             TagFilter::Specific(t) => write!(f, "#{}", t),
+            TagFilter::Expression { source, .. } => write!(f, "[{}]", source),
+            TagFilter::Date(date) => write!(f, "{}", date),
         }
     }
 }
 
-
+/// Focusable column in the miller-columns layout.
 #[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Column {
+    Tags,
+    Notes,
+    Preview,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum ViewMode {
     Active,
+    /// Notes marked as favorites with 'f', archived ones included.
+    Favorites,
     Archived,
+    /// Active notes not updated within `stale_after_days`, oldest first.
+    Stale,
+    /// Notes with a reminder set, soonest first.
+    Reminders,
+    /// Deleted notes, most recent first, until restored or purged.
+    Trash,
+}
+
+impl ViewMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            ViewMode::Active => "Notes",
+            ViewMode::Favorites => "Favorites",
+            ViewMode::Archived => "Archived Notes",
+            ViewMode::Stale => "Stale Notes",
+            ViewMode::Reminders => "Reminders",
+            ViewMode::Trash => "Trash",
+        }
+    }
+}
+
+/// Where the sort order chosen with 'o' or 's' is kept between runs.
+const SORT_MODE_FILE_NAME: &str = "sort_mode";
+
+/// Every mode but `Title` applies in every view and puts the most, or most recent, first.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SortMode {
+    /// Alphabetical, or the view's own order (e.g. oldest first in Stale).
+    #[default]
+    Title,
+    Created,
+    Modified,
+    Size,
+    TagCount,
+}
+
+impl SortMode {
+    pub const ALL: [SortMode; 5] = [
+        SortMode::Title,
+        SortMode::Created,
+        SortMode::Modified,
+        SortMode::Size,
+        SortMode::TagCount,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Title => "title",
+            SortMode::Created => "newest",
+            SortMode::Modified => "last modified",
+            SortMode::Size => "size",
+            SortMode::TagCount => "tag count",
+        }
+    }
+
+    /// As written to the state file.
+    fn name(self) -> &'static str {
+        match self {
+            SortMode::Title => "title",
+            SortMode::Created => "created",
+            SortMode::Modified => "updated",
+            SortMode::Size => "size",
+            SortMode::TagCount => "tags",
+        }
+    }
+
+    /// The mode saved by the last run, if any.
+    fn load() -> Self {
+        let saved =
+            fs::read_to_string(data_dir::state_file(SORT_MODE_FILE_NAME)).unwrap_or_default();
+        Self::ALL
+            .into_iter()
+            .find(|m| m.name() == saved.trim())
+            .unwrap_or_default()
+    }
+
+    fn save(self) {
+        let path = data_dir::state_file(SORT_MODE_FILE_NAME);
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(path, self.name());
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -46,61 +341,736 @@ pub enum InputMode {
     SelectingTagFilter,
     Searching,
     ShowHelp,
+    ConfirmingQuit,
+    StatusHistory,
+    SelectingExportFormat,
+    /// Pick one of `SortMode::ALL`.
+    SelectingSortMode,
+    /// Pick the `CopyFormat` to copy the selected note to the clipboard in.
+    SelectingCopyFormat,
+    ConfirmingLinkRewrite,
+    LinkGraph,
+    OrphanReport,
+    LintReport,
+    SizeReport,
+    /// Earlier versions of `revision_note`, with a preview and restore.
+    History,
+    /// Files attached to `attachment_note`: open, save, rename or delete them.
+    Attachments,
+    /// Typing a new name or a save path for the highlighted attachment.
+    NamingAttachment,
+    /// Set or snooze the reminder of `reminder_note`.
+    Reminder,
+    EnteringPassphrase,
+    EditingFilterExpression,
+    EditingDateRange,
+    /// New name for the tag highlighted in `SelectingTagFilter` or
+    /// `ManagingTags`, on every note.
+    RenamingTag,
+    /// Every tag with its note count, to rename, merge or delete tags.
+    ManagingTags,
+    ConfirmingTagDelete,
+    /// Another instance has the selected note open; asks before editing it too.
+    ConfirmingConcurrentEdit,
+    ConfirmingArchive,
+    /// Asks before the tag taxonomy file overwrites colors and parents.
+    ConfirmingTaxonomyImport,
+    /// Diff of `edit_review`, saved or discarded from here.
+    ReviewingEdit,
+    /// A URL was pasted: link it into the selected note or clip it as a new note.
+    ConfirmingUrlPaste,
+    /// Numbered tags toggled on `quick_tag_note` by pressing 1-9.
+    TogglingTags,
+    /// Switch notebooks, or move the selected note into one.
+    SelectingNotebook,
+    NamingNotebook,
+    /// `:` command line; see `COMMANDS`.
+    Command,
+    /// Typing into `inline_edit` in the preview pane.
+    InlineEdit,
+    /// Both versions of `edit_conflict`, to pick one or merge them.
+    ResolvingConflict,
+    /// Lock screen; hides everything until the passphrase is entered.
+    Locked,
+}
+
+/// Commands of the `:` command line with their usage, run by `events::run_command`.
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("new", "new [title]        create a note"),
+    ("edit", "edit               edit the selected note"),
+    ("rename", "rename [title]     rename the selected note"),
+    (
+        "delete",
+        "delete             move the selected note to the trash",
+    ),
+    (
+        "archive",
+        "archive            archive / unarchive the selected note",
+    ),
+    ("tag", "tag TAG...         add tags to the selected note"),
+    (
+        "untag",
+        "untag TAG...       remove tags from the selected note",
+    ),
+    (
+        "filter",
+        "filter [#TAG|EXPR] filter by tag or tag expression; none clears it",
+    ),
+    ("search", "search [TEXT]      search titles and content"),
+    (
+        "type",
+        "type [TYPE]        show or set the note's type, e.g. journal or template",
+    ),
+    (
+        "view",
+        "view               cycle Active/Favorites/Archived/Stale/Reminders/Trash",
+    ),
+    (
+        "reconnect",
+        "reconnect          test the database connection, reconnecting if it dropped",
+    ),
+    (
+        "tags",
+        "tags               list tags to rename, merge or delete them",
+    ),
+    ("help", "help               show the key bindings"),
+    ("quit", "quit               quit"),
+];
+
+impl InputMode {
+    /// Name shown in the status bar.
+    pub fn label(self) -> &'static str {
+        match self {
+            InputMode::Normal => "NORMAL",
+            InputMode::EditingFilename => "NEW NOTE",
+            InputMode::EditingTags | InputMode::TogglingTags => "TAGS",
+            InputMode::RenamingScript => "RENAME",
+            InputMode::SelectingTagFilter
+            | InputMode::EditingFilterExpression
+            | InputMode::EditingDateRange => "FILTER",
+            InputMode::RenamingTag => "RENAME",
+            InputMode::ManagingTags => "TAGS",
+            InputMode::Searching => "SEARCH",
+            InputMode::ShowHelp => "HELP",
+            InputMode::ConfirmingDelete
+            | InputMode::ConfirmingQuit
+            | InputMode::ConfirmingLinkRewrite
+            | InputMode::ConfirmingConcurrentEdit
+            | InputMode::ConfirmingArchive
+            | InputMode::ConfirmingTaxonomyImport
+            | InputMode::ConfirmingTagDelete => "CONFIRM",
+            InputMode::ConfirmingUrlPaste => "PASTE",
+            InputMode::ReviewingEdit => "REVIEW",
+            InputMode::ResolvingConflict => "CONFLICT",
+            InputMode::Command => "COMMAND",
+            InputMode::InlineEdit => "EDIT",
+            InputMode::StatusHistory => "MESSAGES",
+            InputMode::SelectingExportFormat => "EXPORT",
+            InputMode::SelectingSortMode => "SORT",
+            InputMode::SelectingCopyFormat => "COPY",
+            InputMode::LinkGraph | InputMode::OrphanReport => "LINKS",
+            InputMode::LintReport => "LINT",
+            InputMode::SizeReport => "STORAGE",
+            InputMode::History => "HISTORY",
+            InputMode::Attachments | InputMode::NamingAttachment => "FILES",
+            InputMode::Reminder => "REMINDER",
+            InputMode::EnteringPassphrase => "PASSPHRASE",
+            InputMode::SelectingNotebook | InputMode::NamingNotebook => "NOTEBOOKS",
+            InputMode::Locked => "LOCKED",
+        }
+    }
+}
+
+type ConnectionCheck = io::Result<(Database, ConnectionReport)>;
+type NotesFetch = Result<Vec<Note>, tokio_postgres::Error>;
+type ContentFetch = Result<Option<String>, tokio_postgres::Error>;
+type SearchFetch = Result<db::SearchMatches, tokio_postgres::Error>;
+
+/// Work that ran in the background and is back; see `AppState::fetched`.
+pub enum Fetched {
+    Notes(NotesArrival),
+    Content(i32, Result<ContentFetch, JoinError>),
+    Search(String, Result<SearchFetch, JoinError>),
+    Call(CallDone),
+}
+
+/// Tag metadata and counts read again after a tag was renamed or deleted.
+struct TagReload {
+    meta: Result<Vec<TagMeta>, db::Error>,
+    counts: Result<Vec<(String, i64)>, db::Error>,
+}
+
+impl TagReload {
+    async fn load(db: &mut Database) -> Self {
+        Self {
+            meta: db.get_tag_meta().await,
+            counts: db.tag_counts().await,
+        }
+    }
+}
+
+/// Applies the result of a call made by `AppState::spawn_call`.
+pub type CallDone = Box<dyn FnOnce(&mut AppState, &mut Database) + Send>;
+type QueuedCall = Pin<Box<dyn Future<Output = CallDone> + Send>>;
+
+/// Database calls spawned off the event loop. One task runs them one after
+/// another, so writes land in the order they were made.
+struct CallQueue {
+    queued: tokio::sync::mpsc::UnboundedSender<QueuedCall>,
+    done: tokio::sync::mpsc::UnboundedReceiver<CallDone>,
+    /// Calls made whose result has not been applied yet.
+    pending: usize,
+}
+
+impl CallQueue {
+    fn start() -> Self {
+        let (queued, mut calls) = tokio::sync::mpsc::unbounded_channel::<QueuedCall>();
+        let (finished, done) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(call) = calls.recv().await {
+                if finished.send(call.await).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            queued,
+            done,
+            pending: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AttachmentAction {
+    Rename,
+    /// Write a copy to the path typed.
+    SaveAs,
+}
+
+/// What a background load of the note list has to show so far.
+pub enum NotesArrival {
+    /// The first notes of the initial load, shown while the rest are read.
+    FirstPage(Vec<Note>),
+    All(Result<NotesFetch, JoinError>),
 }
 
 pub struct AppState {
     pub all_notes: Vec<Note>,
+    /// Lowercased title and tags of each of `all_notes`, by index, one per line;
+    /// rebuilt with the list so typing a search does not lowercase every note.
+    search_index: Vec<String>,
     pub notes: Vec<Note>,
+    /// Notes marked with Space; while any are, 'd', 'x' and 't' act on all of them.
+    /// Only listed notes of your own database stay marked.
+    pub marked: HashSet<i32>,
     pub list_state: ListState,
     pub status_message: String,
+    pub status_history: VecDeque<(DateTime<Local>, String)>,
+    statuses_set: u64,
+    pub history_list_state: ListState,
     pub script_content_preview: String,
     pub input_mode: InputMode,
-    pub filename_input: String,
+    /// Modes underneath `input_mode`, so popups can layer and unwind in order.
+    pub mode_stack: Vec<InputMode>,
+    pub filename_input: TextInput,
+    /// Validation problem with the current title input, shown inline in the popup.
+    pub input_error: Option<String>,
+    /// Existing notes resembling the title being typed for a new note.
+    pub title_suggestions: Vec<(i32, String)>,
+    pub selected_suggestion: usize,
+    /// Content and tags the note being named starts with, copied from a template note.
+    pub new_from_template: Option<(String, Vec<String>)>,
+    pub title_rules: TitleRules,
+    pub pending_rename: Option<PendingRename>,
+
+    pub graph_entries: Vec<GraphEntry>,
+    pub graph_list_state: ListState,
+
+    pub orphan_report: Vec<ReportEntry>,
+    pub report_list_state: ListState,
+
+    pub size_report: SizeReport,
+    pub size_list_state: ListState,
+
+    /// Read-only databases merged into the list.
+    pub sources: Vec<Source>,
+    /// Notes other pgnotes instances currently have open in an editor.
+    pub presence: Vec<Presence>,
+    /// Notes whose changes by other instances are announced; toggled with 'w'.
+    pub watched: HashSet<i32>,
+    /// Watched notes changed elsewhere and not viewed since.
+    pub changed_elsewhere: HashSet<i32>,
+    /// Highlighted message shown over the list until the deadline passes.
+    pub toast: Option<(String, Instant)>,
+
+    pub revision_note: Option<i32>,
+    pub revisions: Vec<Revision>,
+    pub revision_list_state: ListState,
+
+    pub attachment_note: Option<i32>,
+    pub attachments: Vec<Attachment>,
+    pub attachment_list_state: ListState,
+    pub attachment_input: TextInput,
+    /// What `attachment_input` is for.
+    pub attachment_action: AttachmentAction,
+    /// 'd' was pressed once on the highlighted attachment; a second press deletes it.
+    attachment_delete_armed: bool,
+
+    pub lint_rules: LintRules,
+    pub confirm: Confirmations,
+    pub lint_findings: Vec<Finding>,
+
+    pub reminder_note: Option<i32>,
+    /// Whether the reminder popup was opened because the reminder came due.
+    pub reminder_due: bool,
+    /// Reminders already announced, so each due time pops up only once.
+    pub announced_reminders: HashSet<(i32, DateTime<Utc>)>,
+
+    /// Decrypted content and passphrase of encrypted notes unlocked this session.
+    pub unlocked_notes: HashMap<i32, (String, String)>,
+    pub passphrase_input: String,
+    pub passphrase_purpose: Option<PassphrasePurpose>,
+    /// A new passphrase's first entry, while it is typed again to confirm it.
+    pub passphrase_first: Option<String>,
+    /// Secret-tagged note currently shown unmasked; cleared when another note is selected.
+    pub revealed_note: Option<i32>,
+    /// Shows placeholder titles and content instead of real notes; display only.
+    pub screencast: bool,
+
+    pub lock_passphrase: Option<String>,
+    pub lock_idle: Option<Duration>,
+    pub lock_input: String,
+    pub last_activity: Instant,
     pub help_message: String,
     pub editor_cmd: String,
+    pub db_url: String,
+    /// `user@host:port/dbname` of `db_url`, without the password, for the status bar.
+    pub connection_label: String,
+    /// Started with --safe-mode: read-only, and nothing runs outside the database.
+    pub safe_mode: bool,
+    pub tls: TlsConfig,
+    pub connection_check: Option<Receiver<ConnectionCheck>>,
+    /// The note list being loaded in the background; see `refresh_notes`.
+    pub notes_fetch: Option<JoinHandle<NotesFetch>>,
+    /// The first page of the load in flight, when nothing is listed yet.
+    notes_first_page: Option<oneshot::Receiver<Vec<Note>>>,
+    /// Note to select once the load in flight has been applied.
+    select_after_refresh: Option<i32>,
+    /// Note whose content the preview is waiting for; see `load_content`.
+    content_request: Option<i32>,
+    /// The content load in flight, and whose content it is.
+    content_fetch: Option<(i32, JoinHandle<ContentFetch>)>,
+    /// Started by the first `spawn_call`.
+    calls: Option<CallQueue>,
+    /// Set while the database circuit breaker is open: time left until the next retry.
+    pub db_offline: Option<Duration>,
+    /// Set while a lost connection is being replaced; see `Database::poll_reconnect`.
+    pub db_reconnecting: bool,
 
     pub active_filter: TagFilter,
+    /// Saved boolean filter expressions, listed after the tags in the selector.
+    pub smart_filters: Vec<String>,
+    pub filter_expr_input: TextInput,
+    pub command_input: TextInput,
+    pub date_range_input: TextInput,
+    /// The tag `RenamingTag` renames, and what it is renamed to.
+    pub renaming_tag: Option<String>,
+    pub tag_rename_input: TextInput,
+    /// Tags and their note counts in `ManagingTags`.
+    pub tag_counts: Vec<(String, i64)>,
+    pub tag_list_state: ListState,
+    /// Tag picked up with 'm' in `ManagingTags`, to be merged into the next one picked.
+    pub merging_tag: Option<String>,
+    /// Colors and parents from the `tag_meta` table, keyed by tag name.
+    pub tag_meta: HashMap<String, TagMeta>,
     pub available_filters: Vec<TagFilter>,
     pub filter_list_state: ListState,
 
+    pub notebooks: Vec<Notebook>,
+    /// Applied on top of the view, tag filter and search.
+    pub active_notebook: NotebookFilter,
+    pub notebook_list_state: ListState,
+    pub notebook_input: TextInput,
+    pub tag_editor: TagEditor,
+    /// Tags all marked notes shared when the tag editor opened for them.
+    pub bulk_tags: Option<Vec<String>>,
+    pub edit_review: Option<EditReview>,
+    pub inline_edit: Option<InlineEdit>,
+    pub edit_conflict: Option<EditConflict>,
+    /// Conflicts found replaying pending saves, shown one after another.
+    pub conflict_queue: VecDeque<EditConflict>,
+    /// URL waiting for `ConfirmingUrlPaste`.
+    pub pasted_url: Option<String>,
+
+    /// Miller-columns layout: tags | notes | preview, navigated with h/l.
+    pub columns_view: bool,
+    pub focused_column: Column,
+    pub column_filter_state: ListState,
+    /// Note picked up with 'm' in the columns view, waiting to be dropped on a tag.
+    pub moving_note: Option<i32>,
+
     pub search_query: String,
+    /// Search matches titles only instead of titles, tags and content; Tab toggles.
+    pub search_titles_only: bool,
+    /// Ids of the notes `Database::search_notes` found for the query it last ran.
+    search_matches: Option<(String, HashSet<i32>)>,
+    /// When the query typed last is due to run; see `poll_search`.
+    search_due: Option<Instant>,
+    /// The search in flight, and its query.
+    search_fetch: Option<(String, JoinHandle<SearchFetch>)>,
+    pub search_history: SearchHistory,
     pub preview_scroll: u16,
+    /// Note the preview shows, whose scroll goes to `read_positions` when it changes.
+    preview_note: Option<i32>,
+    /// Where each note's preview was last scrolled to this session.
+    read_positions: HashMap<i32, u16>,
+    /// Columns scrolled past in previews that don't wrap, such as tables.
+    pub preview_hscroll: u16,
+    pub preview_refresh: PreviewRefresh,
+    /// When the preview, left behind by j/k, should catch up with the selection.
+    preview_due: Option<Instant>,
 
     pub view_mode: ViewMode,
+    /// Tag filter and search query remembered for each view while it is not shown.
+    pub view_filters: HashMap<ViewMode, (TagFilter, String)>,
+    pub sort_mode: SortMode,
+    pub stale_after_days: i64,
+    /// How many most-used tags `open_quick_tags` offers.
+    pub quick_tag_count: usize,
+    /// Numbered in this order while `TogglingTags`; kept fixed so numbers don't shift.
+    pub quick_tags: Vec<String>,
+    quick_tag_note: Option<i32>,
+    pub list_density: ListDensity,
+    pub list_position: ListPosition,
+    pub list_size: Option<u16>,
+    pub theme: Theme,
+
+    pub pending_saves: Vec<PendingSave>,
+
+    pub export_dir: PathBuf,
+    pub export_format_state: ListState,
+    pub sort_mode_state: ListState,
+    pub copy_format_state: ListState,
 }
 
 impl AppState {
-    pub fn new(db_url: String, editor_cmd: String) -> Self {
+    pub fn new(config: &Config) -> Self {
         let help_message = format!(
-            "Welcome to Postgres Notes!\n\nDatabase: {}\n\n--- Keybinds ---\n'j'/'k'        : Navigate notes\n'Enter'/'e'    : Edit selected note\n'a'            : Add a new note\n'd'            : Delete selected note\n'r'            : Rename selected note\n'x'            : Archive/Unarchive note ‼️\n'v'            : Toggle Archive view ‼️\n't'            : Edit tags for note\n'Shift+t'      : Filter by Tag\n'/'            : Search Titles\n'Ctrl+j/k'     : Scroll Preview\n'?'            : Toggle help\n'q'            : Quit",
-            db_url
+            "Welcome to Postgres Notes!\n\nDatabase: {}\n\n--- Keybinds ---\n'j'/'k'        : Navigate notes\n'Enter'/'e'    : Edit selected note\n'i'            : Edit inline in the preview pane (Ctrl+s save, Esc close)\n'Shift+i'      : Open this note inline with Enter/'e' too\n'Space'        : Mark note; 'd', 'x' and 't' then act on all marked ('Esc' unmarks)\n'a'            : Add a new note (Up/Down/Tab: open a similar existing note)\n'n'            : New note from the selected template note (see :type)\n'd'            : Move note to Trash (in Trash: delete permanently)\n'r'            : Rename selected note\n'x'            : Archive/Unarchive note (in Trash: restore) ‼️\n'v'            : Cycle Active/Favorites/Archived/Stale/Reminders/Trash views ‼️\n'f'            : Add / remove note from Favorites\n'y'            : Copy note to the clipboard as Markdown, HTML or plain text\n'w'            : Watch / unwatch note for changes made in other instances (●)\n'Shift+a'      : Set / snooze reminder\n'Shift+v'      : New version of note (archives the original)\n'Shift+h'      : Revision history of note (Enter restores)\n'Shift+f'      : Attachments of note (Enter open, 's' save as, 'r' rename, 'd' delete)\n'u'            : Touch note (mark as reviewed)\n't'            : Edit tags for note\n'Ctrl+t'       : Quick tags: toggle numbered tags with 1-9\n's'            : Sort by title / last modified\n'o'            : Choose sort order (title, newest, last modified, size, tag count)\n'p'            : Pin / unpin note to the top of every list\n'Ctrl+p'       : Pin / unpin note within the current tag filter\n'#'            : Filter by the selected note's first tag\n'Shift+g'      : Tags with note counts ('r' rename, 'm' merge, 'd' delete)\n'b'            : Notebooks (Enter switch, 'm' move note here, 'n' new, 'd' delete)\n'Shift+t'      : Filter by Tag or date ('n' expression, 's' save it, 'x' delete saved,\n                 'r' date range, 'Shift+r' rename tag everywhere,\n                 'e'/'i' export/import tag taxonomy)\n'/'            : Search titles, tags and content (Up/Down: recent searches,\n                 Tab: titles only); also tag:NAME -tag:NAME \"phrase\"\n                 after:YYYY-MM-DD before:YYYY-MM-DD\n':'            : Command line, e.g. :tag work, :filter #work, :new Title (Tab completes)\n'Ctrl+j/k'     : Scroll Preview (Left/Right: tables sideways)\n'Shift+d'      : Toggle list density\n'Shift+m'      : Status message history\n'Shift+p'      : Test / reconnect database (reloads sources)\n'c'            : Toggle tag/note/preview columns (h/l to move)\n'm'            : In columns: move note to a tag ('m', pick tag, 'm')\n'Shift+e'      : Export listed notes to one document\n'g'            : Link graph of selected note\n'Shift+o'      : Orphaned notes and broken links\n'Shift+z'      : Note sizes and database usage\n'Shift+u'      : Unlock / lock encrypted note\n'Shift+x'      : Encrypt / decrypt note\n'Shift+r'      : Reveal / mask #secret note\n'Shift+s'      : Toggle screencast mode (placeholder text)\n'?' / 'F1'     : Toggle help\n'Ctrl+l'       : Lock screen\n'Ctrl+c'       : Close all popups\n'q'            : Quit",
+            config.database_url
         );
 
         Self {
             all_notes: Vec::new(),
+            search_index: Vec::new(),
             notes: Vec::new(),
+            marked: HashSet::new(),
             list_state: ListState::default(),
             status_message: "Welcome! Press '?' for help.".to_string(),
+            statuses_set: 0,
+            status_history: VecDeque::new(),
+            history_list_state: ListState::default(),
             script_content_preview: "".to_string(),
             input_mode: InputMode::Normal,
-            filename_input: String::new(),
+            mode_stack: Vec::new(),
+            filename_input: TextInput::default(),
+            input_error: None,
+            title_suggestions: Vec::new(),
+            new_from_template: None,
+            selected_suggestion: 0,
+            title_rules: config.titles.clone(),
+            pending_rename: None,
+
+            graph_entries: Vec::new(),
+            graph_list_state: ListState::default(),
+
+            orphan_report: Vec::new(),
+            report_list_state: ListState::default(),
+
+            size_report: SizeReport::default(),
+            size_list_state: ListState::default(),
+
+            sources: config
+                .sources
+                .iter()
+                .map(|source| Source::new(source, &config.tls))
+                .collect(),
+            presence: Vec::new(),
+            watched: load_watched(),
+            changed_elsewhere: HashSet::new(),
+            toast: None,
+
+            revision_note: None,
+            revisions: Vec::new(),
+            revision_list_state: ListState::default(),
+            attachment_note: None,
+            attachments: Vec::new(),
+            attachment_list_state: ListState::default(),
+            attachment_input: TextInput::default(),
+            attachment_action: AttachmentAction::Rename,
+            attachment_delete_armed: false,
+
+            lint_rules: config.lint.clone(),
+            confirm: config.confirm,
+            lint_findings: Vec::new(),
+
+            reminder_note: None,
+            reminder_due: false,
+            announced_reminders: HashSet::new(),
+
+            unlocked_notes: HashMap::new(),
+            passphrase_input: String::new(),
+            passphrase_purpose: None,
+            passphrase_first: None,
+            revealed_note: None,
+            screencast: false,
+
+            lock_passphrase: config.lock_passphrase.clone(),
+            lock_idle: config
+                .lock_idle_minutes
+                .map(|m| Duration::from_secs(m * 60)),
+            lock_input: String::new(),
+            last_activity: Instant::now(),
             help_message,
-            editor_cmd,
+            editor_cmd: config.get_editor_command(),
+            db_url: config.database_url.clone(),
+            connection_label: connection_label(&config.database_url),
+            safe_mode: false,
+            tls: config.tls.clone(),
+            connection_check: None,
+            notes_fetch: None,
+            notes_first_page: None,
+            select_after_refresh: None,
+            content_request: None,
+            content_fetch: None,
+            calls: None,
+            db_offline: None,
+            db_reconnecting: false,
 
             active_filter: TagFilter::All,
+            smart_filters: Vec::new(),
+            filter_expr_input: TextInput::default(),
+            command_input: TextInput::default(),
+            notebooks: Vec::new(),
+            active_notebook: NotebookFilter::All,
+            notebook_list_state: ListState::default(),
+            notebook_input: TextInput::default(),
+            tag_editor: TagEditor::default(),
+            bulk_tags: None,
+            pasted_url: None,
+            edit_review: None,
+            inline_edit: None,
+            edit_conflict: None,
+            conflict_queue: VecDeque::new(),
+            date_range_input: TextInput::default(),
+            renaming_tag: None,
+            tag_rename_input: TextInput::default(),
+            tag_counts: Vec::new(),
+            tag_list_state: ListState::default(),
+            merging_tag: None,
+            tag_meta: HashMap::new(),
             available_filters: Vec::new(),
             filter_list_state: ListState::default(),
 
+            columns_view: false,
+            focused_column: Column::Notes,
+            column_filter_state: ListState::default(),
+            moving_note: None,
+
             search_query: String::new(),
+            search_titles_only: false,
+            search_matches: None,
+            search_fetch: None,
+            search_due: None,
+            search_history: SearchHistory::load(data_dir::state_file(
+                search_history::SEARCH_HISTORY_FILE_NAME,
+            )),
             preview_scroll: 0,
+            preview_note: None,
+            read_positions: HashMap::new(),
+            preview_hscroll: 0,
+            preview_refresh: config.preview_refresh,
+            preview_due: None,
             view_mode: ViewMode::Active,
+            view_filters: HashMap::new(),
+            sort_mode: SortMode::load(),
+            stale_after_days: config.stale_after_days,
+            quick_tag_count: config.quick_tags,
+            quick_tags: Vec::new(),
+            quick_tag_note: None,
+            list_density: config.list_density,
+            list_position: config.list_position,
+            list_size: config.list_size,
+            theme: Theme::from_config(config),
+
+            pending_saves: Vec::new(),
+
+            export_dir: config.get_export_dir(),
+            export_format_state: ListState::default(),
+            sort_mode_state: ListState::default(),
+            copy_format_state: ListState::default(),
+        }
+    }
+
+    /// Starts loading the note list in the background, replacing any load still
+    /// in flight. `apply_fetched_notes` merges the result once it arrives.
+    pub fn refresh_notes(&mut self, db: &Database) {
+        if let Some(task) = self.notes_fetch.take() {
+            task.abort();
+        }
+        // A refreshed list replaces the old one at once; only an empty one is
+        // worth filling a page early.
+        let (sender, receiver) = oneshot::channel();
+        let first_page = self.all_notes.is_empty().then_some(sender);
+        self.notes_first_page = first_page.is_some().then_some(receiver);
+        match db.load_notes(first_page) {
+            Ok(fetch) => self.notes_fetch = Some(tokio::spawn(fetch)),
+            Err(e) => self.set_status(format!("DB Error: {}", e)),
+        }
+    }
+
+    /// Like `refresh_notes`, then selects note `id` once the list has loaded.
+    pub fn refresh_notes_and_select(&mut self, db: &Database, id: i32) {
+        self.select_after_refresh = Some(id);
+        self.refresh_notes(db);
+    }
+
+    /// Loads the note list and applies it before returning, for callers that act
+    /// on the new list right away.
+    pub async fn refresh_notes_now(&mut self, db: &mut Database) {
+        self.refresh_notes(db);
+        while self.notes_fetch.is_some() {
+            let fetched = self.fetched_notes().await;
+            self.apply_fetched_notes(db, fetched);
+        }
+    }
+
+    /// Resolves when the background load has something to show: its first page,
+    /// then the whole list. Never, if no load is running.
+    pub async fn fetched_notes(&mut self) -> NotesArrival {
+        next_notes(&mut self.notes_fetch, &mut self.notes_first_page).await
+    }
+
+    /// Resolves when any work running in the background has something to show.
+    pub async fn fetched(&mut self) -> Fetched {
+        tokio::select! {
+            arrival = next_notes(&mut self.notes_fetch, &mut self.notes_first_page) => {
+                Fetched::Notes(arrival)
+            }
+            (id, content) = finished(&mut self.content_fetch) => Fetched::Content(id, content),
+            (query, matches) = finished(&mut self.search_fetch) => Fetched::Search(query, matches),
+            done = next_call(&mut self.calls) => Fetched::Call(done),
+        }
+    }
+
+    pub fn apply_fetched(&mut self, db: &mut Database, fetched: Fetched) {
+        match fetched {
+            Fetched::Notes(arrival) => self.apply_fetched_notes(db, arrival),
+            Fetched::Content(id, content) => self.apply_fetched_content(db, id, content),
+            Fetched::Search(query, matches) => self.apply_search(db, query, matches),
+            Fetched::Call(done) => done(self, db),
+        }
+    }
+
+    /// Runs a database call off the event loop, after every call made before it,
+    /// and `then` with its result once `fetched` has it back. `call` gets a
+    /// `Database::detached` copy of `db`; while the database can't be reached,
+    /// `then` runs right away with the error.
+    pub fn spawn_call<T, F>(
+        &mut self,
+        db: &mut Database,
+        call: impl FnOnce(Database) -> F,
+        then: impl FnOnce(&mut AppState, &mut Database, Result<T, db::Error>) + Send + 'static,
+    ) where
+        T: Send + 'static,
+        F: Future<Output = Result<T, db::Error>> + Send + 'static,
+    {
+        let running = match db.detached() {
+            Ok(detached) => call(detached),
+            Err(e) => return then(self, db, Err(e)),
+        };
+        let queue = self.calls.get_or_insert_with(CallQueue::start);
+        queue.pending += 1;
+        let _ = queue.queued.send(Box::pin(async move {
+            let result = running.await;
+            Box::new(move |app: &mut AppState, db: &mut Database| {
+                db.settle_detached(&result);
+                then(app, db, result);
+            }) as CallDone
+        }));
+    }
+
+    /// `spawn_call` for a call whose result only matters to the database, e.g.
+    /// announcing presence.
+    pub fn spawn_quiet<T, F>(&mut self, db: &mut Database, call: impl FnOnce(Database) -> F)
+    where
+        T: Send + 'static,
+        F: Future<Output = Result<T, db::Error>> + Send + 'static,
+    {
+        self.spawn_call(db, call, |_, _, _| {});
+    }
+
+    /// Waits for the calls still running and applies their results, e.g. so
+    /// quitting doesn't lose a save.
+    pub async fn finish_calls(&mut self, db: &mut Database) {
+        while self.calls.as_ref().is_some_and(|queue| queue.pending > 0) {
+            let done = next_call(&mut self.calls).await;
+            done(self, db);
         }
     }
 
-    pub fn refresh_notes(&mut self, db: &mut Database) -> io::Result<()> {
-        match db.get_all_notes() {
-            Ok(fetched_notes) => {
+    pub fn apply_fetched_notes(&mut self, db: &mut Database, arrival: NotesArrival) {
+        let fetched = match arrival {
+            NotesArrival::FirstPage(page) => {
+                self.all_notes = page;
+                self.rebuild_search_index();
+                self.apply_current_filter();
+                self.list_state
+                    .select((!self.notes.is_empty()).then_some(0));
+                self.update_preview();
+                return;
+            }
+            NotesArrival::All(fetched) => fetched,
+        };
+        self.notes_fetch = None;
+        let select = self.select_after_refresh.take();
+        let fetched = match fetched {
+            Ok(result) => db.settle(result),
+            Err(e) => {
+                self.set_status(format!("DB Error: note list load failed: {}", e));
+                return;
+            }
+        };
+        match fetched {
+            Ok(mut fetched_notes) => {
+                // Content already loaded stays valid while the note is unchanged.
+                let loaded: HashMap<i32, &Note> = self
+                    .all_notes
+                    .iter()
+                    .filter(|n| n.content_loaded && n.source.is_none())
+                    .map(|n| (n.id, n))
+                    .collect();
+                for note in &mut fetched_notes {
+                    if let Some(old) = loaded.get(&note.id)
+                        && old.updated_at == note.updated_at
+                    {
+                        note.content = old.content.clone();
+                        note.content_loaded = true;
+                    }
+                }
                 self.all_notes = fetched_notes;
+                // Search again so new and edited notes are matched too.
+                if !self.search_query.is_empty() {
+                    self.search_due = Some(Instant::now());
+                }
+                self.all_notes
+                    .extend(self.sources.iter().flat_map(|s| s.notes.iter().cloned()));
+                self.rebuild_search_index();
+                if self.columns_view {
+                    self.rebuild_available_filters();
+                    self.sync_column_filter_selection();
+                }
 
                 self.apply_current_filter();
 
@@ -117,21 +1087,45 @@ impl AppState {
                     }
                 }
                 self.update_preview();
+                if let Some(id) = select {
+                    self.select_note_by_id(id);
+                }
             }
             Err(e) => self.set_status(format!("DB Error: {}", e)),
         }
-        Ok(())
+    }
+
+    fn rebuild_search_index(&mut self) {
+        self.search_index = self.all_notes.iter().map(search_key).collect();
     }
 
     pub fn apply_current_filter(&mut self) {
+        let stale_cutoff = Utc::now() - TimeDelta::days(self.stale_after_days);
+        let searched = self
+            .search_matches
+            .as_ref()
+            .filter(|(query, _)| *query == self.search_query)
+            .map(|(_, ids)| ids);
+        let parsed = query::parse(&self.search_query);
+        let text = parsed.text.to_lowercase();
+        let mut fuzzy = search::Fuzzy::new(&parsed.text);
         self.notes = self
             .all_notes
             .iter()
-            .filter(|n| {
-
+            .zip(&self.search_index)
+            .filter(|(n, key)| {
                 let matches_view = match self.view_mode {
+                    _ if n.deleted_at.is_some() => self.view_mode == ViewMode::Trash,
                     ViewMode::Active => !n.archived,
+                    ViewMode::Favorites => n.favorite,
                     ViewMode::Archived => n.archived,
+                    ViewMode::Stale => {
+                        !n.archived
+                            && n.note_type.goes_stale()
+                            && n.updated_at.is_some_and(|t| t < stale_cutoff)
+                    }
+                    ViewMode::Reminders => !n.archived && n.remind_at.is_some(),
+                    ViewMode::Trash => false,
                 };
 
                 // Check Tag Filter
@@ -139,26 +1133,119 @@ impl AppState {
                     TagFilter::All => true,
                     TagFilter::Untagged => n.tags.is_empty(),
                     TagFilter::Specific(tag) => n.tags.contains(tag),
+                    TagFilter::Expression { expr, .. } => expr.matches(&n.tags),
+                    TagFilter::Date(date) => date.matches(n),
                 };
 
-                // Check Search Query
-                let matches_search = if self.search_query.is_empty() {
-                    true
-                } else {
-                    n.title
-                        .to_lowercase()
-                        .contains(&self.search_query.to_lowercase())
+                let matches_notebook = match self.active_notebook {
+                    NotebookFilter::All => true,
+                    NotebookFilter::Unfiled => n.notebook_id.is_none(),
+                    NotebookFilter::Notebook(id) => n.notebook_id == Some(id),
                 };
 
-                matches_view && matches_tag && matches_search
+                // Check Search Query
+                let matches_search = match (&n.source, searched) {
+                    _ if text.is_empty() => true,
+                    _ if fuzzy.score(&n.title).is_some() => true,
+                    _ if self.search_titles_only => false,
+                    (None, Some(ids)) => ids.contains(&n.id),
+                    // Until the query has run, and for sources, match what is loaded.
+                    // Ciphertext is never searched.
+                    _ => {
+                        key.contains(&text)
+                            || (!n.encrypted && n.content.to_lowercase().contains(&text))
+                    }
+                } && parsed.matches(n, !self.search_titles_only);
+
+                matches_view && matches_tag && matches_notebook && matches_search
             })
-            .cloned()
+            .map(|(n, _)| n.clone())
             .collect();
 
         self.notes.sort_by(|a, b| a.title.cmp(&b.title));
+
+        match self.view_mode {
+            _ if self.sort_mode == SortMode::Created => {
+                self.notes.sort_by_key(|n| std::cmp::Reverse(n.created_at))
+            }
+            _ if self.sort_mode == SortMode::Modified => {
+                self.notes.sort_by_key(|n| std::cmp::Reverse(n.updated_at))
+            }
+            _ if self.sort_mode == SortMode::Size => {
+                self.notes.sort_by_key(|n| std::cmp::Reverse(n.size))
+            }
+            _ if self.sort_mode == SortMode::TagCount => {
+                self.notes.sort_by_key(|n| std::cmp::Reverse(n.tags.len()))
+            }
+            ViewMode::Active | ViewMode::Favorites => {}
+            // Most recently archived first, so fresh mistakes are easy to restore.
+            ViewMode::Archived => self.notes.sort_by_key(|n| std::cmp::Reverse(n.archived_at)),
+            // Longest-neglected first.
+            ViewMode::Stale => self.notes.sort_by_key(|n| n.updated_at),
+            ViewMode::Reminders => self.notes.sort_by_key(|n| n.remind_at),
+            ViewMode::Trash => self.notes.sort_by_key(|n| std::cmp::Reverse(n.deleted_at)),
+        }
+
+        // Best title matches first; notes found by tag or content after them.
+        if !text.is_empty() {
+            self.notes
+                .sort_by_cached_key(|n| std::cmp::Reverse(fuzzy.score(&n.title).unwrap_or(0)));
+        }
+
+        if let TagFilter::Specific(tag) = &self.active_filter {
+            self.notes.sort_by_key(|n| !n.pinned_in.contains(tag));
+        }
+        self.notes.sort_by_key(|n| !n.pinned);
+
+        let listed: HashSet<i32> = self
+            .notes
+            .iter()
+            .filter(|n| n.source.is_none())
+            .map(|n| n.id)
+            .collect();
+        self.marked.retain(|id| listed.contains(id));
+    }
+
+    /// Marks or unmarks the selected note and moves on to the next one.
+    pub fn toggle_mark(&mut self) {
+        let Some(note) = self.get_selected_note() else {
+            return;
+        };
+        if let Some(source) = &note.source {
+            self.set_status(format!("Notes from '{}' are read-only.", source));
+            return;
+        }
+        let id = note.id;
+        if !self.marked.remove(&id) {
+            self.marked.insert(id);
+        }
+        self.next();
+        if self.marked.is_empty() {
+            self.set_status("No notes marked.".to_string());
+        } else {
+            self.set_status(format!(
+                "{} note(s) marked: 'd', 'x' and 't' act on all of them. [Esc] unmarks.",
+                self.marked.len()
+            ));
+        }
+    }
+
+    /// The marked notes, in list order.
+    pub fn marked_notes(&self) -> Vec<&Note> {
+        self.notes
+            .iter()
+            .filter(|n| self.marked.contains(&n.id) && n.source.is_none())
+            .collect()
     }
 
     pub fn open_tag_selector(&mut self) {
+        self.rebuild_available_filters();
+        self.push_mode(InputMode::SelectingTagFilter);
+        self.filter_list_state.select(Some(0));
+        self.set_status("Select tag to filter. [Enter] confirm, [Esc] cancel.".to_string());
+    }
+
+    pub fn rebuild_available_filters(&mut self) {
         // 1. Collect unique tags
         let mut unique_tags: HashSet<String> = HashSet::new();
         for note in &self.all_notes {
@@ -178,11 +1265,28 @@ impl AppState {
         for tag in sorted_tags {
             self.available_filters.push(TagFilter::Specific(tag));
         }
+        self.available_filters
+            .extend(DateFilter::presets().into_iter().map(TagFilter::Date));
+        for source in &self.smart_filters {
+            if let Ok(expr) = filter_expr::parse(source) {
+                self.available_filters.push(TagFilter::Expression {
+                    source: source.clone(),
+                    expr,
+                });
+            }
+        }
+    }
 
-        // 4. Set state
-        self.input_mode = InputMode::SelectingTagFilter;
-        self.filter_list_state.select(Some(0));
-        self.set_status("Select tag to filter. [Enter] confirm, [Esc] cancel.".to_string());
+    pub fn apply_filter(&mut self, filter: TagFilter) {
+        self.active_filter = filter;
+        self.apply_current_filter();
+        // Reset list selection
+        if !self.notes.is_empty() {
+            self.list_state.select(Some(0));
+        } else {
+            self.list_state.select(None);
+        }
+        self.update_preview();
     }
 
     pub fn next_filter(&mut self) {
@@ -219,10 +1323,139 @@ impl AppState {
         self.filter_list_state.select(Some(i));
     }
 
+    pub fn push_mode(&mut self, mode: InputMode) {
+        if self.input_mode != InputMode::Normal {
+            self.mode_stack.push(self.input_mode);
+        }
+        self.input_mode = mode;
+    }
+
+    pub fn pop_mode(&mut self) {
+        self.input_mode = self.mode_stack.pop().unwrap_or(InputMode::Normal);
+    }
+
+    /// `pop_mode` if `mode` is still the current one, for results that come back
+    /// after the popup they were for may have been closed.
+    pub fn pop_mode_from(&mut self, mode: InputMode) {
+        if self.input_mode == mode {
+            self.pop_mode();
+        }
+    }
+
+    pub fn close_all_modes(&mut self) {
+        self.mode_stack.clear();
+        self.input_mode = InputMode::Normal;
+    }
+
+    /// The title in the input, with the title template applied when it names a new note.
+    pub fn typed_title(&self) -> String {
+        let input = self.filename_input.value();
+        if self.input_mode == InputMode::EditingFilename && !input.trim().is_empty() {
+            self.title_rules
+                .normalize(&self.title_rules.apply_template(input))
+        } else {
+            self.title_rules.normalize(input)
+        }
+    }
+
+    /// Normalizes the title input and checks it against the configured rules and
+    /// existing titles. Returns the normalized title when it can be submitted.
+    pub fn validate_title_input(&mut self) -> Option<String> {
+        let title = self.typed_title();
+        let renaming_id = match self.input_mode {
+            InputMode::RenamingScript => self.get_selected_note().map(|n| n.id),
+            _ => None,
+        };
+
+        self.input_error = if title.is_empty() {
+            None
+        } else if let Err(e) = self.title_rules.validate(&title) {
+            Some(e)
+        } else if self
+            .all_notes
+            .iter()
+            .any(|n| n.title == title && Some(n.id) != renaming_id)
+        {
+            Some(format!("A note titled '{}' already exists.", title))
+        } else {
+            None
+        };
+
+        match self.input_error {
+            None => Some(title),
+            Some(_) => None,
+        }
+    }
+
+    /// Looks up existing notes resembling the new title typed so far.
+    pub async fn update_title_suggestions(&mut self, db: &mut Database) {
+        let title = self.filename_input.value().trim();
+        let previous = self
+            .title_suggestions
+            .get(self.selected_suggestion)
+            .map(|s| s.0);
+        self.title_suggestions = if title.chars().filter(|c| c.is_alphanumeric()).count() < 3 {
+            Vec::new()
+        } else {
+            // A failed lookup only costs the hint; the title is still checked on Enter.
+            db.similar_titles(title).await.unwrap_or_default()
+        };
+        self.selected_suggestion = self
+            .title_suggestions
+            .iter()
+            .position(|s| Some(s.0) == previous)
+            .unwrap_or(0);
+    }
+
     pub fn set_status(&mut self, message: String) {
+        let message = if self.screencast {
+            lorem::status(&message)
+        } else {
+            message
+        };
+        self.statuses_set += 1;
+        if self.status_history.len() >= STATUS_HISTORY_LIMIT {
+            self.status_history.pop_front();
+        }
+        self.status_history
+            .push_back((Local::now(), message.clone()));
         self.status_message = message;
     }
 
+    /// Messages set so far; `report_slow_calls` compares counts to tell whether
+    /// an event set one.
+    pub fn statuses_set(&self) -> u64 {
+        self.statuses_set
+    }
+
+    /// Adds "(took 1.4s)" to the status an event set if the database was slow
+    /// while handling it, so a slow connection isn't mistaken for a slow app.
+    pub fn report_slow_calls(&mut self, db: &mut Database, statuses_before: u64) {
+        let Some(took) = db.take_slow_time() else {
+            return;
+        };
+        let took = db::format_duration(took);
+        if self.statuses_set == statuses_before {
+            self.set_status(format!("The database took {} to answer.", took));
+            return;
+        }
+        self.status_message = format!("{} (took {})", self.status_message, took);
+        if let Some((_, message)) = self.status_history.back_mut() {
+            message.clone_from(&self.status_message);
+        }
+    }
+
+    pub fn open_status_history(&mut self) {
+        // Newest entries are shown first.
+        self.history_list_state
+            .select(if self.status_history.is_empty() {
+                None
+            } else {
+                Some(0)
+            });
+        self.push_mode(InputMode::StatusHistory);
+    }
+
     pub fn get_selected_note(&self) -> Option<&Note> {
         self.list_state.selected().and_then(|i| self.notes.get(i))
     }
@@ -242,7 +1475,7 @@ impl AppState {
             None => 0,
         };
         self.list_state.select(Some(i));
-        self.update_preview();
+        self.selection_moved();
     }
 
     pub fn previous(&mut self) {
@@ -260,16 +1493,185 @@ impl AppState {
             None => 0,
         };
         self.list_state.select(Some(i));
-        self.update_preview();
+        self.selection_moved();
+    }
+
+    fn selection_moved(&mut self) {
+        match self.preview_refresh {
+            PreviewRefresh::Immediate => self.update_preview(),
+            PreviewRefresh::Settled => self.preview_due = Some(Instant::now() + PREVIEW_SETTLE),
+        }
+    }
+
+    /// When the preview should catch up with the selection.
+    pub fn preview_due(&self) -> Option<Instant> {
+        self.preview_due
+    }
+
+    /// Brings a preview left behind by `selection_moved` up to date once it is
+    /// due, or right away with `now`.
+    pub fn settle_preview(&mut self, now: bool) {
+        if self
+            .preview_due
+            .is_some_and(|due| now || Instant::now() >= due)
+        {
+            self.update_preview();
+        }
     }
 
     pub fn update_preview(&mut self) {
-        self.preview_scroll = 0;
+        self.preview_due = None;
+        if let Some(id) = self.preview_note {
+            if self.preview_scroll > 0 {
+                self.read_positions.insert(id, self.preview_scroll);
+            } else {
+                self.read_positions.remove(&id);
+            }
+        }
+        let selected_id = self.get_selected_note().map(|n| n.id);
+        if let Some(id) = selected_id {
+            self.changed_elsewhere.remove(&id);
+        }
+        // Back to where the note was left; the renderer clamps it if the note shrank.
+        self.preview_note = selected_id;
+        self.preview_scroll = selected_id
+            .and_then(|id| self.read_positions.get(&id).copied())
+            .unwrap_or(0);
+        self.preview_hscroll = 0;
 
-        if let Some(note) = self.get_selected_note() {
-            self.script_content_preview = note.content.clone();
+        if self.revealed_note != selected_id {
+            self.revealed_note = None;
+        }
+
+        self.content_request = self
+            .get_selected_note()
+            .filter(|n| !n.content_loaded)
+            .map(|n| n.id);
+        self.script_content_preview = if let Some(note) = self.get_selected_note() {
+            let content = if !note.content_loaded {
+                "Loading…".to_string()
+            } else if !note.encrypted {
+                note.content.clone()
+            } else if let Some((plaintext, _)) = self.unlocked_notes.get(&note.id) {
+                plaintext.clone()
+            } else {
+                "🔒 Encrypted note. Press 'U' to unlock.".to_string()
+            };
+            if note.tags.iter().any(|t| t == SECRET_TAG) && self.revealed_note != Some(note.id) {
+                format!(
+                    "[#{} note masked, press 'R' to reveal]\n\n{}",
+                    SECRET_TAG,
+                    mask_text(&content)
+                )
+            } else {
+                content
+            }
         } else {
-            self.script_content_preview = "No notes found.".to_string();
+            "No notes found.".to_string()
+        };
+        if self.screencast {
+            self.script_content_preview = lorem::text(&self.script_content_preview);
+        }
+    }
+
+    /// Starts loading the content the preview is waiting for in the background.
+    /// Runs between events; `apply_fetched_content` shows it.
+    pub fn load_content(&mut self, db: &Database) {
+        let Some(id) = self.content_request.take() else {
+            return;
+        };
+        if self
+            .content_fetch
+            .as_ref()
+            .is_some_and(|(loading, _)| *loading == id)
+        {
+            return;
+        }
+        if let Some((_, task)) = self.content_fetch.take() {
+            task.abort();
+        }
+        match db.fetch_note_content(id) {
+            Ok(fetch) => self.content_fetch = Some((id, tokio::spawn(fetch))),
+            Err(e) => self.show_content(id, Err(e)),
+        }
+    }
+
+    fn apply_fetched_content(
+        &mut self,
+        db: &mut Database,
+        id: i32,
+        fetched: Result<ContentFetch, JoinError>,
+    ) {
+        self.content_fetch = None;
+        match fetched {
+            Ok(result) => self.show_content(id, db.settle(result)),
+            Err(e) => self.set_status(format!("DB Error: content load failed: {}", e)),
+        }
+    }
+
+    fn show_content(&mut self, id: i32, content: Result<Option<String>, db::Error>) {
+        match content {
+            Ok(Some(content)) => {
+                self.store_contents(HashMap::from([(id, content)]));
+                if self.get_selected_note().is_some_and(|n| n.id == id) {
+                    let scroll = (self.preview_scroll, self.preview_hscroll);
+                    self.update_preview();
+                    (self.preview_scroll, self.preview_hscroll) = scroll;
+                }
+            }
+            Ok(None) => self.set_status("The note no longer exists.".to_string()),
+            Err(e) => {
+                self.script_content_preview = "Content could not be loaded.".to_string();
+                self.set_status(format!("DB Error: {}", e));
+            }
+        }
+    }
+
+    /// Loads the selected note's content if the preview has not yet, e.g. because
+    /// that failed. Returns `false` if it is still missing.
+    pub async fn ensure_selected_content(&mut self, db: &mut Database) -> bool {
+        let Some(id) = self
+            .get_selected_note()
+            .filter(|n| !n.content_loaded)
+            .map(|n| n.id)
+        else {
+            return true;
+        };
+        let content = db.get_note_content(id).await;
+        self.show_content(id, content);
+        self.get_selected_note().is_some_and(|n| n.content_loaded)
+    }
+
+    /// Loads the content of every note, for features that look inside all of them
+    /// (search, links, export). Returns `false`, with a status, if that failed.
+    pub async fn load_all_content(&mut self, db: &mut Database) -> bool {
+        let missing: Vec<i32> = self
+            .all_notes
+            .iter()
+            .filter(|n| n.source.is_none() && !n.content_loaded)
+            .map(|n| n.id)
+            .collect();
+        match db.get_note_contents(&missing).await {
+            Ok(contents) => {
+                self.store_contents(contents);
+                true
+            }
+            Err(e) => {
+                self.set_status(format!("DB Error: {}", e));
+                false
+            }
+        }
+    }
+
+    /// Keeps fetched content, by note id, in both the full and the filtered list.
+    fn store_contents(&mut self, contents: HashMap<i32, String>) {
+        for note in self.all_notes.iter_mut().chain(self.notes.iter_mut()) {
+            if note.source.is_none()
+                && let Some(content) = contents.get(&note.id)
+            {
+                note.content = content.clone();
+                note.content_loaded = true;
+            }
         }
     }
 
@@ -281,13 +1683,2597 @@ impl AppState {
         self.preview_scroll = self.preview_scroll.saturating_sub(1);
     }
 
+    /// Scrolls a table preview sideways; the UI clamps it to the widest line.
+    pub fn scroll_preview_sideways(&mut self, right: bool) {
+        const STEP: u16 = 8;
+        self.preview_hscroll = if right {
+            self.preview_hscroll.saturating_add(STEP)
+        } else {
+            self.preview_hscroll.saturating_sub(STEP)
+        };
+    }
 
     pub fn toggle_view_mode(&mut self) {
+        let current = (
+            std::mem::replace(&mut self.active_filter, TagFilter::All),
+            std::mem::take(&mut self.search_query),
+        );
+        self.view_filters.insert(self.view_mode, current);
+
         self.view_mode = match self.view_mode {
-            ViewMode::Active => ViewMode::Archived,
-            ViewMode::Archived => ViewMode::Active,
+            ViewMode::Active => ViewMode::Favorites,
+            ViewMode::Favorites => ViewMode::Archived,
+            ViewMode::Archived => ViewMode::Stale,
+            ViewMode::Stale => ViewMode::Reminders,
+            ViewMode::Reminders => ViewMode::Trash,
+            ViewMode::Trash => ViewMode::Active,
         };
+
+        if let Some((filter, query)) = self.view_filters.remove(&self.view_mode) {
+            self.active_filter = filter;
+            self.search_query = query;
+        }
+        if self.columns_view {
+            self.sync_column_filter_selection();
+        }
         // Reset selection when switching views
         self.list_state.select(None);
     }
-}
\ No newline at end of file
+
+    pub fn toggle_list_density(&mut self) {
+        self.list_density = match self.list_density {
+            ListDensity::Compact => ListDensity::Comfortable,
+            ListDensity::Comfortable => ListDensity::Compact,
+        };
+    }
+
+    /// Queues content that failed to save, replacing any older pending edit of the same note.
+    pub fn queue_pending_save(&mut self, session: EditSession, mine: String, content: String) {
+        self.pending_saves.retain(|p| p.session.id != session.id);
+        self.pending_saves.push(PendingSave {
+            session,
+            mine,
+            content,
+        });
+    }
+
+    /// Retries every pending save off the event loop, keeping the ones that
+    /// still fail. Notes that changed since their edit started go to
+    /// `conflict_queue` instead of being overwritten. `then` gets how many were
+    /// written.
+    pub fn flush_pending_saves(
+        &mut self,
+        db: &mut Database,
+        then: impl FnOnce(&mut AppState, usize) + Send + 'static,
+    ) {
+        let pending = std::mem::take(&mut self.pending_saves);
+        self.spawn_call(
+            db,
+            move |mut db| async move {
+                let mut outcomes = Vec::new();
+                for save in pending {
+                    let outcome = match db
+                        .update_note_content(save.session.id, &save.content, save.session.loaded_at)
+                        .await
+                    {
+                        Ok(true) => Ok(None),
+                        Ok(false) => {
+                            let (session, mine) = (save.session.clone(), save.mine.clone());
+                            Ok(Some(load_edit_conflict(&mut db, session, mine).await))
+                        }
+                        Err(e) => Err(e),
+                    };
+                    outcomes.push((save, outcome));
+                }
+                Ok(outcomes)
+            },
+            move |app, _, result| {
+                let mut flushed = 0;
+                for (save, outcome) in result.unwrap_or_default() {
+                    match outcome {
+                        Ok(None) => flushed += 1,
+                        Ok(Some(conflict)) => {
+                            let id = conflict.session.id;
+                            app.conflict_queue.retain(|c| c.session.id != id);
+                            app.conflict_queue.push_back(conflict);
+                        }
+                        Err(e) => {
+                            app.set_status(format!("Error saving '{}': {}", save.session.title, e));
+                            app.pending_saves.push(save);
+                        }
+                    }
+                }
+                then(app, flushed);
+            },
+        );
+    }
+
+    /// Opens the next queued conflict once nothing else is on screen.
+    pub fn poll_conflict_queue(&mut self) {
+        if self.input_mode != InputMode::Normal || self.edit_conflict.is_some() {
+            return;
+        }
+        let Some(conflict) = self.conflict_queue.pop_front() else {
+            return;
+        };
+        let more = match self.conflict_queue.len() {
+            0 => String::new(),
+            n => format!(" ({} more queued)", n),
+        };
+        self.set_status(format!(
+            "Pending save of '{}' conflicts with a newer version{}. [m] keep mine, [t] keep theirs, [e] merge",
+            self.display_title(&conflict.session.title),
+            more
+        ));
+        self.edit_conflict = Some(conflict);
+        self.push_mode(InputMode::ResolvingConflict);
+    }
+
+    /// Starts a connection test in a background task; see `poll_connection_check`.
+    pub fn start_connection_check(&mut self) {
+        if self.connection_check.is_some() {
+            self.set_status("Connection test already running...".to_string());
+            return;
+        }
+        let (tx, rx) = mpsc::channel();
+        let db_url = self.db_url.clone();
+        let tls = self.tls.clone();
+        tokio::spawn(async move {
+            let _ = tx.send(Database::test_connection(&db_url, &tls).await);
+        });
+        self.connection_check = Some(rx);
+        self.set_status("Testing database connection...".to_string());
+    }
+
+    /// Picks up a finished connection test, swapping in the new connection if
+    /// the current one has been closed.
+    pub fn poll_connection_check(&mut self, db: &mut Database) {
+        let Some(rx) = &self.connection_check else {
+            return;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(io::Error::other("connection test task exited")),
+        };
+        self.connection_check = None;
+
+        match result {
+            Ok((fresh, report)) => {
+                db.record_connection_success();
+                let reconnected = db.is_closed();
+                if reconnected {
+                    *db = fresh;
+                }
+                self.set_status(format!(
+                    "{}PostgreSQL {} at {} ({}@{}): ping {} ms, connect {} ms",
+                    if reconnected { "Reconnected. " } else { "" },
+                    report.server_version,
+                    report.address.as_deref().unwrap_or("local socket"),
+                    report.user,
+                    report.database,
+                    report.latency.as_millis(),
+                    report.connect_time.as_millis(),
+                ));
+                if reconnected {
+                    self.refresh_notes(db);
+                }
+            }
+            Err(e) => {
+                db.record_connection_failure();
+                self.set_status(format!("Connection test failed: {}", e));
+            }
+        }
+    }
+
+    /// Mirrors the breaker state for the offline banner and, once the cooldown
+    /// has run out, probes the server in the background instead of on a keypress.
+    /// A lost connection is left to `poll_reconnect` instead.
+    pub fn sync_db_health(&mut self, db: &Database) {
+        self.db_offline = db.offline_retry_in();
+        self.db_reconnecting = db.is_closed();
+        if self.db_offline.is_some_and(|d| d.is_zero())
+            && !self.db_reconnecting
+            && self.connection_check.is_none()
+        {
+            self.start_connection_check();
+        }
+    }
+
+    /// Drives reconnecting after the connection was lost, and reloads what the
+    /// old connection was keeping current once the new one is in.
+    pub fn poll_reconnect(&mut self, db: &mut Database) {
+        if db.poll_reconnect() {
+            self.set_status("Reconnected to the database.".to_string());
+            if !self.pending_saves.is_empty() {
+                self.flush_pending_saves(db, |app, flushed| {
+                    app.set_status(format!(
+                        "Reconnected; wrote {} pending save(s), {} conflict(s) to review.",
+                        flushed,
+                        app.conflict_queue.len()
+                    ));
+                });
+            }
+            self.refresh_notes(db);
+            self.refresh_presence(db);
+        }
+    }
+
+    pub fn toggle_columns_view(&mut self) {
+        self.columns_view = !self.columns_view;
+        self.moving_note = None;
+        if self.columns_view {
+            self.rebuild_available_filters();
+            self.sync_column_filter_selection();
+            self.focused_column = Column::Notes;
+        }
+    }
+
+    fn sync_column_filter_selection(&mut self) {
+        let idx = self
+            .available_filters
+            .iter()
+            .position(|f| *f == self.active_filter)
+            .unwrap_or(0);
+        self.column_filter_state.select(Some(idx));
+    }
+
+    /// Moves the tag column selection and applies that tag as the filter immediately.
+    pub fn move_column_filter(&mut self, forward: bool) {
+        if self.available_filters.is_empty() {
+            return;
+        }
+        let len = self.available_filters.len();
+        let i = match self.column_filter_state.selected() {
+            Some(i) if forward => (i + 1) % len,
+            Some(i) => (i + len - 1) % len,
+            None => 0,
+        };
+        self.column_filter_state.select(Some(i));
+        self.apply_filter(self.available_filters[i].clone());
+    }
+
+    /// Picks up the selected note and moves focus to the tag column, where
+    /// 'm' again drops it on the highlighted tag.
+    pub fn pick_up_note(&mut self) {
+        if self.view_mode == ViewMode::Trash {
+            self.set_status("Restore the note with 'x' before changing it.".to_string());
+            return;
+        }
+        if let Some(source) = self.selected_source() {
+            self.set_status(format!(
+                "Notes from '{}' are read-only; changes only go to your own database.",
+                source
+            ));
+            return;
+        }
+        let Some((id, title)) = self.get_selected_note().map(|n| (n.id, n.title.clone())) else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        self.moving_note = Some(id);
+        self.focused_column = Column::Tags;
+        self.set_status(format!(
+            "Moving '{}': pick a tag with j/k, [m] to drop, [Esc] to cancel.",
+            title
+        ));
+    }
+
+    /// Replaces the primary (first) tag of the picked-up note with the tag
+    /// highlighted in the tag column.
+    pub fn drop_note_on_tag(&mut self, db: &mut Database) {
+        let Some(id) = self.moving_note else {
+            return;
+        };
+        let target = self
+            .column_filter_state
+            .selected()
+            .and_then(|i| self.available_filters.get(i));
+        let Some(TagFilter::Specific(tag)) = target.cloned() else {
+            self.set_status("Pick a tag to move the note to.".to_string());
+            return;
+        };
+        let Some(note) = self.all_notes.iter().find(|n| n.id == id) else {
+            self.moving_note = None;
+            self.set_status("The note being moved is gone.".to_string());
+            return;
+        };
+        let title = note.title.clone();
+        let mut tags = note.tags.clone();
+        tags.retain(|t| *t != tag);
+        match note.tags.first() {
+            Some(first) if *first != tag => {
+                if let Some(at) = tags.iter().position(|t| t == first) {
+                    tags[at] = tag.clone();
+                }
+            }
+            _ => tags.insert(0, tag.clone()),
+        }
+        self.spawn_call(
+            db,
+            move |mut db| async move { db.update_note_tags(id, &tags).await },
+            move |app, db, result| match result {
+                Ok(_) => {
+                    app.moving_note = None;
+                    app.focused_column = Column::Notes;
+                    app.set_status(format!("Moved '{}' to #{}.", title, tag));
+                    app.refresh_notes_and_select(db, id);
+                }
+                Err(e) => app.set_status(format!("Error updating tags: {}", e)),
+            },
+        );
+    }
+
+    pub fn focus_column_left(&mut self) {
+        self.focused_column = match self.focused_column {
+            Column::Tags | Column::Notes => Column::Tags,
+            Column::Preview => Column::Notes,
+        };
+    }
+
+    pub fn focus_column_right(&mut self) {
+        self.focused_column = match self.focused_column {
+            Column::Tags => Column::Notes,
+            Column::Notes | Column::Preview => Column::Preview,
+        };
+    }
+
+    /// Describes the current list, e.g. "Notes (Filter: #ops) | Search: 'db'".
+    pub fn list_heading(&self) -> String {
+        format!(
+            "{}{} (Filter: {}){}{}",
+            match self.active_notebook {
+                NotebookFilter::All => "".to_string(),
+                _ => format!("[{}] ", self.notebook_label(self.active_notebook)),
+            },
+            self.view_mode.label(),
+            self.active_filter,
+            if self.sort_mode == SortMode::Title {
+                "".to_string()
+            } else {
+                format!(" | Sorted: {}", self.sort_mode.label())
+            },
+            if self.search_query.is_empty() {
+                "".to_string()
+            } else {
+                format!(
+                    " | Search{}: '{}'",
+                    if self.search_titles_only {
+                        " titles"
+                    } else {
+                        ""
+                    },
+                    self.search_query
+                )
+            }
+        )
+    }
+
+    pub async fn open_export_selector(&mut self, db: &mut Database) {
+        if self.notes.is_empty() {
+            self.set_status("Nothing to export.".to_string());
+            return;
+        }
+        if !self.load_all_content(db).await {
+            return;
+        }
+        self.export_format_state.select(Some(0));
+        self.push_mode(InputMode::SelectingExportFormat);
+    }
+
+    pub fn export_current_list(&mut self, format: ExportFormat) {
+        let heading = self.list_heading();
+        match export::export_document(&self.notes, &heading, format, &self.export_dir) {
+            Ok(path) => self.set_status(format!(
+                "Exported {} note(s) to {}",
+                self.notes.len(),
+                path.display()
+            )),
+            Err(e) => self.set_status(format!("Export failed: {}", e)),
+        }
+    }
+
+    /// Notes (other than `id`) containing a wikilink to `title`, leaving out
+    /// those in the Trash and read-only sources, whose links are not rewritten.
+    pub fn notes_linking_to(&self, title: &str, id: i32) -> Vec<(i32, String)> {
+        self.all_notes
+            .iter()
+            .filter(|n| n.deleted_at.is_none() && n.source.is_none())
+            .filter(|n| n.id != id && links::links_to(&n.content, title))
+            .map(|n| (n.id, n.title.clone()))
+            .collect()
+    }
+
+    pub async fn open_link_graph(&mut self, db: &mut Database) {
+        let Some(id) = self.get_selected_note().map(|n| n.id) else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        if !self.load_all_content(db).await {
+            return;
+        }
+        self.center_link_graph(id);
+        self.push_mode(InputMode::LinkGraph);
+    }
+
+    /// Rebuilds the graph around `id` (two hops out) and selects that note in the list.
+    pub fn center_link_graph(&mut self, id: i32) {
+        if let Some(root) = self.all_notes.iter().find(|n| n.id == id) {
+            self.graph_entries = links::neighborhood(&self.all_notes, root, 2);
+            self.graph_list_state.select(Some(0));
+            self.select_note_by_id(id);
+        }
+    }
+
+    /// Selects a note in the list, switching view and clearing filters if it is hidden.
+    pub fn select_note_by_id(&mut self, id: i32) {
+        if !self.notes.iter().any(|n| n.id == id) {
+            let Some((archived, deleted)) = self
+                .all_notes
+                .iter()
+                .find(|n| n.id == id)
+                .map(|n| (n.archived, n.deleted_at.is_some()))
+            else {
+                return;
+            };
+            self.view_mode = if deleted {
+                ViewMode::Trash
+            } else if archived {
+                ViewMode::Archived
+            } else {
+                ViewMode::Active
+            };
+            self.active_filter = TagFilter::All;
+            self.search_query.clear();
+            self.apply_current_filter();
+        }
+        if let Some(idx) = self.notes.iter().position(|n| n.id == id) {
+            self.list_state.select(Some(idx));
+            self.update_preview();
+        }
+    }
+
+    pub async fn open_orphan_report(&mut self, db: &mut Database) {
+        if !self.load_all_content(db).await {
+            return;
+        }
+        self.rebuild_orphan_report();
+        if self.orphan_report.is_empty() {
+            self.set_status("No orphaned notes or broken links.".to_string());
+            return;
+        }
+        self.push_mode(InputMode::OrphanReport);
+    }
+
+    pub fn rebuild_orphan_report(&mut self) {
+        self.orphan_report = links::orphan_report(&self.all_notes);
+        self.clamp_report_selection();
+    }
+
+    /// Takes the links to `target` out of the report once a note by that title
+    /// was created, without waiting for the list to reload.
+    pub fn drop_broken_links_to(&mut self, target: &str) {
+        self.orphan_report.retain(
+            |entry| !matches!(entry, ReportEntry::BrokenLink { target: t, .. } if t == target),
+        );
+        self.clamp_report_selection();
+    }
+
+    fn clamp_report_selection(&mut self) {
+        let selected = self
+            .report_list_state
+            .selected()
+            .unwrap_or(0)
+            .min(self.orphan_report.len().saturating_sub(1));
+        self.report_list_state
+            .select(if self.orphan_report.is_empty() {
+                None
+            } else {
+                Some(selected)
+            });
+    }
+
+    /// Lints freshly saved content and opens the findings panel if anything turned up.
+    pub fn lint_saved_content(&mut self, content: &str) {
+        if !self.lint_rules.enabled {
+            return;
+        }
+        let titles: HashSet<&str> = self.all_notes.iter().map(|n| n.title.as_str()).collect();
+        self.lint_findings = lint::lint(content, &titles, &self.lint_rules);
+        if !self.lint_findings.is_empty() {
+            self.set_status(format!(
+                "Note saved with {} lint finding(s).",
+                self.lint_findings.len()
+            ));
+            self.push_mode(InputMode::LintReport);
+        }
+    }
+
+    /// Content of a note as the user sees it: plaintext, or decrypted if unlocked.
+    /// `None` for an encrypted note that is still locked, or content not loaded yet.
+    pub fn readable_content(&self, note: &Note) -> Option<String> {
+        if !note.content_loaded {
+            return None;
+        }
+        if !note.encrypted {
+            return Some(note.content.clone());
+        }
+        self.unlocked_notes.get(&note.id).map(|(p, _)| p.clone())
+    }
+
+    pub fn open_passphrase_prompt(&mut self, purpose: PassphrasePurpose) {
+        self.passphrase_input.clear();
+        self.passphrase_first = None;
+        self.passphrase_purpose = Some(purpose);
+        self.push_mode(InputMode::EnteringPassphrase);
+        self.set_status("Enter passphrase. [Enter] confirm, [Esc] cancel.".to_string());
+    }
+
+    /// Decrypts a note with `passphrase` and keeps the plaintext for the session.
+    pub fn unlock_note(&mut self, id: i32, passphrase: &str) -> bool {
+        let Some(note) = self
+            .all_notes
+            .iter()
+            .find(|n| n.id == id && n.content_loaded)
+        else {
+            return false;
+        };
+        match crypto::decrypt(&note.content, passphrase) {
+            Ok(plaintext) => {
+                let title = note.title.clone();
+                self.unlocked_notes
+                    .insert(id, (plaintext, passphrase.to_string()));
+                self.update_preview();
+                self.set_status(format!("Note '{}' unlocked for this session.", title));
+                true
+            }
+            Err(e) => {
+                self.set_status(format!("Could not unlock note: {}", e));
+                false
+            }
+        }
+    }
+
+    /// Tries the passphrases of already unlocked notes, so a shared passphrase
+    /// only has to be typed once.
+    pub fn try_known_passphrases(&mut self, id: i32) -> bool {
+        let known: Vec<String> = self
+            .unlocked_notes
+            .values()
+            .map(|(_, p)| p.clone())
+            .collect();
+        let Some(note) = self
+            .all_notes
+            .iter()
+            .find(|n| n.id == id && n.content_loaded)
+        else {
+            return false;
+        };
+        let Some(passphrase) = known
+            .into_iter()
+            .find(|p| crypto::decrypt(&note.content, p).is_ok())
+        else {
+            return false;
+        };
+        self.unlock_note(id, &passphrase)
+    }
+
+    /// Shows the lock screen, asking for a session passphrase first if none is configured.
+    pub fn lock_screen(&mut self) {
+        if self.input_mode == InputMode::Locked {
+            return;
+        }
+        if self.lock_passphrase.is_none() {
+            if self.passphrase_purpose != Some(PassphrasePurpose::SetLock) {
+                self.open_passphrase_prompt(PassphrasePurpose::SetLock);
+                self.set_status("Choose a lock passphrase for this session.".to_string());
+            }
+            return;
+        }
+        self.lock_input.clear();
+        self.push_mode(InputMode::Locked);
+    }
+
+    /// Locks after `lock_idle` without a keypress. Only possible once a passphrase is known.
+    pub fn check_idle_lock(&mut self) {
+        if let Some(idle) = self.lock_idle
+            && self.lock_passphrase.is_some()
+            && self.last_activity.elapsed() >= idle
+        {
+            self.lock_screen();
+        }
+    }
+
+    pub fn try_unlock_screen(&mut self) {
+        let input = std::mem::take(&mut self.lock_input);
+        if self.lock_passphrase.as_deref() == Some(input.as_str()) {
+            self.pop_mode();
+            self.set_status("Unlocked.".to_string());
+        } else {
+            self.set_status("Wrong passphrase.".to_string());
+        }
+    }
+
+    pub fn toggle_reveal_secret(&mut self) {
+        let Some((id, secret)) = self
+            .get_selected_note()
+            .map(|n| (n.id, n.tags.iter().any(|t| t == SECRET_TAG)))
+        else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        if !secret {
+            self.set_status(format!("Note is not tagged #{}.", SECRET_TAG));
+            return;
+        }
+        self.revealed_note = if self.revealed_note == Some(id) {
+            None
+        } else {
+            Some(id)
+        };
+        let scroll = self.preview_scroll;
+        self.update_preview();
+        self.preview_scroll = scroll;
+    }
+
+    /// A title as it should appear on screen, honoring screencast mode.
+    pub fn display_title(&self, title: &str) -> String {
+        if self.screencast {
+            lorem::title(title)
+        } else {
+            title.to_string()
+        }
+    }
+
+    /// Note text as it should appear on screen, honoring screencast mode.
+    pub fn display_text(&self, text: &str) -> String {
+        if self.screencast {
+            lorem::text(text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// A tag as it should appear on screen, honoring screencast mode.
+    pub fn display_tag(&self, tag: &str) -> String {
+        if self.screencast {
+            lorem::word(tag).to_string()
+        } else {
+            tag.to_string()
+        }
+    }
+
+    pub fn toggle_screencast(&mut self) {
+        self.screencast = !self.screencast;
+        let scroll = self.preview_scroll;
+        self.update_preview();
+        self.preview_scroll = scroll;
+        self.set_status(format!(
+            "Screencast mode {}.",
+            if self.screencast { "on" } else { "off" }
+        ));
+    }
+
+    pub async fn load_tag_meta(&mut self, db: &mut Database) {
+        let meta = db.get_tag_meta().await;
+        self.show_tag_meta(meta);
+    }
+
+    fn show_tag_meta(&mut self, meta: Result<Vec<TagMeta>, db::Error>) {
+        match meta {
+            Ok(meta) => {
+                self.tag_meta = meta.into_iter().map(|m| (m.name.clone(), m)).collect();
+            }
+            Err(e) => self.set_status(format!("Error loading tag metadata: {}", e)),
+        }
+    }
+
+    /// Writes every tag with its count, color and parent to the export directory.
+    pub async fn export_taxonomy(&mut self, db: &mut Database) {
+        self.load_tag_meta(db).await;
+        let meta: Vec<TagMeta> = self.tag_meta.values().cloned().collect();
+        match taxonomy::export_taxonomy(&self.all_notes, &meta, &self.export_dir) {
+            Ok(path) => self.set_status(format!("Tag taxonomy exported to {}", path.display())),
+            Err(e) => self.set_status(format!("Taxonomy export failed: {}", e)),
+        }
+    }
+
+    /// Reads the taxonomy file from the export directory into `tag_meta`.
+    pub fn import_taxonomy(&mut self, db: &mut Database) {
+        let path = self.export_dir.join(taxonomy::TAXONOMY_FILE_NAME);
+        let tags = match taxonomy::import_taxonomy(&path) {
+            Ok(tags) => tags,
+            Err(e) => {
+                self.set_status(format!("Taxonomy import failed: {}", e));
+                return;
+            }
+        };
+        self.spawn_call(
+            db,
+            move |mut db| async move {
+                let count = db.upsert_tag_meta(&tags).await?;
+                Ok((count, db.get_tag_meta().await))
+            },
+            move |app, _, result| match result {
+                Ok((count, meta)) => {
+                    app.show_tag_meta(meta);
+                    app.rebuild_available_filters();
+                    app.set_status(format!("Imported {} tag(s) from {}", count, path.display()));
+                }
+                Err(e) => app.set_status(format!("Taxonomy import failed: {}", e)),
+            },
+        );
+    }
+
+    /// Whether `note` is pinned within the active tag filter.
+    pub fn is_pinned_here(&self, note: &Note) -> bool {
+        matches!(&self.active_filter, TagFilter::Specific(tag) if note.pinned_in.contains(tag))
+    }
+
+    pub fn toggle_pin(&mut self, db: &mut Database) {
+        let TagFilter::Specific(tag) = self.active_filter.clone() else {
+            self.set_status("Filter by a tag first ('T') to pin a note within it.".to_string());
+            return;
+        };
+        let Some((id, title)) = self.get_selected_note().map(|n| (n.id, n.title.clone())) else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        self.spawn_call(
+            db,
+            {
+                let tag = tag.clone();
+                move |mut db| async move { db.toggle_pin(id, &tag).await }
+            },
+            move |app, db, result| match result {
+                Ok(pinned) => {
+                    app.set_status(format!(
+                        "'{}' {} #{}.",
+                        title,
+                        if pinned { "pinned in" } else { "unpinned from" },
+                        tag
+                    ));
+                    app.refresh_notes_and_select(db, id);
+                }
+                Err(e) => app.set_status(format!("Error updating pin: {}", e)),
+            },
+        );
+    }
+
+    pub fn open_reminder_popup(&mut self) {
+        let Some(id) = self.get_selected_note().map(|n| n.id) else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        self.reminder_note = Some(id);
+        self.reminder_due = false;
+        self.push_mode(InputMode::Reminder);
+    }
+
+    /// Pops up the first reminder that has come due, unless a popup is already open.
+    pub fn check_due_reminders(&mut self) {
+        if self.input_mode != InputMode::Normal {
+            return;
+        }
+        let now = Utc::now();
+        let due = self.all_notes.iter().find_map(|n| {
+            let at = n
+                .remind_at
+                .filter(|at| *at <= now && !n.archived && n.deleted_at.is_none())?;
+            (!self.announced_reminders.contains(&(n.id, at))).then_some((n.id, at, n.title.clone()))
+        });
+        if let Some((id, at, title)) = due {
+            self.announced_reminders.insert((id, at));
+            self.reminder_note = Some(id);
+            self.reminder_due = true;
+            self.push_mode(InputMode::Reminder);
+            self.set_status(format!("Reminder: '{}'", title));
+        }
+    }
+
+    /// Moves the reminder to `remind_at` (or clears it) and closes the popup.
+    pub fn apply_reminder(&mut self, db: &mut Database, remind_at: Option<DateTime<Utc>>) {
+        let Some(id) = self.reminder_note.take() else {
+            return;
+        };
+        self.pop_mode();
+        self.spawn_call(
+            db,
+            move |mut db| async move { db.set_reminder(id, remind_at).await },
+            move |app, db, result| match result {
+                Ok(_) => {
+                    app.set_status(match remind_at {
+                        Some(at) => format!(
+                            "Reminder set for {}.",
+                            at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                        ),
+                        None => "Reminder cleared.".to_string(),
+                    });
+                    app.refresh_notes(db);
+                }
+                Err(e) => app.set_status(format!("Error updating reminder: {}", e)),
+            },
+        );
+    }
+
+    /// Filters by the selected note's first tag, keeping that note selected.
+    pub fn filter_by_selected_tag(&mut self) {
+        let Some((id, tag)) = self
+            .get_selected_note()
+            .map(|n| (n.id, n.tags.first().cloned()))
+        else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        let Some(tag) = tag else {
+            self.set_status("Selected note has no tags.".to_string());
+            return;
+        };
+        self.apply_filter(TagFilter::Specific(tag));
+        if self.columns_view {
+            self.rebuild_available_filters();
+            self.sync_column_filter_selection();
+        }
+        self.select_note_by_id(id);
+        self.set_status(format!("Filter applied: {}", self.active_filter));
+    }
+
+    pub async fn load_smart_filters(&mut self, db: &mut Database) {
+        let filters = db.get_smart_filters().await;
+        self.show_smart_filters(filters);
+    }
+
+    fn show_smart_filters(&mut self, filters: Result<Vec<String>, db::Error>) {
+        match filters {
+            Ok(filters) => self.smart_filters = filters,
+            Err(e) => self.set_status(format!("Error loading smart filters: {}", e)),
+        }
+    }
+
+    pub fn open_edit_review(&mut self, session: EditSession, edited: String) {
+        let lines = diff::diff_lines(&session.original, &edited);
+        let (added, removed) = diff::stats(&lines);
+        self.edit_review = Some(EditReview {
+            session,
+            edited,
+            lines,
+            scroll: 0,
+        });
+        self.push_mode(InputMode::ReviewingEdit);
+        self.set_status(format!(
+            "+{} -{} lines. [y] save, [n] discard, [e] edit again, [j/k] scroll",
+            added, removed
+        ));
+    }
+
+    /// Shows `mine` next to the note as another instance saved it.
+    pub fn open_edit_conflict(&mut self, db: &mut Database, session: EditSession, mine: String) {
+        self.spawn_call(
+            db,
+            move |mut db| async move { Ok(load_edit_conflict(&mut db, session, mine).await) },
+            |app, _, result| {
+                let Ok(conflict) = result else { return };
+                // Something else took the screen meanwhile; it waits its turn.
+                if app.input_mode != InputMode::Normal || app.edit_conflict.is_some() {
+                    app.conflict_queue.retain(|c| c.session.id != conflict.session.id);
+                    app.conflict_queue.push_front(conflict);
+                    return;
+                }
+                app.set_status(format!(
+                    "'{}' was changed elsewhere while you edited it. [m] keep mine, [t] keep theirs, [e] merge",
+                    app.display_title(&conflict.session.title)
+                ));
+                app.edit_conflict = Some(conflict);
+                app.push_mode(InputMode::ResolvingConflict);
+            },
+        );
+    }
+
+    pub fn open_url_paste(&mut self, url: &str) {
+        let target = match self.get_selected_note() {
+            Some(note) if note.source.is_none() && !note.encrypted => {
+                format!("[l] link it in '{}', ", self.display_title(&note.title))
+            }
+            _ => String::new(),
+        };
+        self.pasted_url = Some(url.to_string());
+        self.push_mode(InputMode::ConfirmingUrlPaste);
+        self.set_status(format!(
+            "Pasted {}: {}[c] clip as a new #{} note, [Esc] cancel",
+            url,
+            target,
+            clip::CLIPPED_TAG
+        ));
+    }
+
+    /// Appends a Markdown link to the selected note.
+    pub async fn append_link_to_selected(&mut self, db: &mut Database, link: String) {
+        if !self.ensure_selected_content(db).await {
+            return;
+        }
+        let Some(note) = self.get_selected_note() else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        if note.source.is_some() || note.encrypted {
+            self.set_status("Links can't be added to read-only or encrypted notes.".to_string());
+            return;
+        }
+        let (id, title, loaded_at) = (note.id, self.display_title(&note.title), note.updated_at);
+        let content = if note.content.trim().is_empty() {
+            format!("{}\n", link)
+        } else {
+            format!("{}\n{}\n", note.content.trim_end(), link)
+        };
+        self.spawn_call(
+            db,
+            move |mut db| async move { db.update_note_content(id, &content, loaded_at).await },
+            move |app, db, result| match result {
+                Ok(true) => {
+                    app.set_status(format!("Added {} to '{}'.", link, title));
+                    app.refresh_notes_and_select(db, id);
+                }
+                Ok(false) => {
+                    app.set_status(format!(
+                        "'{}' was changed in another instance; paste the link again.",
+                        title
+                    ));
+                    app.refresh_notes_and_select(db, id);
+                }
+                Err(e) => app.set_status(format!("Error adding link: {}", e)),
+            },
+        );
+    }
+
+    /// Creates a note titled after the page, tagged #clipped, holding the link.
+    pub fn create_clipped_note(&mut self, db: &mut Database, title: &str, link: String) {
+        let base = self.title_rules.sanitize(title);
+        let mut title = base.clone();
+        for n in 2.. {
+            if !self.all_notes.iter().any(|note| note.title == title) {
+                break;
+            }
+            title = format!("{} ({})", base, n);
+        }
+
+        self.spawn_call(
+            db,
+            {
+                let title = title.clone();
+                move |mut db| async move {
+                    db.create_note_with_content(
+                        &title,
+                        &format!("{}\n", link),
+                        &[clip::CLIPPED_TAG.to_string()],
+                    )
+                    .await
+                }
+            },
+            move |app, db, created| match created {
+                Ok(id) => {
+                    app.set_status(format!("Clipped '{}'.", title));
+                    app.refresh_notes_and_select(db, id);
+                }
+                Err(e) => app.set_status(format!("Error clipping URL: {}", e)),
+            },
+        );
+    }
+
+    pub fn open_tag_editor(&mut self) {
+        let marked = self.marked_notes();
+        self.bulk_tags = marked.first().map(|first| {
+            first
+                .tags
+                .iter()
+                .filter(|tag| marked.iter().all(|n| n.tags.contains(tag)))
+                .cloned()
+                .collect()
+        });
+        let Some(tags) = self
+            .bulk_tags
+            .clone()
+            .or_else(|| self.get_selected_note().map(|n| n.tags.clone()))
+        else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        let known: Vec<String> = self
+            .all_notes
+            .iter()
+            .flat_map(|n| n.tags.iter().cloned())
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        self.tag_editor = TagEditor::new(tags, known);
+        self.push_mode(InputMode::EditingTags);
+        let keys = "[Enter] add tag / save, [Tab] complete, [Backspace] remove, [Esc] cancel";
+        self.set_status(match &self.bulk_tags {
+            Some(_) => format!(
+                "Tags shared by the {} marked notes. {}",
+                self.marked.len(),
+                keys
+            ),
+            None => keys.to_string(),
+        });
+    }
+
+    /// Offers the selected note's tags followed by the most-used other tags.
+    pub fn open_quick_tags(&mut self) {
+        let Some(note) = self.get_selected_note() else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        if note.source.is_some() {
+            self.set_status("Notes from other sources are read-only.".to_string());
+            return;
+        }
+        let (id, mut tags) = (note.id, note.tags.clone());
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for tag in self.all_notes.iter().flat_map(|n| &n.tags) {
+            *counts.entry(tag.as_str()).or_default() += 1;
+        }
+        let mut popular: Vec<(&str, usize)> = counts
+            .into_iter()
+            .filter(|(tag, _)| !tags.iter().any(|t| t == tag))
+            .collect();
+        popular.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        let popular: Vec<String> = popular
+            .into_iter()
+            .take(self.quick_tag_count)
+            .map(|(tag, _)| tag.to_string())
+            .collect();
+        tags.extend(popular);
+        tags.truncate(9);
+
+        if tags.is_empty() {
+            self.set_status("No tags yet; add some with 't'.".to_string());
+            return;
+        }
+        self.quick_tags = tags;
+        self.quick_tag_note = Some(id);
+        self.push_mode(InputMode::TogglingTags);
+        self.set_status("[1-9] add/remove tag, [Esc] done".to_string());
+    }
+
+    /// Adds or removes quick tag `n` (1-based) on the note the toggles were opened for.
+    pub fn toggle_quick_tag(&mut self, db: &mut Database, n: usize) {
+        let (Some(id), Some(tag)) = (self.quick_tag_note, self.quick_tags.get(n - 1).cloned())
+        else {
+            return;
+        };
+        let Some(note) = self.all_notes.iter().find(|note| note.id == id) else {
+            return;
+        };
+        let mut tags = note.tags.clone();
+        let added = if let Some(at) = tags.iter().position(|t| *t == tag) {
+            tags.remove(at);
+            false
+        } else {
+            tags.push(tag.clone());
+            true
+        };
+        // Update right away so the next toggle sees this one, written or not yet.
+        if let Some(at) = self.all_notes.iter().position(|note| note.id == id) {
+            self.all_notes[at].tags = tags.clone();
+            self.search_index[at] = search_key(&self.all_notes[at]);
+        }
+        self.spawn_call(
+            db,
+            move |mut db| async move { db.update_note_tags(id, &tags).await },
+            move |app, db, result| match result {
+                Ok(_) => {
+                    app.set_status(format!(
+                        "{} #{}. [1-9] add/remove tag, [Esc] done",
+                        if added { "Added" } else { "Removed" },
+                        tag
+                    ));
+                    app.refresh_notes_and_select(db, id);
+                }
+                Err(e) => {
+                    app.set_status(format!("Error updating tags: {}", e));
+                    app.refresh_notes_and_select(db, id);
+                }
+            },
+        );
+    }
+
+    /// Tags of the note the quick toggles were opened for.
+    pub fn quick_tag_note_tags(&self) -> &[String] {
+        self.quick_tag_note
+            .and_then(|id| self.all_notes.iter().find(|n| n.id == id))
+            .map_or(&[], |n| n.tags.as_slice())
+    }
+
+    pub async fn load_notebooks(&mut self, db: &mut Database) {
+        let notebooks = db.get_notebooks().await;
+        self.show_notebooks(notebooks);
+    }
+
+    fn show_notebooks(&mut self, notebooks: Result<Vec<Notebook>, db::Error>) {
+        match notebooks {
+            Ok(notebooks) => self.notebooks = notebooks,
+            Err(e) => self.set_status(format!("Error loading notebooks: {}", e)),
+        }
+        if let NotebookFilter::Notebook(id) = self.active_notebook
+            && !self.notebooks.iter().any(|nb| nb.id == id)
+        {
+            self.active_notebook = NotebookFilter::All;
+            self.apply_current_filter();
+        }
+    }
+
+    /// Entries of the notebook selector, in display order.
+    pub fn notebook_choices(&self) -> Vec<NotebookFilter> {
+        [NotebookFilter::All, NotebookFilter::Unfiled]
+            .into_iter()
+            .chain(
+                self.notebooks
+                    .iter()
+                    .map(|nb| NotebookFilter::Notebook(nb.id)),
+            )
+            .collect()
+    }
+
+    pub fn notebook_label(&self, filter: NotebookFilter) -> String {
+        match filter {
+            NotebookFilter::All => "All notebooks".to_string(),
+            NotebookFilter::Unfiled => "Unfiled".to_string(),
+            NotebookFilter::Notebook(id) => self
+                .notebooks
+                .iter()
+                .find(|nb| nb.id == id)
+                .map_or_else(|| format!("#{}", id), |nb| nb.name.clone()),
+        }
+    }
+
+    fn selected_notebook_choice(&self) -> Option<NotebookFilter> {
+        self.notebook_list_state
+            .selected()
+            .and_then(|i| self.notebook_choices().get(i).copied())
+    }
+
+    pub async fn open_notebook_selector(&mut self, db: &mut Database) {
+        self.load_notebooks(db).await;
+        let current = self
+            .notebook_choices()
+            .iter()
+            .position(|c| *c == self.active_notebook);
+        self.notebook_list_state.select(current.or(Some(0)));
+        self.push_mode(InputMode::SelectingNotebook);
+        self.set_status(
+            "[Enter] switch notebook, [m] move selected note here, [n] new, [d] delete, [Esc] close"
+                .to_string(),
+        );
+    }
+
+    pub fn move_notebook_selection(&mut self, forward: bool) {
+        let len = self.notebook_choices().len();
+        let i = match self.notebook_list_state.selected() {
+            Some(i) if forward => (i + 1) % len,
+            Some(i) => (i + len - 1) % len,
+            None => 0,
+        };
+        self.notebook_list_state.select(Some(i));
+    }
+
+    pub fn switch_to_selected_notebook(&mut self) {
+        let Some(choice) = self.selected_notebook_choice() else {
+            return;
+        };
+        self.active_notebook = choice;
+        self.apply_current_filter();
+        self.list_state
+            .select(if self.notes.is_empty() { None } else { Some(0) });
+        self.update_preview();
+        self.pop_mode();
+        self.set_status(format!("Notebook: {}", self.notebook_label(choice)));
+    }
+
+    pub fn move_selected_to_notebook(&mut self, db: &mut Database) {
+        let target = match self.selected_notebook_choice() {
+            Some(NotebookFilter::Notebook(id)) => Some(id),
+            Some(NotebookFilter::Unfiled) => None,
+            _ => {
+                self.set_status("Pick a notebook, or Unfiled, to move the note to.".to_string());
+                return;
+            }
+        };
+        let Some(note) = self.get_selected_note() else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        if note.source.is_some() {
+            self.set_status("Notes from other sources are read-only.".to_string());
+            return;
+        }
+        let (id, title) = (note.id, self.display_title(&note.title));
+        self.spawn_call(
+            db,
+            move |mut db| async move { db.move_to_notebook(id, target).await },
+            move |app, db, result| match result {
+                Ok(()) => {
+                    let label = app.notebook_label(
+                        target.map_or(NotebookFilter::Unfiled, NotebookFilter::Notebook),
+                    );
+                    app.set_status(format!("Moved '{}' to {}.", title, label));
+                    app.pop_mode_from(InputMode::SelectingNotebook);
+                    app.refresh_notes(db);
+                }
+                Err(e) => app.set_status(format!("Error moving note: {}", e)),
+            },
+        );
+    }
+
+    pub fn create_notebook(&mut self, db: &mut Database) {
+        let name = self.notebook_input.value().trim().to_string();
+        if name.is_empty() {
+            self.input_error = Some("Name cannot be empty.".to_string());
+            return;
+        }
+        if self.notebooks.iter().any(|nb| nb.name == name) {
+            self.input_error = Some(format!("A notebook named '{}' already exists.", name));
+            return;
+        }
+        self.spawn_call(
+            db,
+            {
+                let name = name.clone();
+                move |mut db| async move {
+                    let id = db.create_notebook(&name).await?;
+                    Ok((id, db.get_notebooks().await))
+                }
+            },
+            move |app, _, result| match result {
+                Ok((id, notebooks)) => {
+                    app.input_error = None;
+                    app.pop_mode_from(InputMode::NamingNotebook);
+                    app.show_notebooks(notebooks);
+                    let at = app
+                        .notebook_choices()
+                        .iter()
+                        .position(|c| *c == NotebookFilter::Notebook(id));
+                    app.notebook_list_state.select(at);
+                    app.set_status(format!(
+                        "Created notebook '{}'. [m] move the selected note into it.",
+                        name
+                    ));
+                }
+                Err(e) => app.input_error = Some(format!("Error creating notebook: {}", e)),
+            },
+        );
+    }
+
+    pub fn delete_selected_notebook(&mut self, db: &mut Database) {
+        let Some(NotebookFilter::Notebook(id)) = self.selected_notebook_choice() else {
+            self.set_status("Only notebooks you created can be deleted.".to_string());
+            return;
+        };
+        let name = self.notebook_label(NotebookFilter::Notebook(id));
+        self.spawn_call(
+            db,
+            move |mut db| async move {
+                db.delete_notebook(id).await?;
+                Ok(db.get_notebooks().await)
+            },
+            move |app, db, result| match result {
+                Ok(notebooks) => {
+                    app.show_notebooks(notebooks);
+                    let last = app.notebook_choices().len() - 1;
+                    app.notebook_list_state
+                        .select(app.notebook_list_state.selected().map(|i| i.min(last)));
+                    app.set_status(format!(
+                        "Deleted notebook '{}'; its notes are unfiled.",
+                        name
+                    ));
+                    app.refresh_notes(db);
+                }
+                Err(e) => app.set_status(format!("Error deleting notebook: {}", e)),
+            },
+        );
+    }
+
+    pub fn open_command_line(&mut self) {
+        self.command_input.clear();
+        self.input_error = None;
+        self.push_mode(InputMode::Command);
+        self.set_status("Command: [Tab] complete, [Enter] run, [Esc] cancel.".to_string());
+    }
+
+    /// Commands whose name starts with the word typed so far.
+    pub fn command_matches(&self) -> Vec<&'static (&'static str, &'static str)> {
+        let typed = self.command_input.value().trim_start();
+        let name = typed.split_whitespace().next().unwrap_or("");
+        if typed.contains(char::is_whitespace) {
+            return COMMANDS.iter().filter(|(n, _)| *n == name).collect();
+        }
+        COMMANDS
+            .iter()
+            .filter(|(n, _)| n.starts_with(name))
+            .collect()
+    }
+
+    /// Completes the command name when only one command matches.
+    pub fn complete_command(&mut self) {
+        if let [(name, _)] = self.command_matches()[..] {
+            self.command_input.set(format!("{} ", name));
+        }
+    }
+
+    pub fn open_filter_expression_input(&mut self) {
+        match &self.active_filter {
+            TagFilter::Expression { source, .. } => self.filter_expr_input.set_selected(source),
+            _ => self.filter_expr_input.clear(),
+        }
+        self.input_error = None;
+        self.push_mode(InputMode::EditingFilterExpression);
+        self.set_status(
+            "Tag expression, e.g. work AND (urgent OR today). [Enter] apply, [Esc] cancel."
+                .to_string(),
+        );
+    }
+
+    /// Parses the typed expression and applies it, closing the popup; parse errors stay in it.
+    pub fn submit_filter_expression(&mut self) {
+        let source = self.filter_expr_input.value().trim().to_string();
+        match filter_expr::parse(&source) {
+            Ok(expr) => {
+                self.input_error = None;
+                self.apply_filter(TagFilter::Expression { source, expr });
+                self.pop_mode();
+                self.set_status(format!("Filter applied: {}", self.active_filter));
+            }
+            Err(e) => self.input_error = Some(e),
+        }
+    }
+
+    pub fn save_smart_filter(&mut self, db: &mut Database) {
+        let TagFilter::Expression { source, .. } = self.active_filter.clone() else {
+            self.set_status("Apply an expression filter ('n') before saving it.".to_string());
+            return;
+        };
+        self.spawn_call(
+            db,
+            {
+                let source = source.clone();
+                move |mut db| async move {
+                    db.save_smart_filter(&source).await?;
+                    Ok(db.get_smart_filters().await)
+                }
+            },
+            move |app, _, result| match result {
+                Ok(filters) => {
+                    app.show_smart_filters(filters);
+                    app.rebuild_available_filters();
+                    app.set_status(format!("Saved smart filter [{}].", source));
+                }
+                Err(e) => app.set_status(format!("Error saving smart filter: {}", e)),
+            },
+        );
+    }
+
+    pub fn delete_selected_smart_filter(&mut self, db: &mut Database) {
+        let selected = self
+            .filter_list_state
+            .selected()
+            .and_then(|i| self.available_filters.get(i))
+            .cloned();
+        let Some(TagFilter::Expression { source, .. }) = selected else {
+            self.set_status("Only saved expression filters can be deleted.".to_string());
+            return;
+        };
+        self.spawn_call(
+            db,
+            {
+                let source = source.clone();
+                move |mut db| async move {
+                    db.delete_smart_filter(&source).await?;
+                    Ok(db.get_smart_filters().await)
+                }
+            },
+            move |app, _, result| match result {
+                Ok(filters) => {
+                    app.show_smart_filters(filters);
+                    app.rebuild_available_filters();
+                    let last = app.available_filters.len().saturating_sub(1);
+                    app.filter_list_state
+                        .select(app.filter_list_state.selected().map(|i| i.min(last)));
+                    app.set_status(format!("Deleted smart filter [{}].", source));
+                }
+                Err(e) => app.set_status(format!("Error deleting smart filter: {}", e)),
+            },
+        );
+    }
+
+    pub fn open_date_range_input(&mut self) {
+        self.date_range_input.clear();
+        self.input_error = None;
+        self.push_mode(InputMode::EditingDateRange);
+        self.set_status(
+            "Date range, e.g. 'updated 2024-01-01..2024-01-31' or '2024-03-01..'. [Enter] apply."
+                .to_string(),
+        );
+    }
+
+    pub fn submit_date_range(&mut self) {
+        match DateFilter::parse(self.date_range_input.value()) {
+            Ok(date) => {
+                self.input_error = None;
+                self.apply_filter(TagFilter::Date(date));
+                self.pop_mode();
+                self.set_status(format!("Filter applied: {}", self.active_filter));
+            }
+            Err(e) => self.input_error = Some(e),
+        }
+    }
+
+    /// Asks for a new name for the tag highlighted in the filter popup.
+    pub fn open_tag_rename(&mut self) {
+        let selected = self
+            .filter_list_state
+            .selected()
+            .and_then(|i| self.available_filters.get(i))
+            .cloned();
+        match selected {
+            Some(TagFilter::Specific(tag)) => self.start_tag_rename(tag),
+            _ => self.set_status("Highlight a tag to rename it.".to_string()),
+        }
+    }
+
+    fn start_tag_rename(&mut self, tag: String) {
+        self.tag_rename_input.set_selected(tag.as_str());
+        self.set_status(format!(
+            "Rename '{}' on every note; an existing tag's name merges them. [Enter] rename, [Esc] cancel.",
+            tag
+        ));
+        self.renaming_tag = Some(tag);
+        self.input_error = None;
+        self.push_mode(InputMode::RenamingTag);
+    }
+
+    /// Renames `renaming_tag` everywhere and goes back to the filter popup or the
+    /// tag manager with the new name highlighted.
+    pub fn submit_tag_rename(&mut self, db: &mut Database) {
+        let Some(old) = self.renaming_tag.clone() else {
+            self.pop_mode();
+            return;
+        };
+        // Spaces become dashes, as in the tag editor.
+        let new = self
+            .tag_rename_input
+            .value()
+            .trim_start_matches('#')
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-");
+        if new.is_empty() || new.contains(',') {
+            self.input_error = Some("A tag can't be empty or contain commas.".to_string());
+            return;
+        }
+        if new == old {
+            self.input_error = Some("That is already its name.".to_string());
+            return;
+        }
+        self.spawn_call(
+            db,
+            {
+                let (old, new) = (old.clone(), new.clone());
+                move |mut db| async move {
+                    let changed = db.rename_tag(&old, &new).await?;
+                    Ok((changed, TagReload::load(&mut db).await))
+                }
+            },
+            move |app, db, result| match result {
+                Ok((changed, reloaded)) => {
+                    app.renaming_tag = None;
+                    app.input_error = None;
+                    app.pop_mode_from(InputMode::RenamingTag);
+                    app.reload_tags(db, &old, Some(&new), reloaded);
+                    let renamed = TagFilter::Specific(new.clone());
+                    if let Some(i) = app.available_filters.iter().position(|f| *f == renamed) {
+                        app.filter_list_state.select(Some(i));
+                    }
+                    app.set_status(format!(
+                        "Renamed '{}' to '{}' on {} note(s).",
+                        old, new, changed
+                    ));
+                }
+                Err(e) => app.input_error = Some(format!("Error renaming tag: {}", e)),
+            },
+        );
+    }
+
+    /// Shows `reloaded` tags after `old` was renamed to `new`, or deleted when
+    /// `new` is `None`, and reloads the notes; a filter on `old` follows it. The
+    /// tag manager keeps its place on `new`, else on the row `old` was in.
+    fn reload_tags(
+        &mut self,
+        db: &mut Database,
+        old: &str,
+        new: Option<&str>,
+        reloaded: TagReload,
+    ) {
+        // Rename it in the list right away, so the filters below have it before
+        // the reload is in.
+        for note in self.all_notes.iter_mut().filter(|n| n.source.is_none()) {
+            let Some(at) = note.tags.iter().position(|t| t == old) else {
+                continue;
+            };
+            match new {
+                Some(new) if !note.tags.iter().any(|t| t == new) => note.tags[at] = new.to_string(),
+                _ => {
+                    note.tags.remove(at);
+                }
+            }
+        }
+        self.rebuild_search_index();
+        self.refresh_notes(db);
+        self.show_tag_meta(reloaded.meta);
+        self.rebuild_available_filters();
+        if self.active_filter == TagFilter::Specific(old.to_string()) {
+            self.apply_filter(match new {
+                Some(new) => TagFilter::Specific(new.to_string()),
+                None => TagFilter::All,
+            });
+        }
+        let row = self.tag_list_state.selected().unwrap_or(0);
+        self.show_tag_counts(reloaded.counts);
+        let row = new
+            .and_then(|new| self.tag_counts.iter().position(|(tag, _)| tag == new))
+            .unwrap_or(row);
+        let last = self.tag_counts.len().checked_sub(1);
+        self.tag_list_state.select(last.map(|last| row.min(last)));
+    }
+
+    async fn load_tag_counts(&mut self, db: &mut Database) {
+        let counts = db.tag_counts().await;
+        self.show_tag_counts(counts);
+    }
+
+    fn show_tag_counts(&mut self, counts: Result<Vec<(String, i64)>, db::Error>) {
+        match counts {
+            Ok(counts) => self.tag_counts = counts,
+            Err(e) => self.set_status(format!("Error loading tags: {}", e)),
+        }
+    }
+
+    /// Lists every tag with its note count, to rename, merge or delete tags.
+    pub async fn open_tag_manager(&mut self, db: &mut Database) {
+        self.load_tag_counts(db).await;
+        self.merging_tag = None;
+        self.tag_list_state
+            .select((!self.tag_counts.is_empty()).then_some(0));
+        self.push_mode(InputMode::ManagingTags);
+        self.set_status(
+            "[Enter] filter, [r] rename, [m] merge into another tag, [d] delete, [Esc] close"
+                .to_string(),
+        );
+    }
+
+    pub fn move_tag_selection(&mut self, forward: bool) {
+        let len = self.tag_counts.len();
+        if len == 0 {
+            return;
+        }
+        let i = match self.tag_list_state.selected() {
+            Some(i) if forward => (i + 1) % len,
+            Some(i) => (i + len - 1) % len,
+            None => 0,
+        };
+        self.tag_list_state.select(Some(i));
+    }
+
+    /// The tag highlighted in the tag manager and its note count.
+    pub fn selected_managed_tag(&self) -> Option<(String, i64)> {
+        self.tag_list_state
+            .selected()
+            .and_then(|i| self.tag_counts.get(i))
+            .cloned()
+    }
+
+    pub fn filter_by_managed_tag(&mut self) {
+        if let Some((tag, _)) = self.selected_managed_tag() {
+            self.apply_filter(TagFilter::Specific(tag));
+            self.set_status(format!("Filter applied: {}", self.active_filter));
+            self.pop_mode();
+        }
+    }
+
+    pub fn rename_managed_tag(&mut self) {
+        if let Some((tag, _)) = self.selected_managed_tag() {
+            self.start_tag_rename(tag);
+        }
+    }
+
+    /// Picks up the highlighted tag; 'm' again on another tag merges it into that one.
+    pub fn merge_managed_tag(&mut self, db: &mut Database) {
+        let Some((tag, _)) = self.selected_managed_tag() else {
+            return;
+        };
+        let Some(from) = self.merging_tag.take() else {
+            self.set_status(format!(
+                "Merging #{}: highlight the tag to merge it into and press [m]; [Esc] cancels.",
+                tag
+            ));
+            self.merging_tag = Some(tag);
+            return;
+        };
+        if from == tag {
+            self.set_status("Merge cancelled.".to_string());
+            return;
+        }
+        self.spawn_call(
+            db,
+            {
+                let (from, tag) = (from.clone(), tag.clone());
+                move |mut db| async move {
+                    let changed = db.rename_tag(&from, &tag).await?;
+                    Ok((changed, TagReload::load(&mut db).await))
+                }
+            },
+            move |app, db, result| match result {
+                Ok((changed, reloaded)) => {
+                    app.reload_tags(db, &from, Some(&tag), reloaded);
+                    app.set_status(format!(
+                        "Merged #{} into #{} on {} note(s).",
+                        from, tag, changed
+                    ));
+                }
+                Err(e) => app.set_status(format!("Error merging tags: {}", e)),
+            },
+        );
+    }
+
+    /// Deletes the highlighted tag, asking first when `confirm.bulk` is set.
+    pub fn delete_managed_tag(&mut self, db: &mut Database, confirmed: bool) {
+        let Some((tag, count)) = self.selected_managed_tag() else {
+            return;
+        };
+        if self.confirm.bulk && !confirmed {
+            self.push_mode(InputMode::ConfirmingTagDelete);
+            self.set_status(format!("Remove #{} from {} note(s)? (y/n)", tag, count));
+            return;
+        }
+        self.spawn_call(
+            db,
+            {
+                let tag = tag.clone();
+                move |mut db| async move {
+                    let changed = db.delete_tag(&tag).await?;
+                    Ok((changed, TagReload::load(&mut db).await))
+                }
+            },
+            move |app, db, result| match result {
+                Ok((changed, reloaded)) => {
+                    app.reload_tags(db, &tag, None, reloaded);
+                    app.set_status(format!("Removed #{} from {} note(s).", tag, changed));
+                }
+                Err(e) => app.set_status(format!("Error deleting tag: {}", e)),
+            },
+        );
+    }
+
+    /// The line of `note` matching the current search, unless its content must stay hidden.
+    pub fn search_snippet(&self, note: &Note) -> Option<search::Snippet> {
+        if self.search_query.is_empty()
+            || self.search_titles_only
+            || note.encrypted
+            || self.screencast
+            || (note.tags.iter().any(|t| t == SECRET_TAG) && self.revealed_note != Some(note.id))
+        {
+            return None;
+        }
+        if !note.content_loaded {
+            return None;
+        }
+        let parsed = query::parse(&self.search_query);
+        search::snippet(&note.content, &parsed.snippet_text()?.to_lowercase())
+    }
+
+    /// Replaces the query with an entry from the search history.
+    pub fn recall_search(&mut self, older: bool) {
+        let entry = if older {
+            self.search_history.older()
+        } else {
+            self.search_history.newer()
+        };
+        if let Some(query) = entry.map(String::from) {
+            self.search_query = query;
+            self.search_changed();
+            if !self.notes.is_empty() {
+                self.list_state.select(Some(0));
+            }
+        }
+    }
+
+    /// Filters by title at once and runs the full search once typing pauses.
+    pub fn search_changed(&mut self) {
+        self.search_due = Some(Instant::now() + SEARCH_DEBOUNCE);
+        if self.search_titles_only {
+            // Nothing more to run, so this is the final list.
+            self.apply_search_filter();
+        } else {
+            self.apply_current_filter();
+        }
+    }
+
+    /// Switches search between titles only and titles, tags and content.
+    pub fn toggle_search_scope(&mut self) {
+        self.search_titles_only = !self.search_titles_only;
+        self.apply_search_filter();
+        self.set_status(if self.search_titles_only {
+            "Searching titles only. [Tab] to search content too.".to_string()
+        } else {
+            "Searching titles, tags and content. [Tab] for titles only.".to_string()
+        });
+    }
+
+    /// When the search waiting for a pause in typing should run.
+    pub fn search_due(&self) -> Option<Instant> {
+        self.search_due
+    }
+
+    /// Starts the search query once it is due, or whenever the query has not been
+    /// searched yet, e.g. after switching views. Runs between events;
+    /// `apply_search` filters by the result.
+    pub fn poll_search(&mut self, db: &Database) {
+        if self.search_query.is_empty() || self.search_titles_only {
+            self.search_due = None;
+            if let Some((_, task)) = self.search_fetch.take() {
+                task.abort();
+            }
+            return;
+        }
+        // Searched already, or being searched.
+        let searched = [
+            self.search_matches.as_ref().map(|(query, _)| query),
+            self.search_fetch.as_ref().map(|(query, _)| query),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|query| *query == self.search_query);
+        match self.search_due {
+            Some(due) if Instant::now() < due => {}
+            None if searched => {}
+            _ => self.start_search(db),
+        }
+    }
+
+    /// Searches for the current query in the background, replacing any search
+    /// still in flight.
+    fn start_search(&mut self, db: &Database) {
+        self.search_due = None;
+        if let Some((_, task)) = self.search_fetch.take() {
+            task.abort();
+        }
+        let query = self.search_query.clone();
+        let parsed = query::parse(&query);
+        let unloaded: Vec<i32> = self
+            .all_notes
+            .iter()
+            .filter(|n| n.source.is_none() && !n.content_loaded)
+            .map(|n| n.id)
+            .collect();
+        // Phrases are matched here, against every note's content.
+        match db.search_notes(&parsed.text, unloaded, parsed.needs_content()) {
+            Ok(search) => self.search_fetch = Some((query, tokio::spawn(search))),
+            Err(e) => self.search_failed(&e.to_string()),
+        }
+    }
+
+    fn apply_search(
+        &mut self,
+        db: &mut Database,
+        query: String,
+        searched: Result<SearchFetch, JoinError>,
+    ) {
+        self.search_fetch = None;
+        let searched = match searched {
+            Ok(result) => db.settle(result).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match searched {
+            Ok((ids, contents)) => {
+                self.store_contents(contents);
+                self.search_matches = Some((query, ids));
+                self.apply_search_filter();
+            }
+            Err(e) => self.search_failed(&e),
+        }
+    }
+
+    fn search_failed(&mut self, error: &str) {
+        self.set_status(format!("DB Error: search failed: {}", error));
+        // Try again later rather than on every pass.
+        self.search_due = Some(Instant::now() + Duration::from_secs(5));
+    }
+
+    /// Searches for the current query and filters the list by the result before
+    /// returning, for callers that act on it right away.
+    pub async fn run_search(&mut self, db: &mut Database) {
+        self.start_search(db);
+        if self.search_fetch.is_some() {
+            let (query, searched) = finished(&mut self.search_fetch).await;
+            self.apply_search(db, query, searched);
+        }
+    }
+
+    /// Filters by the search, keeping the selected note if it still matches.
+    fn apply_search_filter(&mut self) {
+        let selected = self.get_selected_note().map(|n| n.id);
+        self.apply_current_filter();
+        match selected.and_then(|id| self.notes.iter().position(|n| n.id == id)) {
+            Some(i) => self.list_state.select(Some(i)),
+            None => self
+                .list_state
+                .select((!self.notes.is_empty()).then_some(0)),
+        }
+        self.update_preview();
+    }
+
+    /// Today's journal (title contains today's date) or a note with a reminder due today.
+    pub fn is_todays_note(&self, note: &Note) -> bool {
+        let today = Local::now().date_naive();
+        note.title.contains(&today.format("%Y-%m-%d").to_string())
+            || note
+                .remind_at
+                .is_some_and(|at| at.with_timezone(&Local).date_naive() == today)
+    }
+
+    /// "Design v2" for "Design", "Design v4" for "Design v3", skipping titles already taken.
+    fn next_version_title(&self, title: &str) -> String {
+        let (base, version) = match title.rsplit_once(" v") {
+            Some((base, n)) => match n.parse::<u32>() {
+                Ok(n) => (base, n),
+                Err(_) => (title, 1),
+            },
+            None => (title, 1),
+        };
+        (version + 1..)
+            .map(|v| format!("{} v{}", base, v))
+            .find(|t| !self.all_notes.iter().any(|n| &n.title == t))
+            .unwrap_or_default()
+    }
+
+    /// Asks for the title of a new note starting out as a copy of the selected
+    /// template note.
+    pub async fn open_new_from_template(&mut self, db: &mut Database) {
+        match self.get_selected_note() {
+            None => {
+                self.set_status("No note selected.".to_string());
+                return;
+            }
+            Some(note) if note.note_type != NoteType::Template => {
+                self.set_status(
+                    "'n' makes a new note from a template note; ':type template' makes this one."
+                        .to_string(),
+                );
+                return;
+            }
+            Some(_) => {}
+        }
+        if !self.ensure_selected_content(db).await {
+            return;
+        }
+        let Some(note) = self.get_selected_note() else {
+            return;
+        };
+        let Some(content) = self.readable_content(note) else {
+            self.set_status("Unlock the template with 'U' first.".to_string());
+            return;
+        };
+        let tags = note
+            .tags
+            .iter()
+            .filter(|t| !NoteType::Template.default_tags().contains(&t.as_str()))
+            .cloned()
+            .collect();
+        let template = note.title.clone();
+        self.new_from_template = Some((content, tags));
+        self.push_mode(InputMode::EditingFilename);
+        self.filename_input.clear();
+        self.input_error = None;
+        self.title_suggestions.clear();
+        self.set_status(format!(
+            "Title of the new note from '{}'. Press [Enter] to confirm, [Esc] to cancel.",
+            template
+        ));
+    }
+
+    /// Archives the selected note and continues it as a new version linking back to it.
+    pub async fn supersede_selected(&mut self, db: &mut Database) {
+        if !self.ensure_selected_content(db).await {
+            return;
+        }
+        let Some(note) = self.get_selected_note().cloned() else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        let new_title = self.next_version_title(&note.title);
+        // Ciphertext can't take a prefix, so encrypted notes are copied as they are.
+        let content = if note.encrypted {
+            note.content.clone()
+        } else {
+            format!("Supersedes [[{}]]\n\n{}", note.title, note.content)
+        };
+        self.spawn_call(
+            db,
+            {
+                let (id, new_title) = (note.id, new_title.clone());
+                move |mut db| async move { db.supersede_note(id, &new_title, &content).await }
+            },
+            move |app, db, result| match result {
+                Ok(new_id) => {
+                    if let Some(unlocked) = app.unlocked_notes.get(&note.id).cloned() {
+                        app.unlocked_notes.insert(new_id, unlocked);
+                    }
+                    app.set_status(format!(
+                        "'{}' archived; continued as '{}'.",
+                        note.title, new_title
+                    ));
+                    app.refresh_notes_and_select(db, new_id);
+                }
+                Err(e) => app.set_status(format!("Error creating new version: {}", e)),
+            },
+        );
+    }
+
+    pub async fn open_size_report(&mut self, db: &mut Database) {
+        match db.size_report().await {
+            Ok(report) => {
+                self.size_list_state.select(if report.notes.is_empty() {
+                    None
+                } else {
+                    Some(0)
+                });
+                self.size_report = report;
+                self.push_mode(InputMode::SizeReport);
+            }
+            Err(e) => self.set_status(format!("Error building size report: {}", e)),
+        }
+    }
+
+    pub async fn open_history(&mut self, db: &mut Database) {
+        let Some(id) = self.get_selected_note().map(|n| n.id) else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        match db.get_revisions(id).await {
+            Ok(revisions) if revisions.is_empty() => {
+                self.set_status("No earlier versions of this note.".to_string());
+            }
+            Ok(revisions) => {
+                self.revision_note = Some(id);
+                self.revisions = revisions;
+                self.revision_list_state.select(Some(0));
+                self.push_mode(InputMode::History);
+            }
+            Err(e) => self.set_status(format!("Error loading history: {}", e)),
+        }
+    }
+
+    pub async fn open_attachments(&mut self, db: &mut Database) {
+        let Some(note) = self.get_selected_note() else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        if let Some(source) = &note.source {
+            self.set_status(format!(
+                "Attachments of notes from '{}' are not available.",
+                source
+            ));
+            return;
+        }
+        self.attachment_note = Some(note.id);
+        if self.reload_attachments(db).await && self.attachments.is_empty() {
+            self.set_status("No attachments on this note.".to_string());
+        } else if !self.attachments.is_empty() {
+            self.attachment_list_state.select(Some(0));
+            self.push_mode(InputMode::Attachments);
+        }
+    }
+
+    /// Reads the attachments of `attachment_note` again. Returns `false`, with a
+    /// status, if that failed.
+    async fn reload_attachments(&mut self, db: &mut Database) -> bool {
+        let Some(id) = self.attachment_note else {
+            return false;
+        };
+        let attachments = db.get_attachments(id).await;
+        self.show_attachments(attachments)
+    }
+
+    fn show_attachments(&mut self, attachments: Result<Vec<Attachment>, db::Error>) -> bool {
+        self.attachment_delete_armed = false;
+        match attachments {
+            Ok(attachments) => {
+                self.attachments = attachments;
+                let last = self.attachments.len().checked_sub(1);
+                let selected = self.attachment_list_state.selected();
+                self.attachment_list_state
+                    .select(selected.zip(last).map(|(i, last)| i.min(last)));
+                true
+            }
+            Err(e) => {
+                self.set_status(format!("Error loading attachments: {}", e));
+                false
+            }
+        }
+    }
+
+    pub fn selected_attachment(&self) -> Option<&Attachment> {
+        self.attachment_list_state
+            .selected()
+            .and_then(|i| self.attachments.get(i))
+    }
+
+    pub fn move_attachment_selection(&mut self, down: bool) {
+        self.attachment_delete_armed = false;
+        let last = self.attachments.len().saturating_sub(1);
+        let i = self.attachment_list_state.selected().map_or(0, |i| {
+            if down {
+                (i + 1).min(last)
+            } else {
+                i.saturating_sub(1)
+            }
+        });
+        self.attachment_list_state.select(Some(i));
+    }
+
+    /// Writes the highlighted attachment to a temporary file and hands it to
+    /// the desktop's default application.
+    pub async fn open_selected_attachment(&mut self, db: &mut Database) {
+        let Some((id, name)) = self.selected_attachment().map(|a| (a.id, a.name.clone())) else {
+            return;
+        };
+        let data = match db.get_attachment_data(id).await {
+            Ok(data) => data,
+            Err(e) => {
+                self.set_status(format!("Error reading attachment: {}", e));
+                return;
+            }
+        };
+        let path =
+            std::env::temp_dir().join(format!("pgnotes_{}_{}", id, name.replace(['/', '\\'], "_")));
+        match fs::write(&path, data).and_then(|()| editor::open_with_system(&path)) {
+            Ok(()) => self.set_status(format!("Opened '{}'.", name)),
+            Err(e) => self.set_status(format!("Could not open '{}': {}", name, e)),
+        }
+    }
+
+    pub fn start_attachment_input(&mut self, action: AttachmentAction) {
+        let Some(name) = self.selected_attachment().map(|a| a.name.clone()) else {
+            return;
+        };
+        match action {
+            AttachmentAction::Rename => self.attachment_input.set_selected(name),
+            AttachmentAction::SaveAs => self
+                .attachment_input
+                .set(self.export_dir.join(name).display().to_string()),
+        }
+        self.attachment_action = action;
+        self.input_error = None;
+        self.push_mode(InputMode::NamingAttachment);
+    }
+
+    pub async fn finish_attachment_input(&mut self, db: &mut Database) {
+        let Some((id, name)) = self.selected_attachment().map(|a| (a.id, a.name.clone())) else {
+            self.pop_mode();
+            return;
+        };
+        let value = self.attachment_input.value().trim().to_string();
+        if value.is_empty() {
+            self.input_error = Some("Cannot be empty.".to_string());
+            return;
+        }
+        match self.attachment_action {
+            AttachmentAction::Rename => {
+                let Some(note) = self.attachment_note else {
+                    return;
+                };
+                self.spawn_call(
+                    db,
+                    {
+                        let value = value.clone();
+                        move |mut db| async move {
+                            db.rename_attachment(id, &value).await?;
+                            Ok(db.get_attachments(note).await)
+                        }
+                    },
+                    move |app, _, result| match result {
+                        Ok(attachments) => {
+                            app.input_error = None;
+                            app.pop_mode_from(InputMode::NamingAttachment);
+                            app.show_attachments(attachments);
+                            app.set_status(format!("Renamed '{}' to '{}'.", name, value));
+                        }
+                        Err(e) => app.input_error = Some(format!("Error renaming: {}", e)),
+                    },
+                );
+            }
+            AttachmentAction::SaveAs => {
+                let path = PathBuf::from(shellexpand::tilde(&value).into_owned());
+                if path.exists() {
+                    self.input_error = Some(format!("{} already exists.", path.display()));
+                    return;
+                }
+                let written = match db.get_attachment_data(id).await {
+                    Ok(data) => fs::write(&path, data).map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                };
+                match written {
+                    Ok(()) => {
+                        self.input_error = None;
+                        self.pop_mode();
+                        self.set_status(format!("Saved '{}' to {}.", name, path.display()));
+                    }
+                    Err(e) => self.input_error = Some(format!("Error saving: {}", e)),
+                }
+            }
+        }
+    }
+
+    /// Deletes the highlighted attachment on the second 'd' in a row.
+    pub fn delete_selected_attachment(&mut self, db: &mut Database) {
+        let Some((id, name)) = self.selected_attachment().map(|a| (a.id, a.name.clone())) else {
+            return;
+        };
+        if !self.attachment_delete_armed {
+            self.attachment_delete_armed = true;
+            self.set_status(format!("Press 'd' again to delete '{}'.", name));
+            return;
+        }
+        let Some(note) = self.attachment_note else {
+            return;
+        };
+        self.spawn_call(
+            db,
+            move |mut db| async move {
+                db.delete_attachment(id).await?;
+                Ok(db.get_attachments(note).await)
+            },
+            move |app, _, result| match result {
+                Ok(attachments) => {
+                    app.set_status(format!("Attachment '{}' deleted.", name));
+                    app.show_attachments(attachments);
+                    if app.attachments.is_empty() {
+                        app.pop_mode_from(InputMode::Attachments);
+                    }
+                }
+                Err(e) => app.set_status(format!("Error deleting attachment: {}", e)),
+            },
+        );
+    }
+
+    /// Readable text of the highlighted revision, decrypted with the note's
+    /// session passphrase when it was stored encrypted.
+    pub fn revision_preview(&self) -> String {
+        let Some(revision) = self
+            .revision_list_state
+            .selected()
+            .and_then(|i| self.revisions.get(i))
+        else {
+            return String::new();
+        };
+        let content = if !revision.encrypted {
+            revision.content.clone()
+        } else {
+            match self
+                .revision_note
+                .and_then(|id| self.unlocked_notes.get(&id))
+                .and_then(|(_, passphrase)| crypto::decrypt(&revision.content, passphrase).ok())
+            {
+                Some(plaintext) => plaintext,
+                None => "🔒 Encrypted version. Unlock the note with 'U' to read it.".to_string(),
+            }
+        };
+        let secret = self
+            .revision_note
+            .and_then(|id| self.all_notes.iter().find(|n| n.id == id))
+            .is_some_and(|n| n.tags.iter().any(|t| t == SECRET_TAG))
+            && self.revealed_note != self.revision_note;
+        if self.screencast {
+            lorem::text(&content)
+        } else if secret {
+            mask_text(&content)
+        } else {
+            content
+        }
+    }
+
+    pub fn restore_selected_revision(&mut self, db: &mut Database) {
+        let Some(id) = self.revision_note else {
+            return;
+        };
+        let Some(revision) = self
+            .revision_list_state
+            .selected()
+            .and_then(|i| self.revisions.get(i))
+        else {
+            return;
+        };
+        let (revision_id, saved_at) = (revision.id, revision.saved_at);
+        self.spawn_call(
+            db,
+            move |mut db| async move { db.restore_revision(id, revision_id).await },
+            move |app, db, result| match result {
+                Ok(_) => {
+                    // The restored content may be encrypted differently; unlock again if needed.
+                    app.unlocked_notes.remove(&id);
+                    app.pop_mode_from(InputMode::History);
+                    app.set_status(format!(
+                        "Restored version from {}. The replaced content is in the history.",
+                        saved_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                    ));
+                    app.refresh_notes_and_select(db, id);
+                }
+                Err(e) => app.set_status(format!("Error restoring version: {}", e)),
+            },
+        );
+    }
+
+    /// Fetches every source again; unreachable ones keep their last notes.
+    /// Call `refresh_notes` afterwards to merge the result into the list.
+    pub async fn reload_sources(&mut self) {
+        let mut failures = Vec::new();
+        for source in &mut self.sources {
+            if let Err(e) = source.reload().await {
+                failures.push(format!("Source '{}' unavailable: {}", source.name, e));
+            }
+        }
+        sources::assign_ids(&mut self.sources);
+        for failure in failures {
+            self.set_status(failure);
+        }
+    }
+
+    /// The source of the selected note, if it did not come from the primary database.
+    pub fn selected_source(&self) -> Option<&str> {
+        self.get_selected_note().and_then(|n| n.source.as_deref())
+    }
+
+    pub fn toggle_sort_mode(&mut self) {
+        self.set_sort_mode(match self.sort_mode {
+            SortMode::Title => SortMode::Modified,
+            _ => SortMode::Title,
+        });
+    }
+
+    pub fn open_sort_selector(&mut self) {
+        let current = SortMode::ALL.iter().position(|&m| m == self.sort_mode);
+        self.sort_mode_state.select(current);
+        self.push_mode(InputMode::SelectingSortMode);
+    }
+
+    /// Sorts by `sort_mode` from now on, in later runs too.
+    pub fn set_sort_mode(&mut self, sort_mode: SortMode) {
+        let selected_id = self.get_selected_note().map(|n| n.id);
+        self.sort_mode = sort_mode;
+        sort_mode.save();
+        self.apply_current_filter();
+        if let Some(id) = selected_id {
+            self.select_note_by_id(id);
+        }
+        self.set_status(format!("Sorted by {}.", self.sort_mode.label()));
+    }
+
+    pub fn open_copy_selector(&mut self) {
+        if self.get_selected_note().is_none() {
+            self.set_status("No note selected.".to_string());
+            return;
+        }
+        self.copy_format_state.select(Some(0));
+        self.push_mode(InputMode::SelectingCopyFormat);
+    }
+
+    /// Copies the selected note to the clipboard in `format`. Encrypted notes
+    /// must be unlocked first; their plaintext is what gets copied.
+    pub async fn copy_selected_note(&mut self, db: &mut Database, format: CopyFormat) {
+        if !self.ensure_selected_content(db).await {
+            return;
+        }
+        let Some(note) = self.get_selected_note() else {
+            return;
+        };
+        let content = if !note.encrypted {
+            note.content.clone()
+        } else if let Some((plaintext, _)) = self.unlocked_notes.get(&note.id) {
+            plaintext.clone()
+        } else {
+            self.set_status("Unlock the note with 'U' before copying it.".to_string());
+            return;
+        };
+        let markdown = matches!(
+            preview::filetype(&note.title, &content, note.note_type).as_deref(),
+            None | Some("md" | "markdown")
+        );
+        let title = self.display_title(&note.title);
+        let text = clipboard::render(&content, markdown, format);
+        match clipboard::copy(&text, format, !self.safe_mode) {
+            Ok(via) => self.set_status(format!(
+                "Copied '{}' as {} (via {}).",
+                title,
+                format.label().to_lowercase(),
+                via
+            )),
+            Err(e) => self.set_status(format!("Error copying to the clipboard: {}", e)),
+        }
+    }
+
+    pub async fn load_presence(&mut self, db: &mut Database) {
+        match db.get_presence().await {
+            Ok(presence) => self.presence = presence,
+            Err(e) => self.set_status(format!("Error checking who is editing: {}", e)),
+        }
+    }
+
+    /// `load_presence` off the event loop.
+    pub fn refresh_presence(&mut self, db: &mut Database) {
+        self.spawn_call(
+            db,
+            |mut db| async move { db.get_presence().await },
+            |app, _, result| match result {
+                Ok(presence) => app.presence = presence,
+                Err(e) => app.set_status(format!("Error checking who is editing: {}", e)),
+            },
+        );
+    }
+
+    /// Writes the inline editor's unsaved text to the drafts table every
+    /// `DRAFT_INTERVAL`, or right away with `now` (e.g. when the terminal loses focus).
+    pub fn autosave_draft(&mut self, db: &mut Database, now: bool) {
+        let Some(edit) = self.inline_edit.as_mut() else {
+            return;
+        };
+        let due = edit
+            .draft_saved_at
+            .is_none_or(|at| at.elapsed() >= DRAFT_INTERVAL);
+        if !edit.draft_dirty || !(now || due) {
+            return;
+        }
+        let text = edit.area.text();
+        let draft = match &edit.session.passphrase {
+            Some(passphrase) => match crypto::encrypt(&text, passphrase) {
+                Ok(ciphertext) => ciphertext,
+                Err(_) => return,
+            },
+            None => text.clone(),
+        };
+        // Try again at the next interval rather than on every tick while offline.
+        edit.draft_saved_at = Some(Instant::now());
+        let id = edit.session.id;
+        self.spawn_call(
+            db,
+            move |mut db| async move { db.save_draft(id, &draft).await },
+            move |app, _, result| {
+                // Typing since then is still unsaved.
+                if let Some(edit) = app.inline_edit.as_mut()
+                    && result.is_ok()
+                    && edit.session.id == id
+                    && edit.area.text() == text
+                {
+                    edit.draft_dirty = false;
+                }
+            },
+        );
+    }
+
+    /// Reloads presence when another instance announced a change, and reloads
+    /// and announces watched notes other instances changed.
+    pub fn poll_notifications(&mut self, db: &mut Database) {
+        let notified = db.take_notifications();
+        if notified.presence {
+            self.refresh_presence(db);
+        }
+        let changed: Vec<i32> = notified
+            .changed_notes
+            .into_iter()
+            .filter(|id| self.watched.contains(id))
+            .collect();
+        if changed.is_empty() {
+            return;
+        }
+        let message = match &changed[..] {
+            [id] => match self.all_notes.iter().find(|n| n.id == *id) {
+                Some(note) => format!(
+                    "Watched note '{}' was changed elsewhere.",
+                    self.display_title(&note.title)
+                ),
+                None => "A watched note was changed elsewhere.".to_string(),
+            },
+            _ => format!("{} watched notes were changed elsewhere.", changed.len()),
+        };
+        self.changed_elsewhere.extend(changed);
+        self.show_toast(message);
+        self.refresh_notes(db);
+    }
+
+    /// Starts or stops watching the selected note for changes made elsewhere.
+    pub fn toggle_watch(&mut self) {
+        let Some(note) = self.get_selected_note() else {
+            self.set_status("No note selected.".to_string());
+            return;
+        };
+        if let Some(source) = &note.source {
+            self.set_status(format!("Notes from '{}' can't be watched.", source));
+            return;
+        }
+        let (id, title) = (note.id, self.display_title(&note.title));
+        if self.watched.remove(&id) {
+            self.changed_elsewhere.remove(&id);
+            self.set_status(format!("Stopped watching '{}'.", title));
+        } else {
+            self.watched.insert(id);
+            self.set_status(format!(
+                "Watching '{}': changes from other instances will be announced.",
+                title
+            ));
+        }
+        save_watched(&self.watched);
+    }
+
+    /// Shows `message` as a toast, and in the status line and its history.
+    pub fn show_toast(&mut self, message: String) {
+        self.toast = Some((message.clone(), Instant::now() + TOAST_TIME));
+        self.set_status(message);
+    }
+
+    /// The toast still due to be shown, if any.
+    pub fn current_toast(&self) -> Option<&str> {
+        self.toast
+            .as_ref()
+            .filter(|(_, until)| Instant::now() < *until)
+            .map(|(message, _)| message.as_str())
+    }
+
+    /// The earliest other editor of note `id`, if any.
+    pub fn editing_elsewhere(&self, id: i32) -> Option<&Presence> {
+        self.presence.iter().find(|p| p.note_id == id)
+    }
+
+    /// Question to ask before opening the selected note in the editor while
+    /// another instance has it open.
+    pub fn concurrent_edit_warning(&self) -> Option<String> {
+        let note = self.get_selected_note()?;
+        let other = self.editing_elsewhere(note.id)?;
+        Some(format!(
+            "'{}' is open in {}'s editor since {}. Edit anyway? [y/n]",
+            self.display_title(&note.title),
+            other.client,
+            other.since.with_timezone(&Local).format("%H:%M")
+        ))
+    }
+}
+
+/// The next arrival of the note list load in `fetch`; see `fetched_notes`.
+async fn next_notes(
+    fetch: &mut Option<JoinHandle<NotesFetch>>,
+    first_page: &mut Option<oneshot::Receiver<Vec<Note>>>,
+) -> NotesArrival {
+    let page = match (fetch.as_mut(), first_page.as_mut()) {
+        (None, _) => return std::future::pending().await,
+        (Some(task), None) => return NotesArrival::All(task.await),
+        (Some(task), Some(page)) => tokio::select! {
+            biased;
+            fetched = task => {
+                *first_page = None;
+                return NotesArrival::All(fetched);
+            }
+            page = page => page,
+        },
+    };
+    *first_page = None;
+    match page {
+        Ok(page) => NotesArrival::FirstPage(page),
+        // The load failed before its first page; its error is still to come.
+        Err(_) => Box::pin(next_notes(fetch, first_page)).await,
+    }
+}
+
+/// The next call of `calls` to be done; never, if none is running.
+async fn next_call(calls: &mut Option<CallQueue>) -> CallDone {
+    let Some(queue) = calls.as_mut().filter(|queue| queue.pending > 0) else {
+        return std::future::pending().await;
+    };
+    match queue.done.recv().await {
+        Some(done) => {
+            queue.pending -= 1;
+            done
+        }
+        // The task running them is gone, so nothing more will come back.
+        None => std::future::pending().await,
+    }
+}
+
+/// The result of the task in `fetch`, and what it was started for. Never, if
+/// none is running.
+async fn finished<K: Clone, T>(
+    fetch: &mut Option<(K, JoinHandle<T>)>,
+) -> (K, Result<T, JoinError>) {
+    match fetch {
+        Some((key, task)) => {
+            let result = task.await;
+            (key.clone(), result)
+        }
+        None => std::future::pending().await,
+    }
+}
+
+fn connection_label(db_url: &str) -> String {
+    let Ok(parsed) = db::parse_url(db_url) else {
+        return "invalid database_url".to_string();
+    };
+    let host = match parsed.get_hosts().first() {
+        Some(Host::Tcp(name)) => name.clone(),
+        Some(Host::Unix(path)) => path.display().to_string(),
+        None => "localhost".to_string(),
+    };
+    let mut label = format!("{}@{}", parsed.get_user().unwrap_or("?"), host);
+    if let Some(port) = parsed.get_ports().first().filter(|&&p| p != 5432) {
+        label.push_str(&format!(":{}", port));
+    }
+    if let Some(dbname) = parsed.get_dbname() {
+        label.push_str(&format!("/{}", dbname));
+    }
+    label
+}
+
+/// Hides every visible character but keeps line lengths, so the shape of the note stays readable.
+fn mask_text(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_whitespace() { c } else { '•' })
+        .collect()
+}
+
+/// What a note's title and tags are searched as: lowercased, one per line.
+fn search_key(note: &Note) -> String {
+    let mut key = note.title.to_lowercase();
+    for tag in &note.tags {
+        key.push('\n');
+        key.push_str(&tag.to_lowercase());
+    }
+    key
+}
+
+/// Watched note ids saved by the last run.
+fn load_watched() -> HashSet<i32> {
+    fs::read_to_string(data_dir::state_file(WATCHED_FILE_NAME))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect()
+}
+
+fn save_watched(watched: &HashSet<i32>) {
+    let path = data_dir::state_file(WATCHED_FILE_NAME);
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let mut ids: Vec<i32> = watched.iter().copied().collect();
+    ids.sort_unstable();
+    let lines: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    let _ = fs::write(path, lines.join("\n"));
+}
+
+/// `mine` next to the note as it is in the database now.
+async fn load_edit_conflict(db: &mut Database, session: EditSession, mine: String) -> EditConflict {
+    // Without the other version, keeping mine still works: it overwrites, or
+    // becomes a pending save while offline.
+    let (stored, encrypted, theirs_at) = match db.get_note_version(session.id).await {
+        Ok(Some(version)) => version,
+        Ok(None) => ("(the note was deleted)".to_string(), false, None),
+        Err(e) => (format!("(could not load it: {})", e), false, None),
+    };
+    let theirs = match (&session.passphrase, encrypted) {
+        (Some(passphrase), true) => crypto::decrypt(&stored, passphrase)
+            .unwrap_or_else(|_| "(encrypted with another passphrase)".to_string()),
+        _ => stored,
+    };
+    EditConflict {
+        session,
+        mine,
+        theirs,
+        theirs_at,
+        scroll: 0,
+    }
+}
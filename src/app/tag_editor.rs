@@ -0,0 +1,105 @@
+//! The tag popup: a note's tags as chips plus a field that adds one tag at a time,
+//! so stray whitespace and duplicates never reach the database.
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use super::input::TextInput;
+
+#[derive(Debug, Default, Clone)]
+pub struct TagEditor {
+    pub tags: Vec<String>,
+    pub input: TextInput,
+    /// Chip marked for removal; the next Backspace or Delete removes it.
+    pub focused: Option<usize>,
+    /// Every tag in use, for completion.
+    known: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// What a key press asks of the caller.
+pub enum TagEditorAction {
+    None,
+    Save,
+    Cancel,
+}
+
+impl TagEditor {
+    pub fn new(tags: Vec<String>, known: Vec<String>) -> Self {
+        Self {
+            tags,
+            known,
+            ..Self::default()
+        }
+    }
+
+    /// The known tag the field would complete to with Tab.
+    pub fn completion(&self) -> Option<&str> {
+        let typed = self.input.value().trim();
+        if typed.is_empty() {
+            return None;
+        }
+        self.known
+            .iter()
+            .filter(|t| !self.tags.contains(t))
+            .find(|t| t.starts_with(typed) && t.as_str() != typed)
+            .map(String::as_str)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> TagEditorAction {
+        let empty = self.input.value().is_empty();
+        match key.code {
+            KeyCode::Esc => return TagEditorAction::Cancel,
+            KeyCode::Enter if empty => return TagEditorAction::Save,
+            KeyCode::Enter => self.add_typed(),
+            KeyCode::Tab => {
+                if let Some(tag) = self.completion() {
+                    let tag = tag.to_string();
+                    self.input.set(tag);
+                }
+            }
+            KeyCode::Backspace | KeyCode::Delete if empty => match self.focused.take() {
+                Some(i) => {
+                    self.tags.remove(i);
+                }
+                None if key.code == KeyCode::Backspace => {
+                    self.focused = self.tags.len().checked_sub(1);
+                }
+                None => {}
+            },
+            KeyCode::Left if empty => {
+                self.focused = match self.focused {
+                    Some(i) => Some(i.saturating_sub(1)),
+                    None => self.tags.len().checked_sub(1),
+                };
+            }
+            KeyCode::Right if empty => {
+                self.focused = self.focused.map(|i| i + 1).filter(|&i| i < self.tags.len());
+            }
+            _ => {
+                self.focused = None;
+                self.error = None;
+                self.input.handle_key(key);
+            }
+        }
+        TagEditorAction::None
+    }
+
+    /// Adds what was typed. Commas separate several tags; spaces inside a tag
+    /// become dashes.
+    fn add_typed(&mut self) {
+        let typed = self.input.value().to_string();
+        self.error = None;
+        for tag in typed.split(',') {
+            let tag = tag.split_whitespace().collect::<Vec<_>>().join("-");
+            if tag.is_empty() {
+                continue;
+            }
+            if self.tags.contains(&tag) {
+                self.error = Some(format!("#{} is already on the note.", tag));
+                continue;
+            }
+            self.tags.push(tag);
+        }
+        self.input.clear();
+    }
+}
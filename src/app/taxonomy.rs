@@ -0,0 +1,86 @@
+//! Tag taxonomy files: every tag with its usage count, color and parent, as TOML,
+//! so a tagging scheme can be carried between databases.
+
+use crate::app::state::Note;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::{fs, io, path::Path, path::PathBuf};
+
+pub const TAXONOMY_FILE_NAME: &str = "pgnotes-tags.toml";
+
+/// Metadata kept for a tag in the `tag_meta` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagMeta {
+    pub name: String,
+    pub color: Option<String>,
+    pub parent: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TaxonomyFile {
+    tags: Vec<TaxonomyEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TaxonomyEntry {
+    name: String,
+    /// Informational only; ignored on import.
+    #[serde(default)]
+    count: usize,
+    color: Option<String>,
+    parent: Option<String>,
+}
+
+/// Parent implied by a path-like tag name, e.g. `work` for `work/ops`.
+fn implied_parent(name: &str) -> Option<String> {
+    name.rsplit_once('/').map(|(parent, _)| parent.to_string())
+}
+
+/// Writes all tags used by `notes` or described in `meta` to `dir` and returns the file path.
+pub fn export_taxonomy(notes: &[Note], meta: &[TagMeta], dir: &Path) -> io::Result<PathBuf> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for tag in notes.iter().flat_map(|n| &n.tags) {
+        *counts.entry(tag.as_str()).or_default() += 1;
+    }
+    for m in meta {
+        counts.entry(m.name.as_str()).or_default();
+    }
+    let meta: HashMap<&str, &TagMeta> = meta.iter().map(|m| (m.name.as_str(), m)).collect();
+
+    let tags = counts
+        .into_iter()
+        .map(|(name, count)| {
+            let m = meta.get(name);
+            TaxonomyEntry {
+                name: name.to_string(),
+                count,
+                color: m.and_then(|m| m.color.clone()),
+                parent: m
+                    .and_then(|m| m.parent.clone())
+                    .or_else(|| implied_parent(name)),
+            }
+        })
+        .collect();
+
+    let document = toml::to_string(&TaxonomyFile { tags }).map_err(io::Error::other)?;
+    fs::create_dir_all(dir)?;
+    let path = dir.join(TAXONOMY_FILE_NAME);
+    fs::write(&path, document)?;
+    Ok(path)
+}
+
+/// Reads a taxonomy file back into tag metadata.
+pub fn import_taxonomy(path: &Path) -> io::Result<Vec<TagMeta>> {
+    let file: TaxonomyFile =
+        toml::from_str(&fs::read_to_string(path)?).map_err(io::Error::other)?;
+    Ok(file
+        .tags
+        .into_iter()
+        .filter(|t| !t.name.trim().is_empty())
+        .map(|t| TagMeta {
+            parent: t.parent.or_else(|| implied_parent(&t.name)),
+            name: t.name,
+            color: t.color,
+        })
+        .collect())
+}
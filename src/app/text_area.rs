@@ -0,0 +1,167 @@
+//! Multi-line text buffer of the inline editor, the counterpart of `TextInput`.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Debug, Clone)]
+pub struct TextArea {
+    /// Never empty; the text is these joined with `\n`.
+    lines: Vec<String>,
+    /// Cursor line, and column in chars `0..=len` of that line.
+    row: usize,
+    col: usize,
+    /// First line and display column shown, kept around the cursor by `scroll_to_cursor`.
+    scroll: (usize, usize),
+    /// Rows shown at the last draw; how far PageUp/PageDown move.
+    page: usize,
+}
+
+impl TextArea {
+    /// Holds `text` with the cursor at its start.
+    pub fn new(text: &str) -> Self {
+        Self {
+            lines: text.split('\n').map(str::to_string).collect(),
+            row: 0,
+            col: 0,
+            scroll: (0, 0),
+            page: 1,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn scroll(&self) -> (usize, usize) {
+        self.scroll
+    }
+
+    /// Scrolls so the cursor is inside a `width` by `height` view and returns
+    /// its position within it.
+    pub fn scroll_to_cursor(&mut self, width: usize, height: usize) -> (usize, usize) {
+        self.page = height.max(1);
+        let x = self.lines[self.row][..self.byte_index(self.col)].width();
+        let (top, left) = &mut self.scroll;
+        if self.row < *top {
+            *top = self.row;
+        } else if self.row >= *top + self.page {
+            *top = self.row + 1 - self.page;
+        }
+        if x < *left {
+            *left = x;
+        } else if x >= *left + width.max(1) {
+            *left = x + 1 - width.max(1);
+        }
+        (x - *left, self.row - *top)
+    }
+
+    /// Inserts pasted text at the cursor, line breaks included.
+    pub fn insert(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n");
+        let at = self.byte_index(self.col);
+        let rest = self.lines[self.row].split_off(at);
+        let mut pasted = text.split('\n');
+        self.lines[self.row].push_str(pasted.next().unwrap_or_default());
+        for line in pasted {
+            self.row += 1;
+            self.lines.insert(self.row, line.to_string());
+        }
+        self.col = self.lines[self.row].chars().count();
+        self.lines[self.row].push_str(&rest);
+    }
+
+    /// Applies an editing or cursor key. Returns `true` if the text changed.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char(c) if !ctrl => self.insert(&c.to_string()),
+            KeyCode::Tab => self.insert("    "),
+            KeyCode::Enter => {
+                // Keep the indentation of the line being split.
+                let line = &self.lines[self.row];
+                let indent: String = line
+                    .chars()
+                    .take(self.col)
+                    .take_while(|c| c.is_whitespace())
+                    .collect();
+                self.insert(&format!("\n{}", indent));
+            }
+            KeyCode::Backspace if self.col > 0 => {
+                self.col -= 1;
+                let at = self.byte_index(self.col);
+                self.lines[self.row].remove(at);
+            }
+            KeyCode::Backspace if self.row > 0 => {
+                let line = self.lines.remove(self.row);
+                self.row -= 1;
+                self.col = self.lines[self.row].chars().count();
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Delete if self.col < self.line_len() => {
+                let at = self.byte_index(self.col);
+                self.lines[self.row].remove(at);
+            }
+            KeyCode::Delete if self.row + 1 < self.lines.len() => {
+                let next = self.lines.remove(self.row + 1);
+                self.lines[self.row].push_str(&next);
+            }
+            KeyCode::Char('k') if ctrl => {
+                let at = self.byte_index(self.col);
+                if at == self.lines[self.row].len() {
+                    return false;
+                }
+                self.lines[self.row].truncate(at);
+            }
+            _ => {
+                self.move_cursor(key.code, ctrl);
+                return false;
+            }
+        }
+        true
+    }
+
+    fn move_cursor(&mut self, code: KeyCode, ctrl: bool) {
+        match code {
+            KeyCode::Left if self.col > 0 => self.col -= 1,
+            KeyCode::Left if self.row > 0 => {
+                self.row -= 1;
+                self.col = self.line_len();
+            }
+            KeyCode::Right if self.col < self.line_len() => self.col += 1,
+            KeyCode::Right if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.col = 0;
+            }
+            KeyCode::Up => self.move_to_row(self.row.saturating_sub(1)),
+            KeyCode::Down => self.move_to_row(self.row + 1),
+            KeyCode::PageUp => self.move_to_row(self.row.saturating_sub(self.page)),
+            KeyCode::PageDown => self.move_to_row(self.row + self.page),
+            KeyCode::Home if ctrl => (self.row, self.col) = (0, 0),
+            KeyCode::End if ctrl => {
+                self.row = self.lines.len() - 1;
+                self.col = self.line_len();
+            }
+            KeyCode::Home => self.col = 0,
+            KeyCode::End => self.col = self.line_len(),
+            _ => {}
+        }
+    }
+
+    fn move_to_row(&mut self, row: usize) {
+        self.row = row.min(self.lines.len() - 1);
+        self.col = self.col.min(self.line_len());
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines[self.row].chars().count()
+    }
+
+    fn byte_index(&self, col: usize) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices().nth(col).map_or(line.len(), |(i, _)| i)
+    }
+}
@@ -0,0 +1,272 @@
+use crate::app::config::{ColorScheme, Config, ThemeColors, parse_color};
+use ratatui::style::{Color, Modifier, Style};
+
+/// Styles for every colored element in the UI, resolved once from config.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub list_highlight: Style,
+    pub popup_highlight: Style,
+    pub new_note_popup: Style,
+    pub rename_popup: Style,
+    pub tags_popup: Style,
+    pub search_popup: Style,
+    pub danger_popup: Style,
+    pub list_popup: Style,
+    pub error: Style,
+    pub dim: Style,
+    pub border: Style,
+    pub focused_border: Style,
+    /// Matched text inside search snippets.
+    pub search_match: Style,
+    /// Today's journal and notes due today in the list.
+    pub today: Style,
+    /// Transient notices over the list, e.g. a watched note changed elsewhere.
+    pub toast: Style,
+    /// Tags in the preview header; colored tags keep the modifiers but take
+    /// their taxonomy color.
+    pub tag_chip: Style,
+    pub diff_added: Style,
+    pub diff_removed: Style,
+    /// Preview: Markdown headings and table headers.
+    pub heading: Style,
+    /// Preview: inline code and code blocks without a known language.
+    pub code: Style,
+    /// Preview: highlighted source code, and Markdown list markers.
+    pub keyword: Style,
+    pub string: Style,
+    pub comment: Style,
+    /// Whether per-tag colors from the tag taxonomy are shown.
+    pub tag_colors: bool,
+}
+
+impl Theme {
+    pub fn from_config(config: &Config) -> Self {
+        // https://no-color.org: any non-empty value disables color output.
+        let no_color = std::env::var("NO_COLOR").is_ok_and(|v| !v.is_empty());
+
+        let theme = if no_color {
+            Self::plain()
+        } else {
+            match config.color_scheme {
+                ColorScheme::Default => Self::default(),
+                ColorScheme::HighContrast => Self::high_contrast(),
+                ColorScheme::Plain => Self::plain(),
+            }
+        };
+
+        let theme = if config.background_highlights {
+            theme
+        } else {
+            theme.without_backgrounds()
+        };
+
+        if no_color {
+            theme
+        } else {
+            theme.with_colors(&config.theme)
+        }
+    }
+
+    /// Applies the `[theme]` overrides; entries that aren't colors are skipped
+    /// (and reported at startup).
+    fn with_colors(self, colors: &ThemeColors) -> Self {
+        let color = |value: &Option<String>| value.as_deref().and_then(parse_color);
+        let bg = |style: Style, value: &Option<String>| match color(value) {
+            Some(c) => style.bg(c),
+            None => style,
+        };
+        let fg = |style: Style, value: &Option<String>| match color(value) {
+            Some(c) => style.fg(c),
+            None => style,
+        };
+        let popup = |style: Style| fg(bg(style, &colors.popup_background), &colors.popup_text);
+        Self {
+            list_highlight: bg(self.list_highlight, &colors.list_highlight),
+            popup_highlight: fg(self.popup_highlight, &colors.popup_highlight),
+            new_note_popup: popup(self.new_note_popup),
+            rename_popup: popup(self.rename_popup),
+            tags_popup: popup(self.tags_popup),
+            search_popup: popup(self.search_popup),
+            list_popup: popup(self.list_popup),
+            danger_popup: bg(self.danger_popup, &colors.danger_background),
+            border: fg(self.border, &colors.border),
+            focused_border: fg(self.focused_border, &colors.focused_border),
+            error: fg(self.error, &colors.error),
+            search_match: fg(self.search_match, &colors.search_match),
+            today: fg(self.today, &colors.today),
+            tag_chip: bg(self.tag_chip, &colors.tag_chip),
+            diff_added: fg(self.diff_added, &colors.diff_added),
+            diff_removed: fg(self.diff_removed, &colors.diff_removed),
+            heading: fg(self.heading, &colors.heading),
+            code: fg(self.code, &colors.code),
+            keyword: fg(self.keyword, &colors.keyword),
+            string: fg(self.string, &colors.string),
+            comment: fg(self.comment, &colors.comment),
+            ..self
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        let popup = Style::default()
+            .bg(Color::Black)
+            .fg(Color::White)
+            .add_modifier(Modifier::BOLD);
+        Self {
+            list_highlight: Style::default()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            popup_highlight: Style::default()
+                .bg(Color::White)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            new_note_popup: popup,
+            rename_popup: popup,
+            tags_popup: popup,
+            search_popup: popup,
+            danger_popup: Style::default()
+                .bg(Color::Red)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+            list_popup: popup,
+            error: Style::default()
+                .fg(Color::LightRed)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            dim: Style::default(),
+            border: Style::default(),
+            focused_border: Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            search_match: Style::default()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            today: Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+            tag_chip: Style::default()
+                .bg(Color::White)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            toast: Style::default()
+                .bg(Color::LightCyan)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            diff_added: Style::default()
+                .fg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+            diff_removed: Style::default()
+                .fg(Color::LightRed)
+                .add_modifier(Modifier::BOLD),
+            heading: Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            code: Style::default().fg(Color::LightYellow),
+            keyword: Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+            string: Style::default().fg(Color::LightGreen),
+            comment: Style::default().fg(Color::Gray),
+            tag_colors: true,
+        }
+    }
+
+    /// No colors at all; selection is shown with reverse video.
+    pub fn plain() -> Self {
+        Self {
+            list_highlight: Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            popup_highlight: Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            new_note_popup: Style::default(),
+            rename_popup: Style::default(),
+            tags_popup: Style::default(),
+            search_popup: Style::default(),
+            danger_popup: Style::default().add_modifier(Modifier::BOLD),
+            list_popup: Style::default(),
+            error: Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            dim: Style::default().add_modifier(Modifier::DIM),
+            border: Style::default(),
+            focused_border: Style::default().add_modifier(Modifier::BOLD),
+            search_match: Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            today: Style::default().add_modifier(Modifier::BOLD),
+            tag_chip: Style::default().add_modifier(Modifier::REVERSED),
+            toast: Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            diff_added: Style::default().add_modifier(Modifier::BOLD),
+            diff_removed: Style::default().add_modifier(Modifier::DIM | Modifier::CROSSED_OUT),
+            heading: Style::default().add_modifier(Modifier::BOLD),
+            code: Style::default(),
+            keyword: Style::default().add_modifier(Modifier::BOLD),
+            string: Style::default(),
+            comment: Style::default().add_modifier(Modifier::DIM),
+            tag_colors: false,
+        }
+    }
+
+    /// Swaps the selection highlights for bold/reverse video. Popups, search
+    /// matches, chips and toasts lose all their colors, as their foreground was
+    /// picked to contrast with the background; other styles keep theirs.
+    fn without_backgrounds(self) -> Self {
+        let strip = |style: Style| Style {
+            bg: None,
+            fg: None,
+            ..style
+        };
+        Self {
+            list_highlight: Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            popup_highlight: Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            new_note_popup: strip(self.new_note_popup),
+            rename_popup: strip(self.rename_popup),
+            tags_popup: strip(self.tags_popup),
+            search_popup: strip(self.search_popup),
+            danger_popup: strip(self.danger_popup).add_modifier(Modifier::BOLD),
+            list_popup: strip(self.list_popup),
+            search_match: strip(self.search_match).add_modifier(Modifier::UNDERLINED),
+            tag_chip: strip(self.tag_chip).add_modifier(Modifier::REVERSED),
+            toast: strip(self.toast).add_modifier(Modifier::REVERSED),
+            ..self
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            list_highlight: Style::default()
+                .bg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+            popup_highlight: Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            new_note_popup: Style::default().bg(Color::LightBlue),
+            rename_popup: Style::default().bg(Color::LightYellow).fg(Color::Black),
+            tags_popup: Style::default().bg(Color::LightCyan).fg(Color::Black),
+            search_popup: Style::default().bg(Color::Blue).fg(Color::White),
+            danger_popup: Style::default().bg(Color::Red).fg(Color::White),
+            list_popup: Style::default().bg(Color::DarkGray),
+            error: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            dim: Style::default().add_modifier(Modifier::DIM),
+            border: Style::default(),
+            focused_border: Style::default().fg(Color::LightGreen),
+            search_match: Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            today: Style::default()
+                .fg(Color::LightMagenta)
+                .add_modifier(Modifier::BOLD),
+            tag_chip: Style::default().bg(Color::DarkGray).fg(Color::White),
+            toast: Style::default()
+                .bg(Color::Magenta)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+            diff_added: Style::default().fg(Color::Green),
+            diff_removed: Style::default().fg(Color::Red),
+            heading: Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+            code: Style::default().fg(Color::Yellow),
+            keyword: Style::default().fg(Color::LightMagenta),
+            string: Style::default().fg(Color::Green),
+            comment: Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
+            tag_colors: true,
+        }
+    }
+}
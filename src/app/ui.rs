@@ -1,88 +1,483 @@
+use chrono::{DateTime, Local, Utc};
 use ratatui::{
     Frame,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
 };
 
-use super::state::{AppState, InputMode};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::clipboard::CopyFormat;
+use super::config::{ListDensity, ListPosition, Severity, TitleTruncation};
+use super::diff::DiffLine;
+use super::export::ExportFormat;
+use super::input::TextInput;
+use super::links::{LinkDirection, ReportEntry};
+use super::note_type::NoteType;
+use super::preview;
+use super::query;
+use super::search::{self, Snippet};
+use super::state::{
+    ACTIVITY_WEEKS, AppState, AttachmentAction, Column, InputMode, Note, NotebookFilter, SortMode,
+    TagFilter, ViewMode,
+};
 
 pub fn ui(f: &mut Frame, app: &mut AppState) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(20), Constraint::Percentage(80)].as_ref())
+    if app.input_mode == InputMode::Locked {
+        render_lock_screen(f, app);
+        return;
+    }
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
         .split(f.area());
+    let mut main_area = rows[0];
+    render_status_bar(f, app, rows[1]);
 
-    // --- Left Pane: Note List ---
+    // --- Offline Banner ---
+    if app.db_offline.is_some() || app.db_reconnecting {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(main_area);
+        let message = match app.db_offline {
+            None => " Connection lost, reconnecting...".to_string(),
+            Some(retry_in) if retry_in.is_zero() => {
+                " OFFLINE: database unreachable, retrying now...".to_string()
+            }
+            Some(retry_in) => format!(
+                " OFFLINE: database unreachable, retrying in {}s",
+                retry_in.as_secs() + 1
+            ),
+        };
+        f.render_widget(
+            Paragraph::new(message).style(app.theme.danger_popup),
+            rows[0],
+        );
+        main_area = rows[1];
+    }
+
+    let (list_area, preview_area) = if app.columns_view {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(15),
+                Constraint::Percentage(25),
+                Constraint::Percentage(60),
+            ])
+            .split(main_area);
+        render_tag_column(f, app, columns[0]);
+        (columns[1], columns[2])
+    } else {
+        split_panes(app, main_area)
+    };
+    let border_style = |column: Column| {
+        if app.columns_view && app.focused_column == column {
+            app.theme.focused_border
+        } else {
+            app.theme.border
+        }
+    };
+
+    // --- List Pane ---
 
+    // Borders and the highlight symbol eat into the usable row width.
+    let row_width = list_area.width.saturating_sub(2 + 3) as usize;
+    let search_text = query::parse(&app.search_query).text;
+    let mut fuzzy = (!search_text.is_empty()).then(|| search::Fuzzy::new(&search_text));
     let items: Vec<ListItem> = app
         .notes
         .iter()
         .map(|note| {
-            let label = if note.tags.is_empty() {
-                note.title.clone()
+            let mut title = if note.pinned || app.is_pinned_here(note) {
+                format!("📌 {}", app.display_title(&note.title))
             } else {
-                // Show title + first tag or tag count indicator
-                format!("{} [{}]", note.title, note.tags.join(","))
+                app.display_title(&note.title)
+            };
+            if note.favorite && app.view_mode != ViewMode::Favorites {
+                title = format!("★ {}", title);
+            }
+            if app.editing_elsewhere(note.id).is_some() {
+                title = format!("✎ {}", title);
+            }
+            if app.changed_elsewhere.contains(&note.id) {
+                title = format!("● {}", title);
+            }
+            if let Some(source) = &note.source {
+                title = format!("[@{}] {}", source, title);
+            }
+            if app.marked.contains(&note.id) {
+                title = format!("✓ {}", title);
+            }
+            // Chars of `title` the search matched, after the markers in front.
+            let matched: Vec<usize> = match fuzzy.as_mut() {
+                Some(fuzzy) if !app.screencast => {
+                    let offset = title.chars().count() - note.title.chars().count();
+                    fuzzy
+                        .positions(&note.title)
+                        .into_iter()
+                        .map(|i| i + offset)
+                        .collect()
+                }
+                _ => Vec::new(),
             };
-            ListItem::new(label)
+            let mut lines = match app.list_density {
+                ListDensity::Compact => {
+                    let label = if note.tags.is_empty() {
+                        title
+                    } else {
+                        // Show title + first tag or tag count indicator
+                        let tags: Vec<String> =
+                            note.tags.iter().map(|t| app.display_tag(t)).collect();
+                        format!("{} [{}]", title, tags.join(","))
+                    };
+                    let lines = fit_title(&label, row_width, app.title_rules.truncate);
+                    highlight_chars(lines, &label, &matched, app.theme.search_match)
+                }
+                ListDensity::Comfortable => {
+                    let mut details = if note.tags.is_empty() {
+                        "untagged".to_string()
+                    } else {
+                        note.tags
+                            .iter()
+                            .map(|t| format!("#{}", app.display_tag(t)))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    };
+                    if app.view_mode == ViewMode::Stale
+                        && let Some(updated_at) = note.updated_at
+                    {
+                        details.push_str(&format!(
+                            " · idle {}d",
+                            (Utc::now() - updated_at).num_days()
+                        ));
+                    }
+                    // The created date when sorting by it, else the last change.
+                    let date = match (app.sort_mode, note.updated_at) {
+                        (SortMode::Created, _) | (_, None) => {
+                            note.created_at.map(|at| ("created", at))
+                        }
+                        (_, Some(updated_at)) => Some(("modified", updated_at)),
+                    };
+                    if let Some((label, at)) = date {
+                        details.push_str(&format!(
+                            " · {} {}",
+                            label,
+                            at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                        ));
+                    }
+                    if let Some(remind_at) = note.remind_at {
+                        details.push_str(&format!(
+                            " · ⏰ {}",
+                            remind_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                        ));
+                    }
+                    if let Some(older) = note
+                        .supersedes
+                        .and_then(|id| app.all_notes.iter().find(|n| n.id == id))
+                    {
+                        details.push_str(&format!(
+                            " · supersedes {}",
+                            app.display_title(&older.title)
+                        ));
+                    }
+                    if let Some(newer) =
+                        app.all_notes.iter().find(|n| n.supersedes == Some(note.id))
+                    {
+                        details.push_str(&format!(
+                            " · superseded by {}",
+                            app.display_title(&newer.title)
+                        ));
+                    }
+                    if let Some(archived_at) = note.archived_at {
+                        details.push_str(&format!(
+                            " · archived {}",
+                            archived_at.with_timezone(&Local).format("%Y-%m-%d")
+                        ));
+                    }
+                    let lines = fit_title(&title, row_width, app.title_rules.truncate);
+                    let mut lines =
+                        highlight_chars(lines, &title, &matched, app.theme.search_match);
+                    lines.push(Line::from(Span::styled(
+                        truncate_to_width(&format!("  {}", details), row_width),
+                        app.theme.dim,
+                    )));
+                    lines
+                }
+            };
+            if app.is_todays_note(note) {
+                let title_lines = match app.list_density {
+                    ListDensity::Compact => lines.len(),
+                    ListDensity::Comfortable => lines.len() - 1,
+                };
+                for line in &mut lines[..title_lines] {
+                    *line = std::mem::take(line).patch_style(app.theme.today);
+                }
+            }
+            if let Some(snippet) = app.search_snippet(note) {
+                lines.push(snippet_line(&snippet, row_width, app));
+            }
+            ListItem::new(lines)
         })
         .collect();
 
-
-    let list_title = format!(
-        "{} (Filter: {}){}",
-        match app.view_mode {
-            crate::app::state::ViewMode::Active => "Notes",
-            crate::app::state::ViewMode::Archived => "Archived Notes",
-        },
-        app.active_filter,
-        if app.search_query.is_empty() {
-            "".to_string()
-        } else {
-            format!(" | Search: '{}'", app.search_query)
-        }
-    );
+    let mut list_title = app.list_heading();
+    if app.notes_fetch.is_some() {
+        list_title.push_str(" (loading...)");
+    }
 
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title(list_title))
-        .highlight_style(
-            Style::default()
-                .bg(Color::LightGreen)
-                .add_modifier(Modifier::BOLD),
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(border_style(Column::Notes))
+                .title(list_title),
         )
+        .highlight_style(app.theme.list_highlight)
         .highlight_symbol(">> ");
 
-    f.render_stateful_widget(list, chunks[0], &mut app.list_state);
+    f.render_stateful_widget(list, list_area, &mut app.list_state);
 
-    // --- Right Pane: Preview ---
-    let preview_block = Block::default().borders(Borders::ALL).title("Note Content");
-    let preview_text = Paragraph::new(app.script_content_preview.as_str())
-        .block(preview_block)
-        .wrap(Wrap { trim: false })
-        .scroll((app.preview_scroll, 0));
-
-    f.render_widget(preview_text, chunks[1]);
+    // --- Preview Pane ---
+    let preview_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(border_style(Column::Preview));
+    if app.inline_edit.is_some() {
+        render_inline_editor(f, app, preview_block, preview_area);
+    } else {
+        render_preview(f, app, preview_block, preview_area);
+    }
 
     // --- Popup Windows ---
-    match app.input_mode {
+    // Draw the modal stack bottom-up so layered popups stay visible.
+    let layers: Vec<InputMode> = app
+        .mode_stack
+        .iter()
+        .copied()
+        .chain(std::iter::once(app.input_mode))
+        .collect();
+    for mode in layers {
+        render_popup(f, app, mode);
+    }
+
+    if let Some(message) = app.current_toast() {
+        render_toast(f, app, message, main_area);
+    }
+}
+
+/// A one-line notice in the bottom right corner of `area`, above any popup.
+fn render_toast(f: &mut Frame, app: &AppState, message: &str, area: Rect) {
+    let text = truncate_to_width(message, area.width.saturating_sub(4) as usize);
+    let width = (text.width() as u16 + 4).min(area.width);
+    let height = 3.min(area.height);
+    let toast_area = Rect {
+        x: area.right() - width,
+        y: area.bottom() - height,
+        width,
+        height,
+    };
+    f.render_widget(Clear, toast_area);
+    f.render_widget(
+        Paragraph::new(format!(" {}", text))
+            .block(Block::default().borders(Borders::ALL))
+            .style(app.theme.toast),
+        toast_area,
+    );
+}
+
+fn render_preview(f: &mut Frame, app: &mut AppState, preview_block: Block, preview_area: Rect) {
+    let mut content_area = preview_block.inner(preview_area);
+
+    if let Some(note) = app.get_selected_note() {
+        let header = preview_header(app, note);
+        let header_height =
+            (header.line_count(content_area.width) as u16 + 1).min(content_area.height);
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(header_height), Constraint::Min(0)])
+            .split(content_area);
+        f.render_widget(
+            header.block(
+                Block::default()
+                    .borders(Borders::BOTTOM)
+                    .border_style(app.theme.dim),
+            ),
+            rows[0],
+        );
+        content_area = rows[1];
+    }
+
+    let renderer = match app.get_selected_note() {
+        Some(note) => {
+            preview::renderer_for(&note.title, &app.script_content_preview, note.note_type)
+        }
+        None => Box::new(preview::Plain),
+    };
+    let mut text = renderer.render(&app.script_content_preview, &app.theme);
+
+    // Lines wider than the pane scroll sideways when they don't wrap.
+    let max_hscroll = if renderer.wraps() {
+        0
+    } else {
+        text.width().saturating_sub(content_area.width as usize)
+    };
+    app.preview_hscroll = app.preview_hscroll.min(max_hscroll as u16);
+
+    let sticky: Vec<Line> = text
+        .lines
+        .drain(..renderer.sticky_lines().min(text.lines.len()))
+        .collect();
+    if !sticky.is_empty() {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(sticky.len() as u16), Constraint::Min(0)])
+            .split(content_area);
+        f.render_widget(
+            Paragraph::new(sticky).scroll((0, app.preview_hscroll)),
+            rows[0],
+        );
+        content_area = rows[1];
+    }
+
+    let mut preview_text = Paragraph::new(text);
+    if renderer.wraps() {
+        preview_text = preview_text.wrap(Wrap { trim: false });
+    }
+
+    // Measure the wrapped content against the pane's inner area so the
+    // indicator stays correct after a terminal resize.
+    let inner_width = content_area.width;
+    let inner_height = content_area.height as usize;
+    let total_lines = preview_text.line_count(inner_width);
+    let max_scroll = total_lines.saturating_sub(inner_height);
+    if app.preview_scroll as usize > max_scroll {
+        app.preview_scroll = max_scroll as u16;
+    }
+    let scroll_percent = (app.preview_scroll as usize * 100)
+        .checked_div(max_scroll)
+        .unwrap_or(100);
+
+    f.render_widget(
+        preview_block.title(preview_title(app, scroll_percent, max_hscroll > 0)),
+        preview_area,
+    );
+    f.render_widget(
+        preview_text.scroll((app.preview_scroll, app.preview_hscroll)),
+        content_area,
+    );
+}
+
+/// The inline editor in place of the preview, with the terminal cursor at its cursor.
+fn render_inline_editor(
+    f: &mut Frame,
+    app: &mut AppState,
+    preview_block: Block,
+    preview_area: Rect,
+) {
+    let Some(shown_title) = app
+        .inline_edit
+        .as_ref()
+        .map(|edit| app.display_title(&edit.session.title))
+    else {
+        return;
+    };
+    let Some(edit) = app.inline_edit.as_mut() else {
+        return;
+    };
+    let content_area = preview_block.inner(preview_area);
+    let (x, y) = edit
+        .area
+        .scroll_to_cursor(content_area.width as usize, content_area.height as usize);
+    let (top, left) = edit.area.scroll();
+
+    let modified = edit.area.text() != edit.session.original;
+    let mut title = format!(
+        "Editing {}{} [Ctrl+s] save, [Esc] close",
+        shown_title,
+        if modified { " *" } else { "" }
+    );
+    if modified && !edit.draft_dirty {
+        title.push_str(" · draft saved");
+    }
+
+    let lines: Vec<Line> = edit.area.lines()[top..]
+        .iter()
+        .take(content_area.height as usize)
+        .map(|line| Line::raw(line.clone()))
+        .collect();
+    f.render_widget(
+        preview_block
+            .title(title)
+            .border_style(app.theme.focused_border),
+        preview_area,
+    );
+    f.render_widget(Paragraph::new(lines).scroll((0, left as u16)), content_area);
+    f.set_cursor_position((content_area.x + x as u16, content_area.y + y as u16));
+}
+
+fn render_popup(f: &mut Frame, app: &mut AppState, mode: InputMode) {
+    match mode {
         InputMode::EditingFilename => {
-            let area = centered_fixed_height_rect(50, 3, f.area());
-            let input_text = format!("{}_", app.filename_input);
             let popup_block = Block::default()
                 .title("New Note Title")
                 .borders(Borders::ALL)
-                .style(Style::default().bg(Color::LightBlue));
-            let input_paragraph = Paragraph::new(input_text.as_str()).block(popup_block);
+                .style(app.theme.new_note_popup);
+            render_new_title_input(f, app, popup_block);
+        }
+        InputMode::ConfirmingConcurrentEdit => {
+            let area = centered_fixed_height_rect(60, 3, f.area());
+            let popup_block = Block::default()
+                .title("Note In Use")
+                .borders(Borders::ALL)
+                .style(app.theme.danger_popup);
+
+            let popup_paragraph = Paragraph::new(app.status_message.as_str())
+                .block(popup_block)
+                .alignment(Alignment::Center);
+
             f.render_widget(Clear, area);
-            f.render_widget(input_paragraph, area);
+            f.render_widget(popup_paragraph, area);
         }
         InputMode::ConfirmingDelete => {
             let area = centered_fixed_height_rect(50, 3, f.area());
             let popup_block = Block::default()
                 .title("Confirm Deletion")
                 .borders(Borders::ALL)
-                .style(Style::default().bg(Color::Red).fg(Color::White));
+                .style(app.theme.danger_popup);
+
+            let popup_paragraph = Paragraph::new(app.status_message.as_str())
+                .block(popup_block)
+                .alignment(Alignment::Center);
+
+            f.render_widget(Clear, area);
+            f.render_widget(popup_paragraph, area);
+        }
+        InputMode::ConfirmingArchive
+        | InputMode::ConfirmingTaxonomyImport
+        | InputMode::ConfirmingTagDelete
+        | InputMode::ConfirmingUrlPaste => {
+            let area = centered_fixed_height_rect(60, 3, f.area());
+            let popup_block = Block::default()
+                .title("Confirm")
+                .borders(Borders::ALL)
+                .style(app.theme.danger_popup);
+
+            let popup_paragraph = Paragraph::new(app.status_message.as_str())
+                .block(popup_block)
+                .alignment(Alignment::Center);
+
+            f.render_widget(Clear, area);
+            f.render_widget(popup_paragraph, area);
+        }
+        InputMode::ConfirmingQuit => {
+            let area = centered_fixed_height_rect(60, 3, f.area());
+            let popup_block = Block::default()
+                .title("Unsaved Changes")
+                .borders(Borders::ALL)
+                .style(app.theme.danger_popup);
 
             let popup_paragraph = Paragraph::new(app.status_message.as_str())
                 .block(popup_block)
@@ -92,38 +487,124 @@ pub fn ui(f: &mut Frame, app: &mut AppState) {
             f.render_widget(popup_paragraph, area);
         }
         InputMode::RenamingScript => {
-            let area = centered_fixed_height_rect(50, 3, f.area());
-            let input_text = format!("{}_", app.filename_input);
             let popup_block = Block::default()
                 .title("Rename Note")
                 .borders(Borders::ALL)
-                .style(Style::default().bg(Color::LightYellow).fg(Color::Black));
+                .style(app.theme.rename_popup);
+            render_checked_input(f, app, &app.filename_input, popup_block);
+        }
 
-            let input_paragraph = Paragraph::new(input_text.as_str()).block(popup_block);
+        InputMode::EditingTags => {
+            let editor = &app.tag_editor;
+            let mut chips = Vec::new();
+            for (i, tag) in editor.tags.iter().enumerate() {
+                let style = if editor.focused == Some(i) {
+                    app.theme.danger_popup
+                } else {
+                    app.theme.tag_chip
+                };
+                chips.push(Span::styled(format!(" {} ", tag), style));
+                chips.push(Span::raw(" "));
+            }
+            if chips.is_empty() {
+                chips.push(Span::styled("no tags", app.theme.dim));
+            }
+
+            let mut field = input_line(&editor.input);
+            field.spans.insert(0, Span::raw("+ "));
+            if let Some(completion) = editor.completion() {
+                let rest = &completion[editor.input.value().trim().len()..];
+                field
+                    .spans
+                    .push(Span::styled(format!("{} [Tab]", rest), app.theme.dim));
+            }
+
+            let mut lines = vec![Line::from(chips), field];
+            if let Some(error) = &editor.error {
+                lines.push(Line::from(Span::styled(error.as_str(), app.theme.error)));
+            }
+
+            let area = centered_fixed_height_rect(50, lines.len() as u16 + 2, f.area());
+            let popup_block = Block::default()
+                .title("Edit Tags [Enter] add / save, [Backspace] remove")
+                .borders(Borders::ALL)
+                .style(app.theme.tags_popup);
             f.render_widget(Clear, area);
-            f.render_widget(input_paragraph, area);
+            f.render_widget(Paragraph::new(lines).block(popup_block), area);
         }
 
-        InputMode::EditingTags => {
+        InputMode::EditingFilterExpression => {
+            let popup_block = Block::default()
+                .title("Tag Expression (AND, OR, NOT, parentheses)")
+                .borders(Borders::ALL)
+                .style(app.theme.search_popup);
+            render_checked_input(f, app, &app.filter_expr_input, popup_block);
+        }
+        InputMode::Command => {
+            let mut prompt = input_line(&app.command_input);
+            prompt.spans.insert(0, Span::raw(":"));
+            let mut lines = vec![prompt];
+            lines.extend(
+                app.command_matches()
+                    .into_iter()
+                    .map(|(_, usage)| Line::from(Span::styled(*usage, app.theme.dim))),
+            );
+            let popup_block = Block::default()
+                .title("Command")
+                .borders(Borders::ALL)
+                .style(app.theme.search_popup);
+            let area = centered_fixed_height_rect(60, lines.len() as u16 + 2, f.area());
+            f.render_widget(Clear, area);
+            f.render_widget(Paragraph::new(lines).block(popup_block), area);
+        }
+        InputMode::EditingDateRange => {
+            let popup_block = Block::default()
+                .title("Date Range ([created|updated] YYYY-MM-DD..YYYY-MM-DD)")
+                .borders(Borders::ALL)
+                .style(app.theme.search_popup);
+            render_checked_input(f, app, &app.date_range_input, popup_block);
+        }
+        InputMode::RenamingTag => {
+            let popup_block = Block::default()
+                .title(format!(
+                    "Rename tag '{}' on every note",
+                    app.renaming_tag.as_deref().unwrap_or_default()
+                ))
+                .borders(Borders::ALL)
+                .style(app.theme.search_popup);
+            render_checked_input(f, app, &app.tag_rename_input, popup_block);
+        }
+        InputMode::EnteringPassphrase => {
             let area = centered_fixed_height_rect(50, 3, f.area());
-            let input_text = format!("{}_", app.filename_input);
+            let masked = format!("{}_", "*".repeat(app.passphrase_input.chars().count()));
             let popup_block = Block::default()
-                .title("Edit Tags (comma separated)")
+                .title(if app.passphrase_first.is_some() {
+                    "Confirm passphrase"
+                } else {
+                    "Passphrase"
+                })
                 .borders(Borders::ALL)
-                .style(Style::default().bg(Color::LightCyan).fg(Color::Black));
+                .style(app.theme.tags_popup);
 
-            let input_paragraph = Paragraph::new(input_text.as_str()).block(popup_block);
+            let input_paragraph = Paragraph::new(masked).block(popup_block);
             f.render_widget(Clear, area);
             f.render_widget(input_paragraph, area);
         }
-
         InputMode::Searching => {
             let area = centered_fixed_height_rect(50, 3, f.area());
             let input_text = format!("{}_", app.search_query);
+            let mut title = if app.search_titles_only {
+                "Search Titles".to_string()
+            } else {
+                "Search Titles & Content".to_string()
+            };
+            if let Some(problem) = query::parse(&app.search_query).problem {
+                title = format!("{} · {}", title, problem);
+            }
             let popup_block = Block::default()
-                .title("Search Titles")
+                .title(title)
                 .borders(Borders::ALL)
-                .style(Style::default().bg(Color::Blue).fg(Color::White));
+                .style(app.theme.search_popup);
 
             let input_paragraph = Paragraph::new(input_text.as_str()).block(popup_block);
             f.render_widget(Clear, area);
@@ -148,30 +629,976 @@ pub fn ui(f: &mut Frame, app: &mut AppState) {
             let items: Vec<ListItem> = app
                 .available_filters
                 .iter()
-                .map(|f| ListItem::new(format!("{}", f)))
+                .map(|f| {
+                    let color = match f {
+                        TagFilter::Specific(tag) if app.theme.tag_colors => app
+                            .tag_meta
+                            .get(tag)
+                            .and_then(|m| m.color.as_deref())
+                            .and_then(|c| c.parse::<Color>().ok()),
+                        _ => None,
+                    };
+                    match color {
+                        Some(color) => {
+                            ListItem::new(Span::styled(f.to_string(), Style::default().fg(color)))
+                        }
+                        None => ListItem::new(f.to_string()),
+                    }
+                })
                 .collect();
 
             let list = List::new(items)
                 .block(
                     Block::default()
                         .borders(Borders::ALL)
-                        .title("Filter by Tag")
-                        .style(Style::default().bg(Color::DarkGray)),
-                )
-                .highlight_style(
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
+                        .title("Filter (n/s/x: expressions, r: dates, R: rename, e/i: taxonomy)")
+                        .style(app.theme.list_popup),
                 )
+                .highlight_style(app.theme.popup_highlight)
                 .highlight_symbol("> ");
 
             f.render_widget(Clear, area);
             f.render_stateful_widget(list, area, &mut app.filter_list_state);
         }
-        InputMode::Normal => {}
+        InputMode::TogglingTags => {
+            let on = app.quick_tag_note_tags();
+            let mut chips = Vec::new();
+            for (i, tag) in app.quick_tags.iter().enumerate() {
+                let style = if on.contains(tag) {
+                    app.theme.tag_chip
+                } else {
+                    app.theme.dim
+                };
+                chips.push(Span::styled(format!(" {} {} ", i + 1, tag), style));
+                chips.push(Span::raw(" "));
+            }
+            let area = centered_fixed_height_rect(60, 4, f.area());
+            let paragraph = Paragraph::new(Line::from(chips))
+                .wrap(Wrap { trim: false })
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Quick Tags [1-9] toggle, [Esc] done")
+                        .style(app.theme.tags_popup),
+                );
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph, area);
+        }
+        InputMode::SelectingNotebook => {
+            let area = centered_rect(40, 50, f.area());
+            let items: Vec<ListItem> = app
+                .notebook_choices()
+                .into_iter()
+                .map(|choice| {
+                    let count = app
+                        .all_notes
+                        .iter()
+                        .filter(|n| n.deleted_at.is_none())
+                        .filter(|n| match choice {
+                            NotebookFilter::All => true,
+                            NotebookFilter::Unfiled => n.notebook_id.is_none(),
+                            NotebookFilter::Notebook(id) => n.notebook_id == Some(id),
+                        })
+                        .count();
+                    ListItem::new(Line::from(vec![
+                        Span::raw(app.notebook_label(choice)),
+                        Span::styled(format!(" ({})", count), app.theme.dim),
+                    ]))
+                })
+                .collect();
+
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Notebooks (m: move note here, n: new, d: delete)")
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight)
+                .highlight_symbol("> ");
+
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut app.notebook_list_state);
+        }
+        InputMode::ManagingTags => {
+            let area = centered_rect(40, 50, f.area());
+            let items: Vec<ListItem> = app
+                .tag_counts
+                .iter()
+                .map(|(tag, count)| {
+                    let marker = if app.merging_tag.as_ref() == Some(tag) {
+                        "✓ "
+                    } else {
+                        ""
+                    };
+                    ListItem::new(Line::from(vec![
+                        Span::raw(format!("{}#{}", marker, tag)),
+                        Span::styled(format!(" ({})", count), app.theme.dim),
+                    ]))
+                })
+                .collect();
+            let title = match &app.merging_tag {
+                Some(tag) => format!("Merge #{} into… (m: here, Esc: cancel)", tag),
+                None => "Tags (r: rename, m: merge, d: delete)".to_string(),
+            };
+
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight)
+                .highlight_symbol("> ");
+
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut app.tag_list_state);
+        }
+        InputMode::NamingNotebook => {
+            let popup_block = Block::default()
+                .title("New Notebook")
+                .borders(Borders::ALL)
+                .style(app.theme.new_note_popup);
+            render_checked_input(f, app, &app.notebook_input, popup_block);
+        }
+        InputMode::StatusHistory => {
+            let area = centered_rect(70, 60, f.area());
+            let items: Vec<ListItem> = app
+                .status_history
+                .iter()
+                .rev()
+                .map(|(at, message)| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled(format!("{} ", at.format("%H:%M:%S")), app.theme.dim),
+                        Span::raw(message.as_str()),
+                    ]))
+                })
+                .collect();
+
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Status History")
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight);
+
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut app.history_list_state);
+        }
+        InputMode::ConfirmingLinkRewrite => {
+            let Some(rename) = &app.pending_rename else {
+                return;
+            };
+            let mut lines = vec![
+                Line::from(format!(
+                    "Rename '{}' to '{}'.",
+                    rename.old_title, rename.new_title
+                )),
+                Line::from(format!(
+                    "{} note(s) link to the old title:",
+                    rename.linking_notes.len()
+                )),
+            ];
+            lines.extend(
+                rename
+                    .linking_notes
+                    .iter()
+                    .map(|(_, title)| Line::from(format!("  • {}", app.display_title(title)))),
+            );
+            lines.push(Line::from(""));
+            lines.push(Line::from(app.status_message.as_str()));
+
+            let height = (lines.len() as u16 + 2).min(f.area().height);
+            let area = centered_fixed_height_rect(60, height, f.area());
+            let popup = Paragraph::new(lines).block(
+                Block::default()
+                    .title("Update Links?")
+                    .borders(Borders::ALL)
+                    .style(app.theme.rename_popup),
+            );
+            f.render_widget(Clear, area);
+            f.render_widget(popup, area);
+        }
+        InputMode::LinkGraph => {
+            let area = centered_rect(60, 60, f.area());
+            let items: Vec<ListItem> = app
+                .graph_entries
+                .iter()
+                .map(|entry| {
+                    let marker = match entry.direction {
+                        LinkDirection::Root => "●",
+                        LinkDirection::Outgoing => "→",
+                        LinkDirection::Backlink => "←",
+                    };
+                    let text = format!(
+                        "{}{} {}",
+                        "    ".repeat(entry.depth),
+                        marker,
+                        app.display_title(&entry.title)
+                    );
+                    if entry.note_id.is_some() {
+                        ListItem::new(text)
+                    } else {
+                        ListItem::new(Line::from(vec![
+                            Span::raw(text),
+                            Span::styled(" (missing)", app.theme.error),
+                        ]))
+                    }
+                })
+                .collect();
+
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Links (→ outgoing, ← backlink) [Enter] center, [Esc] close")
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight)
+                .highlight_symbol("> ");
+
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut app.graph_list_state);
+        }
+        InputMode::OrphanReport => {
+            let area = centered_rect(60, 60, f.area());
+            let items: Vec<ListItem> = app
+                .orphan_report
+                .iter()
+                .map(|entry| match entry {
+                    ReportEntry::Orphan { title, .. } => ListItem::new(Line::from(vec![
+                        Span::styled("orphan  ", app.theme.dim),
+                        Span::raw(app.display_title(title)),
+                    ])),
+                    ReportEntry::BrokenLink { source, target } => ListItem::new(Line::from(vec![
+                        Span::styled("broken  ", app.theme.error),
+                        Span::raw(format!(
+                            "{} → [[{}]]",
+                            app.display_title(source),
+                            app.display_title(target)
+                        )),
+                    ])),
+                })
+                .collect();
+
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Orphans & Broken Links [Enter] open / create missing, [Esc] close")
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight)
+                .highlight_symbol("> ");
+
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut app.report_list_state);
+        }
+        InputMode::Reminder => {
+            let Some(note) = app
+                .reminder_note
+                .and_then(|id| app.all_notes.iter().find(|n| n.id == id))
+            else {
+                return;
+            };
+            let mut lines = vec![Line::from(app.display_title(&note.title))];
+            if let Some(at) = note.remind_at {
+                lines.push(Line::from(Span::styled(
+                    format!(
+                        "Currently: {}",
+                        at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                    ),
+                    app.theme.dim,
+                )));
+            }
+            lines.push(Line::from(""));
+            lines.push(Line::from(if app.reminder_due {
+                "Snooze: [1] 10 min  [2] 1 hour  [3] 1 day"
+            } else {
+                "Remind in: [1] 10 min  [2] 1 hour  [3] 1 day"
+            }));
+            lines.push(Line::from(if app.reminder_due {
+                "[Enter] open & dismiss  [c] dismiss  [Esc] later"
+            } else {
+                "[c] clear reminder  [Esc] cancel"
+            }));
+
+            let area = centered_fixed_height_rect(50, lines.len() as u16 + 2, f.area());
+            let popup = Paragraph::new(lines).block(
+                Block::default()
+                    .title(if app.reminder_due {
+                        "Reminder Due"
+                    } else {
+                        "Set Reminder"
+                    })
+                    .borders(Borders::ALL)
+                    .style(app.theme.list_popup),
+            );
+            f.render_widget(Clear, area);
+            f.render_widget(popup, area);
+        }
+        InputMode::SizeReport => {
+            let area = centered_rect(60, 60, f.area());
+            let report = &app.size_report;
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(5), Constraint::Min(0)])
+                .split(area);
+
+            let content_total: i64 = report.notes.iter().map(|(_, _, bytes)| bytes).sum();
+            let summary = vec![
+                Line::from(format!(
+                    "Note content: {} in {} note(s)",
+                    human_bytes(content_total),
+                    report.notes.len()
+                )),
+                Line::from(format!(
+                    "notes table (with indexes/TOAST): {}",
+                    human_bytes(report.notes_table_bytes)
+                )),
+                Line::from(format!(
+                    "Attachments: {}   Database: {}",
+                    report
+                        .attachments_bytes
+                        .map_or("none".to_string(), human_bytes),
+                    human_bytes(report.database_bytes)
+                )),
+            ];
+            let items: Vec<ListItem> = report
+                .notes
+                .iter()
+                .map(|(_, title, bytes)| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled(format!("{:>10}  ", human_bytes(*bytes)), app.theme.dim),
+                        Span::raw(app.display_title(title)),
+                    ]))
+                })
+                .collect();
+
+            f.render_widget(Clear, area);
+            f.render_widget(
+                Paragraph::new(summary).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Storage [Enter] select note, [Esc] close")
+                        .style(app.theme.list_popup),
+                ),
+                rows[0],
+            );
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Largest notes")
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight)
+                .highlight_symbol("> ");
+            f.render_stateful_widget(list, rows[1], &mut app.size_list_state);
+        }
+        InputMode::ReviewingEdit => {
+            let Some(review) = &app.edit_review else {
+                return;
+            };
+            let lines: Vec<Line> = review
+                .lines
+                .iter()
+                .map(|line| match line {
+                    DiffLine::Context(text) => Line::from(format!("  {}", app.display_text(text))),
+                    DiffLine::Added(text) => Line::from(Span::styled(
+                        format!("+ {}", app.display_text(text)),
+                        app.theme.diff_added,
+                    )),
+                    DiffLine::Removed(text) => Line::from(Span::styled(
+                        format!("- {}", app.display_text(text)),
+                        app.theme.diff_removed,
+                    )),
+                    DiffLine::Gap => Line::from(Span::styled("  ⋯", app.theme.dim)),
+                })
+                .collect();
+
+            let area = centered_rect(80, 80, f.area());
+            let title = format!(
+                "Changes to '{}' [y] save, [n] discard, [e] edit again",
+                app.display_title(&review.session.title)
+            );
+            f.render_widget(Clear, area);
+            f.render_widget(
+                Paragraph::new(lines).scroll((review.scroll, 0)).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(app.theme.list_popup),
+                ),
+                area,
+            );
+        }
+        InputMode::ResolvingConflict => {
+            let Some(conflict) = &app.edit_conflict else {
+                return;
+            };
+            let area = centered_rect(90, 80, f.area());
+            f.render_widget(Clear, area);
+            let outer = Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "'{}' changed while you edited it{} [m] keep mine, [t] keep theirs, [e] merge",
+                    app.display_title(&conflict.session.title),
+                    match app.conflict_queue.len() {
+                        0 => String::new(),
+                        n => format!(" ({} more queued)", n),
+                    }
+                ))
+                .style(app.theme.list_popup);
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(outer.inner(area));
+            f.render_widget(outer, area);
+
+            let theirs_title = match conflict.theirs_at {
+                Some(at) => format!(
+                    "Theirs, saved {}",
+                    at.with_timezone(&Local).format("%H:%M:%S")
+                ),
+                None => "Theirs".to_string(),
+            };
+            for (column, title, text) in [
+                (
+                    columns[0],
+                    "Mine".to_string(),
+                    app.display_text(&conflict.mine),
+                ),
+                (columns[1], theirs_title, app.display_text(&conflict.theirs)),
+            ] {
+                f.render_widget(
+                    Paragraph::new(text)
+                        .wrap(Wrap { trim: false })
+                        .scroll((conflict.scroll, 0))
+                        .block(Block::default().borders(Borders::TOP).title(title)),
+                    column,
+                );
+            }
+        }
+        InputMode::History => {
+            let area = centered_rect(80, 80, f.area());
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
+                .split(area);
+
+            let items: Vec<ListItem> = app
+                .revisions
+                .iter()
+                .map(|revision| {
+                    let mut spans = vec![
+                        Span::raw(
+                            revision
+                                .saved_at
+                                .with_timezone(&Local)
+                                .format("%Y-%m-%d %H:%M  ")
+                                .to_string(),
+                        ),
+                        Span::styled(human_bytes(revision.content.len() as i64), app.theme.dim),
+                    ];
+                    if revision.encrypted {
+                        spans.push(Span::raw(" 🔒"));
+                    }
+                    ListItem::new(Line::from(spans))
+                })
+                .collect();
+            let preview = app.revision_preview();
+
+            f.render_widget(Clear, area);
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("History [Enter] restore, [Esc] close")
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight)
+                .highlight_symbol("> ");
+            f.render_stateful_widget(list, columns[0], &mut app.revision_list_state);
+            f.render_widget(
+                Paragraph::new(preview).wrap(Wrap { trim: false }).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Version")
+                        .style(app.theme.list_popup),
+                ),
+                columns[1],
+            );
+        }
+        InputMode::Attachments => {
+            let height = (app.attachments.len() as u16 + 2).min(f.area().height);
+            let area = centered_fixed_height_rect(60, height, f.area());
+            let items: Vec<ListItem> = app
+                .attachments
+                .iter()
+                .map(|attachment| {
+                    ListItem::new(Line::from(vec![
+                        Span::raw(format!("{}  ", attachment.name)),
+                        Span::styled(
+                            format!(
+                                "{} · {}",
+                                human_bytes(attachment.size),
+                                attachment
+                                    .created_at
+                                    .with_timezone(&Local)
+                                    .format("%Y-%m-%d")
+                            ),
+                            app.theme.dim,
+                        ),
+                    ]))
+                })
+                .collect();
+
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Attachments [Enter] open, [s] save as, [r] rename, [d] delete")
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight)
+                .highlight_symbol("> ");
+
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut app.attachment_list_state);
+        }
+        InputMode::NamingAttachment => {
+            let popup_block = Block::default()
+                .title(match app.attachment_action {
+                    AttachmentAction::Rename => "Rename Attachment",
+                    AttachmentAction::SaveAs => "Save Attachment As",
+                })
+                .borders(Borders::ALL)
+                .style(app.theme.new_note_popup);
+            render_checked_input(f, app, &app.attachment_input, popup_block);
+        }
+        InputMode::LintReport => {
+            let height = (app.lint_findings.len() as u16 + 2).min(f.area().height);
+            let area = centered_fixed_height_rect(70, height, f.area());
+            let items: Vec<ListItem> = app
+                .lint_findings
+                .iter()
+                .map(|finding| {
+                    let (label, style) = match finding.severity {
+                        Severity::Error => ("error  ", app.theme.error),
+                        Severity::Warning => ("warning", app.theme.error),
+                        Severity::Info | Severity::Off => ("info   ", app.theme.dim),
+                    };
+                    let location = finding
+                        .line
+                        .map(|l| format!("line {}: ", l))
+                        .unwrap_or_default();
+                    ListItem::new(Line::from(vec![
+                        Span::styled(label, style),
+                        Span::raw(format!(" {}{}", location, finding.message)),
+                    ]))
+                })
+                .collect();
+
+            let list = List::new(items).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Lint Findings [Esc] close")
+                    .style(app.theme.list_popup),
+            );
+
+            f.render_widget(Clear, area);
+            f.render_widget(list, area);
+        }
+        InputMode::SelectingSortMode => {
+            let area = centered_fixed_height_rect(30, SortMode::ALL.len() as u16 + 2, f.area());
+            let items: Vec<ListItem> = SortMode::ALL
+                .iter()
+                .map(|mode| ListItem::new(mode.label()))
+                .collect();
+
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Sort by")
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight)
+                .highlight_symbol("> ");
+
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut app.sort_mode_state);
+        }
+        InputMode::SelectingCopyFormat => {
+            let area = centered_fixed_height_rect(30, CopyFormat::ALL.len() as u16 + 2, f.area());
+            let items: Vec<ListItem> = CopyFormat::ALL
+                .iter()
+                .map(|format| ListItem::new(format.label()))
+                .collect();
+
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Copy note as")
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight)
+                .highlight_symbol("> ");
+
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut app.copy_format_state);
+        }
+        InputMode::SelectingExportFormat => {
+            let area = centered_fixed_height_rect(40, ExportFormat::ALL.len() as u16 + 2, f.area());
+            let items: Vec<ListItem> = ExportFormat::ALL
+                .iter()
+                .map(|format| ListItem::new(format.label()))
+                .collect();
+
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!("Export {} note(s) as", app.notes.len()))
+                        .style(app.theme.list_popup),
+                )
+                .highlight_style(app.theme.popup_highlight)
+                .highlight_symbol("> ");
+
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut app.export_format_state);
+        }
+        InputMode::Normal | InputMode::InlineEdit | InputMode::Locked => {}
+    }
+}
+
+/// First column of the miller-columns layout: every tag filter, applied live.
+/// Replaces the whole screen so nothing from the list or preview stays visible.
+fn render_lock_screen(f: &mut Frame, app: &AppState) {
+    f.render_widget(Clear, f.area());
+    f.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("pgnotes (locked)"),
+        f.area(),
+    );
+    let area = centered_fixed_height_rect(40, 3, f.area());
+    let masked = format!("{}_", "*".repeat(app.lock_input.chars().count()));
+    let input = Paragraph::new(masked).block(
+        Block::default()
+            .title("Passphrase to unlock")
+            .borders(Borders::ALL)
+            .style(app.theme.tags_popup),
+    );
+    f.render_widget(input, area);
+}
+
+fn render_tag_column(f: &mut Frame, app: &mut AppState, area: Rect) {
+    let items: Vec<ListItem> = app
+        .available_filters
+        .iter()
+        .map(|filter| ListItem::new(format!("{}", filter)))
+        .collect();
+    let border_style = if app.focused_column == Column::Tags {
+        app.theme.focused_border
+    } else {
+        app.theme.border
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(border_style)
+                .title(if app.moving_note.is_some() {
+                    "Move to tag"
+                } else {
+                    "Tags"
+                }),
+        )
+        .highlight_style(app.theme.popup_highlight)
+        .highlight_symbol("> ");
+
+    f.render_stateful_widget(list, area, &mut app.column_filter_state);
+}
+
+/// Splits the main area into list and preview panes according to the configured position.
+fn split_panes(app: &AppState, area: Rect) -> (Rect, Rect) {
+    let (direction, list_first) = match app.list_position {
+        ListPosition::Left => (Direction::Horizontal, true),
+        ListPosition::Right => (Direction::Horizontal, false),
+        ListPosition::Top => (Direction::Vertical, true),
+        ListPosition::Bottom => (Direction::Vertical, false),
+    };
+    let default_size = match direction {
+        Direction::Horizontal => 20,
+        Direction::Vertical => 35,
+    };
+    let list_size = app.list_size.unwrap_or(default_size).clamp(10, 90);
+
+    let list_constraint = Constraint::Percentage(list_size);
+    let preview_constraint = Constraint::Percentage(100 - list_size);
+    let constraints = if list_first {
+        [list_constraint, preview_constraint]
+    } else {
+        [preview_constraint, list_constraint]
+    };
+
+    let chunks = Layout::default()
+        .direction(direction)
+        .constraints(constraints)
+        .split(area);
+
+    if list_first {
+        (chunks[0], chunks[1])
+    } else {
+        (chunks[1], chunks[0])
     }
 }
 
+/// Cuts `text` to at most `max_width` terminal columns, ending with an ellipsis
+/// when shortened. Wide characters (CJK, emoji) count as two columns.
+/// A dim grep-style line with the matched text highlighted, cut to `max_width`.
+fn snippet_line(snippet: &Snippet, max_width: usize, app: &AppState) -> Line<'static> {
+    let mut remaining = max_width;
+    let mut spans = Vec::new();
+    for (text, style) in [
+        (format!("  {}", snippet.before), app.theme.dim),
+        (snippet.matched.clone(), app.theme.search_match),
+        (snippet.after.clone(), app.theme.dim),
+    ] {
+        if remaining == 0 {
+            break;
+        }
+        let text = truncate_to_width(&text, remaining);
+        remaining = remaining.saturating_sub(text.width());
+        spans.push(Span::styled(text, style));
+    }
+    Line::from(spans)
+}
+
+/// 1536 -> "1.5 KiB".
+fn human_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+/// A list title cut down to `max_width` columns the configured way.
+fn fit_title(text: &str, max_width: usize, mode: TitleTruncation) -> Vec<Line<'static>> {
+    match mode {
+        TitleTruncation::End => vec![Line::from(truncate_to_width(text, max_width))],
+        TitleTruncation::Middle => vec![Line::from(truncate_middle(text, max_width))],
+        TitleTruncation::Wrap => wrap_to_width(text, max_width)
+            .into_iter()
+            .map(Line::from)
+            .collect(),
+    }
+}
+
+/// Styles the chars at `positions` of `text` in `lines`, which `fit_title` made
+/// from it: rows may drop the spaces they break at, add indentation, and stand
+/// an ellipsis in for a run of chars.
+fn highlight_chars(
+    lines: Vec<Line<'static>>,
+    text: &str,
+    positions: &[usize],
+    style: Style,
+) -> Vec<Line<'static>> {
+    if positions.is_empty() {
+        return lines;
+    }
+    let text: Vec<char> = text.chars().collect();
+    let rows: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    let mut remaining: usize = rows.iter().map(|row| row.chars().count()).sum();
+    let mut at = 0;
+    rows.into_iter()
+        .map(|row| {
+            let mut spans: Vec<Span> = Vec::new();
+            for c in row.chars() {
+                remaining -= 1;
+                while text.get(at).is_some_and(|t| *t != c && t.is_whitespace()) {
+                    at += 1;
+                }
+                let matched = match text.get(at) {
+                    Some(&t) if t == c => {
+                        at += 1;
+                        positions.contains(&(at - 1))
+                    }
+                    // What follows an ellipsis is the end of the text.
+                    _ if c == '…' => {
+                        at = text.len().saturating_sub(remaining);
+                        false
+                    }
+                    _ => false,
+                };
+                let style = if matched { style } else { Style::default() };
+                match spans.last_mut() {
+                    Some(span) if span.style == style => span.content.to_mut().push(c),
+                    _ => spans.push(Span::styled(c.to_string(), style)),
+                }
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Keeps the start and the end of `text`, with an ellipsis in between.
+fn truncate_middle(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+
+    let available = max_width - 1;
+    let tail_width = available / 2;
+    let take = |chars: &mut dyn Iterator<Item = char>, width: usize| {
+        let mut used = 0;
+        chars
+            .take_while(|c| {
+                used += c.width().unwrap_or(0);
+                used <= width
+            })
+            .collect::<String>()
+    };
+    let head = take(&mut text.chars(), available - tail_width);
+    let tail: String = take(&mut text.chars().rev(), tail_width)
+        .chars()
+        .rev()
+        .collect();
+    format!("{}…{}", head, tail)
+}
+
+/// Breaks `text` into rows of at most `max_width` columns at spaces where it
+/// can, indenting the continuation rows so they read as part of the row above.
+fn wrap_to_width(text: &str, max_width: usize) -> Vec<String> {
+    const INDENT: &str = "  ";
+    let mut rows = vec![String::new()];
+    for word in text.split(' ') {
+        let indent = if rows.len() > 1 { INDENT.len() } else { 0 };
+        let row = rows.last_mut().unwrap();
+        if row.width() > indent && row.width() + 1 + word.width() > max_width {
+            rows.push(INDENT.to_string());
+        } else if row.width() > indent {
+            row.push(' ');
+        }
+        for c in word.chars() {
+            let row = rows.last_mut().unwrap();
+            if row.width() + c.width().unwrap_or(0) > max_width && row.width() > INDENT.len() {
+                rows.push(INDENT.to_string());
+            }
+            rows.last_mut().unwrap().push(c);
+        }
+    }
+    rows
+}
+
+fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        // Leave one column for the ellipsis.
+        if used + w + 1 > max_width {
+            break;
+        }
+        truncated.push(c);
+        used += w;
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// Title input popup with an extra line for validation errors when present.
+/// The input's text with a block cursor, or fully highlighted while selected.
+fn input_line(input: &TextInput) -> Line<'static> {
+    let cursor_style = Style::default().add_modifier(Modifier::REVERSED);
+    if input.is_selected() {
+        return Line::from(Span::styled(input.value().to_string(), cursor_style));
+    }
+    let chars: Vec<char> = input.value().chars().collect();
+    let (before, rest) = chars.split_at(input.cursor());
+    let (at, after) = match rest.split_first() {
+        Some((c, after)) => (c.to_string(), after.iter().collect()),
+        None => (" ".to_string(), String::new()),
+    };
+    Line::from(vec![
+        Span::raw(before.iter().collect::<String>()),
+        Span::styled(at, cursor_style),
+        Span::raw(after),
+    ])
+}
+
+/// Single-line input popup with the validation error, if any, on a second line.
+fn render_checked_input(f: &mut Frame, app: &AppState, input: &TextInput, popup_block: Block) {
+    let mut lines = vec![input_line(input)];
+    if let Some(error) = &app.input_error {
+        lines.push(Line::from(Span::styled(error.as_str(), app.theme.error)));
+    }
+
+    let area = centered_fixed_height_rect(50, lines.len() as u16 + 2, f.area());
+    let input_paragraph = Paragraph::new(lines).block(popup_block);
+    f.render_widget(Clear, area);
+    f.render_widget(input_paragraph, area);
+}
+
+/// The new-title input with its validation error and, below, existing notes
+/// with a similar title that Tab opens instead.
+fn render_new_title_input(f: &mut Frame, app: &AppState, popup_block: Block) {
+    let mut lines = vec![input_line(&app.filename_input)];
+    let title = app.typed_title();
+    if app.title_rules.template.is_some() && !app.filename_input.value().trim().is_empty() {
+        lines.push(Line::from(Span::styled(
+            format!("Creates '{}'", title),
+            app.theme.dim,
+        )));
+    }
+    if let Some(error) = &app.input_error {
+        lines.push(Line::from(Span::styled(error.as_str(), app.theme.error)));
+    }
+    for (i, (_, title)) in app.title_suggestions.iter().enumerate() {
+        let text = format!("Did you mean to open '{}'?", title);
+        lines.push(if i == app.selected_suggestion {
+            Line::from(vec![
+                Span::styled(text, app.theme.popup_highlight),
+                Span::raw(" [Tab]"),
+            ])
+        } else {
+            Line::from(Span::styled(text, app.theme.dim))
+        });
+    }
+
+    let area = centered_fixed_height_rect(50, lines.len() as u16 + 2, f.area());
+    let input_paragraph = Paragraph::new(lines).block(popup_block);
+    f.render_widget(Clear, area);
+    f.render_widget(input_paragraph, area);
+}
+
 fn centered_fixed_height_rect(percent_x: u16, height_abs: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -210,4 +1637,143 @@ fn centered_rect(percent_x: u16, height_percent: u16, r: Rect) -> Rect {
             Constraint::Percentage((100 - percent_x) / 2),
         ])
         .split(popup_layout[1])[1]
-}
\ No newline at end of file
+}
+
+/// "Note Content" plus where the selected note comes from and who else has it open.
+/// Mode, filter, note count and connection, then the latest status message.
+fn render_status_bar(f: &mut Frame, app: &AppState, area: Rect) {
+    let mut filter = app.active_filter.to_string();
+    if app.active_notebook != NotebookFilter::All {
+        filter = format!("{} in {}", filter, app.notebook_label(app.active_notebook));
+    }
+    let connection = if app.db_offline.is_some() {
+        format!("{} (offline)", app.connection_label)
+    } else if app.db_reconnecting {
+        format!("{} (reconnecting)", app.connection_label)
+    } else if app.safe_mode {
+        format!("{} (safe mode)", app.connection_label)
+    } else {
+        app.connection_label.clone()
+    };
+    let line = Line::from(vec![
+        Span::styled(format!(" {} ", app.input_mode.label()), app.theme.tag_chip),
+        Span::styled(
+            format!(
+                " {} · {}/{} notes · {} │ ",
+                filter,
+                app.notes.len(),
+                app.all_notes.len(),
+                connection
+            ),
+            app.theme.dim,
+        ),
+        Span::raw(app.status_message.as_str()),
+    ]);
+    f.render_widget(Paragraph::new(line), area);
+}
+
+/// Title, tags and dates above the note body. The list may cut the title
+/// short; the header always shows all of it.
+fn preview_header<'a>(app: &AppState, note: &Note) -> Paragraph<'a> {
+    let mut lines = vec![Line::from(Span::styled(
+        app.display_title(&note.title),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+
+    if !note.tags.is_empty() {
+        let mut chips = Vec::new();
+        for tag in &note.tags {
+            let color = app
+                .tag_meta
+                .get(tag)
+                .and_then(|m| m.color.as_deref())
+                .and_then(|c| c.parse::<Color>().ok())
+                .filter(|_| app.theme.tag_colors);
+            let style = match color {
+                Some(color) if app.theme.tag_chip.bg.is_some() => {
+                    app.theme.tag_chip.bg(color).fg(Color::Black)
+                }
+                Some(color) => app.theme.tag_chip.fg(color),
+                None => app.theme.tag_chip,
+            };
+            chips.push(Span::styled(format!(" {} ", app.display_tag(tag)), style));
+            chips.push(Span::raw(" "));
+        }
+        lines.push(Line::from(chips));
+    }
+
+    let when = |t: DateTime<Utc>| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string();
+    let mut details = Vec::new();
+    if let Some(created_at) = note.created_at {
+        details.push(format!("created {}", when(created_at)));
+    }
+    if let Some(updated_at) = note.updated_at {
+        details.push(format!("updated {}", when(updated_at)));
+    }
+    if let Some(id) = note.notebook_id {
+        details.push(format!(
+            "in {}",
+            app.notebook_label(NotebookFilter::Notebook(id))
+        ));
+    }
+    if let Some(source) = &note.source {
+        details.push(format!("from @{}", source));
+    }
+    if note.note_type != NoteType::Note {
+        details.insert(0, note.note_type.name().to_string());
+    }
+    if !details.is_empty() {
+        lines.push(Line::from(Span::styled(details.join(" · "), app.theme.dim)));
+    }
+
+    let activity = &note.activity;
+    if activity.weekly.iter().any(|&words| words > 0) {
+        lines.push(Line::from(vec![
+            Span::styled(format!("edits, {} weeks ", ACTIVITY_WEEKS), app.theme.dim),
+            Span::styled(sparkline(&activity.weekly), app.theme.keyword),
+            Span::styled(
+                format!(
+                    " +{} −{} words",
+                    activity.words_added, activity.words_removed
+                ),
+                app.theme.dim,
+            ),
+        ]));
+    }
+
+    Paragraph::new(lines).wrap(Wrap { trim: false })
+}
+
+/// One block character per value, scaled to the largest; only zero sits on the baseline.
+fn sparkline(values: &[i64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+    values
+        .iter()
+        .map(|&v| match v {
+            0 => BARS[0],
+            v => BARS[1 + ((v * 6 + max - 1) / max) as usize],
+        })
+        .collect()
+}
+
+fn preview_title(app: &AppState, scroll_percent: usize, scrolls_sideways: bool) -> String {
+    let mut notes = Vec::new();
+    if scrolls_sideways {
+        notes.push("←/→ to scroll".to_string());
+    }
+    if let Some(source) = app.selected_source() {
+        notes.push(format!("@{}, read-only", source));
+    }
+    if let Some(other) = app
+        .get_selected_note()
+        .and_then(|n| app.editing_elsewhere(n.id))
+    {
+        notes.push(format!("open in {}'s editor", other.client));
+    }
+    if notes.is_empty() {
+        format!("Note Content ({}%)", scroll_percent)
+    } else {
+        format!("Note Content ({}) ({}%)", notes.join("; "), scroll_percent)
+    }
+}
@@ -1,10 +1,50 @@
 mod app;
 
 use app::App;
+use app::cli::Command;
+use clap::Parser;
 use std::io;
 
-fn main() -> io::Result<()> {
-    let mut app = App::new()?;
-    app.run()?;
+/// Notes kept in PostgreSQL. Without a subcommand, starts the TUI.
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Cli {
+    /// Keep logs and state apart under this name; defaults to $PGNOTES_PROFILE or "default"
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Browse read-only, without migrations, sources, the editor or other
+    /// external commands; for when normal startup fails
+    #[arg(long)]
+    safe_mode: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[tokio::main]
+async fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+    if let Err(e) = app::data_dir::set_profile(cli.profile) {
+        eprintln!("pgnotes: {}", e);
+        std::process::exit(1);
+    }
+    if let Some(command) = cli.command {
+        if let Err(e) = app::cli::run(command).await {
+            eprintln!("pgnotes: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let mut app = match App::new(cli.safe_mode).await {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("pgnotes: {}", e);
+            if !cli.safe_mode {
+                eprintln!("pgnotes: --safe-mode starts read-only, without running migrations.");
+            }
+            std::process::exit(1);
+        }
+    };
+    app.run().await?;
     Ok(())
 }